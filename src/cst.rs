@@ -0,0 +1,95 @@
+use crate::scanner::Scanner;
+use crate::token::Token;
+use std::ops::Range;
+
+/// A token together with everything written before it since the previous
+/// token: spaces, newlines and comments. That's the trivia the parser throws
+/// away, and all a tool needs to write the file back out exactly as it was.
+#[derive(Clone, Debug)]
+pub struct SyntaxToken {
+    pub token: Token,
+
+    /// The text between the previous token and this one.
+    pub leading: String,
+
+    /// Where the token's own text is in the source, in bytes.
+    pub span: Range<usize>,
+}
+
+/// A concrete syntax tree, kept alongside the abstract one. Where the AST has
+/// only what the program means, this has every character of the source, in
+/// order: each token with its trivia and exact span. Joining them gives the
+/// file back byte for byte.
+///
+/// Tools that change a file, like the refactorings, find what to change in
+/// the AST, then make the change here, by replacing tokens. The rest of the
+/// file, formatting and comments included, comes out untouched.
+///
+/// It is flat: the tokens are all there is. Which statement or expression a
+/// token belongs to is the AST's business, and tokens are how the two are
+/// lined up, by line and column.
+#[derive(Clone, Debug)]
+pub struct ConcreteSyntax {
+    /// Every token, ending with EOF, whose leading trivia is whatever follows
+    /// the last real token.
+    pub tokens: Vec<SyntaxToken>,
+}
+
+impl ConcreteSyntax {
+    pub fn new(source: &str) -> Self {
+        let (tokens, spans) = Scanner::new(source.to_string()).scan_with_spans();
+
+        // The scanner counts characters; spans here are in bytes, so they can
+        // slice the source.
+        let mut offsets: Vec<usize> = source.char_indices().map(|(offset, _)| offset).collect();
+        offsets.push(source.len());
+
+        let mut previous_end = 0;
+        let tokens = tokens
+            .into_iter()
+            .zip(spans)
+            .map(|(token, span)| {
+                let span = offsets[span.start]..offsets[span.end];
+                let leading = source[previous_end..span.start].to_string();
+                previous_end = span.end;
+                SyntaxToken { token, leading, span }
+            })
+            .collect();
+        Self { tokens }
+    }
+
+    /// The tokens alone, ready for the parser.
+    pub fn ast_tokens(&self) -> Vec<Token> {
+        self.tokens.iter().map(|syntax| syntax.token.clone()).collect()
+    }
+
+    /// The source the tree was built from, exactly.
+    pub fn to_source(&self) -> String {
+        self.render(|_, syntax| syntax.token.lexeme.clone())
+    }
+
+    /// The index of the token written at the given line and column.
+    pub fn token_at(&self, line: usize, column: usize) -> Option<usize> {
+        self.tokens.iter().position(|syntax| {
+            let token = &syntax.token;
+            token.line == line && (token.column..token.column + token.lexeme.chars().count()).contains(&column)
+        })
+    }
+
+    /// The source with the text of some tokens replaced, keeping everything
+    /// else, trivia included, as it was. `replacement` is asked about each
+    /// token by index and gives the text to write in its place, or None to
+    /// keep it.
+    pub fn replace(&self, mut replacement: impl FnMut(usize, &SyntaxToken) -> Option<String>) -> String {
+        self.render(|index, syntax| replacement(index, syntax).unwrap_or_else(|| syntax.token.lexeme.clone()))
+    }
+
+    fn render(&self, mut text: impl FnMut(usize, &SyntaxToken) -> String) -> String {
+        let mut source = String::new();
+        for (index, syntax) in self.tokens.iter().enumerate() {
+            source.push_str(&syntax.leading);
+            source.push_str(&text(index, syntax));
+        }
+        source
+    }
+}
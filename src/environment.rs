@@ -1,15 +1,30 @@
 use crate::error::Error;
+use crate::policy::Shadowing;
 use crate::token::Token;
+use crate::value::class::Class;
 use crate::value::function::Function;
+use crate::value::native::Natives;
 use crate::value::object::Object;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::rc::Rc;
 
 /// Mutable type to easily modify values in memory
 pub type MutableEnvironment = Rc<RefCell<Environment>>;
 
+/// How many emptied maps the pool below keeps around at most.
+const POOL_LIMIT: usize = 64;
+
+thread_local! {
+    /// Every call, and every block, creates an environment and usually drops it
+    /// again right away. Most of the cost of that is the map of bindings, which
+    /// allocates its table on the first define. When an environment is
+    /// dropped its map is emptied and kept here, table and all, for the next
+    /// environment to reuse.
+    static POOL: RefCell<Vec<HashMap<String, Object>>> = const { RefCell::new(Vec::new()) };
+}
+
 /// The bindings that associate variables to values need to be stored somewhere.
 /// This storage is called an 'environment'. This is a map where the keys are variable 
 /// names and the values are their values. We could have stuff this map and the code to 
@@ -32,47 +47,128 @@ pub struct Environment {
     /// same name should refer to the same variable. Using the raw string ensures 
     /// all of those tokens refer to the same map key.
     values: HashMap<String, Object>,
+
+    /// The names in `values` declared with `const`, which can't be assigned.
+    constants: HashSet<String>,
     
     /// This is the parent environment (the outer scope).
     enclosing: Option<MutableEnvironment>,
+
+    /// The names bound to natives when the globals were set up. Empty for
+    /// every other environment.
+    natives: HashSet<String>,
+
+    /// Whether the program may declare globals with those names.
+    shadowing: Shadowing,
 }
 
 impl Environment {
-    /// The globals
-    pub fn global_env() -> MutableEnvironment {
+    /// The globals, starting out with the given natives and the built-in
+    /// Error class.
+    pub fn global_env(natives: &Natives) -> MutableEnvironment {
         let mut global = Self {
             name: "global".to_string(),
             values: HashMap::new(),
+            constants: HashSet::new(),
             enclosing: None,
+            natives: natives.iter().map(|native| native.name.to_string()).collect(),
+            shadowing: Shadowing::default(),
         };
-        global.define("clock".to_string(), Object::Function(Function::Clock));
+        for native in natives.iter() {
+            global.define(native.name.to_string(), Object::Function(Function::Native(native.clone())));
+        }
+        global.define("Error".to_string(), Object::Class(Rc::new(Class::error())));
         Rc::new(RefCell::new(global))
     }
 
     /// This constructor creates a new local scope nested inside the given outer one.
     pub fn new(enclosing: MutableEnvironment, name: &str) -> MutableEnvironment {
+        let values = POOL.with(|pool| pool.borrow_mut().pop()).unwrap_or_default();
         Rc::new(RefCell::new(Self {
             name: name.to_string(),
-            values: HashMap::new(),
+            values,
+            constants: HashSet::new(),
             enclosing: Some(enclosing),
+            natives: HashSet::new(),
+            shadowing: Shadowing::default(),
+        }))
+    }
+
+    /// A new environment with the same bindings as this one, and the same
+    /// enclosing environment. Defining or assigning in one doesn't affect the
+    /// other, but the values bound are shared.
+    pub fn fork(&self) -> MutableEnvironment {
+        Rc::new(RefCell::new(Self {
+            name: self.name.clone(),
+            values: self.values.clone(),
+            constants: self.constants.clone(),
+            enclosing: self.enclosing.clone(),
+            natives: self.natives.clone(),
+            shadowing: self.shadowing,
         }))
     }
 
+    /// The names bound to natives, in the globals.
+    pub fn native_names(&self) -> &HashSet<String> {
+        &self.natives
+    }
+
+    pub fn shadowing(&self) -> Shadowing {
+        self.shadowing
+    }
+
+    /// Sets whether the program may declare globals that replace natives.
+    pub fn set_shadowing(&mut self, shadowing: Shadowing) {
+        self.shadowing = shadowing;
+    }
+
+    /// Checks that the program may declare this name here, before it is
+    /// defined. A name declared `const` can't be declared again. Neither can
+    /// a global with the name of a native, under Shadowing::Error.
+    ///
+    /// The resolver already reports such declarations in the same scope of a
+    /// function or block, and reports shadowing natives as warnings or errors,
+    /// before the program runs. This catches the ones it wasn't told about,
+    /// since a program can't be allowed to run past the policy, and globals,
+    /// which can be declared again by a later REPL line or module.
+    pub fn check_declaration(&self, name: &Token) -> Result<(), Error> {
+        if self.constants.contains(&name.lexeme) {
+            return Err(Error::RuntimeError(name.clone(), format!("Can't redeclare constant '{}'.", name.lexeme)));
+        }
+        if self.shadowing == Shadowing::Error && self.natives.contains(&name.lexeme) {
+            return Err(Error::RuntimeError(name.clone(), format!("Can't shadow the native function '{}'.", name.lexeme)));
+        }
+        Ok(())
+    }
+
     /// A variable definition binds a new name to a value.
     pub fn define(&mut self, name: String, value: Object) {
         // A new variable is always declared in the current innermost scope.
         // No need to define in outer scope.
         // eprintln!("env:{} var: {name}: value: {value:#?}", self.name);
+        if !self.constants.is_empty() {
+            self.constants.remove(&name);
+        }
         self.values.insert(name, value);
     }
 
+    /// Defines a variable that can't be assigned afterwards, nor declared
+    /// again (see check_declaration).
+    pub fn define_constant(&mut self, name: String, value: Object) {
+        self.values.insert(name.clone(), value);
+        self.constants.insert(name);
+    }
+
     /// The key difference between assign and define is that assign is not allowed
     /// to create a new variable. It’s a runtime error if the key doesn’t
     /// already exist.
     pub fn assign(&mut self, name: Token, value: Object) -> Result<(), Error> {
         let variable = name.lexeme.clone();
-        if self.values.contains_key(&variable) {
-            self.values.insert(variable, value);
+        if let Some(slot) = self.values.get_mut(&variable) {
+            if self.constants.contains(&variable) {
+                return Err(Error::RuntimeError(name, format!("Can't assign to constant '{variable}'.")));
+            }
+            *slot = value;
             return Ok(());
         }
 
@@ -106,6 +202,12 @@ impl Environment {
         }
     }
 
+    /// Looks up a variable defined directly in this environment, without walking
+    /// out to the enclosing ones.
+    pub fn get_own(&self, name: &str) -> Option<Object> {
+        self.values.get(name).cloned()
+    }
+
     /// The previous get() method dynamically walks the chain of enclosing environments,
     /// scouring each one to see if the variable might be hiding in there somewhere.
     /// With this, we know exactly which environment in the chain will have the variable.
@@ -123,6 +225,31 @@ impl Environment {
          }
     }
 
+    /// The names defined in this environment itself, in no particular order.
+    pub fn names(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    /// The names defined in this environment and each one enclosing it,
+    /// innermost first, leaving out the global environment.
+    pub fn local_names(&self) -> Vec<Vec<String>> {
+        let mut names = Vec::new();
+        if self.enclosing.is_none() {
+            return names;
+        }
+        names.push(self.values.keys().cloned().collect());
+        let mut next = self.enclosing.clone();
+        while let Some(environment) = next {
+            let environment = environment.borrow();
+            if environment.enclosing.is_none() {
+                break;
+            }
+            names.push(environment.values.keys().cloned().collect());
+            next = environment.enclosing.clone();
+        }
+        names
+    }
+
     // Todo: FIX take 0 distance
     fn ancestor(&self, distance: usize) -> MutableEnvironment {
         let mut environment = self.enclosing.clone().expect("No enclosing environment");
@@ -139,6 +266,26 @@ impl Environment {
     }
 }
 
+impl Drop for Environment {
+    fn drop(&mut self) {
+        // Big maps, like a module's, aren't worth holding on to.
+        let mut values = std::mem::take(&mut self.values);
+        if values.capacity() == 0 || values.capacity() > 32 {
+            return;
+        }
+        // Emptying the map can drop the last reference to another environment,
+        // which comes back here, so it has to happen before the pool is borrowed.
+        values.clear();
+        // The pool may already be gone if this runs as the thread shuts down.
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < POOL_LIMIT {
+                pool.push(values);
+            }
+        });
+    }
+}
+
 impl Display for Environment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         
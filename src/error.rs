@@ -1,11 +1,95 @@
 use crate::value::object::Object;
 use crate::token::{Token, TokenType};
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
 
-static mut HAD_ERROR: bool = false;
-static mut HAD_RUNTIME_ERROR: bool = false;
+/// Everything reported so far, and what it means for the exit code.
+#[derive(Debug, Default)]
+pub(crate) struct Reports {
+    had_error: bool,
+    had_runtime_error: bool,
+    exit_status: i32,
+    was_interrupted: bool,
+
+    /// Set while errors are being collected rather than printed (see collect).
+    quiet: bool,
+
+    /// Everything reported so far, in order. Errors are printed as soon as they
+    /// are found, but we also keep them so they can be summarized afterwards.
+    diagnostics: Vec<Diagnostic>,
+}
+
+thread_local! {
+    /// What the program running on this thread has reported. Each thread has
+    /// its own, so a task on a thread of its own can't change how the program
+    /// that spawned it ends. A coroutine's thread is part of the program that
+    /// resumes it, so it reports into the same record (see share).
+    static REPORTS: RefCell<Arc<Mutex<Reports>>> = RefCell::new(Arc::default());
+}
+
+fn with_reports<T>(f: impl FnOnce(&mut Reports) -> T) -> T {
+    REPORTS.with(|reports| f(&mut reports.borrow().lock().unwrap()))
+}
+
+/// The record this thread reports into, for another thread running on its
+/// behalf to report into as well.
+pub(crate) fn shared() -> Arc<Mutex<Reports>> {
+    REPORTS.with(|reports| reports.borrow().clone())
+}
+
+/// Makes this thread report into the record of the thread it runs for.
+pub(crate) fn share(reports: Arc<Mutex<Reports>>) {
+    REPORTS.with(|current| *current.borrow_mut() = reports);
+}
+
+/// Runs `f` with what it reports collected instead of printed, and kept
+/// apart from everything reported before, which is left as it was. Gives
+/// what `f` returned along with what it reported.
+pub fn collect<T>(f: impl FnOnce() -> T) -> (T, Vec<Diagnostic>) {
+    let collecting = Reports { quiet: true, ..Reports::default() };
+    let outer = with_reports(|reports| std::mem::replace(reports, collecting));
+    let result = f();
+    let collected = with_reports(|reports| std::mem::replace(reports, outer));
+    (result, collected.diagnostics)
+}
+
+/// Reports a diagnostic that was collected, as if it had been reported
+/// here in the first place.
+pub fn restate(diagnostic: Diagnostic) {
+    let Diagnostic { kind, file, line, message } = diagnostic;
+    if !quiet() {
+        eprintln!("[{}] {}", location(file.as_deref(), line), message);
+    }
+    record(kind, file.as_deref(), line, message);
+    if kind == "syntax" {
+        with_reports(|reports| reports.had_error = true);
+    }
+}
+
+fn quiet() -> bool {
+    with_reports(|reports| reports.quiet)
+}
+
+/// A record of one reported error.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// "syntax" for errors found before running (scanning, parsing, resolving),
+    /// "runtime" for errors raised while the program executes, and "warning"
+    /// for problems found before running that don't stop it.
+    pub kind: &'static str,
+    /// The file the error is in, when it isn't the main script.
+    pub file: Option<String>,
+    pub line: usize,
+    pub message: String,
+}
 
 pub fn error(line: usize, message: String) {
-    report(line, "".to_string(), message);
+    error_in(None, line, message);
+}
+
+/// Like error(), for code that came from a named file.
+pub fn error_in(file: Option<&str>, line: usize, message: String) {
+    report(file, line, "".to_string(), message);
 }
 
 /// If a runtime error is thrown while evaluating the expression, interpret()
@@ -15,41 +99,144 @@ pub fn error(line: usize, message: String) {
 pub fn runtime_error(error: Error) {
     match error {
         Error::RuntimeError(token, message) => {
-            eprintln!("{}\n[line {}]", message, token.line);
-            unsafe {
-                HAD_RUNTIME_ERROR = true;
+            let file = token.file.as_deref();
+            if !quiet() {
+                eprintln!("{}\n[{}]", message, location(file, token.line));
             }
+            record("runtime", file, token.line, message);
+            with_reports(|reports| reports.had_runtime_error = true);
         }
+        Error::Throw(value, keyword) => uncaught(format!("Uncaught {value}"), &keyword, &[]),
         _ => unreachable!(),
     }
 }
 
+/// Reports a thrown value that nothing caught, described by `message`, along
+/// with the calls that were active when it was thrown, innermost first.
+pub fn uncaught(message: String, keyword: &Token, trace: &[String]) {
+    let file = keyword.file.as_deref();
+    if !quiet() {
+        eprintln!("{message}");
+        match trace {
+            [] => eprintln!("[{}]", location(file, keyword.line)),
+            trace => trace.iter().for_each(|entry| eprintln!("{entry}")),
+        }
+    }
+    record("runtime", file, keyword.line, message);
+    with_reports(|reports| reports.had_runtime_error = true);
+}
+
+/// Reports a program stopped by Ctrl-C, along with the calls that were
+/// active when it stopped, innermost first.
+pub fn interrupted(token: &Token, trace: &[String]) {
+    let file = token.file.as_deref();
+    if !quiet() {
+        eprintln!("Interrupted.");
+        trace.iter().for_each(|entry| eprintln!("{entry}"));
+    }
+    record("runtime", file, token.line, "Interrupted.".into());
+    with_reports(|reports| {
+        reports.had_runtime_error = true;
+        reports.was_interrupted = true;
+    });
+}
+
 /// This reports an error at a given token. It shows the token’s location and the
 /// token itself. This comes in handy since we use tokens throughout the interpreter
 /// to track locations in code.
 pub fn token_error(token: Token, message: String) {
+    let file = token.file.as_deref();
     if token.token_type == TokenType::EOF {
-        report(token.line, " at end".to_string(), message);
+        report(file, token.line, " at end".to_string(), message);
     } else {
-        report(token.line, format!(" at '{}'", token.lexeme), message);
+        report(file, token.line, format!(" at '{}'", token.lexeme), message);
     }
 }
 
-fn report(line: usize, wh: String, message: String) {
-    eprintln!("[line {}] Error{}: {}", line, wh, message);
-    unsafe {
-        HAD_ERROR = true;
+/// Reports code that is bound to fail if it ever runs, like `"a" - 1`. Unlike
+/// an error, a warning doesn't stop the program from running or change the
+/// exit code.
+pub fn token_warning(token: Token, message: String) {
+    let file = token.file.as_deref();
+    if !quiet() {
+        eprintln!("[{}] Warning at '{}': {}", location(file, token.line), token.lexeme, message);
     }
+    record("warning", file, token.line, format!("Warning at '{}': {message}", token.lexeme));
+}
+
+fn report(file: Option<&str>, line: usize, wh: String, message: String) {
+    if !quiet() {
+        eprintln!("[{}] Error{}: {}", location(file, line), wh, message);
+    }
+    record("syntax", file, line, format!("Error{wh}: {message}"));
+    with_reports(|reports| reports.had_error = true);
+}
+
+/// Where an error is, as shown between the brackets: "line 3" in the main
+/// script, or "lib/math.lox line 3" in another file.
+fn location(file: Option<&str>, line: usize) -> String {
+    match file {
+        Some(file) => format!("{file} line {line}"),
+        None => format!("line {line}"),
+    }
+}
+
+fn record(kind: &'static str, file: Option<&str>, line: usize, message: String) {
+    let file = file.map(str::to_string);
+    with_reports(|reports| reports.diagnostics.push(Diagnostic { kind, file, line, message }));
+}
+
+/// Returns a copy of every error reported since the last reset().
+pub fn diagnostics() -> Vec<Diagnostic> {
+    with_reports(|reports| reports.diagnostics.clone())
 }
 
 pub fn had_error() -> bool {
-    unsafe { HAD_ERROR }
+    with_reports(|reports| reports.had_error)
 }
 
 pub fn had_runtime_error() -> bool {
-    unsafe { HAD_RUNTIME_ERROR }
+    with_reports(|reports| reports.had_runtime_error)
+}
+
+/// Sets the exit code for a program that runs without errors, as its entry
+/// point asks for by what it returns.
+pub fn set_exit_status(status: i32) {
+    with_reports(|reports| reports.exit_status = status);
+}
+
+/// The process exit code for whatever has been reported so far. We follow the
+/// sysexits.h conventions: 65 for errors in the input data, 70 for internal
+/// software errors. A program stopped by Ctrl-C exits with 130, as shells
+/// expect of a process ended by SIGINT. A program without errors exits with
+/// 0, unless its entry point returned something else.
+pub fn exit_code() -> i32 {
+    with_reports(|reports| {
+        if reports.had_error {
+            65
+        } else if reports.was_interrupted {
+            130
+        } else if reports.had_runtime_error {
+            70
+        } else {
+            reports.exit_status
+        }
+    })
+}
+
+/// Forgets all reported errors. This is used when one process runs several
+/// independent programs, so a failure in one doesn’t leak into the next.
+/// Errors being collected (see collect) go on being collected.
+pub fn reset() {
+    with_reports(|reports| *reports = Reports { quiet: reports.quiet, ..Reports::default() });
 }
 
+/// Everything that unwinds. Besides the errors proper, the interpreter uses
+/// this for every other way a statement can end than by going on to the
+/// next one: a return, a break, a continue and a throw. That gives all of
+/// them a single path out through Rust's `?`, so a construct that has to
+/// act on whichever of them comes through, like a finally block, handles
+/// them all in one place.
 pub enum Error {
     /// These are syntax errors, used by parser for unwinding and synchronizing.
     /// These are detected and reported before any code is executed.
@@ -63,4 +250,16 @@ pub enum Error {
 
     /// Wrap the return value of a function
     Return(Object),
+
+    /// A value thrown by a `throw` statement, with the statement's keyword.
+    /// The value is boxed to keep errors, and so every Result here, small.
+    Throw(Box<Object>, Token),
+
+    /// Unwinds out of the body of the nearest enclosing loop, or of the loop
+    /// with the label, passing through the loops in between.
+    Break(Option<String>),
+
+    /// Unwinds to the end of the body of the nearest enclosing loop, or of
+    /// the loop with the label.
+    Continue(Option<String>),
 }
@@ -0,0 +1,134 @@
+/// What a test script says should happen when it runs, written in comments
+/// next to the code, in the format of the Crafting Interpreters test suite:
+///
+/// ```text
+/// print 1 + 2; // expect: 3
+/// print nil + 1; // expect runtime error: Operands must be numbers.
+/// var = 1; // Error at '=': Expect variable name.
+/// // [line 7] Error at end: Expect '}' after block.
+/// ```
+///
+/// Each `expect:` is a line the script prints, in order. A runtime error is
+/// expected on the line of its comment, and so is a compile error, unless
+/// the comment gives the line itself, for errors on a line that can't hold a
+/// comment. `// expect parse error` says the script doesn't compile, without
+/// saying which error it gets.
+///
+/// The suite also holds expectations for the C implementation only, written
+/// `// [c line 3]`. Those are skipped; `// [java line 3]` ones are kept.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Expectations {
+    /// Each line the script prints, with the line of the comment expecting it.
+    pub output: Vec<(usize, String)>,
+
+    /// The compile errors the script reports, as they are printed:
+    /// `[line 3] Error at 'x': message`.
+    pub compile_errors: Vec<String>,
+
+    /// Whether the script fails to compile, when no error is spelled out.
+    pub parse_error: bool,
+
+    /// The message of the runtime error the script stops with, and its line.
+    pub runtime_error: Option<(usize, String)>,
+}
+
+impl Expectations {
+    /// Reads the expectations written in a script's comments.
+    pub fn parse(source: &str) -> Self {
+        let mut expectations = Self::default();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let Some((_, comment)) = line.split_once("//") else {
+                continue;
+            };
+            let comment = comment.trim();
+            if let Some(output) = comment.strip_prefix("expect:") {
+                expectations.output.push((line_number, output.trim().to_string()));
+            } else if let Some(message) = comment.strip_prefix("expect runtime error:") {
+                expectations.runtime_error = Some((line_number, message.trim().to_string()));
+            } else if comment == "expect parse error" {
+                expectations.parse_error = true;
+            } else if comment.starts_with("Error") {
+                expectations.compile_errors.push(format!("[line {line_number}] {comment}"));
+            } else if let Some(error) = located_error(comment) {
+                expectations.compile_errors.push(error);
+            }
+        }
+        expectations
+    }
+
+    /// The exit code the script should finish with.
+    pub fn exit_code(&self) -> i32 {
+        if self.parse_error || !self.compile_errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+
+    /// Compares what a run of the script did with what was expected, and
+    /// describes each difference. No differences means the test passed.
+    /// Warnings on stderr are let through: they don't change what a script
+    /// does, and the suite was written before there were any.
+    pub fn check(&self, stdout: &str, stderr: &str, exit_code: i32) -> Vec<String> {
+        let mut failures = Vec::new();
+
+        let printed: Vec<&str> = stdout.lines().collect();
+        for (index, (line, expected)) in self.output.iter().enumerate() {
+            match printed.get(index) {
+                Some(actual) if actual == expected => {}
+                Some(actual) => failures.push(format!("Expected output '{expected}' on line {line} and got '{actual}'.")),
+                None => failures.push(format!("Missing expected output '{expected}' on line {line}.")),
+            }
+        }
+        for extra in printed.iter().skip(self.output.len()) {
+            failures.push(format!("Got output '{extra}' when none was expected."));
+        }
+
+        let errors: Vec<&str> = stderr.lines().filter(|line| !line.contains("] Warning")).collect();
+        if let Some((line, message)) = &self.runtime_error {
+            let location = format!("[line {line}]");
+            match errors.as_slice() {
+                [actual, at, ..] if actual == message && *at == location => {}
+                [] => failures.push(format!("Expected runtime error '{message}' and got none.")),
+                [actual, rest @ ..] => failures.push(format!(
+                    "Expected runtime error '{message}' on line {line} and got '{actual}' {}.",
+                    rest.first().copied().unwrap_or("with no line")
+                )),
+            }
+        } else if !self.compile_errors.is_empty() {
+            for expected in &self.compile_errors {
+                if !errors.contains(&expected.as_str()) {
+                    failures.push(format!("Missing expected error: {expected}"));
+                }
+            }
+            for actual in &errors {
+                if !self.compile_errors.iter().any(|expected| expected == actual) {
+                    failures.push(format!("Unexpected error: {actual}"));
+                }
+            }
+        } else if !self.parse_error {
+            for actual in &errors {
+                failures.push(format!("Unexpected error: {actual}"));
+            }
+        }
+
+        if exit_code != self.exit_code() {
+            failures.push(format!("Expected exit code {} and got {exit_code}.", self.exit_code()));
+        }
+        failures
+    }
+}
+
+/// Reads a compile error given with the line it is on, `[line 3] Error...`,
+/// or `[java line 3] Error...`.
+fn located_error(comment: &str) -> Option<String> {
+    let rest = comment.strip_prefix('[')?;
+    let rest = rest.strip_prefix("java ").unwrap_or(rest);
+    let (line, error) = rest.strip_prefix("line ")?.split_once(']')?;
+    let line: usize = line.parse().ok()?;
+    let error = error.trim();
+    error.starts_with("Error").then(|| format!("[line {line}] {error}"))
+}
@@ -1,5 +1,7 @@
+use crate::stmt::FunctionDeclaration;
 use crate::value::object::Object;
 use crate::token::Token;
+use std::rc::Rc;
 use std::fmt::Display;
 
 /// Expr is the base class that all expression types inherit from.
@@ -58,6 +60,32 @@ pub enum Expr {
     /// and the name is the property of that instance to be assigned the value.
     Set { object: Box<Expr>, name: Token, value: Box<Expr> },
     
+    /// A list literal, `[a, b, c]`. Each element is evaluated in order.
+    List { elements: Vec<Expr> },
+
+    /// A map literal, `{key: value, ...}`. The brace is kept for reporting a
+    /// key that can't be used in a map.
+    Map { brace: Token, entries: Vec<(Expr, Expr)> },
+
+    /// Reading an element, `object[index]`. The bracket is kept for reporting
+    /// errors like an index that is out of bounds.
+    Index { object: Box<Expr>, bracket: Token, index: Box<Expr> },
+
+    /// Assigning to an element, `object[index] = value`. Like Set, this is what
+    /// an Index expression turns into when it is the target of an assignment.
+    IndexSet { object: Box<Expr>, bracket: Token, index: Box<Expr>, value: Box<Expr> },
+
+    /// `++` or `--` applied to a variable or a field. Unlike compound assignment
+    /// this can't be desugared, because the postfix form evaluates to the value
+    /// the target had before it changed. The target is always a Variable, a Get,
+    /// or an Index.
+    Update { operator: Token, target: Box<Expr>, prefix: bool },
+
+    /// An anonymous function, written `fun (a, b) { ... }` or, when the body is
+    /// a single returned expression, `fun (a, b) -> expression`. It shares the
+    /// declaration type with named functions, under the name "lambda".
+    Lambda { decl: Rc<FunctionDeclaration> },
+
     /// Inside a method body, a 'this' expression evaluates to the class instance 
     /// that the method was called on. Or, more specifically, since methods are 
     /// accessed and then invoked as two steps, 'this' refer to the object that 
@@ -66,6 +94,85 @@ pub enum Expr {
     
     /// Contains the token for the 'super' keyword and the name of the method being looked up. 
     Super { keyword: Token, method: Token },
+
+    /// `match (value) { pattern -> result, ... }` tries the pattern of each
+    /// arm against the value, in order, and evaluates to the result of the
+    /// first one that fits. The keyword is kept for reporting a value that
+    /// none of them fit.
+    Match { keyword: Token, subject: Box<Expr>, arms: Vec<MatchArm> },
+}
+
+/// One arm of a match: a pattern, and the expression the match evaluates to
+/// when the pattern fits.
+#[derive(Clone, Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+}
+
+/// What the value of a match is tried against.
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    /// A literal fits the values equal to it: `1`, `"x"`, `true` or `nil`.
+    Literal { value: Object },
+
+    /// A name fits any value, and is bound to it while the arm's result is
+    /// evaluated. Given a type, `n: Number`, it only fits values of that
+    /// type, which is either one of the type names the checker knows or a
+    /// class, which fits its instances and those of its subclasses. The type
+    /// is a Variable, so that a class name can be resolved like any other.
+    /// The name `_` binds nothing, which makes `_` on its own fit anything.
+    Binding { name: Option<Token>, type_name: Option<Box<Expr>> },
+}
+
+impl Expr {
+    /// The line the expression starts on, as far as its tokens tell. A
+    /// literal keeps no token, so one on its own has no line.
+    pub fn line(&self) -> Option<usize> {
+        use Expr::*;
+        match self {
+            Literal { .. } => None,
+            Unary { operator, .. } => Some(operator.line),
+            Update { operator, prefix: true, .. } => Some(operator.line),
+            Update { operator, target, prefix: false } => target.line().or(Some(operator.line)),
+            Binary { left, operator, .. } | Logical { left, operator, .. } => left.line().or(Some(operator.line)),
+            Grouping { expression } => expression.line(),
+            Variable { name } | Assign { name, .. } => Some(name.line),
+            Call { callee, paren, .. } => callee.line().or(Some(paren.line)),
+            Get { object, name } | Set { object, name, .. } => object.line().or(Some(name.line)),
+            Index { object, bracket, .. } | IndexSet { object, bracket, .. } => object.line().or(Some(bracket.line)),
+            List { elements } => elements.iter().find_map(Expr::line),
+            Map { brace, .. } => Some(brace.line),
+            Lambda { decl } => Some(decl.name.line),
+            This { keyword } | Super { keyword, .. } | Match { keyword, .. } => Some(keyword.line),
+        }
+    }
+
+    /// The name of the kind of expression this is, for counting them.
+    pub fn kind(&self) -> &'static str {
+        use Expr::*;
+        match self {
+            Literal { .. } => "Literal",
+            Unary { .. } => "Unary",
+            Binary { .. } => "Binary",
+            Grouping { .. } => "Grouping",
+            Variable { .. } => "Variable",
+            Assign { .. } => "Assign",
+            Logical { .. } => "Logical",
+            Call { .. } => "Call",
+            Get { .. } => "Get",
+            Set { .. } => "Set",
+            List { .. } => "List",
+            Map { .. } => "Map",
+            Index { .. } => "Index",
+            IndexSet { .. } => "IndexSet",
+            Update { .. } => "Update",
+            Lambda { .. } => "Lambda",
+            This { .. } => "This",
+            Super { .. } => "Super",
+            Match { .. } => "Match",
+        }
+    }
 }
 
 impl Display for Expr {
@@ -83,13 +190,48 @@ impl Display for Expr {
             Assign { name, value } => f.write_fmt(format_args!("(= {} {})", name.lexeme, value)),
             Logical { left, operator, right } => f.write_fmt(format_args!("({} {left} {right})", operator.lexeme)),
             Call { callee, arguments, paren: _ } => {
-                let string_vec = arguments.into_iter().map(Expr::to_string).collect::<Vec<String>>();
+                let string_vec = arguments.iter().map(Expr::to_string).collect::<Vec<String>>();
                 f.write_fmt(format_args!("(call {callee} {})", string_vec.join(" ")))
             }, 
             Get { object, name } => f.write_fmt(format_args!("(. {} {})", object, name.lexeme)),
             Set { object, name, value } => f.write_fmt(format_args!("(= {} {} {})", object, name.lexeme, value)),
+            Update { operator, target, prefix: true } => f.write_fmt(format_args!("({} {target})", operator.lexeme)),
+            Update { operator, target, prefix: false } => f.write_fmt(format_args!("({target} {})", operator.lexeme)),
+            List { elements } => {
+                let string_vec = elements.iter().map(Expr::to_string).collect::<Vec<String>>();
+                f.write_fmt(format_args!("(list {})", string_vec.join(" ")))
+            }
+            Map { entries, .. } => {
+                let string_vec = entries.iter().map(|(key, value)| format!("({key} {value})")).collect::<Vec<String>>();
+                f.write_fmt(format_args!("(map {})", string_vec.join(" ")))
+            }
+            Index { object, index, .. } => f.write_fmt(format_args!("(index {object} {index})")),
+            IndexSet { object, index, value, .. } => f.write_fmt(format_args!("(= (index {object} {index}) {value})")),
+            Lambda { decl } => {
+                let params = decl.params.iter().map(|param| param.lexeme.as_str()).collect::<Vec<&str>>();
+                f.write_fmt(format_args!("(fun ({}))", params.join(" ")))
+            }
             This { .. } => { "this".to_string() }.fmt(f),
             Super { method, .. } => f.write_fmt(format_args!("(super {})", method)),
+            Match { subject, arms, .. } => {
+                let string_vec = arms.iter().map(|arm| format!("({} {})", arm.pattern, arm.body)).collect::<Vec<String>>();
+                f.write_fmt(format_args!("(match {subject} {})", string_vec.join(" ")))
+            }
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Literal { value } => Expr::Literal { value: value.clone() }.fmt(f),
+            Pattern::Binding { name, type_name } => {
+                f.write_str(name.as_ref().map_or("_", |name| name.lexeme.as_str()))?;
+                match type_name.as_deref() {
+                    Some(Expr::Variable { name }) => f.write_fmt(format_args!(": {}", name.lexeme)),
+                    _ => Ok(()),
+                }
+            }
         }
     }
 }
@@ -0,0 +1,146 @@
+use crate::expr::{Expr, MatchArm};
+use crate::stmt::{FunctionDeclaration, Stmt};
+use std::rc::Rc;
+
+/// A transformation of the syntax tree that takes a tree and builds a new one.
+///
+/// Most passes over the tree only care about a few kinds of node: constant
+/// folding looks at operators on literals, a desugaring rewrites one construct
+/// into others. Everything else just has to be taken apart and put back
+/// together with its children transformed. A Fold does that part once.
+///
+/// Each method has a default that rebuilds the node from its transformed
+/// children, by calling the free function of the same name. A pass overrides
+/// the methods for the nodes it cares about, and calls the free function from
+/// its override when it wants the children folded as usual:
+///
+/// ```ignore
+/// impl Fold for Negate {
+///     fn fold_expr(&mut self, expr: Expr) -> Expr {
+///         match fold::fold_expr(self, expr) {
+///             Expr::Literal { value: Object::Number(n) } => Expr::Literal { value: Object::Number(-n) },
+///             other => other,
+///         }
+///     }
+/// }
+/// ```
+///
+/// The new tree is made of new nodes, so it has to be folded before it is
+/// resolved: the resolver's side table refers to expressions by address.
+pub trait Fold {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        fold_expr(self, expr)
+    }
+
+    fn fold_stmt(&mut self, stmt: Stmt) -> Stmt {
+        fold_stmt(self, stmt)
+    }
+
+    fn fold_function(&mut self, decl: Rc<FunctionDeclaration>) -> Rc<FunctionDeclaration> {
+        fold_function(self, decl)
+    }
+
+    fn fold_program(&mut self, statements: Vec<Stmt>) -> Vec<Stmt> {
+        statements.into_iter().map(|stmt| self.fold_stmt(stmt)).collect()
+    }
+}
+
+/// Rebuilds an expression with each of its subexpressions, and the body of a
+/// lambda, folded.
+pub fn fold_expr<F: Fold + ?Sized>(folder: &mut F, expr: Expr) -> Expr {
+    let mut fold = |expr: Box<Expr>| Box::new(folder.fold_expr(*expr));
+    match expr {
+        Expr::Literal { .. } | Expr::Variable { .. } | Expr::This { .. } | Expr::Super { .. } => expr,
+        Expr::Unary { operator, right } => Expr::Unary { operator, right: fold(right) },
+        Expr::Binary { left, operator, right } => Expr::Binary { left: fold(left), operator, right: fold(right) },
+        Expr::Logical { left, operator, right } => Expr::Logical { left: fold(left), operator, right: fold(right) },
+        Expr::Grouping { expression } => Expr::Grouping { expression: fold(expression) },
+        Expr::Assign { name, value } => Expr::Assign { name, value: fold(value) },
+        Expr::Get { object, name } => Expr::Get { object: fold(object), name },
+        Expr::Set { object, name, value } => Expr::Set { object: fold(object), name, value: fold(value) },
+        Expr::Index { object, bracket, index } => Expr::Index { object: fold(object), bracket, index: fold(index) },
+        Expr::IndexSet { object, bracket, index, value } => {
+            Expr::IndexSet { object: fold(object), bracket, index: fold(index), value: fold(value) }
+        }
+        Expr::Update { operator, target, prefix } => Expr::Update { operator, target: fold(target), prefix },
+        Expr::Call { callee, arguments, paren } => {
+            let callee = fold(callee);
+            let arguments = arguments.into_iter().map(|argument| folder.fold_expr(argument)).collect();
+            Expr::Call { callee, arguments, paren }
+        }
+        Expr::List { elements } => {
+            Expr::List { elements: elements.into_iter().map(|element| folder.fold_expr(element)).collect() }
+        }
+        Expr::Map { brace, entries } => {
+            let entries = entries.into_iter().map(|(key, value)| (folder.fold_expr(key), folder.fold_expr(value))).collect();
+            Expr::Map { brace, entries }
+        }
+        Expr::Lambda { decl } => Expr::Lambda { decl: folder.fold_function(decl) },
+        Expr::Match { keyword, subject, arms } => {
+            let subject = fold(subject);
+            let arms = arms
+                .into_iter()
+                .map(|arm| MatchArm { pattern: arm.pattern, body: folder.fold_expr(arm.body) })
+                .collect();
+            Expr::Match { keyword, subject, arms }
+        }
+    }
+}
+
+/// Rebuilds a statement with its expressions, nested statements and the
+/// bodies of its functions folded.
+pub fn fold_stmt<F: Fold + ?Sized>(folder: &mut F, stmt: Stmt) -> Stmt {
+    let mut fold = |stmt: Box<Stmt>| Box::new(folder.fold_stmt(*stmt));
+    match stmt {
+        Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Import { .. } | Stmt::Interface { .. } => stmt,
+        Stmt::Block { statements } => Stmt::Block { statements: folder.fold_program(statements) },
+        Stmt::If { condition, then_branch, else_branch } => {
+            let then_branch = fold(then_branch);
+            let else_branch = else_branch.map(fold);
+            Stmt::If { condition: folder.fold_expr(condition), then_branch, else_branch }
+        }
+        Stmt::While { keyword, condition, body, increment, label } => {
+            let body = fold(body);
+            let increment = increment.map(|increment| folder.fold_expr(increment));
+            Stmt::While { keyword, condition: folder.fold_expr(condition), body, increment, label }
+        }
+        Stmt::ForIn { name, keyword, collection, body, label } => {
+            let body = fold(body);
+            Stmt::ForIn { name, keyword, collection: folder.fold_expr(collection), body, label }
+        }
+        Stmt::Expression { expression } => Stmt::Expression { expression: folder.fold_expr(expression) },
+        Stmt::Print { keyword, expression } => Stmt::Print { keyword, expression: folder.fold_expr(expression) },
+        Stmt::Var { name, type_name, initializer, mutable } => {
+            let initializer = initializer.map(|initializer| folder.fold_expr(initializer));
+            Stmt::Var { name, type_name, initializer, mutable }
+        }
+        Stmt::Return { keyword, value } => Stmt::Return { keyword, value: value.map(|value| folder.fold_expr(value)) },
+        Stmt::Throw { keyword, value } => Stmt::Throw { keyword, value: folder.fold_expr(value) },
+        Stmt::Try { body, name, handler, finally } => {
+            let body = folder.fold_program(body);
+            let handler = folder.fold_program(handler);
+            Stmt::Try { body, name, handler, finally: finally.map(|finally| folder.fold_program(finally)) }
+        }
+        Stmt::Function { decl } => Stmt::Function { decl: folder.fold_function(decl) },
+        Stmt::Class { name, superclass, interfaces, methods, class_methods } => {
+            let superclass = superclass.map(|superclass| folder.fold_expr(superclass));
+            let interfaces = interfaces.into_iter().map(|interface| folder.fold_expr(interface)).collect();
+            let methods = methods.into_iter().map(|method| folder.fold_function(method)).collect();
+            let class_methods = class_methods.into_iter().map(|method| folder.fold_function(method)).collect();
+            Stmt::Class { name, superclass, interfaces, methods, class_methods }
+        }
+        Stmt::Extend { keyword, class, methods, class_methods } => {
+            let methods = methods.into_iter().map(|method| folder.fold_function(method)).collect();
+            let class_methods = class_methods.into_iter().map(|method| folder.fold_function(method)).collect();
+            Stmt::Extend { keyword, class: folder.fold_expr(class), methods, class_methods }
+        }
+    }
+}
+
+/// Rebuilds a function declaration with its body folded. Declarations are
+/// shared, so one that is shared still is copied first.
+pub fn fold_function<F: Fold + ?Sized>(folder: &mut F, decl: Rc<FunctionDeclaration>) -> Rc<FunctionDeclaration> {
+    let mut decl = Rc::try_unwrap(decl).unwrap_or_else(|decl| (*decl).clone());
+    decl.body = folder.fold_program(std::mem::take(&mut decl.body));
+    Rc::new(decl)
+}
@@ -2,16 +2,36 @@ use crate::environment::{Environment, MutableEnvironment};
 use crate::error;
 use crate::error::Error;
 use crate::error::Error::RuntimeError;
-use crate::expr::Expr;
+use crate::interrupt;
+use crate::expr::{Expr, Pattern};
+use crate::macros::Macro;
+use crate::module::{FileResolver, ModuleResolver, ModuleSource};
+use crate::pipeline::{Pipeline, Resolved};
+use crate::policy::{Capability, Limits, Policy, Shadowing};
+use crate::resolver::Resolver;
 use crate::stmt::Stmt;
+use crate::task::{Settings, Task, TaskGroup};
 use crate::token::TokenType::*;
+use crate::value::channel::{Channel, Message};
 use crate::value::class;
+use crate::value::interface;
+use crate::value::coroutine::{self, Stop};
 use crate::value::function::Function;
-use crate::value::object::Object;
+use crate::value::instance::{self, MutableInstance};
+use crate::value::module::Module;
+use crate::value::map::{self, InstanceKey, MapKey, MutableMap};
+use crate::value::native::Natives;
+use crate::value::object::{self, MutableList, Object};
 use crate::value::object::Object::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::cell::RefCell;
+use std::io::{self, BufWriter, Stdout, Write};
 use std::rc::Rc;
-use crate::token::Token;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use crate::token::{Token, TokenType};
 
 /// Interpreter is the third step. It takes in the AST produced by the parser and
 /// recursively traverse it, building up a value which it ultimately returned.
@@ -32,41 +52,1161 @@ pub struct Interpreter {
     /// That is, its distance to the outer environment where the interpreter can
     /// find the variable’s value.
     locals: Option<HashMap<*const Expr, usize>>,
+
+    /// Counters collected while running, used for run reports.
+    pub stats: Stats,
+
+    /// Finds the source code for `import` statements.
+    modules: Rc<dyn ModuleResolver>,
+
+    /// The name of the module (or script) whose code is running, if known.
+    /// Imports are resolved relative to it.
+    current_module: Option<std::string::String>,
+
+    /// Modules that have been imported without an alias, keyed by the name
+    /// the module resolver gave them, so importing one twice runs it once.
+    imported: HashSet<std::string::String>,
+
+    /// Module objects for modules imported under an alias, by the same key.
+    namespaces: HashMap<std::string::String, Rc<Module>>,
+
+    /// The statements of every imported module.
+    module_code: Vec<Rc<Vec<Stmt>>>,
+
+    /// Symbols defined for conditional compilation, applied to imported modules.
+    defines: HashSet<std::string::String>,
+
+    /// The natives the globals started with, which tasks it spawns start
+    /// with too.
+    natives: Natives,
+
+    /// What the running script is allowed to do outside the interpreter.
+    policy: Policy,
+
+    /// While this is set, printed lines are collected here instead of being
+    /// written to stdout.
+    captured_output: Option<std::string::String>,
+
+    /// Printed lines wait here until there are enough of them to be worth a
+    /// write, so a program printing in a loop doesn't pay for one write per
+    /// line. Flushed by flush(), before an error is reported and once the
+    /// program is over.
+    stdout: BufWriter<Stdout>,
+
+    /// Whether printed lines may wait in the buffer, or are written right away.
+    buffered: bool,
+
+    /// The calls in progress, outermost first. The top-level script isn't a
+    /// call, so it has no frame of its own.
+    frames: Vec<Frame>,
+
+    /// Functions registered with atExit(), in the order they were registered,
+    /// each with the line it was registered on.
+    exit_hooks: Vec<(Object, usize)>,
+
+    /// Functions scheduled with setTimeout() that haven't been called yet, and
+    /// the id the next one will get. See run_event_loop.
+    timers: Vec<Timer>,
+    next_timer: usize,
+
+    /// Where the error being unwound happened, taken while the calls it is
+    /// unwinding out of still had their frames. Handed over to whatever
+    /// catches or reports the error.
+    error_trace: Option<Vec<std::string::String>>,
+
+    /// The setters running, innermost last, each with the instance and
+    /// property it is setting.
+    setting: Vec<(MutableInstance, std::string::String)>,
+
+    /// The limits of the program running, and when it runs out of each: the
+    /// count of statements executed it may not pass, and the time.
+    limits: Limits,
+    last_step: Option<usize>,
+    deadline: Option<Instant>,
+
+    /// Called with the line of every statement as it starts, and of every
+    /// check of a loop's condition, for tools like coverage and profilers.
+    line_hook: Option<Box<dyn FnMut(usize)>>,
+
+    /// Whether code inside functions is kept from changing globals.
+    globals_frozen: bool,
+
+    /// Whether `+` joins a string and a number.
+    mixed_concatenation: bool,
+
+    /// An error raised by code run by a native, like eval(), on its way out
+    /// of the native's call.
+    native_error: Option<Error>,
+
+    /// While a coroutine runs, how many frames there were when it was
+    /// resumed. The ones above are the coroutine's own, kept aside while it
+    /// is suspended.
+    coroutine_base: Option<usize>,
+
+    /// Tells this interpreter apart from every other one made by the
+    /// process, even one made later at the same address, for coroutines to
+    /// know which interpreter they run in (see resume).
+    id: usize,
+
+    /// The threads of the program this interpreter runs, or runs a task of,
+    /// and the tasks it has spawned itself, which it waits for before it is
+    /// done (see join_tasks).
+    tasks: Arc<TaskGroup>,
+    spawned: Vec<JoinHandle<()>>,
+
+    /// The macros the program was compiled with, to compile imported
+    /// modules and eval'd code with too.
+    macros: Vec<Rc<dyn Macro>>,
+
+    /// The built-in Error class, which caught runtime errors are instances
+    /// of. It is kept here so that a script defining an Error of its own
+    /// doesn't change what runtime errors are.
+    error_class: Rc<class::Class>,
+}
+
+/// A function scheduled with setTimeout(): when it is due, and the line it
+/// was scheduled on, as the place to report its errors.
+#[derive(Debug)]
+struct Timer {
+    id: usize,
+    due: Instant,
+    function: Object,
+    line: usize,
+}
+
+/// How long the event loop sleeps at a time while it waits for a timer, so
+/// that it notices being interrupted.
+const TIMER_POLL: Duration = Duration::from_millis(10);
+
+/// A call in progress: what was called, and the line it was called from.
+#[derive(Clone, Debug)]
+struct Frame {
+    function: std::string::String,
+    line: usize,
+}
+
+/// How much work the interpreter has done.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// Number of statements executed, including those inside function bodies.
+    pub statements: usize,
+
+    /// Number of calls made to functions, methods, and classes.
+    pub calls: usize,
+
+    /// How many times each kind of statement and expression was run, by the
+    /// name of the kind. Counting every node costs a little on each one, so
+    /// it is only done once asked for with `Interpreter::count_nodes`, and
+    /// is None until then.
+    pub nodes: Option<BTreeMap<&'static str, usize>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Interpreter {
-        let global = Environment::global_env();
+        let global = Environment::global_env(&Natives::standard());
         Self {
             environment: global.clone(),
+            error_class: error_class(&global),
             globals: global,
             locals: None,
+            stats: Stats::default(),
+            modules: Rc::new(FileResolver::with_includes(Vec::new())),
+            current_module: None,
+            imported: HashSet::new(),
+            namespaces: HashMap::new(),
+            module_code: Vec::new(),
+            defines: HashSet::new(),
+            natives: Natives::standard(),
+            policy: Policy::default(),
+            captured_output: None,
+            stdout: BufWriter::new(io::stdout()),
+            buffered: true,
+            frames: Vec::new(),
+            exit_hooks: Vec::new(),
+            timers: Vec::new(),
+            next_timer: 1,
+            error_trace: None,
+            setting: Vec::new(),
+            limits: Limits::default(),
+            last_step: None,
+            deadline: None,
+            line_hook: None,
+            globals_frozen: false,
+            mixed_concatenation: false,
+            native_error: None,
+            coroutine_base: None,
+            id: next_id(),
+            tasks: TaskGroup::new(),
+            spawned: Vec::new(),
+            macros: Vec::new(),
         }
     }
 
     pub fn new_with_resolver(locals: HashMap<*const Expr, usize>) -> Interpreter {
-        let global = Environment::global_env();
+        let global = Environment::global_env(&Natives::standard());
         Self {
             environment: global.clone(),
+            error_class: error_class(&global),
             globals: global,
             locals: Some(locals),
+            stats: Stats::default(),
+            modules: Rc::new(FileResolver::with_includes(Vec::new())),
+            current_module: None,
+            imported: HashSet::new(),
+            namespaces: HashMap::new(),
+            module_code: Vec::new(),
+            defines: HashSet::new(),
+            natives: Natives::standard(),
+            policy: Policy::default(),
+            captured_output: None,
+            stdout: BufWriter::new(io::stdout()),
+            buffered: true,
+            frames: Vec::new(),
+            exit_hooks: Vec::new(),
+            timers: Vec::new(),
+            next_timer: 1,
+            error_trace: None,
+            setting: Vec::new(),
+            limits: Limits::default(),
+            last_step: None,
+            deadline: None,
+            line_hook: None,
+            globals_frozen: false,
+            mixed_concatenation: false,
+            native_error: None,
+            coroutine_base: None,
+            id: next_id(),
+            tasks: TaskGroup::new(),
+            spawned: Vec::new(),
+            macros: Vec::new(),
+        }
+    }
+
+    /// Replaces the module resolver used by `import` statements.
+    pub fn set_module_resolver(&mut self, modules: Box<dyn ModuleResolver>) {
+        self.modules = Rc::from(modules);
+    }
+
+    /// A new interpreter that starts where this one is: same globals, same
+    /// imported modules, same settings. What either does to its globals from
+    /// then on, the other doesn't see.
+    ///
+    /// This is how a host pays for loading a prelude once, rather than once
+    /// per script. Forking copies the table of global bindings, not the
+    /// values in it, so it costs about as much as the number of globals.
+    /// Values that can be changed in place, like lists and instances, are
+    /// shared between the two.
+    ///
+    /// Only the global environment is copied, so this is meant to be called
+    /// between programs, not while one is running. Exit hooks stay behind.
+    pub fn fork(&self) -> Interpreter {
+        let globals = self.globals.borrow().fork();
+        Self {
+            environment: globals.clone(),
+            globals,
+            locals: self.locals.clone(),
+            stats: Stats::default(),
+            modules: self.modules.clone(),
+            current_module: self.current_module.clone(),
+            imported: self.imported.clone(),
+            namespaces: self.namespaces.clone(),
+            module_code: self.module_code.clone(),
+            defines: self.defines.clone(),
+            natives: self.natives.clone(),
+            policy: self.policy.clone(),
+            captured_output: None,
+            stdout: BufWriter::new(io::stdout()),
+            buffered: self.buffered,
+            frames: Vec::new(),
+            exit_hooks: Vec::new(),
+            timers: Vec::new(),
+            next_timer: 1,
+            error_trace: None,
+            setting: Vec::new(),
+            limits: Limits::default(),
+            last_step: None,
+            deadline: None,
+            line_hook: None,
+            globals_frozen: self.globals_frozen,
+            mixed_concatenation: self.mixed_concatenation,
+            native_error: None,
+            coroutine_base: None,
+            id: next_id(),
+            tasks: TaskGroup::new(),
+            spawned: Vec::new(),
+            macros: self.macros.clone(),
+            error_class: self.error_class.clone(),
+        }
+    }
+
+    /// Starts the globals over with the given natives in place of the
+    /// standard ones. Anything defined before is gone, so this is for setting
+    /// up an interpreter, before it runs anything.
+    pub fn set_natives(&mut self, natives: &Natives) {
+        let shadowing = self.globals.borrow().shadowing();
+        self.globals = Environment::global_env(natives);
+        self.globals.borrow_mut().set_shadowing(shadowing);
+        self.environment = self.globals.clone();
+        self.error_class = error_class(&self.globals);
+        self.natives = natives.clone();
+    }
+
+    /// Sets the macros that code compiled while the program runs, like
+    /// imported modules, goes through.
+    pub fn set_macros(&mut self, macros: Vec<Rc<dyn Macro>>) {
+        self.macros = macros;
+    }
+
+    /// Sets whether the program may declare globals with the names of
+    /// natives, replacing them. See policy::Shadowing.
+    pub fn set_shadowing(&mut self, shadowing: Shadowing) {
+        self.globals.borrow_mut().set_shadowing(shadowing);
+    }
+
+    /// A resolver for code compiled while the program runs, like imported
+    /// modules, that holds it to the same shadowing policy as the program.
+    fn resolver(&self) -> Resolver {
+        let globals = self.globals.borrow();
+        Resolver::new().with_shadowing(globals.shadowing(), globals.native_names().clone())
+    }
+
+    /// Sets the symbols that `#if` directives in imported modules test against.
+    pub fn set_defines(&mut self, defines: HashSet<std::string::String>) {
+        self.defines = defines;
+    }
+
+    /// Binds a name in the global environment, replacing any existing value.
+    pub fn define_global(&mut self, name: &str, value: Object) {
+        self.globals.borrow_mut().define(name.to_string(), value);
+    }
+
+    /// Returns the value of a global variable, if it is defined.
+    pub fn global(&self, name: &str) -> Option<Object> {
+        self.globals.borrow().get_own(name)
+    }
+
+    /// The name of every global, natives included, in no particular order.
+    pub fn global_names(&self) -> Vec<std::string::String> {
+        self.globals.borrow().names()
+    }
+
+    /// Describes the calls in progress, innermost first, in the form
+    /// "[line 3] in greet()". A frame only records where it was called from,
+    /// so the line a function has reached is the one its callee was called
+    /// from. The innermost frame is the native asking for the trace, which
+    /// is left out.
+    pub fn stack_trace(&self) -> Vec<std::string::String> {
+        match self.frames.last() {
+            Some(native) => self.trace(self.frames.len() - 1, native.line),
+            None => Vec::new(),
+        }
+    }
+
+    /// The trace of the first `depth` frames, when the innermost of them has
+    /// reached `line`.
+    fn trace(&self, depth: usize, mut line: usize) -> Vec<std::string::String> {
+        let mut trace = Vec::with_capacity(depth + 1);
+        for frame in self.frames[..depth].iter().rev() {
+            trace.push(format!("[line {line}] in {}()", frame.function));
+            line = frame.line;
+        }
+        trace.push(format!("[line {line}] in script"));
+        trace
+    }
+
+    /// Reports an error that ended the program, or the REPL line, or an exit
+    /// hook. A thrown value is reported with where it was thrown from.
+    pub fn report(&mut self, error: Error) {
+        // What the program printed before the error comes before it.
+        self.flush();
+        let trace = self.error_trace.take();
+        match error {
+            Error::Throw(value, keyword) => {
+                let trace = trace.unwrap_or_else(|| self.trace(self.frames.len(), keyword.line));
+                error::uncaught(self.uncaught_message(&value), &keyword, &trace);
+            }
+            RuntimeError(token, _) if interrupt::requested() => {
+                // Dealt with, so whatever runs next, like exit hooks or the
+                // REPL's next line, isn't stopped too.
+                interrupt::clear();
+                let trace = trace.unwrap_or_else(|| self.trace(self.frames.len(), token.line));
+                error::interrupted(&token, &trace);
+            }
+            error => error::runtime_error(error),
+        }
+    }
+
+    /// The name of the function that called the function asking, "script" if
+    /// that was the top level, or None if there is no function asking.
+    pub fn caller_name(&self) -> Option<std::string::String> {
+        // The last frame is the native itself, the one before that the
+        // function asking.
+        match self.frames.len() {
+            0..=1 => None,
+            2 => Some("script".into()),
+            n => Some(self.frames[n - 3].function.clone()),
+        }
+    }
+
+    /// Registers a function to be called, with no arguments, once the program
+    /// is over. Takes the line of the call in progress, the call to atExit(),
+    /// as the place to report the function's errors.
+    pub fn at_exit(&mut self, function: Object) {
+        let line = self.frames.last().map_or(0, |frame| frame.line);
+        self.exit_hooks.push((function, line));
+    }
+
+    /// Schedules a function to be called, with no arguments, once the delay
+    /// has passed and the program's top level is done (see run_event_loop).
+    /// Returns the timer's id, for clear_timeout, or None if the delay is
+    /// too long for the clock to say when it ends.
+    pub fn set_timeout(&mut self, function: Object, delay: Duration) -> Option<usize> {
+        let due = Instant::now().checked_add(delay)?;
+        let line = self.frames.last().map_or(0, |frame| frame.line);
+        let id = self.next_timer;
+        self.next_timer += 1;
+        self.timers.push(Timer { id, due, function, line });
+        Some(id)
+    }
+
+    /// Cancels a scheduled function. Returns whether it was still to be called.
+    pub fn clear_timeout(&mut self, id: usize) -> bool {
+        let count = self.timers.len();
+        self.timers.retain(|timer| timer.id != id);
+        self.timers.len() < count
+    }
+
+    /// Runs the event loop: calls the functions scheduled with setTimeout(),
+    /// each once its time has come, the one due first first, and those due
+    /// at the same time in the order they were scheduled. A function may
+    /// schedule more, and the loop goes on until none are left.
+    ///
+    /// Programs that don't schedule anything never get here with anything to
+    /// do. One that does runs its top level first, as usual, and is over once
+    /// its timers are. In between, nothing blocks: the loop sleeps until the
+    /// next timer is due.
+    ///
+    /// A function that fails is reported like any runtime error, and the rest
+    /// are dropped uncalled. Waiting is stopped the same way by being
+    /// interrupted, or by going over the program's time limit.
+    pub fn run_event_loop(&mut self) {
+        while let Some(next) = (0..self.timers.len()).min_by_key(|&i| (self.timers[i].due, self.timers[i].id)) {
+            let timer = self.timers.remove(next);
+            let paren = Token::new(RIGHT_PAREN, ")".into(), None, timer.line, 0);
+            let result = self.wait_until(timer.due, &paren).and_then(|_| {
+                self.frames.push(Frame { function: "setTimeout".into(), line: timer.line });
+                let result = self.call_checked(&timer.function, Vec::new(), &paren);
+                self.frames.pop();
+                result
+            });
+            if let Err(error) = result {
+                self.report(error);
+                self.timers.clear();
+            }
+        }
+        self.flush();
+    }
+
+    fn wait_until(&mut self, due: Instant, token: &Token) -> Result<(), Error> {
+        // What was printed shows up before the wait, not after it.
+        self.flush();
+        loop {
+            self.check_limits(token)?;
+            let now = Instant::now();
+            if now >= due {
+                return Ok(());
+            }
+            std::thread::sleep((due - now).min(TIMER_POLL));
         }
     }
 
+    /// Runs a program as a script whose result is its exit status: the value
+    /// of a `return` at its top level, which ends it there, or else of its
+    /// last statement, if that is an expression statement. The result has to
+    /// be an integer, or nil for 0. Errors are reported as by interpret(),
+    /// and then there is no exit status.
+    pub fn run_script(&mut self, statements: &[Stmt]) -> Option<i32> {
+        let result = match self.execute_for_value(statements) {
+            Err(Error::Return(value)) => Ok(value),
+            result => result,
+        };
+        let line = statements.iter().rev().find_map(Stmt::line).unwrap_or(1);
+        let status = result.and_then(|value| script_status(&value, line));
+        match status {
+            Ok(status) => {
+                self.flush();
+                Some(status)
+            }
+            Err(error) => {
+                self.report(error);
+                None
+            }
+        }
+    }
+
+    /// Calls the global function a program names as its entry point, like
+    /// `main`, once its top level has run, and gives the exit code it asks
+    /// for: the integer it returns, or 0 if it returns nil. The token is the
+    /// function's name, for errors in the call. An error, or a return value
+    /// that isn't an exit code, is reported, and there is no exit code.
+    pub fn call_entry_point(&mut self, name: &Token, args: Vec<Object>) -> Option<i32> {
+        let function = self.globals.borrow().get(name);
+        let result = function.and_then(|function| self.call_checked(&function, args, name));
+        let status = result.and_then(|value| {
+            exit_status(&value).ok_or_else(|| RuntimeError(name.clone(),
+                format!("{}() must return an integer or nil, not {value}.", name.lexeme),
+            ))
+        });
+        match status {
+            Ok(status) => Some(status),
+            Err(error) => {
+                self.report(error);
+                None
+            }
+        }
+    }
+
+    /// Calls the functions registered with atExit(), the most recently
+    /// registered first. This happens after the top-level program finishes,
+    /// or after the runtime error that stopped it has been reported. A hook
+    /// that fails is reported like any runtime error, and the rest still run.
+    /// A hook may register more hooks; they run too. Then, with the program
+    /// truly over, whatever it printed is flushed.
+    pub fn run_exit_hooks(&mut self) {
+        self.join_tasks();
+        while let Some((function, line)) = self.exit_hooks.pop() {
+            let paren = Token::new(RIGHT_PAREN, ")".into(), None, line, 0);
+            let result = match function.as_callable(&paren) {
+                Ok(callable) => {
+                    self.frames.push(Frame { function: "atExit".into(), line });
+                    let result = callable.call(self, Vec::new(), &paren);
+                    self.frames.pop();
+                    result
+                }
+                Err(error) => Err(error),
+            };
+            if let Err(error) = result {
+                self.report(error);
+            }
+        }
+        self.flush();
+    }
+
+    /// Runs the body of a try statement, and the handler if the body raised
+    /// an error and there is a catch clause.
+    fn execute_try(&mut self, body: &[Stmt], name: Option<&Token>, handler: &[Stmt]) -> Result<(), Error> {
+        let scope = Environment::new(self.environment.clone(), "try");
+        let result = self.execute_block(body, scope);
+        let Some(name) = name else { return result };
+        let error = match result {
+            Err(RuntimeError(token, message)) if self.over_limit().is_none() => {
+                let error = self.error_instance(message, &token);
+                self.caught(error, &token)
+            }
+            Err(Error::Throw(value, keyword)) => self.caught(*value, &keyword),
+            result => return result,
+        };
+        let scope = Environment::new(self.environment.clone(), "catch");
+        scope.borrow_mut().define(name.lexeme.clone(), error);
+        self.execute_block(handler, scope)
+    }
+
+    /// A runtime error as a value a script can catch: an instance of Error,
+    /// with its message and line. caught() adds the stack.
+    fn error_instance(&self, message: std::string::String, token: &Token) -> Object {
+        let mut error = instance::Instance::new(self.error_class.clone());
+        error.fields.insert("message".into(), String(message));
+        error.fields.insert("line".into(), Number(token.line as f64));
+        Instance(Rc::new(RefCell::new(error)))
+    }
+
+    /// How a thrown value nothing caught is reported. An Error, or an
+    /// instance of a subclass of it, shows its class and message, so
+    /// rethrowing a caught runtime error still says what went wrong.
+    pub(crate) fn uncaught_message(&self, value: &Object) -> std::string::String {
+        if let Instance(instance) = value {
+            let instance = instance.borrow();
+            if let (true, Some(message)) = (instance.klass.inherits_from(&self.error_class), instance.fields.get("message")) {
+                return format!("Uncaught {}: {message}", instance.klass.name);
+            }
+        }
+        format!("Uncaught {value}")
+    }
+
+    /// Gives a caught error to its handler. An instance that was thrown gets
+    /// a `stack` field listing where it was thrown from, innermost first, if
+    /// it doesn't have one already.
+    fn caught(&mut self, error: Object, token: &Token) -> Object {
+        let trace = self.error_trace.take().unwrap_or_else(|| self.trace(self.frames.len(), token.line));
+        if let Instance(instance) = &error {
+            let stack = Token { lexeme: "stack".into(), ..token.clone() };
+            if !instance.borrow().fields.contains_key("stack") {
+                let trace = trace.into_iter().map(String).collect();
+                instance.borrow_mut().set(&stack, List(Rc::new(RefCell::new(trace))));
+            }
+        }
+        error
+    }
+
+    /// Calls a function or class, after checking that it takes as many
+    /// arguments as it is given.
+    pub(crate) fn call_checked(&mut self, callee: &Object, args: Vec<Object>, paren: &Token) -> Result<Object, Error> {
+        let callable = callee.as_callable(paren)?;
+        if callable.variadic() && args.len() < callable.arity() {
+            return Err(RuntimeError(paren.clone(),
+                format!("Expected at least {} arguments but got {}.", callable.arity(), args.len()),
+            ));
+        }
+        if !callable.variadic() && args.len() != callable.arity() {
+            return Err(RuntimeError(paren.clone(),
+                format!("Expected {} arguments but got {}.", callable.arity(), args.len()),
+            ));
+        }
+        self.call(callee, args, paren)
+    }
+
+    /// Calls a method the language itself looks for on an instance, like
+    /// equals() or hash(), if the instance's class has one. The token is the
+    /// operator or bracket that caused the call, for errors in it.
+    fn call_hook(&mut self, object: &Object, method: &str, args: Vec<Object>, token: &Token) -> Option<Result<Object, Error>> {
+        let Instance(instance) = object else {
+            return None;
+        };
+        let method = instance.borrow().klass.find_method(method)?;
+        Some(self.call_checked(&Function(method.bind(object)), args, token))
+    }
+
+    /// Whether two values are equal, for `==` and `!=`. When both are
+    /// instances and the left one's class has an equals() method, it is
+    /// called with the right one, and whatever it returns is taken as true or
+    /// false. So a class of values, like points, can have two instances with
+    /// the same fields count as equal. An instance is never equal to a value
+    /// of another kind, whichever side it is on.
+    fn equal(&mut self, left: Object, right: Object, operator: &Token) -> Result<bool, Error> {
+        if !matches!((&left, &right), (Instance(_), Instance(_))) {
+            return Ok(left.is_equal(right));
+        }
+        match self.call_hook(&left, "equals", vec![right.clone()], operator) {
+            Some(result) => Ok(result?.is_truthy()),
+            None => Ok(left.is_equal(right)),
+        }
+    }
+
+    /// The key a value is stored under in the map, or None if it can't be a
+    /// key. An instance can be one if its class has a hash() method, which
+    /// has to return a number or a string. If the map already has a key with
+    /// the same hash that the instance equals(), that key is the one given,
+    /// so a hash shared by instances that aren't equal doesn't make them the
+    /// same key. Without an equals() method, the hash alone decides. See
+    /// map::InstanceKey.
+    pub(crate) fn map_key(&mut self, map: &MutableMap, value: &Object, token: &Token) -> Result<Option<MapKey>, Error> {
+        let Some(hash) = self.call_hook(value, "hash", Vec::new(), token) else {
+            return Ok(MapKey::from_object(value));
+        };
+        let Instance(instance) = value else { unreachable!("only instances have hooks") };
+        let hash = match MapKey::from_object(&hash?) {
+            Some(hash @ (MapKey::Number(_) | MapKey::String(_))) => hash,
+            _ => return Err(RuntimeError(token.clone(), "hash() must return a number or a string.".into())),
+        };
+        // The keys are copied out first, since equals() may use the map.
+        let same_hash: Vec<InstanceKey> = map
+            .borrow()
+            .keys()
+            .filter_map(|key| match key {
+                MapKey::Instance(key) if *key.hash == hash => Some(key.clone()),
+                _ => None,
+            })
+            .collect();
+        for key in same_hash {
+            let other = Instance(key.instance.clone());
+            let equal = match self.call_hook(value, "equals", vec![other], token) {
+                Some(result) => result?.is_truthy(),
+                None => true,
+            };
+            if equal {
+                return Ok(Some(MapKey::Instance(key)));
+            }
+        }
+        Ok(Some(MapKey::Instance(InstanceKey { hash: Box::new(hash), instance: instance.clone() })))
+    }
+
+    /// Like map_key, for natives, which fail with a message. An error raised
+    /// in hash() is kept to unwind out of the native's call, as eval does.
+    pub(crate) fn native_map_key(&mut self, map: &MutableMap, value: &Object) -> Result<Option<MapKey>, std::string::String> {
+        let paren = self.native_paren();
+        self.map_key(map, value, &paren).map_err(|error| self.native_failure(error))
+    }
+
+    /// Calls a function or class for a native, like the comparator given to
+    /// sort(). A native can call back into the script this way as often as it
+    /// needs to, and the calls can call natives in turn: each is checked and
+    /// traced like a call in the script, made from the line the native was
+    /// called on. An error raised in one, thrown or not, unwinds out of the
+    /// native's call just as it was raised, as with eval.
+    pub fn call_from_native(&mut self, callee: &Object, args: Vec<Object>) -> Result<Object, std::string::String> {
+        let paren = self.native_paren();
+        self.call_checked(callee, args, &paren).map_err(|error| self.native_failure(error))
+    }
+
+    /// A token standing for the call of the native running, for errors in
+    /// the calls it makes, since natives are given no token of their own.
+    fn native_paren(&self) -> Token {
+        let line = self.frames.last().map_or(0, |frame| frame.line);
+        Token::new(RIGHT_PAREN, ")".into(), None, line, 0)
+    }
+
+    /// Calls a function or class whose arguments have been checked, in a
+    /// frame of its own.
+    fn call(&mut self, callee: &Object, args: Vec<Object>, paren: &Token) -> Result<Object, Error> {
+        let callable = callee.as_callable(paren)?;
+        self.check_limits(paren)?;
+        self.stats.calls += 1;
+        let function = match callee {
+            Function(function) => function.name(),
+            Class(class) => class.name.clone(),
+            _ => unreachable!("as_callable() only accepts functions and classes"),
+        };
+        self.frames.push(Frame { function, line: paren.line });
+        let result = callable.call(self, args, paren);
+        if let Err(RuntimeError(token, _) | Error::Throw(_, token)) = &result {
+            // The innermost call the error leaves is where it happened.
+            if self.error_trace.is_none() {
+                self.error_trace = Some(self.trace(self.frames.len(), token.line));
+            }
+        }
+        self.frames.pop();
+        result
+    }
+
+    /// Looks up a property of an instance, a module, or a class. A getter
+    /// found this way is run, and what it returns is the property's value.
+    ///
+    /// Instances and their class each have methods the other doesn't, so a
+    /// method looked up on the wrong one fails saying where it is instead.
+    fn get_property(&mut self, object: Object, name: &Token) -> Result<Object, Error> {
+        let property = match &object {
+            Instance(instance) => match instance::Instance::get(instance, name) {
+                Ok(property) => property,
+                Err(_) if instance.borrow().klass.find_class_method(&name.lexeme).is_some() => {
+                    let class = instance.borrow().klass.name.clone();
+                    let message = format!("'{}' is a class method of '{class}'; call it on the class.", name.lexeme);
+                    return Err(RuntimeError(name.clone(), message));
+                }
+                Err(error) => return Err(error),
+            },
+            Object::Module(module) => return module.get(name),
+            // `config.port` is short for `config["port"]`.
+            Map(map) => {
+                let key = MapKey::String(name.lexeme.clone());
+                return map.borrow().get(&key).cloned().ok_or_else(|| RuntimeError(name.clone(), format!("Undefined key '{key}'.")));
+            }
+            Class(klass) => match klass.find_class_method(&name.lexeme) {
+                Some(method) => Function(method.bind(&object)),
+                None if klass.find_method(&name.lexeme).is_some() || klass.find_setter(&name.lexeme).is_some() => {
+                    let message = format!("'{}' is an instance method of '{}'; call it on an instance.", name.lexeme, klass.name);
+                    return Err(RuntimeError(name.clone(), message));
+                }
+                None => {
+                    let message = format!("Class '{}' has no class method '{}'.", klass.name, name.lexeme);
+                    return Err(RuntimeError(name.clone(), message));
+                }
+            },
+            other => {
+                let message = format!("Can't read property '{}' of a {}.", name.lexeme, other.type_name());
+                return Err(RuntimeError(name.clone(), message));
+            }
+        };
+        match property {
+            Function(getter) if getter.is_getter() => self.call(&Function(getter), Vec::new(), name),
+            property => Ok(property),
+        }
+    }
+
+    /// Assigns to a property of an instance, through its class's setter if it
+    /// has one. Inside the setter, assigning to the property it sets on the
+    /// same instance writes the field, so a setter can check a value and
+    /// then store it under its own name.
+    fn set_property(&mut self, instance: &MutableInstance, name: &Token, value: Object) -> Result<(), Error> {
+        let setting = self.setting.iter().any(|(target, property)| Rc::ptr_eq(target, instance) && *property == name.lexeme);
+        let setter = instance.borrow().klass.find_setter(&name.lexeme);
+        match setter {
+            Some(setter) if !setting => {
+                self.setting.push((instance.clone(), name.lexeme.clone()));
+                let result = self.call(&Function(setter.bind(&Instance(instance.clone()))), vec![value], name);
+                self.setting.pop();
+                result.map(|_| ())
+            }
+            _ => {
+                instance.borrow_mut().set(name, value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts collecting printed output instead of writing it to stdout.
+    pub fn start_capture(&mut self) {
+        self.captured_output.get_or_insert_with(std::string::String::new);
+    }
+
+    /// Stops collecting output and returns what was printed since start_capture().
+    pub fn finish_capture(&mut self) -> std::string::String {
+        self.captured_output.take().unwrap_or_default()
+    }
+
+    fn write_line(&mut self, line: &str) {
+        match &mut self.captured_output {
+            Some(output) => {
+                output.push_str(line);
+                output.push('\n');
+            }
+            None => {
+                // If stdout has gone away there is no one to tell.
+                let _ = writeln!(self.stdout, "{line}");
+                if !self.buffered {
+                    self.flush();
+                }
+            }
+        }
+    }
+
+    /// Writes a line to stderr, for the program's diagnostics. What it
+    /// printed to stdout so far is flushed first, so that on a terminal the
+    /// two come out in the order they were written.
+    pub fn write_error_line(&mut self, line: &str) {
+        self.flush();
+        eprintln!("{line}");
+    }
+
+    /// Chooses whether printed lines are buffered, which they are unless
+    /// turned off. An interactive session wants to see each line as soon as
+    /// it is printed.
+    pub fn set_buffered(&mut self, buffered: bool) {
+        self.buffered = buffered;
+        if !buffered {
+            self.flush();
+        }
+    }
+
+    /// Writes out any printed lines still waiting in the buffer.
+    pub fn flush(&mut self) {
+        let _ = self.stdout.flush();
+    }
+
+    /// Asks to be told the line of each statement the interpreter executes,
+    /// as it starts. A loop reports each check of whether to go round again:
+    /// a while loop, or a for loop desugared into one, at its condition
+    /// before every iteration and at its increment after, a for-in loop at
+    /// the line of its `in` before it starts and after each element. A block is
+    /// only the statements in it, so it isn't reported itself.
+    pub fn on_line(&mut self, hook: impl FnMut(usize) + 'static) {
+        self.line_hook = Some(Box::new(hook));
+    }
+
+    fn line_event(&mut self, line: Option<usize>) {
+        if let (Some(hook), Some(line)) = (&mut self.line_hook, line) {
+            hook(line);
+        }
+    }
+
+    /// Freezes the globals, as far as functions are concerned: from now on,
+    /// a function that assigns to a global variable fails with a runtime
+    /// error. Top-level code still declares and sets globals as usual. In a
+    /// larger program that's where the globals are set up, and a function
+    /// writing one is more likely a typo or a missing `var` than intended.
+    pub fn freeze_globals(&mut self) {
+        self.globals_frozen = true;
+    }
+
+    /// Lets `+` join a string and a number, writing the number as `print`
+    /// would: `"count: " + 3` is `"count: 3"`, and `1.5 + "x"` is `"1.5x"`.
+    /// Plain Lox only adds two numbers or joins two strings, and anything
+    /// else fails, so that is still what happens unless this is called.
+    /// Other values aren't turned into strings either way: `"a" + nil` is
+    /// more likely a mistake than meant.
+    pub fn allow_mixed_concatenation(&mut self) {
+        self.mixed_concatenation = true;
+    }
+
+    /// Starts counting how many times each kind of statement and expression
+    /// runs, in `stats.nodes`. It tells which parts of the language a program
+    /// leans on: a loop that reads a variable on each pass shows up as a
+    /// great many Variable nodes.
+    pub fn count_nodes(&mut self) {
+        self.stats.nodes.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Limits what the program about to run may do, counting from now.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.last_step = limits.max_steps.map(|steps| self.stats.statements + steps);
+        self.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    /// Which limit the program has gone over, if any, as the message to stop
+    /// it with. Once a program is over a limit it stays over it, which is what
+    /// keeps `try` from catching the error. Being interrupted counts as going
+    /// over a limit, and lasts until the error is reported.
+    fn over_limit(&self) -> Option<std::string::String> {
+        if interrupt::requested() {
+            return Some("Interrupted.".into());
+        }
+        if let (Some(last), Some(steps)) = (self.last_step, self.limits.max_steps) {
+            if self.stats.statements > last {
+                return Some(format!("Exceeded the limit of {steps} steps."));
+            }
+        }
+        match (self.deadline, self.limits.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                Some(format!("Exceeded the time limit of {timeout:?}."))
+            }
+            _ => None,
+        }
+    }
+
+    /// Stops the program if it has gone over one of its limits. Only a loop or
+    /// a call can keep a program running for long, so this is checked on
+    /// every iteration and every call, at the token given.
+    fn check_limits(&self, token: &Token) -> Result<(), Error> {
+        match self.over_limit() {
+            Some(message) => Err(RuntimeError(token.clone(), message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Restricts what scripts can do outside the interpreter.
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    /// Fails with a runtime error at the given token unless the policy
+    /// allows the capability.
+    pub fn require(&self, capability: Capability, token: &Token) -> Result<(), Error> {
+        self.allow(capability).map_err(|message| RuntimeError(token.clone(), message))
+    }
+
+    /// Like require(), for natives, which report errors as a message.
+    pub fn allow(&self, capability: Capability) -> Result<(), std::string::String> {
+        match self.policy.allows(capability) {
+            true => Ok(()),
+            false => Err(format!("The '{capability}' capability is not allowed.")),
+        }
+    }
+
+    /// The environment the code running right now defines its variables in.
+    pub fn current_environment(&self) -> MutableEnvironment {
+        self.environment.clone()
+    }
+
+    /// Compiles the source and runs it in the given environment, as if it had
+    /// been written where that environment is: it sees the variables there,
+    /// and what it declares at its top level is defined there. If the code
+    /// ends with an expression statement, its value is returned, and
+    /// otherwise nil.
+    ///
+    /// This is for natives, so it fails with a message. Errors in compiling
+    /// the code are collected rather than reported, and the call fails with
+    /// the first of them, so a `try` around it can catch that like any other
+    /// failure and the program isn't marked as having failed to compile.
+    /// Warnings are reported as usual. An error raised by the code, thrown or
+    /// not, is kept to unwind out of the native's call just as it was raised
+    /// (see take_native_error), so that a `try` around the call can catch
+    /// what was thrown.
+    pub fn eval_in(&mut self, environment: MutableEnvironment, source: &str) -> Result<Object, std::string::String> {
+        let pipeline = Pipeline::new().with_defines(self.defines.clone()).with_macros(&self.macros);
+        let (compiled, diagnostics) = error::collect(|| {
+            let tokens = pipeline.preprocess(pipeline.scan(source));
+            let program = pipeline.parse(tokens, source)?;
+            let statements = Rc::new(pipeline.optimize(program).statements);
+            let locals = self.locals.as_ref().map(|_| self.resolver().resolve_in(&environment.borrow().local_names(), &statements));
+            Some((statements, locals))
+        });
+        let (errors, warnings): (Vec<_>, Vec<_>) = diagnostics.into_iter().partition(|diagnostic| diagnostic.kind == "syntax");
+        warnings.into_iter().for_each(error::restate);
+        let (statements, locals) = match (compiled, errors.first()) {
+            (Some(compiled), None) => compiled,
+            (_, Some(error)) => {
+                return Err(format!("Could not compile the code given to eval: [line {}] {}", error.line, error.message))
+            }
+            (None, None) => return Err("Could not compile the code given to eval.".into()),
+        };
+        if let Some(locals) = locals {
+            self.add_locals(locals);
+        }
+        // The side table points into the code, so it has to be kept.
+        self.module_code.push(statements.clone());
+
+        let previous = std::mem::replace(&mut self.environment, environment);
+        let result = self.execute_for_value(&statements);
+        self.environment = previous;
+        result.map_err(|error| self.native_failure(error))
+    }
+
+    /// Runs statements, and gives the value of the last one if it is an
+    /// expression statement, or otherwise nil.
+    fn execute_for_value(&mut self, statements: &[Stmt]) -> Result<Object, Error> {
+        match statements.split_last() {
+            Some((Stmt::Expression { expression }, rest)) => {
+                rest.iter().try_for_each(|statement| self.execute(statement)).and_then(|_| self.evaluate(expression))
+            }
+            _ => statements.iter().try_for_each(|statement| self.execute(statement)).map(|_| Nil),
+        }
+    }
+
+    /// Keeps an error raised by code a native ran, to unwind out of the
+    /// native's call in place of its message (see take_native_error), and
+    /// gives the message for the native to fail with.
+    fn native_failure(&mut self, error: Error) -> std::string::String {
+        let message = match &error {
+            RuntimeError(_, message) => message.clone(),
+            Error::Throw(value, _) => self.uncaught_message(value),
+            _ => std::string::String::new(),
+        };
+        self.native_error = Some(error);
+        message
+    }
+
+    /// The error raised by code a native ran, if the native failed because of
+    /// one, to unwind out of its call in place of the native's message.
+    pub(crate) fn take_native_error(&mut self) -> Option<Error> {
+        self.native_error.take()
+    }
+
+    /// Runs a coroutine until it yields or finishes, and gives what it
+    /// yielded or returned. The first resume calls its function, with the
+    /// value as the argument if the function takes one.
+    ///
+    /// While it runs, the coroutine's calls go on top of the resumer's, so
+    /// a stack trace shows both. An error it raises unwinds out of the
+    /// resume, as with eval, and the coroutine is finished.
+    pub(crate) fn resume(&mut self, coroutine: &coroutine::Coroutine, value: Object) -> Result<Object, std::string::String> {
+        let paren = self.native_paren();
+        let function = Function(coroutine.function().clone());
+        let takes_value = function.as_callable(&paren).is_ok_and(|callable| callable.arity() > 0);
+        let interpreter: *mut Interpreter = self;
+        // The coroutine's calls keep using the interpreter that started it,
+        // where it was then, for as long as they run. So it can only be
+        // resumed by that same interpreter, in the same place: not a fork of
+        // it, nor one that has been moved, which it would find gone.
+        let owner = (self.id, interpreter as usize);
+        let start = move |value| {
+            // Safety: the coroutine's thread only runs while the thread that
+            // resumed it waits, below, and only ever for the interpreter that
+            // started it, so the interpreter is there, and never used by both
+            // at once (see Coroutine).
+            let interpreter = unsafe { &mut *interpreter };
+            let args = if takes_value { vec![value] } else { Vec::new() };
+            interpreter.call(&function, args, &paren)
+        };
+
+        let environment = self.environment.clone();
+        let base = self.frames.len();
+        let outer = self.coroutine_base.replace(base);
+        let stop = coroutine.resume(owner, value, start);
+        self.coroutine_base = outer;
+        self.environment = environment;
+        self.frames.truncate(base);
+        match stop? {
+            Stop::Yielded(value) => Ok(value),
+            Stop::Finished(result) => result.map_err(|error| self.native_failure(error)),
+        }
+    }
+
+    /// Suspends the coroutine running, handing the value to whatever resumed
+    /// it, and gives the value it is resumed with next.
+    pub(crate) fn yield_value(&mut self, value: Object) -> Result<Object, std::string::String> {
+        let Some(base) = self.coroutine_base else {
+            return Err("Can't yield outside a coroutine.".into());
+        };
+        let environment = self.environment.clone();
+        let frames = self.frames.split_off(base);
+        let resumed = coroutine::suspend(value).expect("coroutines run on threads of their own");
+        self.environment = environment;
+        self.frames.extend(frames);
+        Ok(resumed)
+    }
+
+    /// Starts a task that calls the function on a thread of its own, with
+    /// the argument if the function takes one, and gives the channel that
+    /// what it returns will be sent on. See Task.
+    pub(crate) fn spawn(&mut self, function: &Function, argument: Object) -> Result<Object, std::string::String> {
+        self.allow(Capability::Threads)?;
+        let Function::UserDefined { declaration, .. } = function else {
+            return Err("Can't spawn a native function.".into());
+        };
+        let argument = match declaration.params.is_empty() {
+            true => None,
+            false => Some(Message::from_object(&argument)?),
+        };
+        let result = Arc::new(Channel::new());
+        // What the program printed comes before anything the task prints.
+        self.flush();
+        let policy = self.policy.clone();
+        let settings = Settings { natives: self.natives.clone(), policy, limits: self.limits, mixed_concatenation: self.mixed_concatenation };
+        let task = Task::new(declaration, argument, settings, self.tasks.clone(), result.clone());
+        self.spawned.push(task.start());
+        Ok(Channel(result))
+    }
+
+    /// Waits for the tasks this interpreter has spawned to finish. A program
+    /// isn't over until its tasks are, so this is the first thing that
+    /// happens once it is (see run_exit_hooks).
+    pub fn join_tasks(&mut self) {
+        let spawned = std::mem::take(&mut self.spawned);
+        if spawned.is_empty() {
+            return;
+        }
+        self.tasks.waiting(|| {
+            for task in spawned {
+                let _ = task.join();
+            }
+        });
+    }
+
+    /// The threads of the program, for natives that wait on the others.
+    pub(crate) fn task_group(&self) -> &TaskGroup {
+        &self.tasks
+    }
+
+    /// Makes this interpreter one running a task, among the threads of the
+    /// program that spawned it.
+    pub(crate) fn set_task_group(&mut self, tasks: Arc<TaskGroup>) {
+        self.tasks = tasks;
+    }
+
+    /// Sets the name of the script about to run, so imports in it can be
+    /// resolved relative to its location.
+    pub fn set_script_name(&mut self, name: &str) {
+        self.current_module = Some(name.to_string());
+    }
+
+    /// Merges a table produced by another call to `Resolver::resolve`. This is how
+    /// an interpreter that outlives a single program (a REPL session) learns about
+    /// the locals in each new piece of code before running it.
+    pub fn add_locals(&mut self, locals: HashMap<*const Expr, usize>) {
+        self.locals.get_or_insert_with(HashMap::new).extend(locals);
+    }
+
     /// Takes in a list of statements — in other words, a program.
-    pub fn interpret(&mut self, statements: &Vec<Stmt>) {
+    pub fn interpret(&mut self, statements: &[Stmt]) {
         for statement in statements {
             match self.execute(statement) {
                 Ok(_) => continue,
                 Err(error) => {
-                    error::runtime_error(error);
+                    self.report(error);
                     break;
                 }
             }
         }
+        self.flush();
     }
     
-    pub fn execute_block(&mut self, statements: &Vec<Stmt>, block_scope: MutableEnvironment) -> Result<(), Error> {
+    pub fn execute_block(&mut self, statements: &[Stmt], block_scope: MutableEnvironment) -> Result<(), Error> {
         let previous = self.environment.clone();
         self.environment = block_scope;
         let result = statements.iter().try_for_each(|stmt| self.execute(stmt));
@@ -76,23 +1216,36 @@ impl Interpreter {
 
     /// This is the statement analogue to the evaluate() method we have for expressions.
     /// Unlike expressions, statements produce no values, so the return type is Void, not Object.
-    fn execute(&mut self, stmt: &Stmt) -> Result<(), Error> {
+    pub(crate) fn execute(&mut self, stmt: &Stmt) -> Result<(), Error> {
+        self.stats.statements += 1;
+        if let Some(nodes) = &mut self.stats.nodes {
+            *nodes.entry(stmt.kind()).or_insert(0) += 1;
+        }
+        if self.line_hook.is_some() && !matches!(stmt, Stmt::While { .. }) {
+            self.line_event(stmt.line());
+        }
         match stmt {
             Stmt::Expression { expression } => {
                 self.evaluate(expression)?;
                 Ok(())
             }
-            Stmt::Print { expression } => {
+            Stmt::Print { keyword, expression } => {
+                self.require(Capability::Print, keyword)?;
                 let evaluated = self.evaluate(expression)?;
-                println!("{evaluated}");
+                self.write_line(&evaluated.to_string());
                 Ok(())
             }
-            Stmt::Var { name, initializer } => {
+            Stmt::Var { name, initializer, mutable, .. } => {
                 let mut value = Nil;
                 if let Some(expr) = initializer {
                     value = self.evaluate(expr)?;
                 }
-                self.environment.borrow_mut().define(name.lexeme.clone(), value.clone());
+                self.environment.borrow().check_declaration(name)?;
+                if *mutable {
+                    self.environment.borrow_mut().define(name.lexeme.clone(), value.clone());
+                } else {
+                    self.environment.borrow_mut().define_constant(name.lexeme.clone(), value.clone());
+                }
                 Ok(())
             }
             Stmt::Block { statements } => {
@@ -100,25 +1253,42 @@ impl Interpreter {
                 self.execute_block(statements, block_scope)?;
                 Ok(())
             }
-            Stmt::Class { name, superclass, methods } => {
+            Stmt::Class { name, superclass, interfaces, methods, class_methods: class_level } => {
                 // Step 1: Evaluate superclass (if present)
                 let superclass_klass = if let Some(expr) = superclass {
+                    // The error points at the superclass's name, which may be
+                    // on another line than the class's.
+                    let token = match expr {
+                        Expr::Variable { name } => name,
+                        _ => name,
+                    };
                     match self.evaluate(expr)? {
-                        Class(klass) => Some(Rc::new(klass)),
-                        _ => return Err(RuntimeError(name.clone(), "Superclass must be a class.".into())),
+                        Class(klass) => Some(klass),
+                        _ => return Err(RuntimeError(token.clone(), "Superclass must be a class.".into())),
                     }
                 } else {
                     None
                 };
 
+                // The interfaces it implements are evaluated alongside it.
+                let mut implemented = Vec::new();
+                for expr in interfaces {
+                    let Expr::Variable { name: token } = expr else { unreachable!("interfaces are named") };
+                    match self.evaluate(expr)? {
+                        Interface(interface) => implemented.push(interface),
+                        _ => return Err(RuntimeError(token.clone(), "Can only implement an interface.".into())),
+                    }
+                }
+
                 // Step 2: Predefine the class name in the environment to allow self-references
+                self.environment.borrow().check_declaration(name)?;
                 self.environment.borrow_mut().define(name.lexeme.clone(), Nil);
 
                 // Step 3: Create the environment where methods will close over
                 let fn_env = match &superclass_klass {
                     Some(super_klass) => {
                         let super_env = Environment::new(self.environment.clone(), "super env");
-                        let super_object = Class(super_klass.as_ref().clone());
+                        let super_object = Class(super_klass.clone());
                         super_env.borrow_mut().define("super".into(), super_object);
                         super_env
                     }
@@ -127,14 +1297,33 @@ impl Interpreter {
 
                 // Step 4: Convert each method declaration into a Function
                 let mut class_methods = HashMap::new();
+                let mut setters = HashMap::new();
                 for method in methods {
-                    let is_init = method.name.lexeme == "init";
+                    let is_init = method.name.lexeme == "init" && !method.setter;
                     let func = Function::new(method.clone(), fn_env.clone(), is_init);
-                    class_methods.insert(method.name.lexeme.clone(), func); 
+                    match method.setter {
+                        true => setters.insert(method.name.lexeme.clone(), func),
+                        false => class_methods.insert(method.name.lexeme.clone(), func),
+                    };
                 }
 
+                // Methods on the class itself are never initializers.
+                let class_level_methods = class_level
+                    .iter()
+                    .map(|method| (method.name.lexeme.clone(), Function::new(method.clone(), fn_env.clone(), false)))
+                    .collect();
+
                 // Step 5: Construct the class and assign it to the original variable name
-                let class_obj = Class(class::Class::new(name.lexeme.clone(), superclass_klass, class_methods));
+                let klass = class::Class::new(
+                    name.lexeme.clone(), superclass_klass, class_methods, class_level_methods, setters, implemented,
+                );
+
+                // Once it has all its methods, inherited ones included, it can
+                // be checked against the interfaces it says it implements.
+                for interface in &klass.interfaces {
+                    interface.check(&klass).map_err(|message| RuntimeError(name.clone(), message))?;
+                }
+                let class_obj = Class(Rc::new(klass));
                 self.environment.borrow_mut().assign(name.clone(), class_obj)?;
                 Ok(())
             }
@@ -147,12 +1336,52 @@ impl Interpreter {
                 }
                 Ok(())
             },
-            Stmt::While { condition, body } => {
-                while self.evaluate(condition)?.is_truthy() {
-                    self.execute(body)?;
+            Stmt::While { keyword, condition, body, increment, label } => {
+                let condition_line = condition.line().unwrap_or(keyword.line);
+                while {
+                    self.line_event(Some(condition_line));
+                    self.evaluate(condition)?.is_truthy()
+                } {
+                    self.check_limits(keyword)?;
+                    if !keep_looping(self.execute(body), label)? {
+                        break;
+                    }
+                    if let Some(increment) = increment {
+                        self.line_event(increment.line().or(Some(keyword.line)));
+                        self.evaluate(increment)?;
+                    }
+                }
+                Ok(())
+            },
+            Stmt::ForIn { name, keyword, collection, body, label } => {
+                // The elements are copied out before the loop starts, so the
+                // body can change the collection without upsetting the loop.
+                let items: Vec<Object> = match self.evaluate(collection)? {
+                    List(list) => list.borrow().clone(),
+                    Map(entries) => map::sorted_keys(&entries.borrow()).iter().map(MapKey::to_object).collect(),
+                    String(string) => string.chars().map(|c| String(c.to_string())).collect(),
+                    _ => return Err(RuntimeError(keyword.clone(), "Can only iterate over lists, maps and strings.".into())),
+                };
+                for item in items {
+                    self.check_limits(keyword)?;
+                    // Each iteration gets a fresh variable, so closures made in
+                    // the body capture the element they were made for.
+                    let scope = Environment::new(self.environment.clone(), "block");
+                    scope.borrow_mut().define(name.lexeme.clone(), item);
+                    if !keep_looping(self.execute_block(std::slice::from_ref(body.as_ref()), scope), label)? {
+                        break;
+                    }
+                    self.line_event(Some(keyword.line));
                 }
                 Ok(())
             },
+            Stmt::Break { label, .. } => {
+                // Like return, break is implemented by unwinding. The loop that
+                // catches it is the nearest one, or the one with the label, so
+                // that's the one we leave.
+                Err(Error::Break(label.as_ref().map(|label| label.lexeme.clone())))
+            },
+            Stmt::Continue { label, .. } => Err(Error::Continue(label.as_ref().map(|label| label.lexeme.clone()))),
             Stmt::Function { decl } => {
                 // This is similar to how we interpret other literal expressions. We take a
                 // function syntax node (Stmt::Function) — a compile-time representation of
@@ -161,6 +1390,7 @@ impl Interpreter {
                 //
                 // Also, this closure “closes over” and holds on to the surrounding variables
                 // where the function is declared.
+                self.environment.borrow().check_declaration(&decl.name)?;
                 let func = Function::new(decl.clone(), self.environment.clone(), false);
                 let name = func.name();
                 let value = Function(func);
@@ -182,6 +1412,95 @@ impl Interpreter {
                 // that began executing the body.
                 Err(Error::Return(return_value))
             },
+            Stmt::Throw { keyword, value } => Err(Error::Throw(Box::new(self.evaluate(value)?), keyword.clone())),
+            Stmt::Try { body, name, handler, finally } => {
+                let result = self.execute_try(body, name.as_ref(), handler);
+                let Some(finally) = finally else { return result };
+                // A run stopped for going over its limits is stopped, with
+                // no more code run, finally blocks included.
+                if self.over_limit().is_some() {
+                    return result;
+                }
+                // However the rest ended, that carries on after the finally
+                // block, unless the block cuts it short with an ending of its own.
+                // The trace of an error still unwinding is put aside, so that
+                // it goes with that error if it carries on, and is dropped
+                // along with it if the block ends some other way.
+                let pending_trace = self.error_trace.take();
+                let scope = Environment::new(self.environment.clone(), "finally");
+                self.execute_block(finally, scope)?;
+                self.error_trace = pending_trace;
+                result
+            },
+            Stmt::Interface { name, methods } => {
+                let methods = methods.iter().map(|method| (method.name.lexeme.clone(), method.params.len())).collect();
+                let interface = interface::Interface::new(name.lexeme.clone(), methods);
+                self.environment.borrow().check_declaration(name)?;
+                self.environment.borrow_mut().define(name.lexeme.clone(), Interface(Rc::new(interface)));
+                Ok(())
+            }
+            Stmt::Extend { class, methods, class_methods, .. } => {
+                let klass = match self.evaluate(class)? {
+                    Class(klass) => klass,
+                    _ => {
+                        let Expr::Variable { name } = class else { unreachable!("extend names a class") };
+                        return Err(RuntimeError(name.clone(), "Can only extend a class.".into()));
+                    }
+                };
+
+                // The methods close over where the extension is, not where
+                // the class was declared. One with the name of a method the
+                // class already has takes its place.
+                for method in methods {
+                    let is_init = method.name.lexeme == "init" && !method.setter;
+                    let func = Function::new(method.clone(), self.environment.clone(), is_init);
+                    match method.setter {
+                        true => klass.setters.borrow_mut().insert(method.name.lexeme.clone(), func),
+                        false => klass.methods.borrow_mut().insert(method.name.lexeme.clone(), func),
+                    };
+                }
+                for method in class_methods {
+                    let func = Function::new(method.clone(), self.environment.clone(), false);
+                    klass.class_methods.borrow_mut().insert(method.name.lexeme.clone(), func);
+                }
+                Ok(())
+            }
+            Stmt::Import { keyword, path, alias } => {
+                if self.modules.reads_files() {
+                    self.require(Capability::Files, keyword)?;
+                }
+                let module = self.modules.load(path, self.current_module.as_deref())
+                    .map_err(|message| RuntimeError(keyword.clone(), message))?;
+                match alias {
+                    None => {
+                        // A plain import runs the module’s top-level code in the
+                        // global environment, once.
+                        if !self.imported.insert(module.name.clone()) {
+                            return Ok(());
+                        }
+                        let statements = self.compile_module(keyword, path, &module, false)?;
+                        self.run_module(&module.name, &statements, self.globals.clone())
+                    }
+                    Some(alias) => {
+                        // An aliased import runs the module in an environment of its
+                        // own and binds the alias to a module object wrapping it.
+                        let module_object = match self.namespaces.get(&module.name) {
+                            Some(module_object) => module_object.clone(),
+                            None => {
+                                let statements = self.compile_module(keyword, path, &module, true)?;
+                                let scope = Environment::new(self.globals.clone(), &module.name);
+                                let module_object = Rc::new(Module::new(alias.lexeme.clone(), scope.clone()));
+                                self.namespaces.insert(module.name.clone(), module_object.clone());
+                                self.run_module(&module.name, &statements, scope)?;
+                                module_object
+                            }
+                        };
+                        self.environment.borrow().check_declaration(alias)?;
+                        self.environment.borrow_mut().define(alias.lexeme.clone(), Object::Module(module_object));
+                        Ok(())
+                    }
+                }
+            },
         }
     }
 
@@ -189,6 +1508,9 @@ impl Interpreter {
     /// operator, etc. — we have a corresponding chunk of code that knows how to evaluate
     /// that tree and produce a result represented by the Object enum.
     pub fn evaluate(&mut self, expression: &Expr) -> Result<Object, Error> {
+        if let Some(nodes) = &mut self.stats.nodes {
+            *nodes.entry(expression.kind()).or_insert(0) += 1;
+        }
         match expression {
             Expr::Literal { value } => Ok(value.clone()),
             Expr::Grouping { expression } => self.evaluate(expression),
@@ -196,6 +1518,7 @@ impl Interpreter {
                 let value = self.evaluate(right)?;
                 match (&operator.token_type, value) {
                     (MINUS, Number(n)) => Ok(Number(-n)),
+                    (TILDE, Number(n)) => Ok(Number(!(n as i64) as f64)),
                     (BANG, value) => Ok(Boolean(!value.is_truthy())),
                     _ => Err(RuntimeError(operator.clone(), "Operand must be a number.".into()))
                 }
@@ -203,18 +1526,45 @@ impl Interpreter {
             Expr::Binary { left, operator, right } => {
                 let left = self.evaluate(left)?;
                 let right = self.evaluate(right)?;
+                // Arithmetic on numbers is most of what a busy loop does, so it
+                // is worked out first, straight from the doubles, without
+                // moving the operands into the match below.
+                if let (Number(l), Number(r)) = (&left, &right) {
+                    if let Some(value) = number_operation(&operator.token_type, *l, *r) {
+                        return Ok(value);
+                    }
+                }
                 match (&operator.token_type, left, right) {
-                    (STAR,  Number(left), Number(right)) => Ok(Number(left * right)),
-                    (SLASH, Number(left), Number(right)) => Ok(Number(left / right)),
-                    (PLUS,  Number(left), Number(right)) => Ok(Number(left + right)),
+                    // Both sides have been evaluated, in order, for their effects.
+                    (COMMA, _, right) => Ok(right),
+                    (LESS_LESS | GREATER_GREATER, Number(left), Number(right)) => {
+                        let amount = right as i64;
+                        if !(0..64).contains(&amount) {
+                            return Err(RuntimeError(operator.clone(), "Shift amount must be between 0 and 63.".into()));
+                        }
+                        let value = match operator.token_type {
+                            LESS_LESS => (left as i64) << amount,
+                            _ => (left as i64) >> amount,
+                        };
+                        Ok(Number(value as f64))
+                    }
                     (PLUS,  String(left), String(right)) => Ok(String(left + right.as_str())),
-                    (MINUS, Number(left), Number(right)) => Ok(Number(left - right)),
-                    (GREATER, Number(left), Number(right)) => Ok(Boolean(left > right)),
-                    (GREATER_EQUAL, Number(left), Number(right)) => Ok(Boolean(left >= right)),
-                    (LESS, Number(left), Number(right)) => Ok(Boolean(left < right)),
-                    (LESS_EQUAL, Number(left), Number(right)) => Ok(Boolean(left <= right)),
-                    (BANG_EQUAL,  left, right) => Ok(Boolean(!left.is_equal(right))),
-                    (EQUAL_EQUAL, left, right) => Ok(Boolean(left.is_equal(right))),
+                    (PLUS, String(left), right @ Number(_)) if self.mixed_concatenation => Ok(String(format!("{left}{right}"))),
+                    (PLUS, left @ Number(_), String(right)) if self.mixed_concatenation => Ok(String(format!("{left}{right}"))),
+                    // Strings compare character by character, by code point, so
+                    // "Z" < "a" and a prefix comes before what it starts.
+                    (GREATER, String(left), String(right)) => Ok(Boolean(left > right)),
+                    (GREATER_EQUAL, String(left), String(right)) => Ok(Boolean(left >= right)),
+                    (LESS, String(left), String(right)) => Ok(Boolean(left < right)),
+                    (LESS_EQUAL, String(left), String(right)) => Ok(Boolean(left <= right)),
+                    (BANG_EQUAL,  left, right) => Ok(Boolean(!self.equal(left, right, operator)?)),
+                    (EQUAL_EQUAL, left, right) => Ok(Boolean(self.equal(left, right, operator)?)),
+                    // Only instances belong to a class, or implement an interface.
+                    // Anything else is of no class at all.
+                    (IS, Instance(instance), Class(class)) => Ok(Boolean(instance.borrow().klass.inherits_from(&class))),
+                    (IS, Instance(instance), Interface(interface)) => Ok(Boolean(instance.borrow().klass.implements(&interface))),
+                    (IS, _, Class(_) | Interface(_)) => Ok(Boolean(false)),
+                    (IS, _, _) => Err(RuntimeError(operator.clone(), "Right operand of 'is' must be a class or an interface.".into())),
                     _ => Err(RuntimeError(operator.clone(), "Operands must be numbers.".into()))
                 }
             }
@@ -257,30 +1607,117 @@ impl Interpreter {
                     args_evaluated.push(self.evaluate(argument)?);
                 }
                 
-                let callable = callee_evaluated.as_callable(paren)?;
-                if args_evaluated.len() != callable.arity() {
-                    return Err(RuntimeError(paren.clone(),
-                        format!("Expected {} arguments but got {}.", callable.arity(), args_evaluated.len()),
-                    ));
-                }
-                
-                callable.call(self, args_evaluated)
+                self.call_checked(&callee_evaluated, args_evaluated, paren)
             },
             Expr::Get { object, name } => {
                 let object_evaluated = self.evaluate(object)?;
-                if let Instance(instance) = object_evaluated {
-                    return instance.borrow().get(name)
-                }
-                Err(RuntimeError(name.clone(), "Only instances have properties.".into()))
+                self.get_property(object_evaluated, name)
             },
             Expr::Set { object, name, value } => {
-                let object_evaluated = self.evaluate(object)?;
-                if let Instance(instance) = object_evaluated {
-                    let value_evaluated = self.evaluate(value)?;
-                    instance.borrow_mut().set(name, value_evaluated.clone());
-                    return Ok(value_evaluated);
+                match self.evaluate(object)? {
+                    Instance(instance) => {
+                        let value_evaluated = self.evaluate(value)?;
+                        self.set_property(&instance, name, value_evaluated.clone())?;
+                        Ok(value_evaluated)
+                    }
+                    Map(map) => {
+                        let value_evaluated = self.evaluate(value)?;
+                        map.borrow_mut().insert(MapKey::String(name.lexeme.clone()), value_evaluated.clone());
+                        Ok(value_evaluated)
+                    }
+                    _ => Err(RuntimeError(name.clone(), "Only instances have fields.".into())),
+                }
+            }
+            Expr::List { elements } => {
+                let mut values = Vec::with_capacity(elements.len());
+                for element in elements {
+                    values.push(self.evaluate(element)?);
+                }
+                Ok(List(Rc::new(RefCell::new(values))))
+            }
+            Expr::Map { brace, entries } => {
+                let map = Rc::new(RefCell::new(HashMap::new()));
+                for (key, value) in entries {
+                    let key = self.evaluate(key)?;
+                    let Some(key) = self.map_key(&map, &key, brace)? else {
+                        return Err(RuntimeError(brace.clone(), key_error(&key)));
+                    };
+                    let value = self.evaluate(value)?;
+                    map.borrow_mut().insert(key, value);
+                }
+                Ok(Map(map))
+            }
+            Expr::Index { object, bracket, index } => {
+                self.element(object, bracket, index, false)?.read(self)
+            }
+            Expr::IndexSet { object, bracket, index, value } => {
+                // The value comes first, so that whatever it changes, like
+                // popping the list being assigned into, is done before the
+                // element is looked for.
+                let value = self.evaluate(value)?;
+                let element = self.element(object, bracket, index, true)?;
+                element.write(self, bracket, value.clone())?;
+                Ok(value)
+            }
+            Expr::Lambda { decl } => {
+                // The same as a function declaration, except that the function
+                // object is the value of the expression instead of being bound to a name.
+                Ok(Function(Function::new(decl.clone(), self.environment.clone(), false)))
+            }
+            Expr::Match { keyword, subject, arms } => {
+                let value = self.evaluate(subject)?;
+                for arm in arms {
+                    if !self.fits(&arm.pattern, &value)? {
+                        continue;
+                    }
+                    // The result is evaluated in a scope of its own, as the
+                    // resolver expects, with the binding if there is one.
+                    let scope = Environment::new(self.environment.clone(), "match");
+                    if let Pattern::Binding { name: Some(name), .. } = &arm.pattern {
+                        scope.borrow_mut().define(name.lexeme.clone(), value.clone());
+                    }
+                    let previous = std::mem::replace(&mut self.environment, scope);
+                    let result = self.evaluate(&arm.body);
+                    self.environment = previous;
+                    return result;
                 }
-                Err(RuntimeError(name.clone(), "Only instances have fields.".into()))
+                Err(RuntimeError(keyword.clone(), format!("No pattern matches the value {value}.")))
+            }
+            Expr::Update { operator, target, prefix } => {
+                let delta = match operator.token_type {
+                    PLUS_PLUS => 1.0,
+                    _ => -1.0,
+                };
+                let not_a_number = || RuntimeError(operator.clone(), "Operand must be a number.".into());
+                let old = match target.as_ref() {
+                    Expr::Variable { name } => {
+                        let Number(old) = self.lookup_variable(target, name)? else { return Err(not_a_number()) };
+                        self.assign_variable(target, name.clone(), Number(old + delta))?;
+                        old
+                    }
+                    Expr::Index { object, bracket, index } => {
+                        let element = self.element(object, bracket, index, false)?;
+                        let Number(old) = element.read(self)? else { return Err(not_a_number()) };
+                        element.write(self, bracket, Number(old + delta))?;
+                        old
+                    }
+                    Expr::Get { object, name } => match self.evaluate(object)? {
+                        Instance(instance) => {
+                            let Number(old) = self.get_property(Instance(instance.clone()), name)? else { return Err(not_a_number()) };
+                            self.set_property(&instance, name, Number(old + delta))?;
+                            old
+                        }
+                        Map(map) => {
+                            let Number(old) = self.get_property(Map(map.clone()), name)? else { return Err(not_a_number()) };
+                            map.borrow_mut().insert(MapKey::String(name.lexeme.clone()), Number(old + delta));
+                            old
+                        }
+                        _ => return Err(RuntimeError(name.clone(), "Only instances have fields.".into())),
+                    },
+                    _ => unreachable!("the parser only accepts variables and fields"),
+                };
+                // The prefix form evaluates to the new value, the postfix form to the old one.
+                Ok(Number(if *prefix { old + delta } else { old }))
             }
             Expr::This { keyword } => {
                 self.lookup_variable(expression, keyword)
@@ -290,15 +1727,130 @@ impl Interpreter {
                 let Class(superclass) = self.environment.borrow().get_at(distance, "super")? else {
                     return Err(RuntimeError(method.clone(), "super is not a class.".into()));
                 };
+                // In a class method, `this` is the class, and `super` finds
+                // the superclass's class methods.
                 let instance_object = self.environment.borrow().get_at(distance - 1, "this")?;
-                let Some(super_method) = superclass.find_method(&method.lexeme) else {
+                let super_method = match instance_object {
+                    Class(_) => superclass.find_class_method(&method.lexeme),
+                    _ => superclass.find_method(&method.lexeme),
+                };
+                let Some(super_method) = super_method else {
                     return Err(RuntimeError(method.clone(), format!("Undefined property '{}'.", method.lexeme))); 
                 };
-                Ok(Function(super_method.bind(&instance_object)))
+                match super_method.bind(&instance_object) {
+                    getter if getter.is_getter() => self.call(&Function(getter), Vec::new(), method),
+                    super_method => Ok(Function(super_method)),
+                }
+            }
+        }
+    }
+
+    /// Evaluates the two halves of `object[index]` and checks that they name a
+    /// valid place: an element of a list that exists, or an entry of a map. A
+    /// map entry only has to exist already when it is going to be read.
+    fn element(&mut self, object: &Expr, bracket: &Token, index: &Expr, writing: bool) -> Result<Element, Error> {
+        let error = |message: std::string::String| Err(RuntimeError(bracket.clone(), message));
+        match (self.evaluate(object)?, self.evaluate(index)?) {
+            (List(list), Number(index)) => {
+                let length = list.borrow().len();
+                match object::position(index, length, "list") {
+                    Ok(position) => Ok(Element::Item(list, position)),
+                    Err(message) => error(message),
+                }
+            }
+            (String(_), _) if writing => error("Strings can't be changed.".into()),
+            (String(string), Number(index)) => match object::position(index, string.chars().count(), "string") {
+                Ok(position) => Ok(Element::Character(string.chars().nth(position).unwrap())),
+                Err(message) => error(message),
+            },
+            (List(_) | String(_), _) => error("Index must be a number.".into()),
+            (Map(map), key) => {
+                let Some(key) = self.map_key(&map, &key, bracket)? else {
+                    return error(key_error(&key));
+                };
+                if !writing && !map.borrow().contains_key(&key) {
+                    return error(format!("Undefined key '{key}'."));
+                }
+                Ok(Element::Entry(map, key))
+            }
+            // `point["x"]` is `point.x`, with a name worked out as the program runs.
+            (Instance(instance), String(name)) => {
+                let mut name = Token::new(IDENTIFIER, name, None, bracket.line, bracket.column);
+                name.file = bracket.file.clone();
+                Ok(Element::Property(instance, name))
             }
+            (Instance(_), _) => error("Property names must be strings.".into()),
+            _ => error("Only lists, maps, strings and instances can be indexed.".into()),
         }
     }
 
+    /// Whether a value fits a pattern of a match arm. A type is one of the
+    /// names the type checker knows, or else it is looked up as a variable
+    /// and has to be a class.
+    fn fits(&mut self, pattern: &Pattern, value: &Object) -> Result<bool, Error> {
+        let type_expr = match pattern {
+            Pattern::Literal { value: literal } => return Ok(literal.is_equal(value.clone())),
+            Pattern::Binding { type_name: None, .. } => return Ok(true),
+            Pattern::Binding { type_name: Some(type_expr), .. } => type_expr,
+        };
+        let Expr::Variable { name } = type_expr.as_ref() else { unreachable!("the parser makes types variables") };
+        let fits = match name.lexeme.as_str() {
+            "Any" => true,
+            "Nil" => matches!(value, Nil),
+            "Bool" => matches!(value, Boolean(_)),
+            "Number" => matches!(value, Number(_)),
+            "String" => matches!(value, String(_)),
+            "List" => matches!(value, List(_)),
+            "Map" => matches!(value, Map(_)),
+            "Module" => matches!(value, Module(_)),
+            "Function" => matches!(value, Function(_)),
+            "Class" => matches!(value, Class(_)),
+            _ => match (self.lookup_variable(type_expr, name)?, value) {
+                (Class(class), Instance(instance)) => instance.borrow().klass.inherits_from(&class),
+                (Class(_), _) => false,
+                _ => return Err(RuntimeError(name.clone(), format!("'{}' is not a type or a class.", name.lexeme))),
+            },
+        };
+        Ok(fits)
+    }
+
+    /// A module goes through the same front end as a script. Its statements are
+    /// kept alive for as long as the interpreter, because the side table of
+    /// locals refers to their expressions by address.
+    fn compile_module(&mut self, keyword: &Token, path: &str, module: &ModuleSource, namespaced: bool) -> Result<Rc<Vec<Stmt>>, Error> {
+        // Errors in the module name the file they are in. Modules found in
+        // the current directory drop the redundant "./".
+        let file = module.name.strip_prefix("./").unwrap_or(&module.name);
+        let pipeline = Pipeline::new()
+            .with_defines(self.defines.clone())
+            .with_filename(&module.name)
+            .with_diagnostic_file(file)
+            .with_macros(&self.macros);
+        let source = &module.source;
+        let tokens = pipeline.preprocess(pipeline.scan(source));
+        let resolved = pipeline.parse(tokens, source).and_then(|program| match namespaced {
+            true => pipeline.resolve_module(program),
+            false => pipeline.resolve(&mut self.resolver(), program),
+        });
+        let Some(Resolved { program, locals }) = resolved else {
+            return Err(RuntimeError(keyword.clone(), format!("Could not compile module '{path}'.")));
+        };
+
+        let statements = Rc::new(program.statements);
+        self.module_code.push(statements.clone());
+        self.add_locals(locals);
+        Ok(statements)
+    }
+
+    /// Runs a module’s top-level code in the given environment. While it runs,
+    /// imports inside it are resolved relative to the module itself.
+    fn run_module(&mut self, name: &str, statements: &[Stmt], scope: MutableEnvironment) -> Result<(), Error> {
+        let previous = self.current_module.replace(name.to_string());
+        let result = self.execute_block(statements, scope);
+        self.current_module = previous;
+        result
+    }
+
     fn lookup_variable(&self, expression: &Expr, name: &Token) -> Result<Object, Error> {
         if self.locals.is_none() {
             return self.environment.borrow().get(name);
@@ -318,6 +1870,8 @@ impl Interpreter {
         let distance = self.get_depth(expr);
         if let Some(distance) = distance {
             self.environment.borrow_mut().assign_at(distance, name, value)
+        } else if self.globals_frozen && !self.frames.is_empty() {
+            Err(RuntimeError(name.clone(), format!("Can't assign to global '{}' from inside a function; globals are frozen.", name.lexeme)))
         } else {
             self.globals.borrow_mut().assign(name, value)
         }
@@ -331,3 +1885,131 @@ impl Interpreter {
     }
 }
 
+/// A place in a list or map that `object[index]` refers to, already checked
+/// by Interpreter::element(). A character of a string can be read but, as
+/// strings are values, never written. A property of an instance is read and
+/// written as with a dot, getters and setters included, so that takes the
+/// interpreter.
+enum Element {
+    Item(MutableList, usize),
+    Entry(MutableMap, MapKey),
+    Character(char),
+    Property(MutableInstance, Token),
+}
+
+impl Element {
+    fn read(&self, interpreter: &mut Interpreter) -> Result<Object, Error> {
+        match self {
+            Element::Item(list, position) => Ok(list.borrow()[*position].clone()),
+            Element::Entry(map, key) => Ok(map.borrow()[key].clone()),
+            Element::Character(c) => Ok(String(c.to_string())),
+            Element::Property(instance, name) => interpreter.get_property(Instance(instance.clone()), name),
+        }
+    }
+
+    /// Stores the value in the element. A list may have shrunk since the
+    /// element was found, so its position is checked again.
+    fn write(&self, interpreter: &mut Interpreter, bracket: &Token, value: Object) -> Result<(), Error> {
+        match self {
+            Element::Item(list, position) => {
+                let mut list = list.borrow_mut();
+                let length = list.len();
+                let Some(item) = list.get_mut(*position) else {
+                    let message = format!("Index {position} is out of bounds for a list of length {length}.");
+                    return Err(RuntimeError(bracket.clone(), message));
+                };
+                *item = value;
+            }
+            Element::Entry(map, key) => {
+                map.borrow_mut().insert(key.clone(), value);
+            }
+            Element::Character(_) => unreachable!("strings are rejected before anything is written"),
+            Element::Property(instance, name) => interpreter.set_property(instance, name, value)?,
+        }
+        Ok(())
+    }
+}
+
+/// The error for a value that can't be a map key.
+fn key_error(key: &Object) -> std::string::String {
+    match key {
+        Instance(_) => "Only instances whose class has a hash() method can be map keys.".into(),
+        _ => "Map keys must be strings or numbers.".into(),
+    }
+}
+
+/// Finds the built-in Error class in a new global environment.
+fn error_class(globals: &MutableEnvironment) -> Rc<class::Class> {
+    match globals.borrow().get_own("Error") {
+        Some(Class(class)) => class,
+        _ => unreachable!("every global environment starts with the Error class"),
+    }
+}
+
+/// Decides, from how one run of a loop body ended, whether the loop goes on.
+/// A `break` or `continue` is the loop's own if it has no label or the loop's
+/// label; any other is for an enclosing loop, and carries on unwinding.
+fn keep_looping(result: Result<(), Error>, label: &Option<Token>) -> Result<bool, Error> {
+    let ours = |target: &Option<std::string::String>| match (target, label) {
+        (None, _) => true,
+        (Some(target), Some(label)) => *target == label.lexeme,
+        (Some(_), None) => false,
+    };
+    match result {
+        Err(Error::Break(target)) if ours(&target) => Ok(false),
+        Err(Error::Continue(target)) if ours(&target) => Ok(true),
+        result => result.map(|_| true),
+    }
+}
+
+/// A binary operator on two numbers that can't fail, or None for the others,
+/// which are left to the general case. Equality between numbers is here too,
+/// since only instances have equals() methods to call.
+#[inline]
+fn number_operation(operator: &TokenType, left: f64, right: f64) -> Option<Object> {
+    Some(match operator {
+        PLUS => Number(left + right),
+        MINUS => Number(left - right),
+        STAR => Number(left * right),
+        SLASH => Number(left / right),
+        // Like Rust's, the remainder takes the sign of the dividend.
+        PERCENT => Number(left % right),
+        // Numbers are doubles, so the bitwise operators first truncate
+        // their operands to 64-bit integers.
+        AMPERSAND => Number(((left as i64) & (right as i64)) as f64),
+        PIPE => Number(((left as i64) | (right as i64)) as f64),
+        CARET => Number(((left as i64) ^ (right as i64)) as f64),
+        GREATER => Boolean(left > right),
+        GREATER_EQUAL => Boolean(left >= right),
+        LESS => Boolean(left < right),
+        LESS_EQUAL => Boolean(left <= right),
+        EQUAL_EQUAL => Boolean(left == right),
+        BANG_EQUAL => Boolean(left != right),
+        _ => return None,
+    })
+}
+
+/// A number no interpreter made before has. See Interpreter::id.
+fn next_id() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The exit status a script gives by its result, or the error for a result
+/// that isn't one, at the line the script ended on.
+pub(crate) fn script_status(value: &Object, line: usize) -> Result<i32, Error> {
+    exit_status(value).ok_or_else(|| {
+        let token = Token::new(RETURN, "return".into(), None, line, 0);
+        RuntimeError(token, format!("A script's result must be an integer or nil, not {value}."))
+    })
+}
+
+/// The exit status a script or its entry point gives by its result: the
+/// result itself if it is an integer, or 0 for nil.
+fn exit_status(value: &Object) -> Option<i32> {
+    match value {
+        Nil => Some(0),
+        Number(status) if status.fract() == 0.0 => Some(*status as i32),
+        _ => None,
+    }
+}
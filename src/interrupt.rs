@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when the user asks for the running program to stop, by pressing
+/// Ctrl-C. The interpreter checks it wherever it checks a run's limits, on
+/// every loop iteration and every call, so an interrupted program stops at
+/// the next one with an error saying where, instead of being killed.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Set while nothing is running, like while a prompt waits for a line, when
+/// Ctrl-C has nothing to stop and is ignored.
+static IDLE: AtomicBool = AtomicBool::new(false);
+
+/// Whether the program running has been asked to stop.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Asks the program running to stop, as Ctrl-C does.
+pub fn request() {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Forgets a request to stop, once it has been dealt with.
+pub fn clear() {
+    REQUESTED.store(false, Ordering::Relaxed);
+}
+
+/// Runs `f`, which runs no code of the program, with Ctrl-C ignored, so
+/// pressing it doesn't count towards killing the process either.
+pub fn idle<T>(f: impl FnOnce() -> T) -> T {
+    IDLE.store(true, Ordering::Relaxed);
+    let result = f();
+    IDLE.store(false, Ordering::Relaxed);
+    result
+}
+
+/// Makes Ctrl-C ask the program running to stop, instead of killing the
+/// process. A program that doesn't get to a check, like one stuck inside a
+/// native, can still be killed by pressing Ctrl-C a second time.
+///
+/// Only Unix is supported; elsewhere Ctrl-C keeps its default behavior.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        signal(SIGINT, on_interrupt);
+    }
+}
+
+#[cfg(unix)]
+const SIGINT: i32 = 2;
+
+#[cfg(unix)]
+extern "C" {
+    fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    fn _exit(status: i32) -> !;
+}
+
+/// Runs when the process gets SIGINT, in the middle of whatever it was
+/// doing, so it does no more than a signal handler safely can.
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: i32) {
+    if IDLE.load(Ordering::Relaxed) {
+        return;
+    }
+    if REQUESTED.swap(true, Ordering::Relaxed) {
+        // The way shells report a process ended by SIGINT.
+        unsafe { _exit(130) }
+    }
+}
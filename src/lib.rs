@@ -0,0 +1,31 @@
+pub mod cst;
+pub mod environment;
+pub mod error;
+pub mod expectation;
+pub mod expr;
+pub mod fold;
+pub mod interpreter;
+pub mod interrupt;
+pub mod lox;
+pub mod macros;
+pub mod module;
+pub mod optimizer;
+pub mod parser;
+pub mod pass;
+pub mod pipeline;
+pub mod policy;
+pub mod preprocessor;
+pub mod program;
+pub mod refactor;
+pub mod references;
+pub mod report;
+pub mod resolver;
+pub mod scanner;
+pub mod server;
+pub mod session;
+pub mod stmt;
+pub mod symbol;
+pub mod task;
+pub mod token;
+pub mod typecheck;
+pub mod value;
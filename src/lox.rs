@@ -1,54 +1,386 @@
 use crate::error;
-use crate::interpreter::Interpreter;
-use crate::parser::Parser;
+use crate::error::Diagnostic;
+use crate::interpreter::{Interpreter, Stats};
+use crate::interrupt;
+use crate::pipeline::Pipeline;
+use crate::policy::Limits;
+use crate::program::Program;
 use crate::resolver::Resolver;
-use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+use crate::token::{Token, TokenType};
+use crate::typecheck::TypeChecker;
+use crate::value::object::Object;
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::rc::Rc;
 
 pub fn tokenize(file_contents: String) {
-    let mut scanner = Scanner::new(file_contents);
-    let tokens = scanner.scan_tokens();
+    let tokens = Pipeline::new().scan(&file_contents);
     for token in tokens {
         println!("{}", token);
     }
 }
 
+/// Prints the tokens as an aligned table, one row per token, with the token
+/// index, type, lexeme, literal, line, and column. Types and lexemes are colored
+/// by the kind of token unless the NO_COLOR environment variable is set.
+pub fn tokenize_pretty(file_contents: String) {
+    let tokens = Pipeline::new().scan(&file_contents);
+    let color = env::var_os("NO_COLOR").is_none();
+
+    let header = ["#", "TYPE", "LEXEME", "LITERAL", "LINE", "COL"].map(String::from);
+    let rows: Vec<[String; 6]> = tokens
+        .iter()
+        .enumerate()
+        .map(|(index, token)| {
+            [
+                index.to_string(),
+                token.token_type.to_string(),
+                token.lexeme.clone(),
+                token.literal.clone().unwrap_or_default(),
+                token.line.to_string(),
+                token.column.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.clone().map(|cell| cell.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |row: &[String; 6], paint: &str| {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(i, (cell, width))| {
+                // Numbers are right-aligned, text is left-aligned.
+                let padded = match i {
+                    0 | 4 | 5 => format!("{cell:>width$}"),
+                    _ => format!("{cell:<width$}"),
+                };
+                match (i, paint.is_empty()) {
+                    (1 | 2, false) => format!("{paint}{padded}\x1b[0m"),
+                    _ => padded,
+                }
+            })
+            .collect();
+        cells.join("  ").trim_end().to_string()
+    };
+
+    println!("{}", format_row(&header, ""));
+    for (row, token) in rows.iter().zip(&tokens) {
+        let paint = if color { token_color(&token.token_type) } else { "" };
+        println!("{}", format_row(row, paint));
+    }
+}
+
+/// The ANSI color for each category of token: keywords, literals, identifiers,
+/// operators, and punctuation.
+fn token_color(token_type: &TokenType) -> &'static str {
+    use TokenType::*;
+    match token_type {
+        AND | BREAK | CATCH | CLASS | CONST | CONTINUE | ELSE | FALSE | FINALLY | FUN | FOR | IF | IMPORT | MATCH | NIL | OR
+        | PRINT | RETURN | SUPER | THIS | THROW | TRUE | TRY | VAR | WHILE => "\x1b[35m",
+        STRING | NUMBER => "\x1b[32m",
+        IDENTIFIER => "\x1b[36m",
+        MINUS | PERCENT | PLUS | SLASH | STAR | BANG | BANG_EQUAL | EQUAL | EQUAL_EQUAL | GREATER
+        | GREATER_EQUAL | GREATER_GREATER | LESS | LESS_EQUAL | LESS_LESS | AMPERSAND | PIPE | CARET
+        | TILDE | MINUS_EQUAL | PLUS_EQUAL | SLASH_EQUAL | STAR_EQUAL | MINUS_MINUS
+        | PLUS_PLUS | ARROW => "\x1b[33m",
+        EOF => "\x1b[2m",
+        _ => "",
+    }
+}
+
 pub fn parse(file_contents: String) {
-    let mut lexer = Scanner::new(file_contents);
-    let tokens = lexer.scan_tokens();
-    let mut parser = Parser::new(tokens);
-    if let Ok(expr) = parser.expression() {
+    let pipeline = Pipeline::new();
+    let tokens = pipeline.scan(&file_contents);
+    if let Some(expr) = pipeline.parse_expression(tokens) {
         println!("{expr}");
     }
 }
 
 pub fn evaluate(file_contents: String) {
-    let mut lexer = Scanner::new(file_contents);
-    let tokens = lexer.scan_tokens();
-    let mut parser = Parser::new(tokens);
-    if let Ok(expr) = parser.expression() {
-        let mut interpreter = Interpreter::new();
-        match interpreter.evaluate(&expr) {
-            Ok(evaluated) => println!("{evaluated}"),
-            Err(error) => error::runtime_error(error),
-        }
+    if let Some(value) = evaluate_expression(&file_contents).value {
+        println!("{value}");
     }
 }
 
-pub fn run(file_contents: String) {
-    let mut lexer = Scanner::new(file_contents);
-    let tokens = lexer.scan_tokens();
-    let mut parser = Parser::new(tokens);
-    let stmts = parser.parse();
-    let mut resolver = Resolver::new();
-    let locals = resolver.resolve(&stmts);
+/// What came of evaluating some code: its value, if it produced one without
+/// an error, along with everything it printed and every error reported.
+#[derive(Debug)]
+pub struct EvalOutcome {
+    pub value: Option<Object>,
+    pub stdout: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A class a script defined, as Lox::classes describes it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassInfo {
+    /// The name of the global the class is bound to.
+    pub name: String,
+    pub superclass: Option<String>,
 
-    // Stop if there was a resolution error.
+    /// The methods of its instances, inherited ones included, with the
+    /// number of arguments each takes, sorted by name.
+    pub methods: Vec<(String, usize)>,
+
+    /// The same, for the methods called on the class itself.
+    pub class_methods: Vec<(String, usize)>,
+}
+
+/// Evaluates a single expression, like the `evaluate` command, but hands the
+/// result back instead of printing it.
+pub fn evaluate_expression(source: &str) -> EvalOutcome {
+    error::reset();
+    let pipeline = Pipeline::new();
+    let tokens = pipeline.scan(source);
+    let mut interpreter = Interpreter::new();
+    interpreter.start_capture();
+    let value = pipeline.parse_expression(tokens).and_then(|expr| match interpreter.evaluate(&expr) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            interpreter.report(error);
+            None
+        }
+    });
+    EvalOutcome { value, stdout: interpreter.finish_capture(), diagnostics: error::diagnostics() }
+}
+
+/// Checks a program against its type annotations without running it. Type
+/// errors are reported like any other compile error.
+pub fn typecheck(file_contents: String) {
+    let pipeline = Pipeline::new();
+    let tokens = pipeline.preprocess(pipeline.scan(&file_contents));
+    let Some(program) = pipeline.parse(tokens, &file_contents) else { return };
+    let mut resolver = Resolver::new().with_references();
+    resolver.resolve(&program.statements);
     if error::had_error() {
         return;
     }
+    let references = resolver.take_references().unwrap_or_default();
+    TypeChecker::new(&references).check(&program.statements);
+}
+
+/// Runs a program through every stage of the given pipeline and returns the
+/// interpreter's counters for the run.
+pub fn run(mut pipeline: Pipeline, file_contents: String) -> Stats {
+    match pipeline.compile(&file_contents) {
+        Some(resolved) => pipeline.execute(resolved),
+        None => Stats::default(),
+    }
+}
+
+/// An interactive prompt. Each line is scanned, parsed, resolved, and run
+/// against the same interpreter, so declarations stick around between lines.
+/// A line holding a bare expression prints its value, and the trailing
+/// semicolon can be left off. Errors are reported and then forgotten, so a
+/// mistake doesn't end the session.
+pub fn repl() {
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut pipeline = Pipeline::new().with_unbuffered_output();
+    let mut resolver = Resolver::new();
+    let mut interpreter = pipeline.interpreter();
+    // Ctrl-C stops the line running, and the session carries on. At the
+    // prompt it is ignored, however often it is pressed; Ctrl-D ends the
+    // session.
+    interrupt::install();
+
+    // The interpreter's side table points into each line's syntax tree,
+    // so every tree has to outlive the session.
+    let mut history: Vec<Vec<Stmt>> = Vec::new();
+
+    loop {
+        if interactive {
+            print!("> ");
+            io::stdout().flush().unwrap();
+        }
+        let mut line = String::new();
+        match interrupt::idle(|| stdin.lock().read_line(&mut line)) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        error::reset();
+        interrupt::clear();
+        let mut tokens = pipeline.preprocess(pipeline.scan(&line));
+        if tokens.len() == 1 {
+            continue;
+        }
+        add_implicit_semicolon(&mut tokens);
+
+        let Some(program) = pipeline.parse(tokens, &line) else { continue };
+        let Some(resolved) = pipeline.resolve(&mut resolver, program) else { continue };
+        interpreter.add_locals(resolved.locals);
+
+        let statements = resolved.program.statements;
+        match statements.as_slice() {
+            [Stmt::Expression { expression }] => match interpreter.evaluate(expression) {
+                Ok(value) => println!("{value}"),
+                Err(error) => interpreter.report(error),
+            },
+            statements => interpreter.interpret(statements),
+        }
+        if !error::had_runtime_error() {
+            interpreter.run_event_loop();
+        }
+        history.push(statements);
+    }
+    interpreter.run_exit_hooks();
+    if interactive {
+        println!();
+    }
+}
+
+/// Lets a REPL line end without a semicolon, by adding one if the line doesn't
+/// already end with a semicolon or a closing brace.
+fn add_implicit_semicolon(tokens: &mut Vec<Token>) {
+    let eof = tokens.len() - 1;
+    let last = &tokens[eof - 1];
+    if !matches!(last.token_type, TokenType::SEMICOLON | TokenType::RIGHT_BRACE) {
+        let semicolon = Token::new(TokenType::SEMICOLON, ";".into(), None, last.line, last.column + last.lexeme.len());
+        tokens.insert(eof, semicolon);
+    }
+}
 
-    let mut interpreter = Interpreter::new_with_resolver(locals);
-    interpreter.interpret(&stmts);
+/// An interpreter for programs embedded in a host application. The host can
+/// put values into the global environment before running a script, and read
+/// them back out afterwards. Globals persist across runs, so a host can also
+/// load a library script first and then run code that uses it.
+///
+/// A host that runs many short scripts against the same library, like a
+/// server handling requests, loads the library once and then runs each script
+/// in a fork, which starts with everything the library defined and whose
+/// changes go no further than the fork.
+pub struct Lox {
+    pipeline: Pipeline,
+    interpreter: Interpreter,
+
+    /// Every program run so far, including those run before this was forked.
+    /// The interpreter refers into their syntax trees.
+    programs: Vec<Rc<Program>>,
 }
 
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self::with_pipeline(Pipeline::new())
+    }
+
+    /// Creates an interpreter configured by the given pipeline.
+    pub fn with_pipeline(mut pipeline: Pipeline) -> Self {
+        let interpreter = pipeline.interpreter();
+        Self { pipeline, interpreter, programs: Vec::new() }
+    }
+
+    /// Defines a global variable, converting the value with the From impls
+    /// on Object: `lox.set_global("retries", 3)`.
+    pub fn set_global(&mut self, name: &str, value: impl Into<Object>) {
+        self.interpreter.define_global(name, value.into());
+    }
 
+    /// Reads a global variable back, converting it with the TryFrom impls on
+    /// Object: `let retries: f64 = lox.get_global("retries")?`.
+    pub fn get_global<T: TryFrom<Object, Error = String>>(&self, name: &str) -> Result<T, String> {
+        match self.interpreter.global(name) {
+            Some(value) => T::try_from(value),
+            None => Err(format!("Undefined variable '{name}'.")),
+        }
+    }
+
+    /// Every class bound to a global so far, sorted by name, the built-in
+    /// Error class included. A host can run a script that declares a plugin,
+    /// then check it has the methods the host is going to call, before
+    /// calling any of them.
+    pub fn classes(&self) -> Vec<ClassInfo> {
+        let mut names = self.interpreter.global_names();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| match self.interpreter.global(&name) {
+                Some(Object::Class(class)) => Some(ClassInfo {
+                    name,
+                    superclass: class.superclass.as_ref().map(|superclass| superclass.name.clone()),
+                    methods: class.method_arities().into_iter().collect(),
+                    class_methods: class.class_method_arities().into_iter().collect(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Runs a script without printing anything. If the last statement is an
+    /// expression, its value is the outcome's value, as in the REPL.
+    pub fn evaluate(&mut self, source: &str) -> EvalOutcome {
+        error::reset();
+        self.interpreter.start_capture();
+        let mut value = None;
+        if let Some(resolved) = self.pipeline.compile(source) {
+            self.interpreter.add_locals(resolved.locals);
+            self.interpreter.set_limits(resolved.program.limits);
+            let interpreter = &mut self.interpreter;
+            let result = match resolved.program.statements.split_last() {
+                Some((Stmt::Expression { expression }, rest)) => rest
+                    .iter()
+                    .try_for_each(|statement| interpreter.execute(statement))
+                    .and_then(|_| interpreter.evaluate(expression))
+                    .map(Some),
+                _ => resolved.program.statements.iter().try_for_each(|statement| interpreter.execute(statement)).map(|_| None),
+            };
+            match result {
+                Ok(result) => {
+                    value = result;
+                    self.interpreter.run_event_loop();
+                }
+                Err(error) => self.interpreter.report(error),
+            }
+            self.programs.push(Rc::new(resolved.program));
+        }
+        EvalOutcome { value, stdout: self.interpreter.finish_capture(), diagnostics: error::diagnostics() }
+    }
+
+    /// Runs a script and returns the exit code a command-line run would have:
+    /// 0 on success, 65 for a compile error, 70 for a runtime error.
+    pub fn run(&mut self, source: &str) -> i32 {
+        error::reset();
+        if let Some(resolved) = self.pipeline.compile(source) {
+            self.interpreter.add_locals(resolved.locals);
+            self.interpreter.set_limits(resolved.program.limits);
+            self.interpreter.interpret(&resolved.program.statements);
+            if !error::had_runtime_error() {
+                self.interpreter.run_event_loop();
+            }
+            self.programs.push(Rc::new(resolved.program));
+        }
+        error::exit_code()
+    }
+
+    /// Limits what every program run from now on may do, here and in the
+    /// interpreters forked from this one. See Pipeline::with_limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.pipeline = std::mem::take(&mut self.pipeline).with_limits(limits);
+    }
+
+    /// Calls the hook with the line of each statement as it runs. See
+    /// Interpreter::on_line.
+    pub fn on_line(&mut self, hook: impl FnMut(usize) + 'static) {
+        self.interpreter.on_line(hook);
+    }
+
+    /// A copy of this interpreter to run a script in, with every global
+    /// defined so far, whose own globals are its own. See Interpreter::fork.
+    pub fn fork(&self) -> Lox {
+        Self { pipeline: self.pipeline.fork(), interpreter: self.interpreter.fork(), programs: self.programs.clone() }
+    }
+}
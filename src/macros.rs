@@ -0,0 +1,54 @@
+use crate::scanner::Scanner;
+use crate::token::Token;
+use crate::token::TokenType::EOF;
+
+/// A rewrite of the token stream, run after the preprocessor and before the
+/// parser. It's a way to try out new syntax without touching the parser: a
+/// macro looks for the tokens of the construct it adds, and puts in their
+/// place tokens the parser already knows. One that adds a `times` loop
+/// turns
+///
+/// ```text
+/// times(3) { print "hi"; }
+/// ```
+///
+/// into
+///
+/// ```text
+/// for (var i = 0; i < 3; i = i + 1) { print "hi"; }
+/// ```
+///
+/// A macro gets the whole stream, EOF included, and gives back the whole
+/// rewritten stream, which has to end with the EOF too. It fails with the
+/// token it was looking at and a message, reported like a syntax error.
+///
+/// Macros are registered on the pipeline (see `Pipeline::with_macro`) and run
+/// in the order they were registered, each on what the one before gave back.
+/// They apply to imported modules and to code given to eval as well.
+pub trait Macro {
+    fn expand(&self, tokens: Vec<Token>) -> Result<Vec<Token>, (Token, String)>;
+}
+
+/// A plain function can be a macro.
+impl<F> Macro for F
+where
+    F: Fn(Vec<Token>) -> Result<Vec<Token>, (Token, String)>,
+{
+    fn expand(&self, tokens: Vec<Token>) -> Result<Vec<Token>, (Token, String)> {
+        self(tokens)
+    }
+}
+
+/// Scans a piece of Lox into tokens for a macro to put into the stream, all
+/// placed where `at` is, so an error in them points at the code the macro
+/// rewrote. The EOF is left off.
+pub fn tokens(source: &str, at: &Token) -> Vec<Token> {
+    let mut tokens = Scanner::new(source.to_string()).scan_tokens();
+    tokens.retain(|token| token.token_type != EOF);
+    for token in &mut tokens {
+        token.line = at.line;
+        token.column = at.column;
+        token.file = at.file.clone();
+    }
+    tokens
+}
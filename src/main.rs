@@ -1,51 +1,585 @@
-pub mod environment;
-pub mod error;
-pub mod expr;
-pub mod interpreter;
-pub mod parser;
-pub mod scanner;
-pub mod stmt;
-pub mod token;
-pub mod value;
-pub mod resolver;
-pub mod lox;
-
+use codecrafters_interpreter::expectation::Expectations;
+use codecrafters_interpreter::module::FileResolver;
+use codecrafters_interpreter::pipeline::Pipeline;
+use codecrafters_interpreter::policy::{Capability, Policy, Shadowing};
+use codecrafters_interpreter::report::{self, Report};
+use codecrafters_interpreter::value::native::Natives;
+use codecrafters_interpreter::value::object::Object;
+use codecrafters_interpreter::lox::{self, Lox};
+use codecrafters_interpreter::{error, interrupt, refactor, server};
+use std::collections::HashSet;
 use std::env;
 use std::fs;
-use std::io::{self, Write};
-use std::process::exit;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+use std::time::Instant;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("repl") {
+        lox::repl();
+        return;
+    }
     if args.len() < 3 {
-        writeln!(io::stderr(), "Usage: {} tokenize <filename>", args[0]).unwrap();
+        eprintln!("Usage: {} tokenize <filename>", args[0]);
         return;
     }
 
     let command = &args[1];
-    let filename = &args[2];
+    let (mut options, mut filenames) = parse_options(&args[2..]);
+    if command == "serve" {
+        exit(run_server(&filenames, &options));
+    }
+    if filenames.is_empty() {
+        eprintln!("Usage: {} {command} <filename>", args[0]);
+        exit(64);
+    }
+
+    if command == "refactor" {
+        exit(run_refactoring(&filenames, &options));
+    }
+    if command == "test" {
+        exit(run_tests(&filenames, &options));
+    }
+    if command == "refs" {
+        exit(run_find_references(&filenames[0], &options));
+    }
+
+    // With an entry point, the names after the script are its arguments.
+    if options.call.is_some() {
+        options.arguments = filenames.split_off(1);
+    }
+
+    // The report file holds this invocation's reports, one line per file
+    // run, so it starts out empty. Failing to write it is reported along
+    // with the first report.
+    if let (Some(_), Some(path)) = (&options.report, &options.report_file) {
+        let _ = fs::File::create(path);
+    }
+
+    // Several files can be run in one go. Each one gets a fresh interpreter and
+    // a clean error state, and we report how every file went.
+    if command == "run" && filenames.len() > 1 {
+        let mut status = 0;
+        for filename in &filenames {
+            let code = run_file(command, filename, &options);
+            match code {
+                0 => eprintln!("[{filename}] ok"),
+                _ => eprintln!("[{filename}] failed with exit code {code}"),
+            }
+            status = status.max(code);
+        }
+        exit(status);
+    }
+
+    let code = run_file(command, &filenames[0], &options);
+    if code != 0 {
+        exit(code);
+    }
+}
+
+/// Flags that can follow the command, e.g. `tokenize --pretty <filename>`.
+#[derive(Default)]
+struct Options {
+    /// Print tokens as an aligned, colored table instead of the plain format.
+    pretty: bool,
+
+    /// After a run, emit a summary in this format (only "json" for now).
+    report: Option<String>,
+
+    /// Write the report to this file instead of stderr.
+    report_file: Option<String>,
+
+    /// Extra directories to search for imported modules, from `--include`.
+    includes: Vec<PathBuf>,
+
+    /// Symbols for `#if` directives, from `--define`.
+    defines: HashSet<String>,
+
+    /// Capabilities the script may use, narrowed by `--deny` and `--sandbox`.
+    policy: Policy,
+
+    /// A position in the file, from `--line` and `--col`, for commands that
+    /// act on the name written there.
+    line: Option<usize>,
+    column: Option<usize>,
 
-    let file_contents = fs::read_to_string(filename).unwrap_or_else(|_| {
+    /// The last line of a range starting at `--line`, from `--end-line`.
+    end_line: Option<usize>,
+
+    /// Warn about operations that are bound to fail before running, from `--warnings`.
+    warnings: bool,
+
+    /// Write each printed line right away instead of buffering, from `--unbuffered`.
+    unbuffered: bool,
+
+    /// Keep functions from assigning to globals, from `--freeze-globals`.
+    freeze_globals: bool,
+
+    /// Let `+` join a string and a number, from `--mixed-concat`.
+    mixed_concatenation: bool,
+
+    /// The natives to define instead of the standard ones, from `--natives`.
+    natives: Option<Natives>,
+
+    /// What to do about globals named after natives, from `--shadowing` or `--strict`.
+    shadowing: Shadowing,
+
+    /// Run the official Crafting Interpreters test suite, from `test --official`.
+    official: bool,
+
+    /// After a run, list how many times each kind of node ran, from `--explain`.
+    explain: bool,
+
+    /// Exit with the script's result, from `--exit-status`.
+    exit_status: bool,
+
+    /// The socket to answer requests on, from `serve --socket`.
+    socket: Option<String>,
+
+    /// The function to call once the script's top level has run, from `--call`.
+    call: Option<String>,
+
+    /// The arguments to call it with: whatever follows the script.
+    arguments: Vec<String>,
+}
+
+/// Splits the arguments after the command into flags and file names.
+fn parse_options(args: &[String]) -> (Options, Vec<String>) {
+    let mut options = Options::default();
+    let mut filenames = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--pretty" => options.pretty = true,
+            "--report" => match args.next().map(String::as_str) {
+                Some("json") => options.report = Some("json".into()),
+                _ => {
+                    eprintln!("--report expects a format: json");
+                    exit(64);
+                }
+            },
+            "--report-file" => options.report_file = Some(flag_value(&mut args, "--report-file")),
+            "--include" => options.includes.push(flag_value(&mut args, "--include").into()),
+            "--define" => {
+                options.defines.insert(flag_value(&mut args, "--define"));
+            }
+            "--sandbox" => options.policy = Policy::output_only(),
+            "--warnings" => options.warnings = true,
+            "--unbuffered" => options.unbuffered = true,
+            "--freeze-globals" => options.freeze_globals = true,
+            "--mixed-concat" => options.mixed_concatenation = true,
+            "--explain" => options.explain = true,
+            "--socket" => options.socket = Some(flag_value(&mut args, "--socket")),
+            "--exit-status" => options.exit_status = true,
+            "--call" => options.call = Some(flag_value(&mut args, "--call")),
+            "--official" => options.official = true,
+            "--strict" => options.shadowing = Shadowing::Error,
+            "--shadowing" => {
+                let name = flag_value(&mut args, "--shadowing");
+                match Shadowing::named(&name) {
+                    Some(shadowing) => options.shadowing = shadowing,
+                    None => {
+                        eprintln!("--shadowing expects allow, warn or error");
+                        exit(64);
+                    }
+                }
+            }
+            "--natives" => {
+                let name = flag_value(&mut args, "--natives");
+                match Natives::named(&name) {
+                    Some(natives) => options.natives = Some(natives),
+                    None => {
+                        eprintln!("Unknown set of natives: {name}");
+                        exit(64);
+                    }
+                }
+            }
+            "--allow" => {
+                let name = flag_value(&mut args, "--allow");
+                match Capability::named(&name) {
+                    Some(capability) => options.policy = options.policy.clone().allow(capability),
+                    None => {
+                        eprintln!("Unknown capability: {name}");
+                        exit(64);
+                    }
+                }
+            }
+            "--deny" => {
+                let name = flag_value(&mut args, "--deny");
+                match Capability::named(&name) {
+                    Some(capability) => options.policy = options.policy.clone().deny(capability),
+                    None => {
+                        eprintln!("Unknown capability: {name}");
+                        exit(64);
+                    }
+                }
+            }
+            "--line" => options.line = Some(number_value(&mut args, "--line")),
+            "--col" => options.column = Some(number_value(&mut args, "--col")),
+            "--end-line" => options.end_line = Some(number_value(&mut args, "--end-line")),
+            flag if flag.starts_with("--") => {
+                eprintln!("Unknown option: {flag}");
+                exit(64);
+            }
+            _ => filenames.push(arg.clone()),
+        }
+    }
+    (options, filenames)
+}
+
+/// Takes the value that must follow a flag, or exits with a usage error.
+fn flag_value<'a>(args: &mut impl Iterator<Item = &'a String>, flag: &str) -> String {
+    match args.next() {
+        Some(value) => value.clone(),
+        None => {
+            eprintln!("{flag} expects a value");
+            exit(64);
+        }
+    }
+}
+
+/// Takes the positive number that must follow a flag, or exits with a usage error.
+fn number_value<'a>(args: &mut impl Iterator<Item = &'a String>, flag: &str) -> usize {
+    match flag_value(args, flag).parse() {
+        Ok(number) if number > 0 => number,
+        _ => {
+            eprintln!("{flag} expects a positive number");
+            exit(64);
+        }
+    }
+}
+
+/// Runs `refactor <refactoring> <arguments...> <filename>`, printing the
+/// rewritten source. The file itself is left alone.
+fn run_refactoring(args: &[String], options: &Options) -> i32 {
+    let Some(filename) = args.last() else {
+        return refactor_usage();
+    };
+    let Ok(source) = fs::read_to_string(filename) else {
         eprintln!("Failed to read file {filename}");
-        exit(65);
-    });
+        return 65;
+    };
+    let result = match (&args[..args.len() - 1], options.line, options.column) {
+        ([refactoring, old, new], Some(line), Some(column)) if refactoring == "rename" => {
+            refactor::rename(&source, line, column, old, new)
+        }
+        ([refactoring, name], Some(line), _) if refactoring == "extract-function" => {
+            let end_line = options.end_line.unwrap_or(line);
+            refactor::extract_function(&source, line, end_line, name)
+        }
+        ([refactoring, name], Some(line), Some(column)) if refactoring == "inline-variable" => {
+            refactor::inline_variable(&source, line, column, name)
+        }
+        _ => return refactor_usage(),
+    };
+    match result {
+        Ok(source) => {
+            print!("{source}");
+            0
+        }
+        Err(message) => {
+            eprintln!("{filename}: {message}");
+            65
+        }
+    }
+}
+
+fn refactor_usage() -> i32 {
+    eprintln!("Usage: refactor rename <old> <new> --line <line> --col <column> <filename>");
+    eprintln!("       refactor extract-function <name> --line <line> [--end-line <line>] <filename>");
+    eprintln!("       refactor inline-variable <name> --line <line> --col <column> <filename>");
+    64
+}
+
+/// Runs `refs <filename>`, printing where the name at the position given by
+/// `--line` and `--col` is declared and used, one `file:line:column` per line.
+fn run_find_references(filename: &str, options: &Options) -> i32 {
+    let (Some(line), Some(column)) = (options.line, options.column) else {
+        eprintln!("refs expects a position: --line <line> --col <column>");
+        return 64;
+    };
+    let Ok(source) = fs::read_to_string(filename) else {
+        eprintln!("Failed to read file {filename}");
+        return 65;
+    };
+    match refactor::find_references(&source, line, column) {
+        Ok(occurrences) => {
+            for occurrence in occurrences {
+                println!("{filename}:{}:{}", occurrence.line, occurrence.column);
+            }
+            0
+        }
+        Err(message) => {
+            eprintln!("{filename}: {message}");
+            65
+        }
+    }
+}
+
+/// Runs `test <files or directories...>`: each script is run in a process of
+/// its own, and what it printed and how it ended are checked against the
+/// expectations in its comments. See expectation::Expectations.
+fn run_tests(paths: &[String], options: &Options) -> i32 {
+    let Ok(interpreter) = env::current_exe() else {
+        eprintln!("Can't find the interpreter to run the tests with.");
+        return 70;
+    };
+    if options.official {
+        return run_official_tests(&interpreter, Path::new(&paths[0]));
+    }
+
+    let mut scripts = Vec::new();
+    for path in paths {
+        collect_scripts(&PathBuf::from(path), &mut scripts);
+    }
+    let mut failed = 0;
+    for script in &scripts {
+        let failures = test_script(&interpreter, script);
+        match failures.is_empty() {
+            true => println!("PASS {}", script.display()),
+            false => {
+                failed += 1;
+                println!("FAIL {}", script.display());
+                for failure in failures {
+                    println!("  {failure}");
+                }
+            }
+        }
+    }
+    println!("{} passed, {failed} failed.", scripts.len() - failed);
+    if failed > 0 { 1 } else { 0 }
+}
+
+/// The directories of the official test suite that aren't for this
+/// interpreter: benchmarks, the limits of the bytecode VM, and the scanning
+/// and expression tests, which are run in special modes of the early
+/// chapters.
+const OFFICIAL_SKIPPED: [&str; 4] = ["benchmark", "limit", "scanning", "expressions"];
+
+/// Runs `test --official <dir>`: the test suite from the Crafting Interpreters
+/// repository, given either its `test` directory or the repository itself.
+/// The suite keeps a directory per feature, and features come in roughly
+/// chapter by chapter, so the pass rate of each directory shows how far along
+/// the interpreter is. Scripts at the top of the suite are counted together.
+fn run_official_tests(interpreter: &Path, dir: &Path) -> i32 {
+    let dir = match dir.join("test").is_dir() {
+        true => dir.join("test"),
+        false => dir.to_path_buf(),
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        eprintln!("Failed to read directory {}", dir.display());
+        return 65;
+    };
+    let mut entries: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    entries.sort();
+
+    let mut groups: Vec<(String, Vec<PathBuf>)> = vec![("(top level)".into(), Vec::new())];
+    for entry in entries {
+        let name = entry.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        if entry.is_dir() {
+            if OFFICIAL_SKIPPED.contains(&name.as_str()) {
+                continue;
+            }
+            let mut scripts = Vec::new();
+            collect_scripts(&entry, &mut scripts);
+            groups.push((name, scripts));
+        } else if entry.extension().is_some_and(|extension| extension == "lox") {
+            groups[0].1.push(entry);
+        }
+    }
+    groups.retain(|(_, scripts)| !scripts.is_empty());
+
+    let width = groups.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("total".len());
+    let (mut passed, mut total) = (0, 0);
+    for (name, scripts) in &groups {
+        let group_passed = scripts.iter().filter(|script| test_script(interpreter, script).is_empty()).count();
+        println!("{}", pass_rate(name, width, group_passed, scripts.len()));
+        passed += group_passed;
+        total += scripts.len();
+    }
+    println!("{}", pass_rate("total", width, passed, total));
+    if passed < total { 1 } else { 0 }
+}
+
+fn pass_rate(name: &str, width: usize, passed: usize, total: usize) -> String {
+    let rate = passed as f64 * 100.0 / total.max(1) as f64;
+    format!("{name:<width$}  {:>9}  {rate:5.1}%", format!("{passed}/{total}"))
+}
+
+/// Runs one test script and describes each way it didn't do what its
+/// comments expect.
+fn test_script(interpreter: &Path, script: &Path) -> Vec<String> {
+    let Ok(source) = fs::read_to_string(script) else {
+        return vec![format!("Failed to read file {}", script.display())];
+    };
+    let expectations = Expectations::parse(&source);
+    match Command::new(interpreter).arg("run").arg(script).output() {
+        Ok(output) => expectations.check(
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+            output.status.code().unwrap_or(-1),
+        ),
+        Err(err) => vec![format!("Could not run the script: {err}")],
+    }
+}
+
+/// Adds the script at the path, or every `.lox` file under it if it is a
+/// directory, in order by name.
+fn collect_scripts(path: &Path, scripts: &mut Vec<PathBuf>) {
+    if !path.is_dir() {
+        scripts.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else { return };
+    let mut entries: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|extension| extension == "lox") {
+            collect_scripts(&entry, scripts);
+        }
+    }
+}
+
+/// Runs the command on a single file and returns the exit code it should produce.
+fn run_file(command: &str, filename: &str, options: &Options) -> i32 {
+    error::reset();
+
+    let file_contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(_) => {
+            eprintln!("Failed to read file {filename}");
+            return 65;
+        }
+    };
     //eprintln!("{file_contents}");
 
-    match command.as_str() {
+    match command {
+        "tokenize" if options.pretty => lox::tokenize_pretty(file_contents),
         "tokenize" => lox::tokenize(file_contents),
         "parse" => lox::parse(file_contents),
         "evaluate" => lox::evaluate(file_contents),
-        "run" => lox::run(file_contents),
+        "typecheck" => lox::typecheck(file_contents),
+        "run" => {
+            interrupt::install();
+            let started = Instant::now();
+            let mut pipeline = pipeline(options).with_filename(filename);
+            if options.explain {
+                pipeline = pipeline.with_node_counts();
+            }
+            if options.exit_status {
+                pipeline = pipeline.with_exit_status();
+            }
+            if let Some(name) = &options.call {
+                let arguments = options.arguments.iter().map(|argument| argument_value(argument)).collect();
+                pipeline = pipeline.with_entry_point(name, arguments);
+            }
+            let stats = lox::run(pipeline, file_contents);
+            if options.explain {
+                eprint!("{}", report::explain(&stats));
+            }
+            if options.report.is_some() {
+                let report = Report {
+                    exit_code: error::exit_code(),
+                    diagnostics: error::diagnostics(),
+                    stats,
+                    elapsed: started.elapsed(),
+                };
+                emit_report(&report, options);
+            }
+        }
         _ => {
-            writeln!(io::stderr(), "Unknown command: {}", command).unwrap();
-            return;
+            eprintln!("Unknown command: {}", command);
+            return 0;
+        }
+    }
+
+    error::exit_code()
+}
+
+/// A pipeline with the settings the flags give for running code.
+fn pipeline(options: &Options) -> Pipeline {
+    let modules = FileResolver::with_includes(options.includes.clone());
+    let mut pipeline = Pipeline::new()
+        .with_defines(options.defines.clone())
+        .with_modules(Box::new(modules))
+        .with_policy(options.policy.clone())
+        .with_shadowing(options.shadowing);
+    if options.warnings {
+        pipeline = pipeline.with_warnings();
+    }
+    if options.unbuffered {
+        pipeline = pipeline.with_unbuffered_output();
+    }
+    if options.freeze_globals {
+        pipeline = pipeline.with_frozen_globals();
+    }
+    if options.mixed_concatenation {
+        pipeline = pipeline.with_mixed_concatenation();
+    }
+    if let Some(natives) = &options.natives {
+        pipeline = pipeline.with_natives(natives.clone());
+    }
+    pipeline
+}
+
+/// Runs `serve --socket <path>`: answers requests to run code until stopped,
+/// so tools that run a lot of small scripts don't start a process for each.
+/// Scripts named on the command line are run first, and every request
+/// starts with what they defined. See server::serve.
+fn run_server(filenames: &[String], options: &Options) -> i32 {
+    let Some(socket) = &options.socket else {
+        eprintln!("serve expects a socket: --socket <path>");
+        return 64;
+    };
+    let mut base = Lox::with_pipeline(pipeline(options));
+    for filename in filenames {
+        let Ok(source) = fs::read_to_string(filename) else {
+            eprintln!("Failed to read file {filename}");
+            return 65;
+        };
+        let code = base.run(&source);
+        if code != 0 {
+            return code;
+        }
+    }
+    match server::serve(socket, base) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("Failed to serve on {socket}: {err}");
+            74
         }
     }
+}
 
-    if error::had_error() {
-        exit(65);
+/// A command-line argument as the value an entry point gets: a number if it
+/// reads as one, true, false or nil if it is one of those words, and
+/// otherwise the string as it was given.
+fn argument_value(argument: &str) -> Object {
+    match argument {
+        "true" => Object::Boolean(true),
+        "false" => Object::Boolean(false),
+        "nil" => Object::Nil,
+        _ => match argument.parse::<f64>() {
+            Ok(number) if number.is_finite() => Object::Number(number),
+            _ => Object::String(argument.to_string()),
+        },
     }
-    if error::had_runtime_error() {
-        exit(70);
+}
+
+/// Writes the run report to the requested file, after the reports of the
+/// files run before it, or to stderr so it stays out of the program's own
+/// output.
+fn emit_report(report: &Report, options: &Options) {
+    let json = report.to_json();
+    match &options.report_file {
+        Some(path) => {
+            let appended = fs::OpenOptions::new().append(true).create(true).open(path);
+            if let Err(err) = appended.and_then(|mut file| writeln!(file, "{json}")) {
+                eprintln!("Failed to write report to {path}: {err}");
+            }
+        }
+        None => eprintln!("{json}"),
     }
-}
\ No newline at end of file
+}
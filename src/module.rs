@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The source text of a module, along with a name that identifies where it was
+/// found (a file path for modules on disk). Two imports that find the same
+/// module end up with the same name, which is how we import each module once.
+pub struct ModuleSource {
+    pub name: String,
+    pub source: String,
+}
+
+/// Finds the source code for an `import`. The interpreter doesn't care where
+/// modules live; the file system is just the default. Embedders can provide
+/// their own resolver to load modules from memory, an archive, or a virtual
+/// file system.
+pub trait ModuleResolver {
+    /// Looks up the module named by an import statement. `importer` is the name
+    /// of the module (or script) containing the import, if it has one, so that
+    /// paths can be resolved relative to it. On failure, returns a message
+    /// explaining why the module could not be loaded.
+    fn load(&self, path: &str, importer: Option<&str>) -> Result<ModuleSource, String>;
+
+    /// Whether loading a module reads it from disk, so that importing needs
+    /// the files capability.
+    fn reads_files(&self) -> bool {
+        false
+    }
+}
+
+/// Loads modules from disk. A path is first looked up relative to the file
+/// that imports it, and then in each search path in order.
+pub struct FileResolver {
+    search_paths: Vec<PathBuf>,
+}
+
+impl FileResolver {
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Self { search_paths }
+    }
+
+    /// The default search order: the current directory, then the given include
+    /// directories (from `--include` flags), then the directories listed in the
+    /// LOX_PATH environment variable.
+    pub fn with_includes(includes: Vec<PathBuf>) -> Self {
+        let mut search_paths = vec![PathBuf::from(".")];
+        search_paths.extend(includes);
+        if let Some(lox_path) = env::var_os("LOX_PATH") {
+            search_paths.extend(env::split_paths(&lox_path));
+        }
+        Self::new(search_paths)
+    }
+}
+
+impl FileResolver {
+    /// Where a module would be, in the order the places are tried: next to
+    /// the importer first, then in each search path. A place that comes up
+    /// twice, like the importer's directory also being a search path, is
+    /// only tried once.
+    fn candidates(&self, path: &str, importer: Option<&str>) -> Vec<PathBuf> {
+        // A script run as `main.lox` has an empty parent, which means the
+        // current directory.
+        let importer_dir = importer
+            .and_then(|importer| Path::new(importer).parent())
+            .map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir });
+        let mut candidates: Vec<PathBuf> = Vec::new();
+        for dir in importer_dir.into_iter().chain(self.search_paths.iter().map(PathBuf::as_path)) {
+            let candidate = dir.join(path);
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+        candidates
+    }
+}
+
+impl ModuleResolver for FileResolver {
+    fn load(&self, path: &str, importer: Option<&str>) -> Result<ModuleSource, String> {
+        let candidates = self.candidates(path, importer);
+        for candidate in &candidates {
+            if !candidate.is_file() {
+                continue;
+            }
+            return match fs::read_to_string(candidate) {
+                Ok(source) => Ok(ModuleSource { name: candidate.display().to_string(), source }),
+                Err(err) => Err(format!("Could not read module '{path}' from {}: {err}.", candidate.display())),
+            };
+        }
+        let tried: Vec<String> = candidates.iter().map(|candidate| candidate.display().to_string()).collect();
+        Err(format!("Could not find module '{path}'. Tried {}.", tried.join(", ")))
+    }
+
+    fn reads_files(&self) -> bool {
+        true
+    }
+}
+
+/// Serves modules from a map of names to source code, for embedders that keep
+/// their scripts in memory, and for tests.
+#[derive(Default)]
+pub struct MemoryResolver {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: &str, source: &str) {
+        self.modules.insert(path.to_string(), source.to_string());
+    }
+}
+
+impl ModuleResolver for MemoryResolver {
+    fn load(&self, path: &str, _importer: Option<&str>) -> Result<ModuleSource, String> {
+        match self.modules.get(path) {
+            Some(source) => Ok(ModuleSource { name: path.to_string(), source: source.clone() }),
+            None => Err(format!("Could not find module '{path}'.")),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::expr::Expr;
+use crate::fold::{self, Fold};
+use crate::stmt::Stmt;
+use crate::token::TokenType::*;
+use crate::value::object::Object::*;
+
+/// Constant folding: an operator whose operands are all literals gives the
+/// same answer every time the program runs, so it is worked out once, here,
+/// and replaced by a literal. `60 * 60 * 24` becomes `86400`, and a check
+/// like `if (!false)` no longer evaluates anything.
+///
+/// Only operations that can't fail are folded. `-"x"` is left alone, so the
+/// runtime error it causes still happens at runtime, at the right line.
+pub struct ConstantFolder;
+
+impl Fold for ConstantFolder {
+    fn fold_expr(&mut self, expr: Expr) -> Expr {
+        // Children first, so `1 + 2 + 3` folds from the inside out.
+        match fold::fold_expr(self, expr) {
+            Expr::Grouping { expression } if matches!(*expression, Expr::Literal { .. }) => *expression,
+            Expr::Unary { operator, right } => match (&operator.token_type, &*right) {
+                (MINUS, Expr::Literal { value: Number(n) }) => Expr::Literal { value: Number(-n) },
+                (BANG, Expr::Literal { value }) => Expr::Literal { value: Boolean(!value.is_truthy()) },
+                _ => Expr::Unary { operator, right },
+            },
+            Expr::Binary { left, operator, right } => {
+                let (Expr::Literal { value: l }, Expr::Literal { value: r }) = (&*left, &*right) else {
+                    return Expr::Binary { left, operator, right };
+                };
+                let value = match (&operator.token_type, l, r) {
+                    (COMMA, _, r) => r.clone(),
+                    (STAR, Number(l), Number(r)) => Number(l * r),
+                    (SLASH, Number(l), Number(r)) => Number(l / r),
+                    (PLUS, Number(l), Number(r)) => Number(l + r),
+                    (PLUS, String(l), String(r)) => String(format!("{l}{r}")),
+                    (MINUS, Number(l), Number(r)) => Number(l - r),
+                    (GREATER, Number(l), Number(r)) => Boolean(l > r),
+                    (GREATER_EQUAL, Number(l), Number(r)) => Boolean(l >= r),
+                    (LESS, Number(l), Number(r)) => Boolean(l < r),
+                    (LESS_EQUAL, Number(l), Number(r)) => Boolean(l <= r),
+                    (GREATER, String(l), String(r)) => Boolean(l > r),
+                    (GREATER_EQUAL, String(l), String(r)) => Boolean(l >= r),
+                    (LESS, String(l), String(r)) => Boolean(l < r),
+                    (LESS_EQUAL, String(l), String(r)) => Boolean(l <= r),
+                    (EQUAL_EQUAL, l, r) => Boolean(l.is_equal(r.clone())),
+                    (BANG_EQUAL, l, r) => Boolean(!l.is_equal(r.clone())),
+                    _ => return Expr::Binary { left, operator, right },
+                };
+                Expr::Literal { value }
+            }
+            other => other,
+        }
+    }
+}
+
+/// Runs the optimization passes over a parsed program.
+pub fn optimize(statements: Vec<Stmt>) -> Vec<Stmt> {
+    ConstantFolder.fold_program(statements)
+}
+
@@ -1,13 +1,17 @@
 use crate::error;
 use crate::error::Error;
 use crate::error::Error::ParseError;
-use crate::expr::Expr;
-use crate::stmt::{Stmt, FunctionDeclaration};
+use crate::expr::{Expr, MatchArm, Pattern};
+use crate::program::{Program, SourceMap};
+use crate::stmt::{Stmt, FunctionDeclaration, MethodSignature};
 use crate::token::{Token, TokenType};
 use crate::value::object::Object;
 use std::rc::Rc;
 use TokenType::*;
 
+/// The methods of a class body, as they are kept in Stmt::Class.
+type Methods = Vec<Rc<FunctionDeclaration>>;
+
 /// Parsing is the second step in compiler. Like the scanner, the parser consumes a
 /// flat input sequence, only now we’re reading tokens instead of characters, and returns
 /// a corresponding *Abstract Syntax Tree (AST)* to be passed on to the interpreter.
@@ -39,31 +43,53 @@ use TokenType::*;
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+
+    /// The text the tokens were scanned from, and the file it came from.
+    /// Neither is needed to parse, but they're handed on in the Program.
+    source: SourceMap,
+    file: Option<String>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self { tokens, ..Self::default() }
+    }
+
+    /// Records where the tokens came from, so the parsed Program can say.
+    pub fn with_source(mut self, source: &str, file: Option<&str>) -> Self {
+        self.source = SourceMap::new(source);
+        self.file = file.map(str::to_string);
+        self
     }
 
     /// This is the starting point for the grammar and represents a complete Lox script. 
     /// It parses a series of statements, as many as it can find until it hits the end.
     /// program → declaration* EOF ;
-    pub fn parse(&mut self) -> Vec<Stmt> {
+    pub fn parse(&mut self) -> Program {
         let mut stmts = Vec::new();
         while !self.is_at_end() {
-            if let Some(stmt) = self.declaration_checked() {
-                stmts.push(stmt);
+            if let Some(declared) = self.declaration_checked() {
+                stmts.extend(declared);
             }
         }
-        stmts
+
+        let mut program = Program {
+            statements: stmts,
+            source: std::mem::take(&mut self.source),
+            file: self.file.take(),
+            ..Program::default()
+        };
+        for token in self.tokens.iter().filter(|token| token.token_type == IDENTIFIER) {
+            program.strings.intern(&token.lexeme);
+        }
+        program
     }
 
     // ---------------------------------------------
     // Declarations
     // ---------------------------------------------
-    fn declaration_checked(&mut self) -> Option<Stmt> {
-        match self.declaration() {
+    fn declaration_checked(&mut self) -> Option<Vec<Stmt>> {
+        match self.declarations() {
             Ok(value) => Some(value),
             Err(_) => {
                 // As soon as the parser detects an error, it enters panic mode. It knows at least
@@ -87,29 +113,73 @@ impl Parser {
         }
     }
 
+    /// Most declarations are a single statement. Destructuring declares several
+    /// variables at once, and is desugared into a declaration for each.
+    fn declarations(&mut self) -> Result<Vec<Stmt>, Error> {
+        if self.check(VAR) && (self.check_next(LEFT_PAREN) || self.check_next(LEFT_BRACKET)) {
+            self.advance();
+            return self.destructuring_declaration();
+        }
+        self.declaration().map(|statement| vec![statement])
+    }
+
     /// These statements declare names for variables, functions, classes
-    /// declaration → classDecl | funDecl | varDecl | statement ;
+    /// declaration → classDecl | extendDecl | interfaceDecl | funDecl | varDecl | importDecl | statement ;
     fn declaration(&mut self) -> Result<Stmt, Error> {
-        if self.match_token([CLASS]) {
+        if self.match_token([IMPORT]) {
+            self.import_declaration()
+        } else if self.match_token([CLASS]) {
             self.class_declaration()
-        } else if self.match_token([FUN]) {
+        } else if self.check(IDENTIFIER) && self.peek().lexeme == "extend" && self.check_next(IDENTIFIER) {
+            self.advance();
+            self.extend_declaration()
+        } else if self.check(IDENTIFIER) && self.peek().lexeme == "interface" && self.check_next(IDENTIFIER) {
+            self.advance();
+            self.interface_declaration()
+        } else if self.check(FUN) && !self.check_next(LEFT_PAREN) {
+            // `fun` followed by a name declares a function. Followed by a '(' it
+            // starts an anonymous function, which is an expression statement.
+            self.advance();
             match self.function("function") {
                 Ok(value) => Ok(Stmt::Function { decl: Rc::new(value) }),
                 Err(err) => Err(err),
             }
         } else if self.match_token([VAR]) {
             self.var_declaration()
+        } else if self.match_token([CONST]) {
+            self.const_declaration()
         } else {
             self.statement()
         }
     }
 
+    /// The module to import is named by a string, which the interpreter's module
+    /// resolver turns into source code. "as" isn't a reserved word; it only has
+    /// meaning right after the module path.
+    /// importDecl → "import" STRING ( "as" IDENTIFIER )? ";" ;
+    fn import_declaration(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous();
+        let path = self.consume(STRING, "Expect module path after 'import'.")?;
+        let mut alias = None;
+        if self.check(IDENTIFIER) && self.peek().lexeme == "as" {
+            self.advance();
+            alias = Some(self.consume(IDENTIFIER, "Expect module name after 'as'.")?);
+        }
+        self.consume(SEMICOLON, "Expect ';' after module path.")?;
+        Ok(Stmt::Import { keyword, path: path.literal.unwrap_or_default(), alias })
+    }
+
     /// After the class name, we can have a < followed by the superclass’s name. The superclass 
     /// clause is optional because we don’t have to have a superclass. Unlike some other 
     /// object-oriented languages like Java, Lox has no root “Object” class that everything 
     /// inherits from, so when we omit the superclass clause, the class has no superclass, 
     /// not even an implicit one.
-    /// classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )? "{" function* "}" ;
+    /// classDecl → "class" IDENTIFIER ( "<" IDENTIFIER )? ( ":" IDENTIFIER ( "," IDENTIFIER )* )?
+    ///              "{" ( "class"? function | setter )* "}" ;
+    /// setter    → "set" IDENTIFIER "(" IDENTIFIER ")" block ;
+    ///
+    /// Like "as", "set" isn't a reserved word. It only starts a setter when a
+    /// name follows it, so a method can still be called `set`.
     fn class_declaration(&mut self) -> Result<Stmt, Error> {
         let name = self.consume(IDENTIFIER, "Expect class name.")?;
         
@@ -118,33 +188,235 @@ impl Parser {
             self.consume(IDENTIFIER, "Expect superclass name.")?;
             superclass = Some(Expr::Variable { name: self.previous() }); 
         }
+
+        let mut interfaces = Vec::new();
+        if self.match_token([COLON]) {
+            loop {
+                let interface = self.consume(IDENTIFIER, "Expect interface name.")?;
+                interfaces.push(Expr::Variable { name: interface });
+                if !self.match_token([COMMA]) {
+                    break;
+                }
+            }
+        }
         
+        let (methods, class_methods) = self.class_body()?;
+        Ok(Stmt::Class { name,superclass, interfaces, methods, class_methods })
+    }
+
+    /// An interface only has the signatures of its methods, with no bodies.
+    /// Like "extend", "interface" isn't a reserved word.
+    /// interfaceDecl → "interface" IDENTIFIER "{" ( IDENTIFIER "(" parameters? ")" ";" )* "}" ;
+    fn interface_declaration(&mut self) -> Result<Stmt, Error> {
+        let name = self.consume(IDENTIFIER, "Expect interface name.")?;
+        self.consume(LEFT_BRACE, "Expect '{' before interface body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(RIGHT_BRACE) && !self.is_at_end() {
+            let name = self.consume(IDENTIFIER, "Expect method name.")?;
+            self.consume(LEFT_PAREN, "Expect '(' after method name.")?;
+            let params = self.parameters()?.names;
+            self.consume(SEMICOLON, "Expect ';' after method signature.")?;
+            methods.push(MethodSignature { name, params });
+        }
+
+        self.consume(RIGHT_BRACE, "Expect '}' after interface body.")?;
+        Ok(Stmt::Interface { name, methods })
+    }
+
+    /// Like "set", "extend" isn't a reserved word. A name followed by another
+    /// name can't start any other statement, so that is enough to tell.
+    /// extendDecl → "extend" IDENTIFIER "{" ( "class"? function | setter )* "}" ;
+    fn extend_declaration(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous();
+        let name = self.consume(IDENTIFIER, "Expect class name after 'extend'.")?;
+        let (methods, class_methods) = self.class_body()?;
+        Ok(Stmt::Extend { keyword, class: Expr::Variable { name }, methods, class_methods })
+    }
+
+    /// The methods between a class's braces, and those marked `class`.
+    fn class_body(&mut self) -> Result<(Methods, Methods), Error> {
         self.consume(LEFT_BRACE, "Expect '{{' before class body.")?;
 
         let mut methods = Vec::new();
+        let mut class_methods = Vec::new();
         while !self.check(RIGHT_BRACE) && !self.is_at_end() {
-            methods.push(Rc::new(self.function("method")?));
+            if self.match_token([CLASS]) {
+                class_methods.push(Rc::new(self.function("method")?));
+            } else if self.check(IDENTIFIER) && self.peek().lexeme == "set" && self.check_next(IDENTIFIER) {
+                self.advance();
+                methods.push(Rc::new(self.setter()?));
+            } else {
+                methods.push(Rc::new(self.function("method")?));
+            }
         }
 
         self.consume(RIGHT_BRACE, "Expect '}}' after class body.")?;
-        Ok(Stmt::Class { name,superclass, methods })
+        Ok((methods, class_methods))
     }
     
     /// This parses functions and methods (inside classes). We’ll pass in "function" or “method” 
     /// for kind so that the error messages are specific to the kind of declaration being parsed.
-    /// function → IDENTIFIER "(" parameters? ")" block ;
+    /// function → IDENTIFIER "(" parameters? ")" ( "->" type )? block ;
+    /// A method may leave out its parameter list, making it a getter:
+    /// getter → IDENTIFIER ( "->" type )? block ;
+    /// parameters → ( param ( "," param )* ( "," "..." IDENTIFIER )? ) | "..." IDENTIFIER ;
     fn function(&mut self, kind: &str) -> Result<FunctionDeclaration, Error> {
         let name = self.consume(IDENTIFIER, format!("Expect {kind} name").as_str())?;
-        self.consume(LEFT_PAREN, format!("Expect '(' after {kind} name.").as_str())?;
+        let getter = kind == "method" && !self.check(LEFT_PAREN);
+        let Parameters { names: params, types: param_types, variadic } = match getter {
+            true => Parameters { names: Vec::new(), types: Vec::new(), variadic: false },
+            false => {
+                self.consume(LEFT_PAREN, format!("Expect '(' after {kind} name.").as_str())?;
+                self.parameters()?
+            }
+        };
+        let return_type = match self.match_token([ARROW]) {
+            true => Some(self.type_name()?),
+            false => None,
+        };
+
+        self.consume(LEFT_BRACE, format!("Expect '{{' before {kind} body.").as_str())?;
+        let body = self.block()?;
+        Ok(FunctionDeclaration { name, params, body, param_types, return_type, variadic, getter, setter: false })
+    }
+
+    /// A setter, after its `set`. It is a method like any other, apart from
+    /// taking exactly one parameter, the value being assigned.
+    fn setter(&mut self) -> Result<FunctionDeclaration, Error> {
+        let mut decl = self.function("setter")?;
+        if decl.params.len() != 1 || decl.variadic {
+            return Err(self.error(decl.name, "A setter takes exactly one parameter."));
+        }
+        decl.setter = true;
+        Ok(decl)
+    }
+
+    /// An anonymous function. The `fun` keyword has already been consumed.
+    /// lambda → "fun" "(" parameters? ")" ( block | "->" assignment ) ;
+    ///
+    /// The arrow form is sugar for a block holding a single return statement.
+    fn lambda(&mut self) -> Result<Expr, Error> {
+        let keyword = self.previous();
+        self.consume(LEFT_PAREN, "Expect '(' after 'fun'.")?;
+        let Parameters { names: params, types: param_types, variadic } = self.parameters()?;
+
+        let body = if self.match_token([ARROW]) {
+            let arrow = self.previous();
+            let value = self.assignment()?;
+            vec![Stmt::Return { keyword: arrow, value: Some(value) }]
+        } else {
+            self.consume(LEFT_BRACE, "Expect '{' or '->' before function body.")?;
+            self.block()?
+        };
+        let name = Token { token_type: IDENTIFIER, lexeme: "lambda".into(), ..keyword };
+        let decl = FunctionDeclaration { name, params, body, param_types, return_type: None, variadic, getter: false, setter: false };
+        Ok(Expr::Lambda { decl: Rc::new(decl) })
+    }
+
+    /// list → "[" ( assignment ( "," assignment )* ","? )? "]" ;
+    fn list(&mut self) -> Result<Expr, Error> {
+        let mut elements = Vec::new();
+        while !self.check(RIGHT_BRACKET) {
+            elements.push(self.assignment()?);
+            if !self.match_token([COMMA]) {
+                break;
+            }
+        }
+        self.consume(RIGHT_BRACKET, "Expect ']' after list elements.")?;
+        Ok(Expr::List { elements })
+    }
+
+    /// A '{' that starts a statement is always a block, so a map literal can
+    /// only appear where an expression is expected.
+    /// map → "{" ( entry ( "," entry )* ","? )? "}" ;
+    /// entry → assignment ":" assignment ;
+    fn map(&mut self) -> Result<Expr, Error> {
+        let brace = self.previous();
+        let mut entries = Vec::new();
+        while !self.check(RIGHT_BRACE) {
+            let key = self.assignment()?;
+            self.consume(COLON, "Expect ':' after map key.")?;
+            let value = self.assignment()?;
+            entries.push((key, value));
+            if !self.match_token([COMMA]) {
+                break;
+            }
+        }
+        self.consume(RIGHT_BRACE, "Expect '}' after map entries.")?;
+        Ok(Expr::Map { brace, entries })
+    }
+
+    /// match → "match" "(" expression ")" "{" ( arm ( "," arm )* ","? )? "}" ;
+    /// arm   → pattern "->" assignment ;
+    fn match_expression(&mut self) -> Result<Expr, Error> {
+        let keyword = self.previous();
+        self.consume(LEFT_PAREN, "Expect '(' after 'match'.")?;
+        let subject = self.expression()?;
+        self.consume(RIGHT_PAREN, "Expect ')' after match value.")?;
+        self.consume(LEFT_BRACE, "Expect '{' before match arms.")?;
+        let mut arms = Vec::new();
+        while !self.check(RIGHT_BRACE) {
+            let pattern = self.pattern()?;
+            self.consume(ARROW, "Expect '->' after pattern.")?;
+            let body = self.assignment()?;
+            arms.push(MatchArm { pattern, body });
+            if !self.match_token([COMMA]) {
+                break;
+            }
+        }
+        self.consume(RIGHT_BRACE, "Expect '}' after match arms.")?;
+        Ok(Expr::Match { keyword, subject: Box::new(subject), arms })
+    }
 
-        // parameters → IDENTIFIER ( "," IDENTIFIER )* ;
+    /// pattern → "-"? NUMBER | STRING | "true" | "false" | "nil"
+    ///         | IDENTIFIER ( ":" IDENTIFIER )? ;
+    fn pattern(&mut self) -> Result<Pattern, Error> {
+        if self.match_token([MINUS]) {
+            let number = self.consume(NUMBER, "Expect number after '-' in pattern.")?;
+            let number: f64 = number.literal.unwrap().parse().unwrap();
+            return Ok(Pattern::Literal { value: Object::Number(-number) });
+        }
+        if [NUMBER, STRING, TRUE, FALSE, NIL].into_iter().any(|token_type| self.check(token_type)) {
+            let Expr::Literal { value } = self.primary()? else { unreachable!("a literal token parses to a literal") };
+            return Ok(Pattern::Literal { value });
+        }
+        if self.match_token([IDENTIFIER]) {
+            let name = self.previous();
+            let mut type_name = None;
+            if self.match_token([COLON]) {
+                type_name = Some(Box::new(Expr::Variable { name: self.consume(IDENTIFIER, "Expect type name.")? }));
+            }
+            let name = (name.lexeme != "_").then_some(name);
+            return Ok(Pattern::Binding { name, type_name });
+        }
+        Err(self.error(self.peek(), "Expect pattern."))
+    }
+
+    /// Parses a parameter list up to and including the closing parenthesis.
+    /// parameters → IDENTIFIER ( ":" type )? ( "," IDENTIFIER ( ":" type )? )* ;
+    fn parameters(&mut self) -> Result<Parameters, Error> {
         let mut params = Vec::new();
+        let mut types = Vec::new();
+        let mut variadic = false;
         if !self.check(RIGHT_PAREN) {
             loop {
                 if params.len() > 255 {
                     self.error(self.peek(), "Can't have more than 255 parameters.");
                 }
+                // A rest parameter collects the remaining arguments into a
+                // list, so its type is always a list and it has to come last.
+                if self.match_token([ELLIPSIS]) {
+                    variadic = true;
+                    params.push(self.consume(IDENTIFIER, "Expect parameter name after '...'.")?);
+                    types.push(None);
+                    if self.check(COMMA) {
+                        return Err(self.error(self.peek(), "The rest parameter must be the last one."));
+                    }
+                    break;
+                }
                 params.push(self.consume(IDENTIFIER, "Expect parameter name.")?);
+                types.push(self.type_annotation()?);
                 
                 if !self.match_token([COMMA])  {
                     break;
@@ -152,23 +424,98 @@ impl Parser {
             }
         }
         self.consume(RIGHT_PAREN, "Expect ')' after parameters.")?;
-        
-        self.consume(LEFT_BRACE, format!("Expect '{{' before {kind} body.").as_str())?;
-        let body = self.block()?;
-        Ok(FunctionDeclaration { name, params, body })
+        Ok(Parameters { names: params, types, variadic })
+    }
+
+    /// An optional type after a name, as in `n: Number`.
+    fn type_annotation(&mut self) -> Result<Option<Token>, Error> {
+        match self.match_token([COLON]) {
+            true => Ok(Some(self.type_name()?)),
+            false => Ok(None),
+        }
+    }
+
+    /// type → IDENTIFIER | "nil" ;
+    ///
+    /// Types are named by single identifiers, like Number or the name of a
+    /// class. What the names mean is up to the type checker.
+    fn type_name(&mut self) -> Result<Token, Error> {
+        if self.match_token([IDENTIFIER, NIL]) {
+            return Ok(self.previous());
+        }
+        Err(self.error(self.peek(), "Expect type name."))
     }
 
     /// Parses variable declarations 
-    /// varDecl → "var" IDENTIFIER ( "=" expression )? ";" ;
+    /// varDecl → "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";" ;
     fn var_declaration(&mut self) -> Result<Stmt, Error> {
         let name = self.consume(IDENTIFIER, "Expect variable name")?;
+        let type_name = self.type_annotation()?;
         let mut initializer: Option<Expr> = None;
         if self.match_token([EQUAL]) {
             initializer = Some(self.expression()?);
         }
 
         self.consume(SEMICOLON, "Expect ';' after variable declaration")?;
-        Ok(Stmt::Var { name, initializer })
+        Ok(Stmt::Var { name, type_name, initializer, mutable: true })
+    }
+
+    /// Unpacks the elements of a list into variables, in order:
+    ///
+    /// ```text
+    /// var (a, b) = pair();        var <list> = pair();
+    ///                      -->    var a = <list>[0];
+    ///                             var b = <list>[1];
+    /// ```
+    ///
+    /// The value is stored first in a variable of its own, whose name can't
+    /// be written in Lox, so that it is evaluated just once. Either brackets
+    /// or parentheses can enclose the names. Extra elements are left out, and
+    /// missing ones are an index error, at the opening bracket.
+    /// destructure → "var" ( "(" names ")" | "[" names "]" ) "=" expression ";" ;
+    /// names       → IDENTIFIER ( "," IDENTIFIER )* ;
+    fn destructuring_declaration(&mut self) -> Result<Vec<Stmt>, Error> {
+        let bracket = self.advance();
+        let (closing, message) = match bracket.token_type {
+            LEFT_PAREN => (RIGHT_PAREN, "Expect ')' after variable names."),
+            _ => (RIGHT_BRACKET, "Expect ']' after variable names."),
+        };
+        let mut names = vec![self.consume(IDENTIFIER, "Expect variable name.")?];
+        while self.match_token([COMMA]) {
+            names.push(self.consume(IDENTIFIER, "Expect variable name.")?);
+        }
+        self.consume(closing, message)?;
+        self.consume(EQUAL, "Expect '=' after variable names.")?;
+        let value = self.expression()?;
+        self.consume(SEMICOLON, "Expect ';' after variable declaration")?;
+
+        let list = Token {
+            token_type: IDENTIFIER,
+            lexeme: format!("(destructured {}:{})", bracket.line, bracket.column),
+            ..bracket.clone()
+        };
+        let mut statements = vec![Stmt::Var { name: list.clone(), type_name: None, initializer: Some(value), mutable: true }];
+        for (index, name) in names.into_iter().enumerate() {
+            let element = Expr::Index {
+                object: Box::new(Expr::Variable { name: list.clone() }),
+                bracket: bracket.clone(),
+                index: Box::new(Expr::Literal { value: Object::Number(index as f64) }),
+            };
+            statements.push(Stmt::Var { name, type_name: None, initializer: Some(element), mutable: true });
+        }
+        Ok(statements)
+    }
+
+    /// A constant is a variable that can't be assigned again, so it has to be
+    /// given its value where it is declared.
+    /// constDecl → "const" IDENTIFIER ( ":" type )? "=" expression ";" ;
+    fn const_declaration(&mut self) -> Result<Stmt, Error> {
+        let name = self.consume(IDENTIFIER, "Expect constant name.")?;
+        let type_name = self.type_annotation()?;
+        self.consume(EQUAL, "Expect '=' after constant name.")?;
+        let initializer = Some(self.expression()?);
+        self.consume(SEMICOLON, "Expect ';' after constant declaration.")?;
+        Ok(Stmt::Var { name, type_name, initializer, mutable: false })
     }
 
     // ---------------------------------------------
@@ -176,8 +523,17 @@ impl Parser {
     // ---------------------------------------------
 
     fn statement(&mut self) -> Result<Stmt, Error> {
+        if self.match_token([BREAK]) {
+            return self.break_statement();
+        }
+        if self.match_token([CONTINUE]) {
+            return self.continue_statement();
+        }
+        if self.check(IDENTIFIER) && self.check_next(COLON) {
+            return self.labeled_statement();
+        }
         if self.match_token([FOR]) {
-            return self.for_statement();
+            return self.for_statement(None);
         }
         if self.match_token([IF]) {
             return self.if_statement();
@@ -188,8 +544,14 @@ impl Parser {
         if self.match_token([RETURN]) {
             return self.return_statement();
         }
+        if self.match_token([THROW]) {
+            return self.throw_statement();
+        }
+        if self.match_token([TRY]) {
+            return self.try_statement();
+        }
         if self.match_token([WHILE]) {
-            return self.while_statement();
+            return self.while_statement(None);
         }
         if self.match_token([LEFT_BRACE]) {
             let statements = self.block()?;
@@ -199,12 +561,64 @@ impl Parser {
         self.expression_statement()
     }
 
+    /// A label names the loop that follows it. Only loops can be labeled,
+    /// since only loops can be broken out of.
+    /// labeledStmt → IDENTIFIER ":" ( forStmt | whileStmt ) ;
+    fn labeled_statement(&mut self) -> Result<Stmt, Error> {
+        let label = self.advance();
+        self.advance();
+        if self.match_token([FOR]) {
+            return self.for_statement(Some(label));
+        }
+        if self.match_token([WHILE]) {
+            return self.while_statement(Some(label));
+        }
+        Err(self.error(self.peek(), "Expect loop after label."))
+    }
+
+    /// breakStmt → "break" IDENTIFIER? ";" ;
+    fn break_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous();
+        let label = self.loop_label();
+        self.consume(SEMICOLON, "Expect ';' after 'break'.")?;
+        Ok(Stmt::Break { keyword, label })
+    }
+
+    /// continueStmt → "continue" IDENTIFIER? ";" ;
+    fn continue_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous();
+        let label = self.loop_label();
+        self.consume(SEMICOLON, "Expect ';' after 'continue'.")?;
+        Ok(Stmt::Continue { keyword, label })
+    }
+
+    /// The label naming the loop a `break` or `continue` is for, if any.
+    fn loop_label(&mut self) -> Option<Token> {
+        match self.match_token([IDENTIFIER]) {
+            true => Some(self.previous()),
+            false => None,
+        }
+    }
+
     /// forStmt → "for" "(" ( varDecl | exprStmt | ";" )
     ///           expression? ";"
-    ///           expression? ")" statement ;
-    fn for_statement(&mut self) -> Result<Stmt, Error> {
+    ///           expression? ")" statement
+    ///         | "for" "(" "var"? IDENTIFIER "in" expression ")" statement ;
+    fn for_statement(&mut self, label: Option<Token>) -> Result<Stmt, Error> {
+        let keyword = self.previous();
         self.consume(LEFT_PAREN, "Expect '(' after 'for'.")?;
 
+        // Like "as", "in" isn't a reserved word. A name followed by it can
+        // only start the other kind of loop, since `item in` isn't the start
+        // of any expression.
+        let var = usize::from(self.check(VAR));
+        let is_in = |token: Option<&Token>| token.is_some_and(|token| token.token_type == IDENTIFIER && token.lexeme == "in");
+        if self.tokens.get(self.current + var).is_some_and(|token| token.token_type == IDENTIFIER)
+            && is_in(self.tokens.get(self.current + var + 1))
+        {
+            return self.for_in_statement(label);
+        }
+
         // The first clause is the initializer. It is executed exactly once,
         // before anything else. It’s usually an expression, but for convenience,
         // we also allow a variable declaration. The variable is scoped to the
@@ -248,22 +662,18 @@ impl Parser {
         // de-sugaring comes in. Instead of a 'for' node, we synthesize AST
         // node that express the semantics of the for loop into a while loop.
 
-        // Working backwards, we start with the increment clause. The increment, 
-        // if there is one, executes after the body in each iteration of the loop. 
-        // We do that by replacing the body with a little block that contains the 
-        // original body followed by an expression statement that evaluates the increment.
-        if let Some(increment) = increment {
-            let increment_stmt = Stmt::Expression { expression: increment };
-            body = Stmt::Block { statements: vec![body, increment_stmt] }
-        }
-        
-        // Next, we take the condition and the body and build the loop using a 
-        // primitive while loop. If the condition is omitted, we jam in 'true' 
+        // We take the condition and the body and build the loop using a
+        // primitive while loop. If the condition is omitted, we jam in 'true'
         // to make an infinite loop.
+        //
+        // The increment, if there is one, executes after the body in each
+        // iteration of the loop. We can't simply append it to the body, because
+        // a `continue` skips whatever is left of the body, and the increment
+        // must still run. So the while loop keeps it in a slot of its own.
         if condition.is_none() {
             condition = Some(Expr::Literal { value: Object::Boolean(true) });
         }
-        body = Stmt::While { condition: condition.unwrap(), body: Box::new(body) };
+        body = Stmt::While { keyword, condition: condition.unwrap(), body: Box::new(body), increment, label };
         
         // Finally, if there is an initializer, it runs once before the entire loop. 
         // We do that by, again, replacing the whole statement with a block that runs 
@@ -278,6 +688,19 @@ impl Parser {
         Ok(body)
     }
 
+    /// Iterating over a collection doesn't desugar into a while loop as neatly,
+    /// since stepping through the collection needs state that the program
+    /// can't see, so it gets a statement of its own.
+    fn for_in_statement(&mut self, label: Option<Token>) -> Result<Stmt, Error> {
+        self.match_token([VAR]);
+        let name = self.consume(IDENTIFIER, "Expect variable name.")?;
+        let keyword = self.advance();
+        let collection = self.expression()?;
+        self.consume(RIGHT_PAREN, "Expect ')' after collection.")?;
+        let body = self.statement()?;
+        Ok(Stmt::ForIn { name, keyword, collection, body: Box::new(body), label })
+    }
+
     /// ifStmt → "if" "(" expression ")" statement ( "else" statement )? ;
     fn if_statement(&mut self) -> Result<Stmt, Error> {
         self.consume(LEFT_PAREN, "Expect '(' after 'if'.")?;
@@ -305,9 +728,10 @@ impl Parser {
 
     /// printStmt → "print" expression ";" ;
     fn print_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous();
         let expression = self.expression()?;
         self.consume(SEMICOLON, "Expect ';' after value.")?;
-        Ok(Stmt::Print { expression })
+        Ok(Stmt::Print { keyword, expression })
     }
     
     /// returnStmt → "return" expression? ";" ;
@@ -321,13 +745,46 @@ impl Parser {
         Ok(Stmt::Return { keyword, value })
     }
 
+    /// throwStmt → "throw" expression ";" ;
+    fn throw_statement(&mut self) -> Result<Stmt, Error> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(SEMICOLON, "Expect ';' after thrown value.")?;
+        Ok(Stmt::Throw { keyword, value })
+    }
+
+    /// tryStmt → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+    /// At least one of the two clauses has to be there.
+    fn try_statement(&mut self) -> Result<Stmt, Error> {
+        self.consume(LEFT_BRACE, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+        let mut name = None;
+        let mut handler = Vec::new();
+        if self.match_token([CATCH]) {
+            self.consume(LEFT_PAREN, "Expect '(' after 'catch'.")?;
+            name = Some(self.consume(IDENTIFIER, "Expect error variable name.")?);
+            self.consume(RIGHT_PAREN, "Expect ')' after error variable name.")?;
+            self.consume(LEFT_BRACE, "Expect '{' before catch block.")?;
+            handler = self.block()?;
+        }
+        let mut finally = None;
+        if self.match_token([FINALLY]) {
+            self.consume(LEFT_BRACE, "Expect '{' after 'finally'.")?;
+            finally = Some(self.block()?);
+        } else if name.is_none() {
+            return Err(self.error(self.peek(), "Expect 'catch' or 'finally' after try block."));
+        }
+        Ok(Stmt::Try { body, name, handler, finally })
+    }
+
     /// whileStmt → "while" "(" expression ")" statement ;
-    fn while_statement(&mut self) -> Result<Stmt, Error> {
+    fn while_statement(&mut self, label: Option<Token>) -> Result<Stmt, Error> {
+        let keyword = self.previous();
         self.consume(LEFT_PAREN, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(RIGHT_PAREN, "Expect ')' after condition.")?;
         let body = self.statement()?;
-        Ok(Stmt::While {condition, body: Box::new(body)})
+        Ok(Stmt::While {keyword, condition, body: Box::new(body), increment: None, label})
     }
 
     /// exprStmt → expression ";" ;
@@ -343,7 +800,7 @@ impl Parser {
         let mut statements = Vec::new();
 
         while !self.check(RIGHT_BRACE) && !self.is_at_end() {
-            statements.push(self.declaration()?);
+            statements.extend(self.declarations()?);
         }
 
         self.consume(RIGHT_BRACE, "Expect '}' after block.")?;
@@ -354,13 +811,31 @@ impl Parser {
     // Expressions
     // ---------------------------------------------
 
-    /// expression → assignment ;
+    /// expression → comma ;
     pub fn expression(&mut self) -> Result<Expr, Error> {
-        self.assignment()
+        self.comma()
+    }
+
+    /// The comma operator evaluates its operands left to right and takes the
+    /// value of the last, as in C: `a = (b(), c(), d)` calls b and c, then
+    /// assigns d. It has the lowest precedence of all, so in lists of
+    /// expressions, like arguments and list elements, a comma still separates
+    /// them: those are lists of assignments, and a sequence has to be put in
+    /// parentheses to be one of them.
+    /// comma → assignment ( "," assignment )* ;
+    fn comma(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.assignment()?;
+        while self.match_token([COMMA]) {
+            let operator = self.previous();
+            let right = self.assignment()?;
+            expr = Expr::Binary { left: Box::new(expr), operator, right: Box::new(right) };
+        }
+        Ok(expr)
     }
 
     /// Assigns value to a variable
-    /// assignment → ( call "." )? IDENTIFIER "=" assignment | logic_or ;
+    /// assignment → ( call "." )? IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" ) assignment
+    ///            | logic_or ;
     fn assignment(&mut self) -> Result<Expr, Error> {
         let expr = self.or()?; // Left-hand side, which can be any expression of higher precedence. 
 
@@ -374,10 +849,43 @@ impl Parser {
                 Expr::Get {object, name} => {
                     return Ok(Expr::Set { object, name, value });
                 }
+                Expr::Index { object, bracket, index } => {
+                    return Ok(Expr::IndexSet { object, bracket, index, value });
+                }
                 _ => return Err(self.error(equals, "Invalid assignment target.")),
             }
         }
 
+        // Compound assignment is syntactic sugar. `x += e` becomes `x = x + e`,
+        // with a copy of the target read back as the left operand. The resolver
+        // and interpreter only ever see the plain assignment. One consequence is
+        // that for a property or element target the object expression (and the
+        // index) are evaluated twice.
+        if self.match_token([PLUS_EQUAL, MINUS_EQUAL, STAR_EQUAL, SLASH_EQUAL]) {
+            let equals = self.previous();
+            let (token_type, lexeme) = match equals.token_type {
+                PLUS_EQUAL => (PLUS, "+"),
+                MINUS_EQUAL => (MINUS, "-"),
+                STAR_EQUAL => (STAR, "*"),
+                _ => (SLASH, "/"),
+            };
+            let operator = Token { token_type, lexeme: lexeme.to_string(), ..equals.clone() };
+            let right = self.assignment()?;
+            let value = |left: Expr| Box::from(Expr::Binary { left: Box::from(left), operator, right: Box::from(right) });
+            return match expr {
+                Expr::Variable { name } => Ok(Expr::Assign { value: value(Expr::Variable { name: name.clone() }), name }),
+                Expr::Get { object, name } => {
+                    let current = Expr::Get { object: object.clone(), name: name.clone() };
+                    Ok(Expr::Set { object, name, value: value(current) })
+                }
+                Expr::Index { object, bracket, index } => {
+                    let current = Expr::Index { object: object.clone(), bracket: bracket.clone(), index: index.clone() };
+                    Ok(Expr::IndexSet { object, bracket, index, value: value(current) })
+                }
+                _ => Err(self.error(equals, "Invalid assignment target.")),
+            };
+        }
+
         Ok(expr)
     }
 
@@ -400,13 +908,13 @@ impl Parser {
         Ok(expr)
     }
 
-    /// logic_and → equality ( "and" equality )* ;
+    /// logic_and → bit_or ( "and" bit_or )* ;
     fn and(&mut self) -> Result<Expr, Error> {
-        let mut expr = self.equality()?;
+        let mut expr = self.bit_or()?;
         
         while self.match_token([AND]) {
             let operator = self.previous();
-            let right = self.equality()?;
+            let right = self.bit_or()?;
             expr = Expr::Logical {
                 left: Box::from(expr),
                 operator,
@@ -417,6 +925,61 @@ impl Parser {
         Ok(expr)
     }
 
+    // ----Bitwise operators-----------------------------
+    // As in C, these bind more loosely than equality, so `flags & MASK == 0`
+    // needs parentheses. Each level is an ordinary left-associative binary rule.
+
+    /// bit_or → bit_xor ( "|" bit_xor )* ;
+    fn bit_or(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.bit_xor()?;
+
+        while self.match_token([PIPE]) {
+            let operator = self.previous();
+            let right = self.bit_xor()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// bit_xor → bit_and ( "^" bit_and )* ;
+    fn bit_xor(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.bit_and()?;
+
+        while self.match_token([CARET]) {
+            let operator = self.previous();
+            let right = self.bit_and()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// bit_and → equality ( "&" equality )* ;
+    fn bit_and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.equality()?;
+
+        while self.match_token([AMPERSAND]) {
+            let operator = self.previous();
+            let right = self.equality()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
     /// equal or not-equal
     /// equality → comparison ( ( "!=" | "==" ) comparison )* ;
     fn equality(&mut self) -> Result<Expr, Error> {
@@ -435,12 +998,39 @@ impl Parser {
         Ok(expr)
     }
 
-    /// less than and greater than
-    /// comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+    /// less than and greater than, and checking an instance's class
+    /// comparison → shift ( ( ">" | ">=" | "<" | "<=" | "is" ) shift )* ;
+    ///
+    /// Like "as", "is" isn't a reserved word. A name can't follow an operand,
+    /// so one spelled `is` there can only be the operator.
     fn comparison(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.shift()?;
+
+        loop {
+            let operator = if self.match_token([GREATER, GREATER_EQUAL, LESS, LESS_EQUAL]) {
+                self.previous()
+            } else if self.check(IDENTIFIER) && self.peek().lexeme == "is" {
+                Token { token_type: IS, ..self.advance() }
+            } else {
+                break;
+            };
+            let right = self.shift()?;
+            expr = Expr::Binary {
+                left: Box::from(expr),
+                operator,
+                right: Box::from(right),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    /// bit shifts
+    /// shift → term ( ( "<<" | ">>" ) term )* ;
+    fn shift(&mut self) -> Result<Expr, Error> {
         let mut expr = self.term()?;
 
-        while self.match_token([GREATER, GREATER_EQUAL, LESS, LESS_EQUAL]) {
+        while self.match_token([LESS_LESS, GREATER_GREATER]) {
             let operator = self.previous();
             let right = self.term()?;
             expr = Expr::Binary {
@@ -471,12 +1061,12 @@ impl Parser {
         Ok(expr)
     }
 
-    /// multiplication and division
-    /// factor → unary ( ( "/" | "*" ) unary )* ;
+    /// multiplication, division and remainder
+    /// factor → unary ( ( "/" | "*" | "%" ) unary )* ;
     fn factor(&mut self) -> Result<Expr, Error> {
         let mut expr = self.unary()?;
 
-        while self.match_token([SLASH, STAR]) {
+        while self.match_token([SLASH, STAR, PERCENT]) {
             let operator = self.previous();
             let right = self.unary()?;
             expr = Expr::Binary {
@@ -491,9 +1081,9 @@ impl Parser {
 
     // ----Unary operators-----------------------------
 
-    /// unary → ( "!" | "-" ) unary | call ;
+    /// unary → ( "!" | "-" | "~" ) unary | ( "++" | "--" ) unary | postfix ;
     fn unary(&mut self) -> Result<Expr, Error> {
-        if self.match_token([BANG, MINUS]) {
+        if self.match_token([BANG, MINUS, TILDE]) {
             let operator = self.previous();
             let right = self.unary()?;
             return Ok(Expr::Unary {
@@ -501,11 +1091,34 @@ impl Parser {
                 right: Box::from(right),
             });
         }
+        if self.match_token([PLUS_PLUS, MINUS_MINUS]) {
+            let operator = self.previous();
+            let target = self.unary()?;
+            return self.update(operator, target, true);
+        }
 
-        self.call()
+        self.postfix()
     }
 
-    /// call → primary ( "(" arguments? ")" | "." IDENTIFIER )* ;
+    /// postfix → call ( "++" | "--" )? ;
+    fn postfix(&mut self) -> Result<Expr, Error> {
+        let expr = self.call()?;
+        if self.match_token([PLUS_PLUS, MINUS_MINUS]) {
+            let operator = self.previous();
+            return self.update(operator, expr, false);
+        }
+        Ok(expr)
+    }
+
+    /// Like assignment, `++` and `--` need a target that names a storage location.
+    fn update(&mut self, operator: Token, target: Expr, prefix: bool) -> Result<Expr, Error> {
+        match target {
+            Expr::Variable { .. } | Expr::Get { .. } | Expr::Index { .. } => Ok(Expr::Update { operator, target: Box::from(target), prefix }),
+            _ => Err(self.error(operator, "Invalid increment target.")),
+        }
+    }
+
+    /// call → primary ( "(" arguments? ")" | "." IDENTIFIER | "[" expression "]" )* ;
     fn call(&mut self) -> Result<Expr, Error> {
         let mut callee = self.primary()?;
 
@@ -517,6 +1130,11 @@ impl Parser {
             } else if self.match_token([DOT]) {
                 let name = self.consume(IDENTIFIER, "Expect property name after '.'.")?;
                 callee = Expr::Get { object: callee.into(), name }
+            } else if self.match_token([LEFT_BRACKET]) {
+                let bracket = self.previous();
+                let index = self.expression()?;
+                self.consume(RIGHT_BRACKET, "Expect ']' after index.")?;
+                callee = Expr::Index { object: callee.into(), bracket, index: index.into() }
             } else {
                 break;
             }
@@ -524,7 +1142,7 @@ impl Parser {
         Ok(callee)
     }
     
-    /// arguments → expression ( "," expression )* ;
+    /// arguments → assignment ( "," assignment )* ;
     fn finish_call(&mut self, callee: Expr) -> Result<Expr, Error> {
         let mut arguments = Vec::new();
         if !self.check(RIGHT_PAREN) {
@@ -532,7 +1150,7 @@ impl Parser {
                 if arguments.len() >= 255 {
                     self.error(self.peek(), "Can't have more than 255 arguments.");
                 }
-                arguments.push(self.expression()?);
+                arguments.push(self.assignment()?);
                 if !self.match_token([COMMA]) {
                     break;
                 }
@@ -573,6 +1191,18 @@ impl Parser {
         if self.match_token([THIS]) {
             return Ok(Expr::This { keyword: self.previous() });
         }
+        if self.match_token([FUN]) {
+            return self.lambda();
+        }
+        if self.match_token([LEFT_BRACKET]) {
+            return self.list();
+        }
+        if self.match_token([MATCH]) {
+            return self.match_expression();
+        }
+        if self.match_token([LEFT_BRACE]) {
+            return self.map();
+        }
         if self.match_token([IDENTIFIER]) {
             return Ok(Expr::Variable { name: self.previous() });
         }
@@ -625,6 +1255,14 @@ impl Parser {
         self.peek().token_type == token_type
     }
 
+    /// Like check(), but looks one token further ahead.
+    fn check_next(&self, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
     /// The advance() method consumes the current token and returns it.
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
@@ -668,7 +1306,7 @@ impl Parser {
             }
 
             match self.peek().token_type {
-                CLASS | FUN | VAR | FOR | IF | WHILE | PRINT | RETURN => return,
+                CLASS | FUN | VAR | CONST | FOR | IF | WHILE | PRINT | RETURN | IMPORT | THROW | TRY => return,
                 _ => {}
             }
 
@@ -676,3 +1314,10 @@ impl Parser {
         }
     }
 }
+
+/// A function's parameter list, as the parser reads it.
+struct Parameters {
+    names: Vec<Token>,
+    types: Vec<Option<Token>>,
+    variadic: bool,
+}
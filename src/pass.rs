@@ -0,0 +1,48 @@
+use crate::error;
+use crate::pipeline::Resolved;
+use crate::token::Token;
+
+/// A pass over the syntax tree that an embedder adds to the pipeline: a lint
+/// that looks for code to warn about, or a transform that rewrites it. Passes
+/// run after the resolver, so a lint can tell a local from a global by
+/// looking an expression up in the side table, and before execution, so
+/// an error one reports keeps the program from running.
+///
+/// Passes run in the order they were added to the pipeline (see
+/// `Pipeline::with_pass`), each on the program as the one before left it.
+///
+/// The side table refers to expressions by address, and a rewritten program
+/// is made of new ones. So a pass that changes the program says so, and the
+/// program is resolved again before the next pass runs. A pass that rewrites
+/// the program can't use the table for the parts it has built itself.
+pub trait Pass {
+    /// A short name for the pass, like `no-print`, shown after each message
+    /// it reports.
+    fn name(&self) -> &str;
+
+    /// Looks over, or rewrites, the program. Returns whether it changed it.
+    fn run(&self, resolved: &mut Resolved, reporter: &mut Reporter) -> bool;
+}
+
+/// How a pass reports what it finds, into the same diagnostics as the rest
+/// of the pipeline: printed as they are found, recorded for run reports,
+/// and, for errors, failing the compile.
+pub struct Reporter<'a> {
+    pass: &'a str,
+}
+
+impl<'a> Reporter<'a> {
+    pub(crate) fn new(pass: &'a str) -> Self {
+        Self { pass }
+    }
+
+    /// Reports code that can't be allowed to run.
+    pub fn error(&mut self, token: &Token, message: &str) {
+        error::token_error(token.clone(), format!("{message} [{}]", self.pass));
+    }
+
+    /// Reports code that can run, but that the pass has something to say about.
+    pub fn warning(&mut self, token: &Token, message: &str) {
+        error::token_warning(token.clone(), format!("{message} [{}]", self.pass));
+    }
+}
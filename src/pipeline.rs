@@ -0,0 +1,474 @@
+use crate::error;
+use crate::expr::Expr;
+use crate::interpreter::{Interpreter, Stats};
+use crate::macros::Macro;
+use crate::module::{FileResolver, ModuleResolver};
+use crate::optimizer;
+use crate::parser::Parser;
+use crate::pass::{Pass, Reporter};
+use crate::policy::{Limits, Policy, Shadowing};
+use crate::preprocessor::preprocess;
+use crate::program::Program;
+use crate::resolver::Resolver;
+use crate::scanner::{ScanLimits, Scanner};
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::token::TokenType::IDENTIFIER;
+use crate::typecheck::TypeChecker;
+use crate::value::native::Natives;
+use crate::value::object::Object;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// The stages a program goes through, from source text to execution:
+///
+/// scan → preprocess → parse → optimize → resolve → passes → execute
+///
+/// With warnings turned on, the resolved program is also looked over for
+/// operations that are bound to fail, before it runs. The passes are the
+/// embedder's own, if it added any (see pass::Pass).
+///
+/// Each stage is a method taking the previous stage's output, so a command
+/// can run as much of the pipeline as it needs (tokenize stops after the
+/// scanner, parse after the parser) without re-implementing the plumbing.
+/// Stages that can fail report their errors as they find them and return None,
+/// and the caller stops there.
+///
+/// The pipeline also carries the settings that more than one stage needs.
+pub struct Pipeline {
+    /// Symbols that `#if` directives test against.
+    defines: HashSet<String>,
+
+    /// Finds the source code for imports. Handed over to the interpreter.
+    modules: Option<Box<dyn ModuleResolver>>,
+
+    /// The name of the script, used to resolve relative imports.
+    filename: Option<String>,
+
+    /// The file name errors are reported against. The main script's errors
+    /// carry only a line number, so this is left unset for it.
+    diagnostic_file: Option<String>,
+
+    /// What the program is allowed to do outside the interpreter.
+    policy: Policy,
+
+    /// Whether to warn about operations that are bound to fail.
+    warnings: bool,
+
+    /// Whether the interpreter may buffer what the program prints.
+    buffered: bool,
+
+    /// Whether functions are kept from assigning to globals.
+    frozen_globals: bool,
+
+    /// Whether `+` joins a string and a number.
+    mixed_concatenation: bool,
+
+    /// The natives the interpreter defines, if not the standard ones.
+    natives: Option<Natives>,
+
+    /// Whether the interpreter counts the nodes it runs, by kind.
+    count_nodes: bool,
+
+    /// What to do about globals declared with the names of natives.
+    shadowing: Shadowing,
+
+    /// How long a token in the source may be.
+    scan_limits: ScanLimits,
+
+    /// The most a program may do, whatever its pragmas ask for.
+    limits: Limits,
+
+    /// Rewrites of the token stream, run in order after the preprocessor.
+    macros: Vec<Rc<dyn Macro>>,
+
+    /// Passes over the resolved program, run in order before it executes.
+    passes: Vec<Rc<dyn Pass>>,
+
+    /// The function to call once the top level has run, and its arguments.
+    entry_point: Option<(String, Vec<Object>)>,
+
+    /// Whether the program's result is its exit status, see `with_exit_status`.
+    exit_status: bool,
+}
+
+/// The output of the resolve stage: the program together with the side table
+/// the interpreter uses to find local variables. The table refers to the
+/// program's expressions by address, so the two travel together.
+pub struct Resolved {
+    pub program: Program,
+    pub locals: HashMap<*const Expr, usize>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            defines: HashSet::new(),
+            modules: None,
+            filename: None,
+            diagnostic_file: None,
+            policy: Policy::default(),
+            warnings: false,
+            buffered: true,
+            frozen_globals: false,
+            mixed_concatenation: false,
+            natives: None,
+            count_nodes: false,
+            shadowing: Shadowing::default(),
+            scan_limits: ScanLimits::default(),
+            limits: Limits::default(),
+            macros: Vec::new(),
+            passes: Vec::new(),
+            entry_point: None,
+            exit_status: false,
+        }
+    }
+
+    pub fn with_defines(mut self, defines: HashSet<String>) -> Self {
+        self.defines = defines;
+        self
+    }
+
+    pub fn with_modules(mut self, modules: Box<dyn ModuleResolver>) -> Self {
+        self.modules = Some(modules);
+        self
+    }
+
+    pub fn with_filename(mut self, filename: &str) -> Self {
+        self.filename = Some(filename.to_string());
+        self
+    }
+
+    pub fn with_diagnostic_file(mut self, file: &str) -> Self {
+        self.diagnostic_file = Some(file.to_string());
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn with_warnings(mut self) -> Self {
+        self.warnings = true;
+        self
+    }
+
+    /// Has the interpreter write each printed line as soon as it's printed.
+    pub fn with_unbuffered_output(mut self) -> Self {
+        self.buffered = false;
+        self
+    }
+
+    /// Gives the interpreter these natives instead of the standard ones.
+    pub fn with_natives(mut self, natives: Natives) -> Self {
+        self.natives = Some(natives);
+        self
+    }
+
+    /// A pipeline with the same settings, for compiling the programs of a
+    /// forked interpreter. The module resolver isn't among them: it has been
+    /// handed over to the interpreter, which shares it with its forks.
+    pub fn fork(&self) -> Pipeline {
+        Self {
+            defines: self.defines.clone(),
+            modules: None,
+            filename: self.filename.clone(),
+            diagnostic_file: self.diagnostic_file.clone(),
+            policy: self.policy.clone(),
+            warnings: self.warnings,
+            buffered: self.buffered,
+            frozen_globals: self.frozen_globals,
+            mixed_concatenation: self.mixed_concatenation,
+            natives: self.natives.clone(),
+            count_nodes: self.count_nodes,
+            shadowing: self.shadowing,
+            scan_limits: self.scan_limits,
+            limits: self.limits,
+            macros: self.macros.clone(),
+            passes: self.passes.clone(),
+            entry_point: self.entry_point.clone(),
+            exit_status: self.exit_status,
+        }
+    }
+
+    /// Makes assigning to a global from inside a function a runtime error.
+    /// See Interpreter::freeze_globals.
+    pub fn with_frozen_globals(mut self) -> Self {
+        self.frozen_globals = true;
+        self
+    }
+
+    /// Lets `+` join a string and a number, as in `"count: " + 3`, instead of
+    /// failing. See Interpreter::allow_mixed_concatenation.
+    pub fn with_mixed_concatenation(mut self) -> Self {
+        self.mixed_concatenation = true;
+        self
+    }
+
+    /// Sets what to do about globals declared with the names of natives, both
+    /// when resolving and when running. See policy::Shadowing.
+    pub fn with_shadowing(mut self, shadowing: Shadowing) -> Self {
+        self.shadowing = shadowing;
+        self
+    }
+
+    /// Sets how long a string literal or an identifier in the source may be.
+    pub fn with_scan_limits(mut self, limits: ScanLimits) -> Self {
+        self.scan_limits = limits;
+        self
+    }
+
+    /// Limits what every program may do. A program's pragmas can ask for
+    /// tighter limits than these, but not for looser ones.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Adds a macro, to rewrite the tokens of every program after the ones
+    /// added before it. See macros::Macro.
+    pub fn with_macro(mut self, expansion: impl Macro + 'static) -> Self {
+        self.macros.push(Rc::new(expansion));
+        self
+    }
+
+    /// Adds a pass to run over every program after it is resolved, after the
+    /// passes added before it. See pass::Pass.
+    pub fn with_pass(mut self, pass: impl Pass + 'static) -> Self {
+        self.passes.push(Rc::new(pass));
+        self
+    }
+
+    /// Has the program's top level followed by a call to the named global
+    /// function, like `main`, with the given arguments. The number the
+    /// function returns becomes the exit code. See Interpreter::call_entry_point.
+    pub fn with_entry_point(mut self, name: &str, args: Vec<Object>) -> Self {
+        self.entry_point = Some((name.to_string(), args));
+        self
+    }
+
+    /// Makes the program's result its exit status: a top-level `return` ends
+    /// it with the value returned, and otherwise the value of its final
+    /// expression statement is used. See Interpreter::run_script.
+    pub fn with_exit_status(mut self) -> Self {
+        self.exit_status = true;
+        self
+    }
+
+    /// Adds macros that are already shared, like those an interpreter hands
+    /// on to the code it compiles while running.
+    pub(crate) fn with_macros(mut self, macros: &[Rc<dyn Macro>]) -> Self {
+        self.macros.extend(macros.iter().cloned());
+        self
+    }
+
+    /// Has the interpreter count the nodes it runs, by kind, into the stats
+    /// that `execute` returns. See Interpreter::count_nodes.
+    pub fn with_node_counts(mut self) -> Self {
+        self.count_nodes = true;
+        self
+    }
+
+    /// Turns source text into tokens, directives included.
+    pub fn scan(&self, source: &str) -> Vec<Token> {
+        let mut scanner = Scanner::new(source.to_string()).with_limits(self.scan_limits);
+        if let Some(file) = &self.diagnostic_file {
+            scanner = scanner.with_file(file);
+        }
+        scanner.scan_tokens()
+    }
+
+    /// Applies the conditional compilation directives, then lets the macros
+    /// rewrite what's left. A macro that fails leaves nothing but the EOF for
+    /// the parser, the error having been reported.
+    pub fn preprocess(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let mut tokens = preprocess(tokens, &self.defines);
+        for expansion in &self.macros {
+            let eof = tokens.last().cloned();
+            tokens = match expansion.expand(tokens) {
+                Ok(tokens) => tokens,
+                Err((token, message)) => {
+                    error::token_error(token, message);
+                    return eof.into_iter().collect();
+                }
+            };
+        }
+        tokens
+    }
+
+    /// Parses a whole program. The source text the tokens were scanned from
+    /// is kept in the Program, along with the pipeline's file name and the
+    /// limits its pragmas declare, within the pipeline's own.
+    pub fn parse(&self, tokens: Vec<Token>, source: &str) -> Option<Program> {
+        let mut program = Parser::new(tokens)
+            .with_source(source, self.filename.as_deref())
+            .parse();
+        program.limits = Limits::from_pragmas(source).within(self.limits);
+        match error::had_error() {
+            true => None,
+            false => Some(program),
+        }
+    }
+
+    /// Parses a single expression, for the `parse` and `evaluate` commands.
+    pub fn parse_expression(&self, tokens: Vec<Token>) -> Option<Expr> {
+        Parser::new(tokens).expression().ok()
+    }
+
+    /// Resolves local variables. Takes the resolver so that callers feeding a
+    /// program in pieces, like the REPL, can keep using the same one.
+    pub fn resolve(&self, resolver: &mut Resolver, program: Program) -> Option<Resolved> {
+        let locals = resolver.resolve(&program.statements);
+        match error::had_error() {
+            true => None,
+            false => Some(Resolved { program, locals }),
+        }
+    }
+
+    /// Resolves a module imported under a name, whose top-level code runs in a
+    /// scope of its own.
+    pub fn resolve_module(&self, program: Program) -> Option<Resolved> {
+        let locals = Resolver::new().resolve_module(&program.statements);
+        match error::had_error() {
+            true => None,
+            false => Some(Resolved { program, locals }),
+        }
+    }
+
+    /// Rewrites a parsed program into one that runs faster. This comes before
+    /// resolving, since the resolver's side table refers to expressions by
+    /// address and the rewritten program is made of new ones.
+    pub fn optimize(&self, mut program: Program) -> Program {
+        program.statements = optimizer::optimize(program.statements);
+        program
+    }
+
+    /// Runs everything from the source text up to, but not including, execution.
+    pub fn compile(&self, source: &str) -> Option<Resolved> {
+        let tokens = self.preprocess(self.scan(source));
+        let program = self.optimize(self.parse(tokens, source)?);
+        let natives = self.natives.clone().unwrap_or_else(Natives::standard);
+        let names = natives.iter().map(|native| native.name.to_string()).collect();
+        let resolver = self.resolver().with_shadowing(self.shadowing, names);
+        let mut resolver = match self.warnings {
+            true => resolver.with_references(),
+            false => resolver,
+        };
+        let resolved = self.resolve(&mut resolver, program)?;
+        if let Some(references) = resolver.take_references() {
+            let checker = TypeChecker::new(&references).warnings_only();
+            let mut checker = match self.mixed_concatenation {
+                true => checker.with_mixed_concatenation(),
+                false => checker,
+            };
+            checker.check(&resolved.program.statements);
+        }
+        self.run_passes(resolved)
+    }
+
+    /// Runs the embedder's passes over a resolved program. A program one of
+    /// them rewrote is resolved again before the next runs, so each pass
+    /// sees a side table that matches the tree. An error reported by a pass
+    /// stops the program here.
+    pub fn run_passes(&self, mut resolved: Resolved) -> Option<Resolved> {
+        for pass in &self.passes {
+            let changed = pass.run(&mut resolved, &mut Reporter::new(pass.name()));
+            if changed && !error::had_error() {
+                resolved = self.resolve(&mut self.resolver(), resolved.program)?;
+            }
+        }
+        match error::had_error() {
+            true => None,
+            false => Some(resolved),
+        }
+    }
+
+    /// A resolver for the main program, which may return from its top level
+    /// if its result is its exit status.
+    fn resolver(&self) -> Resolver {
+        match self.exit_status {
+            true => Resolver::new().with_top_level_return(),
+            false => Resolver::new(),
+        }
+    }
+
+    /// Creates an interpreter configured with the pipeline's settings.
+    pub fn interpreter(&mut self) -> Interpreter {
+        let mut interpreter = Interpreter::new_with_resolver(HashMap::new());
+        if let Some(natives) = &self.natives {
+            interpreter.set_natives(natives);
+        }
+        interpreter.set_shadowing(self.shadowing);
+        interpreter.set_macros(self.macros.clone());
+        let modules = self
+            .modules
+            .take()
+            .unwrap_or_else(|| Box::new(FileResolver::with_includes(Vec::new())));
+        interpreter.set_module_resolver(modules);
+        if let Some(filename) = &self.filename {
+            interpreter.set_script_name(filename);
+        }
+        interpreter.set_defines(self.defines.clone());
+        interpreter.set_policy(self.policy.clone());
+        interpreter.set_buffered(self.buffered);
+        if self.frozen_globals {
+            interpreter.freeze_globals();
+        }
+        if self.mixed_concatenation {
+            interpreter.allow_mixed_concatenation();
+        }
+        if self.count_nodes {
+            interpreter.count_nodes();
+        }
+        interpreter
+    }
+
+    /// Runs a resolved program in a new interpreter, then its event loop and
+    /// its exit hooks, and returns the counters collected while running it.
+    pub fn execute(&mut self, resolved: Resolved) -> Stats {
+        let mut interpreter = self.interpreter();
+        interpreter.add_locals(resolved.locals);
+        interpreter.set_limits(resolved.program.limits);
+        if self.exit_status {
+            if let Some(status) = interpreter.run_script(&resolved.program.statements) {
+                error::set_exit_status(status);
+            }
+        } else {
+            interpreter.interpret(&resolved.program.statements);
+        }
+        if let Some((name, args)) = &self.entry_point {
+            if !error::had_runtime_error() {
+                let name = entry_point_token(name, &resolved.program.statements);
+                if let Some(status) = interpreter.call_entry_point(&name, args.clone()) {
+                    error::set_exit_status(status);
+                }
+            }
+        }
+        if !error::had_runtime_error() {
+            interpreter.run_event_loop();
+        }
+        interpreter.run_exit_hooks();
+        interpreter.stats
+    }
+}
+
+/// The token to call an entry point by: the name in its declaration, if the
+/// program's top level declares it, so errors in the call point there, and
+/// otherwise the name on the program's last line.
+fn entry_point_token(name: &str, statements: &[Stmt]) -> Token {
+    let declared = statements.iter().find_map(|statement| match statement {
+        Stmt::Function { decl } if decl.name.lexeme == name => Some(decl.name.clone()),
+        _ => None,
+    });
+    declared.unwrap_or_else(|| {
+        let line = statements.iter().rev().find_map(Stmt::line).unwrap_or(1);
+        Token::new(IDENTIFIER, name.to_string(), None, line, 0)
+    })
+}
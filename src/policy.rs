@@ -0,0 +1,191 @@
+use crate::error;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Something a script can do to the world outside the interpreter. Natives
+/// that touch the outside world check that their capability is allowed
+/// before doing anything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Writing to standard output, including the `print` statement.
+    Print,
+    /// Reading files, including the modules `import` loads from disk.
+    Files,
+    /// Running code given as a string, with `eval()` and `evalIn()`. Unlike the
+    /// others, this has to be asked for.
+    Eval,
+    /// Running functions on threads of their own, with `spawn()`.
+    Threads,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::Print,
+        Capability::Files,
+        Capability::Eval,
+        Capability::Threads,
+    ];
+
+    /// Looks a capability up by the name it is displayed with.
+    pub fn named(name: &str) -> Option<Capability> {
+        Self::ALL.into_iter().find(|capability| capability.to_string() == name)
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Capability::Print => "print",
+            Capability::Files => "files",
+            Capability::Eval => "eval",
+            Capability::Threads => "threads",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The set of capabilities a script is allowed to use. By default everything
+/// is allowed, like any other program the user runs, except running code made
+/// up at runtime, which a script has to be allowed explicitly. A host running
+/// code it doesn't trust, like a web playground, can narrow that down.
+#[derive(Clone, Debug)]
+pub struct Policy {
+    allowed: HashSet<Capability>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::allow_all().deny(Capability::Eval)
+    }
+}
+
+impl Policy {
+    pub fn allow_all() -> Self {
+        Self { allowed: Capability::ALL.into_iter().collect() }
+    }
+
+    pub fn deny_all() -> Self {
+        Self { allowed: HashSet::new() }
+    }
+
+    /// Only lets the script print. Its output is all it can affect.
+    pub fn output_only() -> Self {
+        Self::deny_all().allow(Capability::Print)
+    }
+
+    pub fn allow(mut self, capability: Capability) -> Self {
+        self.allowed.insert(capability);
+        self
+    }
+
+    pub fn deny(mut self, capability: Capability) -> Self {
+        self.allowed.remove(&capability);
+        self
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        self.allowed.contains(&capability)
+    }
+}
+
+/// What happens when a program declares a global with the name of a native,
+/// like `var clock = 1;`. The declaration replaces the native for the rest of
+/// the program, which is sometimes meant, and often a name picked without
+/// knowing it was taken.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shadowing {
+    /// The declaration replaces the native, and nothing is said.
+    Allow,
+    /// The declaration replaces the native, with a warning before the program runs.
+    #[default]
+    Warn,
+    /// The declaration is an error.
+    Error,
+}
+
+impl Shadowing {
+    /// Looks a shadowing policy up by name: allow, warn or error.
+    pub fn named(name: &str) -> Option<Shadowing> {
+        match name {
+            "allow" => Some(Shadowing::Allow),
+            "warn" => Some(Shadowing::Warn),
+            "error" => Some(Shadowing::Error),
+            _ => None,
+        }
+    }
+}
+
+/// How much a script may do before it is stopped: how many statements it may
+/// execute, and for how long it may run. A script over a limit is stopped
+/// with a runtime error, one that `try` doesn't catch.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    pub max_steps: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    /// Reads the limits a script declares for itself, in pragmas: comments
+    /// starting with `//!`, before the first line of code.
+    ///
+    /// ```text
+    /// //!max_steps: 100000
+    /// //!timeout: 2s
+    /// ```
+    ///
+    /// A timeout is a number of seconds (`2s`, `1.5s`) or milliseconds
+    /// (`500ms`). A pragma that can't be read is reported as an error.
+    pub fn from_pragmas(source: &str) -> Limits {
+        let mut limits = Limits::default();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            let Some(pragma) = line.strip_prefix("//!") else {
+                match line.is_empty() || line.starts_with("//") {
+                    true => continue,
+                    false => break,
+                }
+            };
+            if let Err(message) = limits.apply(pragma) {
+                error::error(index + 1, message);
+            }
+        }
+        limits
+    }
+
+    /// These limits, kept within the outer ones: for each, the tighter of
+    /// the two.
+    pub fn within(self, outer: Limits) -> Limits {
+        fn tighter<T: Ord>(inner: Option<T>, outer: Option<T>) -> Option<T> {
+            match (inner, outer) {
+                (Some(inner), Some(outer)) => Some(inner.min(outer)),
+                (inner, outer) => inner.or(outer),
+            }
+        }
+        Limits { max_steps: tighter(self.max_steps, outer.max_steps), timeout: tighter(self.timeout, outer.timeout) }
+    }
+
+    fn apply(&mut self, pragma: &str) -> Result<(), String> {
+        let Some((name, value)) = pragma.split_once(':') else {
+            return Err(format!("Expect ':' after pragma name in '{pragma}'."));
+        };
+        let (name, value) = (name.trim(), value.trim());
+        match name {
+            "max_steps" => {
+                let steps = value.parse().map_err(|_| format!("Invalid step count '{value}'."))?;
+                self.max_steps = Some(steps);
+            }
+            "timeout" => {
+                let (number, unit) = match value.strip_suffix("ms") {
+                    Some(number) => (number, 1000.0),
+                    None => (value.strip_suffix('s').unwrap_or("-"), 1.0),
+                };
+                let seconds = number.trim().parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0);
+                let seconds = seconds.ok_or_else(|| format!("Invalid timeout '{value}', expected seconds or milliseconds, like 2s or 500ms."))?;
+                self.timeout = Some(Duration::from_secs_f64(seconds / unit));
+            }
+            _ => return Err(format!("Unknown pragma '{name}'.")),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use crate::error;
+use crate::token::Token;
+use crate::token::TokenType::*;
+use std::collections::HashSet;
+
+/// The preprocessor runs between the scanner and the parser. It acts on the
+/// conditional compilation directives in the token stream:
+///
+/// ```text
+/// #if DEBUG
+/// print "only when run with --define DEBUG";
+/// #else
+/// print "in normal runs";
+/// #end
+/// ```
+///
+/// Tokens inside a branch whose symbol isn't defined are dropped, so the parser
+/// never sees them. The directive tokens themselves are always dropped.
+/// Directives can be nested.
+pub fn preprocess(tokens: Vec<Token>, defines: &HashSet<String>) -> Vec<Token> {
+    // One entry per open #if: the directive token, whether its condition was
+    // true, and whether we've passed its #else yet.
+    let mut open: Vec<(Token, bool, bool)> = Vec::new();
+    let mut output = Vec::with_capacity(tokens.len());
+
+    for token in tokens {
+        match token.token_type {
+            HASH_IF => {
+                let symbol = token.literal.clone().unwrap_or_default();
+                let condition = defines.contains(&symbol);
+                open.push((token, condition, false));
+            }
+            HASH_ELSE => match open.last_mut() {
+                Some((_, _, in_else)) if !*in_else => *in_else = true,
+                Some(_) => error::token_error(token, "Duplicate '#else' directive.".into()),
+                None => error::token_error(token, "'#else' without '#if'.".into()),
+            },
+            HASH_END => {
+                if open.pop().is_none() {
+                    error::token_error(token, "'#end' without '#if'.".into());
+                }
+            }
+            EOF => {
+                if let Some((directive, _, _)) = open.last() {
+                    error::token_error(directive.clone(), "Unterminated '#if' directive.".into());
+                }
+                output.push(token);
+            }
+            _ => {
+                let active = open.iter().all(|(_, condition, in_else)| condition != in_else);
+                if active {
+                    output.push(token);
+                }
+            }
+        }
+    }
+    output
+}
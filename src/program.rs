@@ -0,0 +1,53 @@
+use crate::policy::Limits;
+use crate::stmt::Stmt;
+use crate::symbol::Interner;
+
+/// The root of the syntax tree. Besides the top-level statements, a Program
+/// keeps hold of where it came from: the source text it was parsed from, the
+/// name of the file holding that text, and the names it uses. Later phases,
+/// and anything reporting errors, can look back at the original code without
+/// it having to be passed around separately.
+#[derive(Default, Debug)]
+pub struct Program {
+    pub statements: Vec<Stmt>,
+
+    /// The source text, indexed by line.
+    pub source: SourceMap,
+
+    /// The file the program was read from, if it came from a file at all.
+    pub file: Option<String>,
+
+    /// Every identifier the program mentions, interned once.
+    pub strings: Interner,
+
+    /// The limits the program declares for itself in its pragmas.
+    pub limits: Limits,
+}
+
+/// Source text along with where each of its lines starts, so the text of a
+/// line can be found from the line number a token carries.
+#[derive(Default, Debug)]
+pub struct SourceMap {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(text.match_indices('\n').map(|(index, _)| index + 1));
+        Self { text: text.to_string(), line_starts }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the text of a line, without its line ending. Lines are numbered
+    /// from 1, the same as in tokens.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map_or(self.text.len(), |next| next - 1);
+        Some(self.text[start..end].trim_end_matches('\r'))
+    }
+}
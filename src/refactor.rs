@@ -0,0 +1,367 @@
+use crate::cst::ConcreteSyntax;
+use crate::error;
+use crate::pipeline::Pipeline;
+use crate::references::{Occurrence, ReferenceIndex};
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::parser::Parser;
+use crate::stmt::Stmt;
+use crate::token::Token;
+use crate::token::TokenType::{self, *};
+
+/// Compiles the source far enough to know where every name is declared and
+/// used. Refactorings work from this, so they only touch the names that really
+/// refer to the same thing.
+pub fn reference_index(source: &str) -> Result<ReferenceIndex, String> {
+    let pipeline = Pipeline::new();
+    let tokens = pipeline.preprocess(pipeline.scan(source));
+    let program = pipeline.parse(tokens, source).ok_or("Can't refactor a program with errors.")?;
+    let mut resolver = Resolver::new().with_references();
+    resolver.resolve(&program.statements);
+    if error::had_error() {
+        return Err("Can't refactor a program with errors.".into());
+    }
+    Ok(resolver.take_references().unwrap_or_default())
+}
+
+/// Finds the declarations of, and references to, the variable, function or
+/// class whose name is written at the given line and column, in source order.
+pub fn find_references(source: &str, line: usize, column: usize) -> Result<Vec<Occurrence>, String> {
+    let index = reference_index(source)?;
+    if index.property_at(line, column) {
+        return Err("Properties and methods are looked up dynamically, so their references can't be found.".into());
+    }
+    match index.binding_at(line, column) {
+        Some(binding) => Ok(binding.occurrences()),
+        None => Err(format!("No variable, function or class at line {line}, column {column}.")),
+    }
+}
+
+/// Moves whole lines of statements into a new function, declared where the
+/// lines were, and calls it from there. Local variables from outside that the
+/// lines use become the function's parameters, in the order they are first
+/// used. Globals are found by the function as they were by the lines.
+///
+/// The lines have to hold a sequence of whole statements, and the extraction
+/// is refused when it would change what the code does: when the lines return,
+/// break or continue, or use `this` or `super`, since those would mean
+/// something else inside the new function; when they assign to a local from
+/// outside, since the function would only get a copy of it; and when they
+/// declare something that is used after them.
+pub fn extract_function(source: &str, first_line: usize, last_line: usize, name: &str) -> Result<String, String> {
+    let index = reference_index(source)?;
+    if !is_identifier(name) {
+        return Err(format!("'{name}' is not a valid name."));
+    }
+    if index.uses_name(name) {
+        return Err(format!("Can't extract to '{name}': the name is already in use."));
+    }
+
+    let inside = |line: usize| (first_line..=last_line).contains(&line);
+    let tokens = Pipeline::new().scan(source);
+    let start = tokens.iter().position(|token| token.token_type != EOF && inside(token.line));
+    let Some(start) = start else {
+        return Err(format!("There is no code on lines {first_line} to {last_line}."));
+    };
+    let end = start + tokens[start..].iter().take_while(|token| inside(token.line) && token.token_type != EOF).count();
+    let selected = &tokens[start..end];
+    if !whole_statements(&tokens, start, end) {
+        return Err(format!("Lines {first_line} to {last_line} don't hold a sequence of whole statements."));
+    }
+
+    let mut snippet = selected.to_vec();
+    snippet.push(tokens[tokens.len() - 1].clone());
+    let statements = Parser::new(snippet).parse().statements;
+    if error::had_error() {
+        return Err(format!("Lines {first_line} to {last_line} don't hold a sequence of whole statements."));
+    }
+    if statements.iter().any(|statement| escapes(statement, &[])) {
+        return Err("Can't extract code that returns, breaks or continues out of it.".into());
+    }
+    if selected.iter().any(|token| matches!(token.token_type, THIS | SUPER)) {
+        return Err("Can't extract code that uses 'this' or 'super'.".into());
+    }
+
+    let mut parameters = Vec::new();
+    for binding in &index.bindings {
+        if binding.declarations.iter().any(|occurrence| inside(occurrence.line)) {
+            if binding.references.iter().any(|occurrence| !inside(occurrence.line)) {
+                return Err(format!("'{}' is declared in the extracted code but used after it.", binding.name));
+            }
+            continue;
+        }
+        let Some(first_use) = binding.references.iter().find(|occurrence| inside(occurrence.line)) else {
+            continue;
+        };
+        if binding.global {
+            continue;
+        }
+        if binding.assignments.iter().any(|occurrence| inside(occurrence.line)) {
+            return Err(format!("Can't extract code that assigns to '{}', which is declared outside it.", binding.name));
+        }
+        parameters.push((*first_use, binding.name.as_str()));
+    }
+    parameters.sort();
+    let parameters = parameters.iter().map(|(_, name)| *name).collect::<Vec<&str>>().join(", ");
+
+    let source_lines: Vec<&str> = source.lines().collect();
+    let body = &source_lines[first_line - 1..last_line.min(source_lines.len())];
+    let indent: String = body[0].chars().take_while(|c| c.is_whitespace()).collect();
+    let mut text = format!("{indent}fun {name}({parameters}) {{\n");
+    for line in body {
+        match line.trim().is_empty() {
+            true => text.push('\n'),
+            false => text.push_str(&format!("  {line}\n")),
+        }
+    }
+    text.push_str(&format!("{indent}}}\n{indent}{name}({parameters});\n"));
+
+    let lines = Lines::new(source);
+    let (start, end) = lines.whole_lines(first_line, last_line);
+    Ok(apply(source, vec![Edit { start, end, text }]))
+}
+
+/// Replaces every use of a variable with its initializer, and removes the
+/// declaration. The variable can't be assigned after it is declared, and the
+/// initializer has to give the same value wherever it is evaluated, so it
+/// can't call anything, assign anything, create a list, map or function, or
+/// read a property or an element, which could change in between. The
+/// variables it reads can't be assigned either, nor shadowed anywhere, so
+/// their names mean the same thing everywhere.
+pub fn inline_variable(source: &str, line: usize, column: usize, name: &str) -> Result<String, String> {
+    let index = reference_index(source)?;
+    let binding = index
+        .binding_at(line, column)
+        .ok_or_else(|| format!("No variable at line {line}, column {column}."))?;
+    if binding.name != name {
+        return Err(format!("The name at line {line}, column {column} is '{}', not '{name}'.", binding.name));
+    }
+    let [declaration] = binding.declarations[..] else {
+        return Err(format!("Can't inline '{name}': it must be declared exactly once."));
+    };
+    if !binding.assignments.is_empty() {
+        return Err(format!("Can't inline '{name}': it is assigned after its declaration."));
+    }
+
+    let tokens = Pipeline::new().scan(source);
+    let position = tokens
+        .iter()
+        .position(|token| Occurrence::new(token) == declaration)
+        .expect("the declaration was scanned from this source");
+    if position == 0 || !matches!(tokens[position - 1].token_type, VAR | CONST) {
+        return Err(format!("Can't inline '{name}': it isn't a variable."));
+    }
+    if tokens[position + 1].token_type != EQUAL {
+        return Err(format!("Can't inline '{name}': it has no initializer."));
+    }
+    let first = position + 2;
+    let semicolon = first + tokens[first..]
+        .iter()
+        .position(|token| matches!(token.token_type, SEMICOLON | EOF))
+        .expect("the scanner always ends with EOF");
+    let initializer = &tokens[first..semicolon];
+
+    let unchanging = initializer.iter().enumerate().all(|(i, token)| match token.token_type {
+        IDENTIFIER => index
+            .binding_at(token.line, token.column)
+            .is_some_and(|used| used.assignments.is_empty() && index.bindings.iter().filter(|other| other.name == used.name).count() == 1),
+        LEFT_PAREN => i == 0 || !ends_operand(&initializer[i - 1].token_type),
+        _ => !changing(&token.token_type),
+    });
+    if !unchanging {
+        return Err(format!("Can't inline '{name}': its initializer might not give the same value each time it is evaluated."));
+    }
+
+    let lines = Lines::new(source);
+    let chars: Vec<char> = source.chars().collect();
+    let text: String = chars[lines.start(&initializer[0])..lines.end(&initializer[initializer.len() - 1])].iter().collect();
+    let text = match initializer.len() {
+        1 => text,
+        _ => format!("({text})"),
+    };
+
+    let mut edits: Vec<Edit> = binding.references.iter().map(|occurrence| lines.occurrence(occurrence, &text)).collect();
+    let (mut start, mut end) = (lines.start(&tokens[position - 1]), lines.end(&tokens[semicolon]));
+    // If the declaration had its line to itself, the line goes too.
+    let line_start = chars[..start].iter().rposition(|&c| c == '\n').map_or(0, |newline| newline + 1);
+    let line_end = chars[end..].iter().position(|&c| c == '\n').map_or(chars.len(), |newline| end + newline + 1);
+    if chars[line_start..start].iter().chain(&chars[end..line_end]).all(|c| c.is_whitespace()) {
+        (start, end) = (line_start, line_end);
+    }
+    edits.push(Edit { start, end, text: String::new() });
+    Ok(apply(source, edits))
+}
+
+/// Renames the variable, function or class whose name is written at the
+/// given line and column, along with every reference to it. The name found
+/// there has to be `old`, as a check that the position is the one meant.
+///
+/// Renaming is refused for properties and methods, since which one `a.name`
+/// means is only known at runtime, and for globals that the program uses but
+/// doesn't declare, like natives. It is also refused if `new` is used as a
+/// name anywhere in the program, so the renamed binding can't capture, or be
+/// captured by, another with the same name.
+pub fn rename(source: &str, line: usize, column: usize, old: &str, new: &str) -> Result<String, String> {
+    let index = reference_index(source)?;
+    if index.property_at(line, column) {
+        return Err(format!("Can't rename '{old}': properties and methods are looked up dynamically."));
+    }
+    let binding = index
+        .binding_at(line, column)
+        .ok_or_else(|| format!("No variable, function or class at line {line}, column {column}."))?;
+    if binding.name != old {
+        return Err(format!("The name at line {line}, column {column} is '{}', not '{old}'.", binding.name));
+    }
+    if binding.declarations.is_empty() {
+        return Err(format!("Can't rename '{old}': it isn't declared in this program."));
+    }
+    if !is_identifier(new) {
+        return Err(format!("'{new}' is not a valid name."));
+    }
+    if index.uses_name(new) {
+        return Err(format!("Can't rename to '{new}': the name is already in use."));
+    }
+    let occurrences = binding.occurrences();
+    let syntax = ConcreteSyntax::new(source);
+    Ok(syntax.replace(|_, syntax| occurrences.contains(&Occurrence::new(&syntax.token)).then(|| new.to_string())))
+}
+
+/// Whether the text scans as a single identifier, which rules out keywords.
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    let valid_start = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if !valid_start || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return false;
+    }
+    let tokens = Scanner::new(text.to_string()).scan_tokens();
+    tokens.len() == 2 && tokens[0].token_type == IDENTIFIER
+}
+
+/// Whether the tokens from `start` up to `end` hold whole statements: they
+/// follow the end of a statement or the start of a block, end a statement
+/// themselves, and their brackets balance. An `else` on either side would be
+/// cut off from its `if`.
+fn whole_statements(tokens: &[Token], start: usize, end: usize) -> bool {
+    let follows_statement = start == 0 || matches!(tokens[start - 1].token_type, SEMICOLON | LEFT_BRACE | RIGHT_BRACE);
+    let ends_statement = matches!(tokens[end - 1].token_type, SEMICOLON | RIGHT_BRACE);
+    if !follows_statement || !ends_statement || tokens[start].token_type == ELSE || tokens[end].token_type == ELSE {
+        return false;
+    }
+    let mut depth = 0;
+    for token in &tokens[start..end] {
+        match token.token_type {
+            LEFT_PAREN | LEFT_BRACE | LEFT_BRACKET => depth += 1,
+            RIGHT_PAREN | RIGHT_BRACE | RIGHT_BRACKET if depth == 0 => return false,
+            RIGHT_PAREN | RIGHT_BRACE | RIGHT_BRACKET => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+/// Whether a statement returns, or breaks or continues a loop outside it.
+/// `loops` holds the labels of the loops around it that are inside too.
+/// Functions and classes declared inside are skipped, since a return in them
+/// stays in them.
+fn escapes(statement: &Stmt, loops: &[Option<&str>]) -> bool {
+    match statement {
+        Stmt::Return { .. } => true,
+        Stmt::Try { body, handler, finally, .. } => {
+            body.iter().chain(handler).chain(finally.iter().flatten()).any(|statement| escapes(statement, loops))
+        }
+        Stmt::Break { label, .. } | Stmt::Continue { label, .. } => match label {
+            Some(label) => !loops.contains(&Some(label.lexeme.as_str())),
+            None => loops.is_empty(),
+        },
+        Stmt::Block { statements } => statements.iter().any(|statement| escapes(statement, loops)),
+        Stmt::If { then_branch, else_branch, .. } => {
+            escapes(then_branch, loops) || else_branch.as_ref().is_some_and(|branch| escapes(branch, loops))
+        }
+        Stmt::While { body, label, .. } | Stmt::ForIn { body, label, .. } => {
+            let loops = [loops, &[label.as_ref().map(|label| label.lexeme.as_str())]].concat();
+            escapes(body, &loops)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a token ends an operand, so a '(' after it starts a call.
+fn ends_operand(token_type: &TokenType) -> bool {
+    matches!(token_type, IDENTIFIER | RIGHT_PAREN | RIGHT_BRACKET | THIS | SUPER | STRING | NUMBER | NIL | TRUE | FALSE)
+}
+
+/// Whether a token in an expression changes something, or reads something
+/// that can change.
+fn changing(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        EQUAL | PLUS_EQUAL | MINUS_EQUAL | STAR_EQUAL | SLASH_EQUAL | PLUS_PLUS | MINUS_MINUS
+            | LEFT_BRACKET | LEFT_BRACE | FUN | DOT
+    )
+}
+
+/// Text to write in place of the characters from `start` up to `end`,
+/// counted in characters like token columns are.
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// Makes the edits, which can't overlap.
+fn apply(source: &str, mut edits: Vec<Edit>) -> String {
+    let mut chars: Vec<char> = source.chars().collect();
+    // Going from the end backward keeps the earlier positions valid.
+    edits.sort_by_key(|edit| edit.start);
+    for edit in edits.iter().rev() {
+        chars.splice(edit.start..edit.end, edit.text.chars());
+    }
+    chars.into_iter().collect()
+}
+
+/// Where each line of the source starts, to turn the lines and columns that
+/// tokens carry into positions in the text.
+struct Lines {
+    starts: Vec<usize>,
+    length: usize,
+}
+
+impl Lines {
+    fn new(source: &str) -> Self {
+        let mut starts = vec![0];
+        let mut length = 0;
+        for (index, c) in source.chars().enumerate() {
+            if c == '\n' {
+                starts.push(index + 1);
+            }
+            length += 1;
+        }
+        Self { starts, length }
+    }
+
+    fn offset(&self, line: usize, column: usize) -> usize {
+        self.starts[line - 1] + column - 1
+    }
+
+    /// Where a token starts. A string token spanning lines carries the line
+    /// it ends on.
+    fn start(&self, token: &Token) -> usize {
+        self.offset(token.line - token.lexeme.matches('\n').count(), token.column)
+    }
+
+    fn end(&self, token: &Token) -> usize {
+        self.start(token) + token.lexeme.chars().count()
+    }
+
+    /// An edit writing `text` over an occurrence of a name.
+    fn occurrence(&self, occurrence: &Occurrence, text: &str) -> Edit {
+        let start = self.offset(occurrence.line, occurrence.column);
+        Edit { start, end: start + occurrence.length, text: text.to_string() }
+    }
+
+    /// The span of the lines from `first` to `last`, with the last line's newline.
+    fn whole_lines(&self, first: usize, last: usize) -> (usize, usize) {
+        let end = self.starts.get(last).copied().unwrap_or(self.length);
+        (self.starts[first - 1], end)
+    }
+}
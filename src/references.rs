@@ -0,0 +1,138 @@
+use crate::token::Token;
+use std::collections::HashMap;
+
+/// A place in the source where a name is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Occurrence {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Occurrence {
+    pub fn new(token: &Token) -> Self {
+        Self { line: token.line, column: token.column, length: token.lexeme.chars().count() }
+    }
+
+    /// Whether the given line and column fall on this occurrence.
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        self.line == line && (self.column..self.column + self.length).contains(&column)
+    }
+}
+
+/// One variable, function, class or parameter, with every place it is
+/// declared and used. Two variables with the same name in different scopes are
+/// different bindings.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub name: String,
+
+    /// Globals are looked up by name at runtime, so every top-level
+    /// declaration of the name, and every use that isn't shadowed by a local,
+    /// belongs to the same binding.
+    pub global: bool,
+
+    /// Where the binding is declared. A local has one declaration. A global
+    /// can be declared again, or not at all in this program, like a native.
+    pub declarations: Vec<Occurrence>,
+
+    pub references: Vec<Occurrence>,
+
+    /// The references that assign to the binding, including `++` and `--`.
+    pub assignments: Vec<Occurrence>,
+}
+
+impl Binding {
+    /// Declarations and references together, in source order.
+    pub fn occurrences(&self) -> Vec<Occurrence> {
+        let mut occurrences: Vec<Occurrence> = self.declarations.iter().chain(&self.references).copied().collect();
+        occurrences.sort();
+        occurrences
+    }
+}
+
+/// What the resolver learned about names: which declaration each use of a
+/// name refers to. The resolver already works this out to find local
+/// variables, and it fills this index in as it goes when asked to (see
+/// `Resolver::with_references`). Editor tools use it to answer questions like
+/// "where is this used?" without running the program.
+///
+/// Properties and methods are looked up on instances at runtime, so which one
+/// `a.name` means can't be known statically. Those occurrences are kept apart.
+#[derive(Clone, Debug, Default)]
+pub struct ReferenceIndex {
+    pub bindings: Vec<Binding>,
+
+    /// Method names and the names after a dot.
+    pub properties: Vec<Occurrence>,
+
+    /// The binding of each global name.
+    globals: HashMap<String, usize>,
+}
+
+impl ReferenceIndex {
+    /// Records the declaration of a local and returns its binding.
+    pub(crate) fn declare_local(&mut self, name: &Token) -> usize {
+        self.bindings.push(Binding {
+            name: name.lexeme.clone(),
+            global: false,
+            declarations: vec![Occurrence::new(name)],
+            references: Vec::new(),
+            assignments: Vec::new(),
+        });
+        self.bindings.len() - 1
+    }
+
+    /// Records a top-level declaration.
+    pub(crate) fn declare_global(&mut self, name: &Token) {
+        let binding = self.global(&name.lexeme);
+        self.bindings[binding].declarations.push(Occurrence::new(name));
+    }
+
+    pub(crate) fn reference(&mut self, binding: usize, name: &Token) {
+        self.bindings[binding].references.push(Occurrence::new(name));
+    }
+
+    /// Marks a reference, recorded already, as one that assigns.
+    pub(crate) fn assignment(&mut self, binding: usize, name: &Token) {
+        self.bindings[binding].assignments.push(Occurrence::new(name));
+    }
+
+    pub(crate) fn property(&mut self, name: &Token) {
+        self.properties.push(Occurrence::new(name));
+    }
+
+    /// The binding of a global name, created on first sight since a global
+    /// can be used before, or without, being declared.
+    pub(crate) fn global(&mut self, name: &str) -> usize {
+        if let Some(&binding) = self.globals.get(name) {
+            return binding;
+        }
+        self.bindings.push(Binding {
+            name: name.to_string(),
+            global: true,
+            declarations: Vec::new(),
+            references: Vec::new(),
+            assignments: Vec::new(),
+        });
+        self.globals.insert(name.to_string(), self.bindings.len() - 1);
+        self.bindings.len() - 1
+    }
+
+    /// The binding whose name is written at the given position, if any.
+    pub fn binding_at(&self, line: usize, column: usize) -> Option<&Binding> {
+        self.bindings.iter().find(|binding| {
+            binding.declarations.iter().chain(&binding.references).any(|occurrence| occurrence.contains(line, column))
+        })
+    }
+
+    /// Whether a property or method name is written at the given position.
+    pub fn property_at(&self, line: usize, column: usize) -> bool {
+        self.properties.iter().any(|occurrence| occurrence.contains(line, column))
+    }
+
+    /// Whether any binding, in any scope, has this name.
+    pub fn uses_name(&self, name: &str) -> bool {
+        self.bindings.iter().any(|binding| binding.name == name)
+    }
+}
@@ -0,0 +1,83 @@
+use crate::error::Diagnostic;
+use crate::interpreter::Stats;
+use std::time::Duration;
+
+/// A machine-readable summary of a run, for tools like CI pipelines that
+/// would rather not scrape stdout and stderr.
+pub struct Report {
+    pub exit_code: i32,
+    pub diagnostics: Vec<Diagnostic>,
+    pub stats: Stats,
+    pub elapsed: Duration,
+}
+
+impl Report {
+    /// Renders the report as a single JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"exit_code\":{},\"diagnostics\":{},\"stats\":{{\"statements\":{},\"calls\":{},\"elapsed_ms\":{:.3}}}}}",
+            self.exit_code,
+            diagnostics_json(&self.diagnostics),
+            self.stats.statements,
+            self.stats.calls,
+            self.elapsed.as_secs_f64() * 1000.0
+        )
+    }
+}
+
+/// Renders diagnostics as a JSON array of objects, each with the kind, the
+/// file if there is one, the line and the message.
+pub fn diagnostics_json(diagnostics: &[Diagnostic]) -> String {
+    let diagnostics: Vec<String> = diagnostics
+        .iter()
+        .map(|d| {
+            let file = match &d.file {
+                Some(file) => format!("\"file\":{},", json_string(file)),
+                None => String::new(),
+            };
+            format!(
+                "{{\"kind\":{},{}\"line\":{},\"message\":{}}}",
+                json_string(d.kind),
+                file,
+                d.line,
+                json_string(&d.message)
+            )
+        })
+        .collect();
+    format!("[{}]", diagnostics.join(","))
+}
+
+/// Lists how many times each kind of node ran, the most frequent first, one
+/// kind per line with the counts lined up. Empty if nodes weren't counted.
+pub fn explain(stats: &Stats) -> String {
+    let Some(nodes) = &stats.nodes else {
+        return String::new();
+    };
+    let mut counts: Vec<(&str, usize)> = nodes.iter().map(|(kind, count)| (*kind, *count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let width = counts.iter().map(|(kind, _)| kind.len()).max().unwrap_or(0);
+    let digits = counts.first().map_or(0, |(_, count)| count.to_string().len());
+    counts
+        .iter()
+        .map(|(kind, count)| format!("{kind:<width$}  {count:>digits$}\n"))
+        .collect()
+}
+
+/// Quotes and escapes a string as a JSON string literal.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
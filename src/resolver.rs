@@ -1,9 +1,11 @@
-use crate::error::token_error;
-use crate::expr::Expr;
+use crate::error::{token_error, token_warning};
+use crate::policy::Shadowing;
+use crate::expr::{Expr, Pattern};
+use crate::references::ReferenceIndex;
 use crate::stmt::{Stmt, FunctionDeclaration};
+use crate::symbol::{Interner, Symbol};
 use crate::token::Token;
-use std::collections::HashMap;
-
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Copy, Debug)]
 enum FunctionType {
@@ -13,6 +15,10 @@ enum FunctionType {
 #[derive(Clone, Copy, Debug)]
 enum ClassType {
     None, Class, SubClass,
+
+    /// The methods of an `extend`, which have `this`, but no `super`: they
+    /// aren't declared where the class's superclass is.
+    Extension,
 }
 
 /// This is kind of step 2.5. After the parser produces the syntax tree, but 
@@ -23,7 +29,7 @@ enum ClassType {
 pub struct Resolver {
     /// This field keeps track of the stack of scopes currently in scope.
     /// Each element in the stack is a Map representing a single block scope.
-    /// Keys, as in Environment, are variable names. The values are Booleans, and
+    /// Keys are the interned variable names. The values are Booleans, and
     /// represents whether we have finished resolving that variable’s initializer.
     /// 
     /// The scope stack is only used for local block scopes. Variables declared
@@ -34,11 +40,32 @@ pub struct Resolver {
     /// Rust doesn't have a Stack data structure. So we are using Vec, and its kinda 
     /// like reversed stack, where the 'top' is the at the end. The innermost scope
     /// is at the 'top' of this stack. 
-    scopes: Vec<HashMap<String, bool>>,
+    scopes: Vec<HashMap<Symbol, bool>>,
+
+    /// Every scope map is keyed on names, and the same handful of names show up
+    /// in scope after scope. Interning them means each distinct lexeme is copied
+    /// once, rather than once per declaration and once per lookup.
+    symbols: Interner,
 
     /// Keeps track of all the resolved variables 
     resolved: HashMap<*const Expr, usize>,
 
+    /// Names declared at the top level. Globals are still looked up dynamically
+    /// at runtime, but remembering them lets a resolver that is fed a program
+    /// piece by piece (like a REPL, one line at a time) know what earlier
+    /// pieces have already defined.
+    globals: HashSet<Symbol>,
+
+    /// The number of scopes that count as the top level. This is zero for a
+    /// script, and one inside a module imported under a name, whose top-level
+    /// code runs in a scope of its own.
+    top_level: usize,
+
+    /// Names a module declares at its top level, declared ahead of time so
+    /// that its functions can use those declared after them, as they could
+    /// globals. Each is taken out when its own declaration is reached.
+    hoisted: HashSet<Symbol>,
+
     /// Much like we track scopes as we walk the tree, this is used to track whether the 
     /// code we are currently visiting is inside a function declaration.
     current_function: FunctionType,
@@ -46,6 +73,38 @@ pub struct Resolver {
     /// This is used to track whether we are inside a class declaration
     /// while traversing the syntax tree. 
     current_class: ClassType,
+
+    /// The loops that enclose the code we are visiting, within the current
+    /// function, with their labels. `break` and `continue` are only valid
+    /// inside one, and one with a label has to be inside a loop with it.
+    loops: Vec<Option<String>>,
+
+    /// Where each name is declared and used, recorded only when asked for
+    /// with `with_references`.
+    references: Option<ReferenceIndex>,
+
+    /// Alongside `scopes`, the binding in the reference index that each name
+    /// declared in a scope refers to.
+    bindings: Vec<HashMap<Symbol, usize>>,
+
+    /// Alongside `scopes`, the names in each scope declared with `const`.
+    constants: Vec<HashSet<Symbol>>,
+
+    /// The names of the natives the program will run with, and what to do
+    /// about a global declared with one of them. Without natives, as with
+    /// `new`, there is nothing to check.
+    natives: HashSet<String>,
+    shadowing: Shadowing,
+
+    /// Whether a `return` may appear outside any function, to end a script
+    /// with its exit status.
+    top_level_return: bool,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Resolver {
@@ -53,18 +112,129 @@ impl Resolver {
     pub fn new() -> Resolver {
         Self {
             scopes: Vec::new(),
+            symbols: Interner::new(),
             resolved: HashMap::new(),
+            globals: HashSet::new(),
+            top_level: 0,
+            hoisted: HashSet::new(),
             current_function: FunctionType::None,
             current_class: ClassType::None,
+            loops: Vec::new(),
+            references: None,
+            bindings: Vec::new(),
+            constants: Vec::new(),
+            natives: HashSet::new(),
+            shadowing: Shadowing::default(),
+            top_level_return: false,
         }
     }
+
+    /// Makes the resolver report globals declared with the name of one of
+    /// these natives, as the shadowing policy says.
+    pub fn with_shadowing(mut self, shadowing: Shadowing, natives: HashSet<String>) -> Self {
+        self.shadowing = shadowing;
+        self.natives = natives;
+        self
+    }
+
+    /// Lets the program's top level `return`, which ends a script with an exit
+    /// status. See Interpreter::run_script.
+    pub fn with_top_level_return(mut self) -> Self {
+        self.top_level_return = true;
+        self
+    }
+
+    /// Makes the resolver build a reference index as it resolves. Fetch it
+    /// afterward with `take_references`.
+    pub fn with_references(mut self) -> Self {
+        self.references = Some(ReferenceIndex::default());
+        self
+    }
+
+    /// Hands over the reference index built so far, if one is being built.
+    pub fn take_references(&mut self) -> Option<ReferenceIndex> {
+        self.references.take()
+    }
     
-    pub fn resolve(&mut self, statements: &Vec<Stmt>) -> HashMap<*const Expr, usize> {
+    /// Resolves the given program and hands over the table of resolved variables.
+    /// The table is moved out rather than cloned, so it can grow as large as the
+    /// program without being copied at the end.
+    ///
+    /// The resolver can be called again with more statements afterward. Each call
+    /// returns only the variables resolved by that call, while the globals seen so
+    /// far are kept, so the caller merges every table into the same interpreter
+    /// (see `Interpreter::add_locals`).
+    pub fn resolve(&mut self, statements: &[Stmt]) -> HashMap<*const Expr, usize> {
+        self.resolve_block(statements);
+        std::mem::take(&mut self.resolved)
+    }
+
+    /// Resolves code that runs in an environment that already exists, given
+    /// the names defined in it and in each one enclosing it, innermost first,
+    /// up to but not including the globals. Each becomes a scope, so the
+    /// code's own top-level declarations go in the innermost, and names it
+    /// doesn't declare are found where they are. Inside a method, `this` and
+    /// `super` are among the names, and can be used.
+    pub fn resolve_in(&mut self, names: &[Vec<String>], statements: &[Stmt]) -> HashMap<*const Expr, usize> {
+        let enclosing_class = self.current_class;
+        for scope in names.iter().rev() {
+            self.begin_scope();
+            for name in scope {
+                self.define_implicit(name);
+            }
+            self.current_class = match (scope.iter().any(|name| name == "super"), scope.iter().any(|name| name == "this")) {
+                (true, _) => ClassType::SubClass,
+                (false, true) if matches!(self.current_class, ClassType::None) => ClassType::Class,
+                _ => self.current_class,
+            };
+        }
+        self.top_level = self.scopes.len();
         self.resolve_block(statements);
-        self.resolved.clone()
+        self.top_level = 0;
+        for _ in names {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+        std::mem::take(&mut self.resolved)
     }
 
-    fn resolve_block(&mut self, statements: &Vec<Stmt>) {
+    /// Resolves a module that is imported under a name. The interpreter runs its
+    /// top-level code in an environment of its own, so we resolve it inside one
+    /// extra scope. Its declarations become locals of that scope, and functions
+    /// in the module find them there instead of looking in the globals.
+    ///
+    /// Every name the module declares at its top level is declared before any
+    /// of it is resolved, so a function can call one declared further down,
+    /// the way functions in a script can use globals declared after them.
+    pub fn resolve_module(&mut self, statements: &[Stmt]) -> HashMap<*const Expr, usize> {
+        self.begin_scope();
+        self.top_level = self.scopes.len();
+        for name in statements.iter().filter_map(declared_name) {
+            // A name declared twice is reported when the second is reached.
+            let symbol = self.symbols.intern(&name.lexeme);
+            if self.hoisted.contains(&symbol) {
+                continue;
+            }
+            self.declare(name);
+            self.define(name);
+            self.hoisted.insert(symbol);
+        }
+        self.resolve_block(statements);
+        self.hoisted.clear();
+        self.top_level = 0;
+        self.end_scope();
+        std::mem::take(&mut self.resolved)
+    }
+
+    /// Whether an earlier (or the current) call to resolve() has declared this
+    /// name at the top level.
+    pub fn knows_global(&self, name: &str) -> bool {
+        self.symbols
+            .lookup(name)
+            .is_some_and(|symbol| self.globals.contains(&symbol))
+    }
+
+    fn resolve_block(&mut self, statements: &[Stmt]) {
         for statement in statements {
             self.resolve_statement(statement);
         }
@@ -82,12 +252,18 @@ impl Resolver {
                 self.resolve_block(statements);
                 self.end_scope();
             }
-            Stmt::Class { name, superclass, methods } => {
+            Stmt::Class { name, superclass, interfaces, methods, class_methods } => {
                 let enclosing_class = self.current_class;
                 self.current_class = ClassType::Class;
                 
                 self.declare(name);
                 self.define(name);
+
+                // The interfaces are looked up where the class is declared,
+                // outside the scope for `super` below.
+                for interface in interfaces {
+                    self.resolve_expression(interface);
+                }
                 
                 // Resolve superclass if it exists
                 if let Some(superclass) = superclass {
@@ -102,9 +278,7 @@ impl Resolver {
                     // If the class declaration has a superclass, then we create a new scope 
                     // surrounding all of its methods. In that scope, we define the name “super”.
                     self.begin_scope();
-                    if let Some(innermost_scope) = self.scopes.last_mut() {
-                        innermost_scope.insert("super".into(), true);
-                    }
+                    self.define_implicit("super");
                 }
                 
                 // Before we step in and start resolving the method bodies, we push a 
@@ -113,28 +287,38 @@ impl Resolver {
                 // to a “local variable” defined in an implicit scope just outside the block 
                 // for the method body.
                 self.begin_scope();
-                if let Some(innermost_scope) = self.scopes.last_mut() {
-                    innermost_scope.insert("this".into(), true);
-                }
+                self.define_implicit("this");
                 
                 for method in methods {
+                    if let Some(references) = &mut self.references {
+                        references.property(&method.name);
+                    }
                     let mut declaration = FunctionType::Method;
                     if method.name.lexeme == "init" {
                         declaration = FunctionType::Initializer;
                     }
                     self.resolve_function(method, declaration);
                 }
+
+                // Class methods have `this` too, bound to the class. None of
+                // them is an initializer, whatever its name.
+                for method in class_methods {
+                    if let Some(references) = &mut self.references {
+                        references.property(&method.name);
+                    }
+                    self.resolve_function(method, FunctionType::Method);
+                }
                 
                 self.end_scope();
                 
                 // Once we’re done resolving the class’s methods, we discard 'super' scope.
-                if let Some(_) = superclass {
+                if superclass.is_some() {
                     self.end_scope();
                 }
                 
                 self.current_class = enclosing_class;
             }
-            Stmt::Var { name, initializer } => {
+            Stmt::Var { name, initializer, mutable, .. } => {
                 // Resolving a variable declaration adds a new entry to the current 
                 // innermost scope’s map. We split binding into two steps, declaring 
                 // then defining. This is to handle if the initializer for a local variable 
@@ -144,6 +328,12 @@ impl Resolver {
                     self.resolve_expression(expr);
                 }
                 self.define(name);
+                if !mutable {
+                    let symbol = self.symbols.intern(&name.lexeme);
+                    if let Some(constants) = self.constants.last_mut() {
+                        constants.insert(symbol);
+                    }
+                }
             }
             Stmt::Function { decl } => {
                 // A function declaration introduces a new scope for its body and 
@@ -168,11 +358,11 @@ impl Resolver {
                     self.resolve_statement(else_branch);
                 }
             }
-            Stmt::Print { expression } => {
+            Stmt::Print { expression, .. } => {
                 self.resolve_expression(expression);
             }
             Stmt::Return { keyword, value } => {
-                if let FunctionType::None = self.current_function {
+                if matches!(self.current_function, FunctionType::None) && !self.top_level_return {
                     token_error(keyword.clone(), "Can't return from top-level code.".into());
                 }
                 
@@ -183,10 +373,98 @@ impl Resolver {
                     self.resolve_expression(expr);
                 }
             }
-            Stmt::While { condition, body } => {
+            Stmt::While { condition, body, increment, label, .. } => {
                 // Same as `if` statements, we resolve condition and body exactly once.
                 self.resolve_expression(condition);
-                self.resolve_statement(body);
+                self.resolve_loop_body(body, label);
+                if let Some(increment) = increment {
+                    self.resolve_expression(increment);
+                }
+            }
+            Stmt::ForIn { name, collection, body, label, .. } => {
+                // The collection is evaluated once, outside the loop variable's scope.
+                self.resolve_expression(collection);
+                self.begin_scope();
+                self.declare(name);
+                self.define(name);
+                self.resolve_loop_body(body, label);
+                self.end_scope();
+            }
+            Stmt::Throw { value, .. } => self.resolve_expression(value),
+            Stmt::Try { body, name, handler, finally } => {
+                // Each part is a block of its own. The handler's block also
+                // holds the caught error.
+                self.begin_scope();
+                self.resolve_block(body);
+                self.end_scope();
+                if let Some(name) = name {
+                    self.begin_scope();
+                    self.declare(name);
+                    self.define(name);
+                    self.resolve_block(handler);
+                    self.end_scope();
+                }
+                if let Some(finally) = finally {
+                    self.begin_scope();
+                    self.resolve_block(finally);
+                    self.end_scope();
+                }
+            }
+            Stmt::Break { keyword, label } | Stmt::Continue { keyword, label } => {
+                if self.loops.is_empty() {
+                    token_error(keyword.clone(), format!("Can't use '{}' outside of a loop.", keyword.lexeme));
+                } else if let Some(label) = label {
+                    if !self.loops.contains(&Some(label.lexeme.clone())) {
+                        token_error(label.clone(), format!("No enclosing loop is labeled '{}'.", label.lexeme));
+                    }
+                }
+            }
+            Stmt::Interface { name, methods } => {
+                self.declare(name);
+                self.define(name);
+                if let Some(references) = &mut self.references {
+                    for method in methods {
+                        references.property(&method.name);
+                    }
+                }
+            }
+            Stmt::Extend { class, methods, class_methods, .. } => {
+                self.resolve_expression(class);
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Extension;
+
+                self.begin_scope();
+                self.define_implicit("this");
+                for method in methods {
+                    if let Some(references) = &mut self.references {
+                        references.property(&method.name);
+                    }
+                    let declaration = match method.name.lexeme.as_str() {
+                        "init" => FunctionType::Initializer,
+                        _ => FunctionType::Method,
+                    };
+                    self.resolve_function(method, declaration);
+                }
+                for method in class_methods {
+                    if let Some(references) = &mut self.references {
+                        references.property(&method.name);
+                    }
+                    self.resolve_function(method, FunctionType::Method);
+                }
+                self.end_scope();
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::Import { keyword, alias, .. } => {
+                // An import defines names in the scope of the importing code,
+                // so it only makes sense at the top level.
+                if self.scopes.len() != self.top_level {
+                    token_error(keyword.clone(), "Can only import at the top level.".into());
+                }
+                if let Some(alias) = alias {
+                    self.declare(alias);
+                    self.define(alias);
+                }
             }
         }
     }
@@ -198,14 +476,18 @@ impl Resolver {
             Expr::Variable { name } => {
                 // It's a compile error if an initializer mentions the variable being initialized.
                 // e.g. var a = a; 
-                if self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) == Some(&false) {
+                let symbol = self.symbols.intern(&name.lexeme);
+                if self.scopes.last().and_then(|scope| scope.get(&symbol)) == Some(&false) {
                     token_error(name.clone(), "Can't read local variable in its own initializer.".into());
                 }
                 self.resolve_local(expression, name);
+                self.reference(name, false);
             }
             Expr::Assign { name, value } => {
                 self.resolve_expression(value);
+                self.check_assignable(name);
                 self.resolve_local(expression, name);
+                self.reference(name, true);
             }
             Expr::Binary { left, right, .. } => {
                 self.resolve_expression(left);
@@ -217,13 +499,19 @@ impl Resolver {
                     self.resolve_expression(argument);
                 }
             }
-            Expr::Get { object, .. } => {
+            Expr::Get { object, name } => {
+                if let Some(references) = &mut self.references {
+                    references.property(name);
+                }
                 // Since properties are looked up dynamically, they don’t get resolved. 
                 // During resolution, we recurse only into the expression to the left 
                 // of the dot. The actual property access happens in the interpreter.
                 self.resolve_expression(object);
             }
-            Expr::Set { object, value, .. } => {
+            Expr::Set { object, name, value } => {
+                if let Some(references) = &mut self.references {
+                    references.property(name);
+                }
                 // Like Get, the property itself is dynamically evaluated, so there’s 
                 // nothing to resolve there. All we need to do is recurse into the two 
                 // subexpressions of Set, the object whose property is being set, 
@@ -231,12 +519,78 @@ impl Resolver {
                 self.resolve_expression(value);
                 self.resolve_expression(object);
             }
-            Expr::Super { keyword, .. } => {
+            Expr::List { elements } => {
+                for element in elements {
+                    self.resolve_expression(element);
+                }
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.resolve_expression(key);
+                    self.resolve_expression(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+            }
+            Expr::IndexSet { object, index, value, .. } => {
+                self.resolve_expression(value);
+                self.resolve_expression(object);
+                self.resolve_expression(index);
+            }
+            Expr::Lambda { decl } => {
+                // Like a function declaration, minus the name to declare.
+                self.resolve_function(decl, FunctionType::Function);
+            }
+            Expr::Match { subject, arms, .. } => {
+                self.resolve_expression(subject);
+                for arm in arms {
+                    let (name, type_name) = match &arm.pattern {
+                        Pattern::Binding { name, type_name } => (name.as_ref(), type_name.as_deref()),
+                        Pattern::Literal { .. } => (None, None),
+                    };
+                    // A class named as a type is looked up where the match is.
+                    if let Some(type_name) = type_name {
+                        self.resolve_expression(type_name);
+                    }
+                    // Each arm's result gets a scope of its own, for its
+                    // binding if it has one.
+                    self.begin_scope();
+                    if let Some(name) = name {
+                        self.declare(name);
+                        self.define(name);
+                    }
+                    self.resolve_expression(&arm.body);
+                    self.end_scope();
+                }
+            }
+            Expr::Update { target, .. } => {
+                // The target is an ordinary Variable or Get, and resolves like one.
+                self.resolve_expression(target);
+                if let Expr::Variable { name } = target.as_ref() {
+                    self.check_assignable(name);
+                    if let Some(binding) = self.binding(name) {
+                        if let Some(references) = &mut self.references {
+                            references.assignment(binding, name);
+                        }
+                    }
+                }
+            }
+            Expr::Super { keyword, method } => {
+                if let Some(references) = &mut self.references {
+                    references.property(method);
+                }
                 if let ClassType::None = self.current_class {
                     token_error(keyword.clone(), "Can't use 'super' outside of a class.".into());
                     return;
                 }
                 
+                if let ClassType::Extension = self.current_class {
+                    token_error(keyword.clone(), "Can't use 'super' in an extension.".into());
+                    return;
+                }
+
                 let ClassType::SubClass = self.current_class else {
                     token_error(keyword.clone(), "Can't use 'super' in a class with no superclass.".into());
                     return;
@@ -275,31 +629,76 @@ impl Resolver {
 
     fn begin_scope(&mut self) {
         self.scopes.push(HashMap::new());
+        self.bindings.push(HashMap::new());
+        self.constants.push(HashSet::new());
     }
 
     fn end_scope(&mut self) {
         self.scopes.pop();
+        self.bindings.pop();
+        self.constants.pop();
     }
 
     /// Declaration adds the variable to the innermost scope so that it shadows any outer 
     /// one and so that we know the variable exists. We mark it as “not ready yet” by 
     /// binding its name to false in the scope map.
     fn declare(&mut self, name: &Token) {
-        let lexeme = name.lexeme.clone();
+        let symbol = self.symbols.intern(&name.lexeme);
+        let at_top_level = self.scopes.len() == self.top_level;
         if let Some(innermost_scope) = self.scopes.last_mut() {
-            if innermost_scope.contains_key(&lexeme) {
+            if at_top_level && self.hoisted.remove(&symbol) {
+                // Declared ahead; now it isn't ready until its initializer has run.
+                innermost_scope.insert(symbol, false);
+                return;
+            }
+            if innermost_scope.contains_key(&symbol) {
                 token_error(name.clone(), "Already a variable with this name in this scope.".into());
             }
             
-            innermost_scope.insert(lexeme, false);
+            innermost_scope.insert(symbol, false);
+            if let Some(references) = &mut self.references {
+                let binding = references.declare_local(name);
+                self.bindings.last_mut().unwrap().insert(symbol, binding);
+            }
+        } else {
+            self.check_shadowing(name);
+            self.globals.insert(symbol);
+            if let Some(references) = &mut self.references {
+                references.declare_global(name);
+            }
         }
     }
     
+    /// Reports a global that takes the name of a native, unless that's allowed.
+    fn check_shadowing(&self, name: &Token) {
+        if !self.natives.contains(&name.lexeme) {
+            return;
+        }
+        match self.shadowing {
+            Shadowing::Allow => {}
+            Shadowing::Warn => token_warning(
+                name.clone(),
+                format!("'{}' shadows the native function of the same name.", name.lexeme),
+            ),
+            Shadowing::Error => token_error(name.clone(), format!("Can't shadow the native function '{}'.", name.lexeme)),
+        }
+    }
+
     /// Sets the variable’s value in the scope map to true to mark it as fully 
     /// initialized and available for use. 
     fn define(&mut self, name: &Token) {
+        let symbol = self.symbols.intern(&name.lexeme);
         if let Some(innermost_scope) = self.scopes.last_mut() {
-            innermost_scope.insert(name.lexeme.clone(), true);
+            innermost_scope.insert(symbol, true);
+        }
+    }
+
+    /// Defines a name the user never declared, like “this” and “super”, in the
+    /// innermost scope.
+    fn define_implicit(&mut self, name: &str) {
+        let symbol = self.symbols.intern(name);
+        if let Some(innermost_scope) = self.scopes.last_mut() {
+            innermost_scope.insert(symbol, true);
         }
     }
 
@@ -307,8 +706,16 @@ impl Resolver {
     /// a matching name. If we find the variable, we resolve it, passing in the number 
     /// of scopes between the current innermost scope and the scope where the variable was found. 
     fn resolve_local(&mut self, expr: &Expr, name: &Token) {
+        let symbol = self.symbols.intern(&name.lexeme);
+        // Before its declaration, a module's name is only there for the
+        // functions that may be called once it is. Top-level code using it
+        // is left to fail, as it would with a global.
+        let early = self.hoisted.contains(&symbol) && matches!(self.current_function, FunctionType::None);
         for (distance, scope) in self.scopes.iter().rev().enumerate() {
-            if scope.contains_key(&name.lexeme) { 
+            if scope.contains_key(&symbol) { 
+                if early && self.scopes.len() - distance == self.top_level {
+                    return;
+                }
                 //let ptr = expr as *const Expr;
                 //eprintln!("Put Distance: ptr: {:?} name: {} lexeme: {} distance: {distance}", ptr, expr.to_string(), name.lexeme);
                 self.resolved.insert(expr, distance);
@@ -317,14 +724,59 @@ impl Resolver {
         }
     }
 
+    /// Reports assigning to a local constant. The scope the name resolves to
+    /// is known here, so unlike a global constant, which is only caught when
+    /// the assignment runs, this is an error before the program starts.
+    fn check_assignable(&mut self, name: &Token) {
+        let symbol = self.symbols.intern(&name.lexeme);
+        let scope = self.scopes.iter().zip(&self.constants).rev().find(|(scope, _)| scope.contains_key(&symbol));
+        if scope.is_some_and(|(_, constants)| constants.contains(&symbol)) {
+            token_error(name.clone(), format!("Can't assign to constant '{}'.", name.lexeme));
+        }
+    }
+
+    /// Records a use of a name in the reference index, and whether it assigns.
+    fn reference(&mut self, name: &Token, assignment: bool) {
+        let Some(binding) = self.binding(name) else { return };
+        let Some(references) = &mut self.references else { return };
+        references.reference(binding, name);
+        if assignment {
+            references.assignment(binding, name);
+        }
+    }
+
+    /// Finds the binding in the reference index that a name refers to. This
+    /// walks the scopes the same way resolve_local() does, and falls back to
+    /// the global of that name. Names like “this” have no binding.
+    fn binding(&mut self, name: &Token) -> Option<usize> {
+        let references = self.references.as_mut()?;
+        let symbol = self.symbols.intern(&name.lexeme);
+        for (scope, bindings) in self.scopes.iter().zip(&self.bindings).rev() {
+            if scope.contains_key(&symbol) {
+                return bindings.get(&symbol).copied();
+            }
+        }
+        Some(references.global(&name.lexeme))
+    }
+
     /// Creates a new scope for the body and then binds variables for each of the function’s 
     /// parameters. This is different from how the interpreter handles function declarations. 
     /// At runtime, declaring a function doesn’t do anything with the function’s body. The 
     /// body doesn’t get touched until later when the function is called. In a static analysis, 
     /// we immediately traverse into the body right then and there.
+    fn resolve_loop_body(&mut self, body: &Stmt, label: &Option<Token>) {
+        self.loops.push(label.as_ref().map(|label| label.lexeme.clone()));
+        self.resolve_statement(body);
+        self.loops.pop();
+    }
+
     fn resolve_function(&mut self, function: &FunctionDeclaration, function_type: FunctionType) {
         let enclosing_function = self.current_function;
         self.current_function = function_type;
+
+        // A loop around the function declaration doesn't make the function body
+        // part of that loop, so `break` and `continue` can't reach it from in here.
+        let enclosing_loops = std::mem::take(&mut self.loops);
         
         self.begin_scope();
         for param in &function.params {
@@ -334,6 +786,17 @@ impl Resolver {
         self.resolve_block(&function.body);
         self.end_scope();
         
+        self.loops = enclosing_loops;
         self.current_function = enclosing_function;
     }
-}
\ No newline at end of file
+}
+
+/// The name a top-level statement declares, if it declares one.
+fn declared_name(statement: &Stmt) -> Option<&Token> {
+    match statement {
+        Stmt::Var { name, .. } | Stmt::Class { name, .. } | Stmt::Interface { name, .. } => Some(name),
+        Stmt::Function { decl } => Some(&decl.name),
+        Stmt::Import { alias, .. } => alias.as_ref(),
+        _ => None,
+    }
+}
@@ -2,6 +2,27 @@ use crate::error;
 use crate::token::TokenType::*;
 use crate::token::{Token, TokenType};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How long a single token may be. Source text can come from anywhere, and
+/// a file holding one string literal a gigabyte long would otherwise be
+/// copied into a token, and again into its literal, before anything noticed.
+/// A token over a limit is reported as a lexical error and dropped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanLimits {
+    /// The most characters a string literal may hold, quotes not included.
+    pub max_string_length: usize,
+
+    /// The most characters an identifier may have.
+    pub max_identifier_length: usize,
+}
+
+impl Default for ScanLimits {
+    fn default() -> Self {
+        Self { max_string_length: 1 << 20, max_identifier_length: 255 }
+    }
+}
 
 /// The first step in any compiler or interpreter is scanning. The scanner
 /// takes in raw source code as a series of characters and groups it into
@@ -18,9 +39,23 @@ pub struct Scanner {
     /// 'start' points to the first character in the lexeme being scanned.
     /// 'current' points at the character currently being considered.
     /// 'line' field tracks what source line current is on.
+    /// 'line_start' is the index of the first character of that line, and
+    /// 'start_column' is the column where the lexeme being scanned began.
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize,
+    start_column: usize,
+
+    /// The name of the file being scanned, stamped on every token and error.
+    file: Option<Arc<str>>,
+
+    /// Where each token's text is in the source, as a range of characters.
+    /// Tokens don't need this; the concrete syntax tree does.
+    spans: Vec<Range<usize>>,
+
+    /// How long a token may be.
+    limits: ScanLimits,
 }
 
 impl Scanner {
@@ -31,19 +66,49 @@ impl Scanner {
             current: 0,
             start: 0,
             line: 1,
+            line_start: 0,
+            start_column: 1,
+            file: None,
+            spans: vec![],
+            limits: ScanLimits::default(),
         }
     }
 
+    /// Replaces the default limits on how long a token may be.
+    pub fn with_limits(mut self, limits: ScanLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Names the file the source came from, for diagnostics.
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.file = Some(Arc::from(file));
+        self
+    }
+
     pub fn scan_tokens(&mut self) -> Vec<Token> {
         while !self.is_at_end() {
             // We are at the beginning of the next lexeme.
             self.start = self.current;
+            self.start_column = self.current - self.line_start + 1;
             self.scan_token();
         }
-        self.tokens.push(Token::new(EOF, String::new(), None, self.line));
+        let column = self.current - self.line_start + 1;
+        let mut eof = Token::new(EOF, String::new(), None, self.line, column);
+        eof.file = self.file.clone();
+        self.tokens.push(eof);
+        self.spans.push(self.source.len()..self.source.len());
         self.tokens.clone()
     }
 
+    /// Scans the tokens along with where each one is in the source, as a
+    /// range of characters. Whatever lies between them is trivia: whitespace,
+    /// comments, and any characters that were reported as errors.
+    pub fn scan_with_spans(&mut self) -> (Vec<Token>, Vec<Range<usize>>) {
+        let tokens = self.scan_tokens();
+        (tokens, self.spans.clone())
+    }
+
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
@@ -54,19 +119,55 @@ impl Scanner {
     /// a scanner works, so we won’t be delegating that task.
     fn scan_token(&mut self) {
         let ln = self.line;
-        let c = self.advance().unwrap();
+        let c = *self.advance().unwrap();
         match c {
             // --------Single-character lexemes ----------------------
             '(' => self.add_token(LEFT_PAREN),
             ')' => self.add_token(RIGHT_PAREN),
             '{' => self.add_token(LEFT_BRACE),
             '}' => self.add_token(RIGHT_BRACE),
+            '[' => self.add_token(LEFT_BRACKET),
+            ']' => self.add_token(RIGHT_BRACKET),
             ',' => self.add_token(COMMA),
-            '.' => self.add_token(DOT),
-            '-' => self.add_token(MINUS),
-            '+' => self.add_token(PLUS),
+            ':' => self.add_token(COLON),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.current += 2;
+                    self.add_token(ELLIPSIS)
+                } else {
+                    self.add_token(DOT)
+                }
+            }
+            '-' => {
+                if self.match_next('=') {
+                    self.add_token(MINUS_EQUAL)
+                } else if self.match_next('-') {
+                    self.add_token(MINUS_MINUS)
+                } else if self.match_next('>') {
+                    self.add_token(ARROW)
+                } else {
+                    self.add_token(MINUS)
+                }
+            }
+            '+' => {
+                if self.match_next('=') {
+                    self.add_token(PLUS_EQUAL)
+                } else if self.match_next('+') {
+                    self.add_token(PLUS_PLUS)
+                } else {
+                    self.add_token(PLUS)
+                }
+            }
             ';' => self.add_token(SEMICOLON),
-            '*' => self.add_token(STAR),
+            '*' => match self.match_next('=') {
+                true => self.add_token(STAR_EQUAL),
+                false => self.add_token(STAR),
+            },
+            '%' => self.add_token(PERCENT),
+            '&' => self.add_token(AMPERSAND),
+            '|' => self.add_token(PIPE),
+            '^' => self.add_token(CARET),
+            '~' => self.add_token(TILDE),
 
             // --------Two-character Operators ----------------------
             // We recognize these lexemes in two stages. e.g. we know
@@ -80,20 +181,30 @@ impl Scanner {
                 true => self.add_token(EQUAL_EQUAL),
                 false => self.add_token(EQUAL),
             },
-            '<' => match self.match_next('=') {
-                true => self.add_token(LESS_EQUAL),
-                false => self.add_token(LESS),
-            },
-            '>' => match self.match_next('=') {
-                true => self.add_token(GREATER_EQUAL),
-                false => self.add_token(GREATER),
-            },
+            '<' => {
+                if self.match_next('=') {
+                    self.add_token(LESS_EQUAL)
+                } else if self.match_next('<') {
+                    self.add_token(LESS_LESS)
+                } else {
+                    self.add_token(LESS)
+                }
+            }
+            '>' => {
+                if self.match_next('=') {
+                    self.add_token(GREATER_EQUAL)
+                } else if self.match_next('>') {
+                    self.add_token(GREATER_GREATER)
+                } else {
+                    self.add_token(GREATER)
+                }
+            }
 
             // --------Newline and Whitespaces ----------------------
             // We simply ignore whitespace character. For newlines, we
             // do the same thing, but we also increment the line counter.
             ' ' | '\r' | '\t' => {}
-            '\n' => self.line += 1,
+            '\n' => self.new_line(),
 
             // --------Longer Lexemes ----------------------------------
             // This is our general strategy for handling longer lexemes.
@@ -101,9 +212,10 @@ impl Scanner {
             // some lexeme-specific code that keeps eating characters
             // until it sees the end.
             '/' => self.comment(),
+            '#' if is_alpha(self.peek()) => self.directive(),
             '"' => self.string(),
-            d if is_digit(*d) => self.number(),
-            a if is_alpha(*a) => self.identifier(),
+            d if is_digit(d) => self.number(),
+            a if is_alpha(a) => self.identifier(),
 
             // --------Invalid characters -------------------------------------
             // We log error and keep scanning. There may be other errors later
@@ -111,7 +223,7 @@ impl Scanner {
             // Otherwise, users will see one tiny error and fix it, only to have
             // the next error appear, and so on.
             _ => {
-                error::error(ln, format!("Unexpected character: {}", c));
+                self.error(ln, format!("Unexpected character: {}", c));
             }
         }
     }
@@ -124,53 +236,154 @@ impl Scanner {
             while self.peek() != '\n' && !self.is_at_end() {
                 self.advance();
             }
+        } else if self.match_next('*') {
+            self.block_comment();
+        } else if self.match_next('=') {
+            self.add_token(SLASH_EQUAL)
         } else {
             self.add_token(SLASH)
         }
     }
 
+    /// A block comment runs from /* to the matching */. Block comments nest,
+    /// so a block of code that already contains one can be commented out.
+    /// The opening /* has been consumed.
+    fn block_comment(&mut self) {
+        let start_line = self.line;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                self.error(start_line, "Unterminated block comment.".to_string());
+                return;
+            }
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.current += 2;
+                depth += 1;
+            } else if self.peek() == '*' && self.peek_next() == '/' {
+                self.current += 2;
+                depth -= 1;
+            } else if *self.advance().unwrap() == '\n' {
+                self.new_line();
+            }
+        }
+    }
+
+    /// Directives like `#if DEBUG` are scanned as single tokens. The name of the
+    /// symbol being tested becomes the literal of a #if token. Acting on them is
+    /// left to the preprocessor, which runs between scanning and parsing.
+    fn directive(&mut self) {
+        while is_alpha(self.peek()) {
+            self.advance();
+        }
+        let name: String = self.source[self.start + 1..self.current].iter().collect();
+        match name.as_str() {
+            "if" => {
+                while self.peek() == ' ' || self.peek() == '\t' {
+                    self.advance();
+                }
+                let symbol_start = self.current;
+                while is_alpha_numeric(self.peek()) {
+                    self.advance();
+                }
+                if symbol_start == self.current {
+                    self.error(self.line, "Expect symbol name after '#if'.".to_string());
+                    return;
+                }
+                let symbol: String = self.source[symbol_start..self.current].iter().collect();
+                self.add_token_with_literal(HASH_IF, Some(symbol));
+            }
+            "else" => self.add_token(HASH_ELSE),
+            "end" => self.add_token(HASH_END),
+            _ => self.error(self.line, format!("Unknown directive: #{name}")),
+        }
+    }
+
     fn string(&mut self) {
+        let start_line = self.line;
         while self.peek() != '"' && !self.is_at_end() {
             // Lox supports multi-line strings
-            if self.peek() == '\n' {
-                self.line += 1;
-            }
+            let newline = self.peek() == '\n';
             self.advance();
+            if newline {
+                self.new_line();
+            }
         }
 
         if self.is_at_end() {
-            error::error(self.line, "Unterminated string.".to_string());
+            self.error(self.line, "Unterminated string.".to_string());
             return;
         }
 
         // The closing ".
         self.advance();
 
+        let length = self.current - self.start - 2;
+        if length > self.limits.max_string_length {
+            let limit = self.limits.max_string_length;
+            self.error(start_line, format!("String literal is too long: {length} characters, the limit is {limit}."));
+            return;
+        }
+
         // Trim the surrounding quotes to produce the actual string
         // value that will be used later by the interpreter.
         let value: String = self.source[self.start + 1..self.current - 1].iter().collect();
         self.add_token_with_literal(STRING, Option::from(value));
     }
 
+    /// Numbers are decimal, with an optional fractional part and exponent
+    /// (2.5e-3), or whole numbers in hexadecimal (0xFF) or binary (0b1010). Digits can be grouped with
+    /// underscores, as in 1_000_000. The lexeme keeps the number as written,
+    /// while the literal is its value in the usual decimal form.
     fn number(&mut self) {
-        while is_digit(self.peek()) {
+        let first = self.source[self.start];
+        let radix = match self.peek() {
+            'x' | 'X' if first == '0' && self.peek_next().is_ascii_hexdigit() => 16,
+            'b' | 'B' if first == '0' && matches!(self.peek_next(), '0' | '1') => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            // Consume the "x" or "b".
             self.advance();
+            self.digits(radix);
+            let digits: String = self.source[self.start + 2..self.current].iter().filter(|&&c| c != '_').collect();
+            match u64::from_str_radix(&digits, radix) {
+                Ok(value) => self.add_token_with_literal(NUMBER, Some(format!("{:?}", value as f64))),
+                Err(_) => self.error(self.line, "Number literal is too large.".to_string()),
+            }
+            return;
         }
 
+        self.digits(10);
+
         // Look for a fractional part
         if self.peek() == '.' && is_digit(self.peek_next()) {
             // Consume the "."
             self.advance();
+            self.digits(10);
+        }
 
-            while is_digit(self.peek()) {
-                self.advance();
+        // Look for an exponent. Without digits after it, the "e" is left to
+        // start an identifier.
+        if matches!(self.peek(), 'e' | 'E') {
+            let sign = matches!(self.peek_next(), '+' | '-') as usize;
+            if self.source.get(self.current + 1 + sign).is_some_and(|c| is_digit(*c)) {
+                // Consume the "e" and the sign.
+                self.current += 1 + sign;
+                self.digits(10);
             }
         }
 
-        let mut value: String = self.source[self.start..self.current].iter().collect();
+        let value: String = self.source[self.start..self.current].iter().filter(|&&c| c != '_').collect();
         let my_int: f64 = value.parse().unwrap();
-        value = format!("{:?}", my_int);
-        self.add_token_with_literal(NUMBER, Option::from(value));
+        self.add_token_with_literal(NUMBER, Some(format!("{:?}", my_int)));
+    }
+
+    /// Consumes digits in the given radix, along with underscores that sit
+    /// between two digits.
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || (self.peek() == '_' && self.peek_next().is_digit(radix)) {
+            self.advance();
+        }
     }
 
     fn identifier(&mut self) {
@@ -178,6 +391,13 @@ impl Scanner {
             self.advance();
         }
 
+        let length = self.current - self.start;
+        if length > self.limits.max_identifier_length {
+            let limit = self.limits.max_identifier_length;
+            self.error(self.line, format!("Identifier is too long: {length} characters, the limit is {limit}."));
+            return;
+        }
+
         let text: String = self.source[self.start..self.current].iter().collect();
         let token_type: TokenType = keywords().get(&*text).unwrap_or(&IDENTIFIER).clone();
         self.add_token(token_type);
@@ -198,7 +418,20 @@ impl Scanner {
     /// Grabs the text of the current lexeme and creates a new token, along with its literal value
     fn add_token_with_literal(&mut self, token_type: TokenType, literal: Option<String>) {
         let text = self.source[self.start..self.current].iter().collect();
-        self.tokens.push(Token::new(token_type, text, literal, self.line));
+        let mut token = Token::new(token_type, text, literal, self.line, self.start_column);
+        token.file = self.file.clone();
+        self.tokens.push(token);
+        self.spans.push(self.start..self.current);
+    }
+
+    fn error(&self, line: usize, message: String) {
+        error::error_in(self.file.as_deref(), line, message);
+    }
+
+    /// Called right after consuming a newline character.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
     }
 
     /// It’s like a conditional advance(). We only consume the
@@ -233,7 +466,7 @@ impl Scanner {
 }
 
 fn is_alpha(c: char) -> bool {
-    (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '_'
+    c.is_ascii_alphabetic() || c == '_'
 }
 
 fn is_alpha_numeric(c: char) -> bool {
@@ -241,25 +474,34 @@ fn is_alpha_numeric(c: char) -> bool {
 }
 
 fn is_digit(c: char) -> bool {
-    c >= '0' && c <= '9'
+    c.is_ascii_digit()
 }
 
 fn keywords() -> HashMap<&'static str, TokenType> {
     HashMap::from([
         ("and", AND),
+        ("break", BREAK),
+        ("catch", CATCH),
         ("class", CLASS),
+        ("const", CONST),
+        ("continue", CONTINUE),
         ("else", ELSE),
         ("false", FALSE),
+        ("finally", FINALLY),
         ("for", FOR),
         ("fun", FUN),
         ("if", IF),
+        ("import", IMPORT),
+        ("match", MATCH),
         ("nil", NIL),
         ("or", OR),
         ("print", PRINT),
         ("return", RETURN),
         ("super", SUPER),
         ("this", THIS),
+        ("throw", THROW),
         ("true", TRUE),
+        ("try", TRY),
         ("var", VAR),
         ("while", WHILE),
     ])
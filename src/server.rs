@@ -0,0 +1,232 @@
+use crate::error;
+use crate::lox::Lox;
+use crate::policy::Limits;
+use crate::report::{diagnostics_json, json_string};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::iter::Peekable;
+use std::time::Duration;
+
+/// How many interpreters the server keeps ready ahead of the requests.
+const POOL_SIZE: usize = 4;
+
+/// How long a request may run. Requests are answered one at a time, so one
+/// that never finishes would keep every later one waiting. Its pragmas can
+/// ask for less time, but not for more.
+const REQUEST_LIMITS: Limits = Limits { max_steps: None, timeout: Some(Duration::from_secs(5)) };
+
+/// Interpreters ready to take a request. Every request gets one of its own,
+/// so nothing a script defines is seen by the next, and each one is forked
+/// from the same interpreter, so they all start with its globals. Forking
+/// happens between requests, while the server would otherwise be waiting,
+/// so a request finds its interpreter already made.
+pub struct Pool {
+    base: Lox,
+    ready: Vec<Lox>,
+}
+
+impl Pool {
+    pub fn new(mut base: Lox) -> Self {
+        base.set_limits(REQUEST_LIMITS);
+        let mut pool = Self { base, ready: Vec::new() };
+        pool.refill();
+        pool
+    }
+
+    /// An interpreter for a request, made now if none is ready.
+    pub fn take(&mut self) -> Lox {
+        self.ready.pop().unwrap_or_else(|| self.base.fork())
+    }
+
+    /// Makes interpreters until enough are ready.
+    pub fn refill(&mut self) {
+        while self.ready.len() < POOL_SIZE {
+            self.ready.push(self.base.fork());
+        }
+    }
+
+    /// Answers one request, a line holding a JSON object like
+    ///
+    /// ```text
+    /// {"command": "evaluate", "source": "print 1; 1 + 2;"}
+    /// ```
+    ///
+    /// with a line holding a JSON object like
+    ///
+    /// ```text
+    /// {"exit_code":0,"value":"3","stdout":"1\n","diagnostics":[]}
+    /// ```
+    ///
+    /// The command is `run`, to run a script, or `evaluate`, which also gives
+    /// the value of its last statement if that is an expression, printed as
+    /// `print` would. The diagnostics are those of a run report, and are
+    /// only given back, not printed. A request that runs for longer than
+    /// REQUEST_LIMITS allows is stopped with a runtime error. A request that
+    /// can't be understood gets `{"error":"..."}`.
+    pub fn answer(&mut self, request: &str) -> String {
+        let request = match parse_object(request) {
+            Ok(request) => request,
+            Err(message) => return error_response(&message),
+        };
+        let Some(source) = request.get("source") else {
+            return error_response("The request has no source.");
+        };
+        let with_value = match request.get("command").map(String::as_str) {
+            Some("run") => false,
+            Some("evaluate") => true,
+            Some(command) => return error_response(&format!("Unknown command: {command}.")),
+            None => return error_response("The request has no command."),
+        };
+
+        let mut lox = self.take();
+        let ((outcome, exit_code), _) = error::collect(|| (lox.evaluate(source), error::exit_code()));
+        let mut response = format!("{{\"exit_code\":{exit_code}");
+        if let Some(value) = outcome.value.filter(|_| with_value) {
+            response += &format!(",\"value\":{}", json_string(&value.to_string()));
+        }
+        let stdout = json_string(&outcome.stdout);
+        response + &format!(",\"stdout\":{stdout},\"diagnostics\":{}}}", diagnostics_json(&outcome.diagnostics))
+    }
+}
+
+fn error_response(message: &str) -> String {
+    format!("{{\"error\":{}}}", json_string(message))
+}
+
+/// Answers requests on a Unix socket at the path, one connection after
+/// another, each request on a line of its own and each answer on the next
+/// line back. See Pool::answer. Runs until the process is stopped.
+///
+/// Interpreters can't be shared between threads, so connections are served
+/// one at a time, in the order they come in. A connection that fails is
+/// dropped, and the server goes on to the next.
+#[cfg(unix)]
+pub fn serve(path: &str, base: Lox) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by a server that is gone would keep the new one
+    // from binding. Anything else at the path is left alone.
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let mut pool = Pool::new(base);
+    for stream in listener.incoming() {
+        if let Err(err) = stream.and_then(|stream| answer_connection(&mut pool, stream)) {
+            eprintln!("Dropped a connection: {err}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn answer_connection(pool: &mut Pool, mut stream: std::os::unix::net::UnixStream) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    for request in reader.lines() {
+        let response = pool.answer(&request?);
+        stream.write_all(response.as_bytes())?;
+        stream.write_all(b"\n")?;
+        pool.refill();
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn serve(_: &str, _: Lox) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "serving on a socket needs Unix"))
+}
+
+/// Reads a JSON object whose values are all strings, the only kind of
+/// request there is.
+fn parse_object(text: &str) -> Result<HashMap<String, String>, String> {
+    let mut chars = text.trim().chars().peekable();
+    let mut object = HashMap::new();
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            let value = parse_string(&mut chars)?;
+            object.insert(key, value);
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("Expected ',' or '}' in the request.".into()),
+            }
+        }
+    }
+    match chars.next() {
+        None => Ok(object),
+        Some(_) => Err("Unexpected text after the request.".into()),
+    }
+}
+
+type Chars<'a> = Peekable<std::str::Chars<'a>>;
+
+fn expect(chars: &mut Chars, expected: char) -> Result<(), String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(c) if c == expected => Ok(()),
+        _ => Err(format!("Expected '{expected}' in the request.")),
+    }
+}
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Reads a JSON string, quotes and escapes included.
+fn parse_string(chars: &mut Chars) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return Err("Expected a string in the request.".into());
+    }
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            None => return Err("Unterminated string in the request.".into()),
+            Some('"') => return Ok(string),
+            Some('\\') => string.push(match chars.next() {
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some('/') => '/',
+                Some('b') => '\u{8}',
+                Some('f') => '\u{c}',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('u') => parse_unicode_escape(chars)?,
+                _ => return Err("Invalid escape in a string in the request.".into()),
+            }),
+            Some(c) => string.push(c),
+        }
+    }
+}
+
+/// Reads the hex digits of a `\u` escape, and of the low half that follows
+/// if the first is the high half of a surrogate pair.
+fn parse_unicode_escape(chars: &mut Chars) -> Result<char, String> {
+    let invalid = || "Invalid \\u escape in a string in the request.".to_string();
+    let hex = |chars: &mut Chars| {
+        let digits: String = chars.by_ref().take(4).collect();
+        u32::from_str_radix(&digits, 16).ok().filter(|_| digits.len() == 4).ok_or_else(invalid)
+    };
+    let high = hex(chars)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return char::from_u32(high).ok_or_else(invalid);
+    }
+    if chars.next() != Some('\\') || chars.next() != Some('u') {
+        return Err(invalid());
+    }
+    let low = hex(chars)?;
+    if !(0xDC00..0xE000).contains(&low) {
+        return Err(invalid());
+    }
+    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).ok_or_else(invalid)
+}
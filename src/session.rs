@@ -0,0 +1,105 @@
+use crate::error::{self, Error};
+use crate::interpreter::{self, Interpreter};
+use crate::pipeline::Pipeline;
+use crate::program::Program;
+
+/// What happened when a session took a step.
+#[derive(Debug, PartialEq)]
+pub enum StepResult {
+    /// One top-level statement ran, and there are more waiting.
+    Stepped,
+
+    /// Every statement has run. Stepping again does nothing.
+    Finished,
+
+    /// A statement raised a runtime error, which has been reported. The
+    /// session is over, the same as a script that dies halfway.
+    Failed(String),
+}
+
+/// A program that runs one top-level statement per call to step(), handing
+/// control back to the host in between. This lets an embedder with a loop of
+/// its own, like a game engine drawing frames or a GUI handling events, run a
+/// script a little at a time instead of blocking until it finishes.
+///
+/// A step is a whole top-level statement, so a long loop or a function call at
+/// the top level still runs to completion within a single step.
+pub struct Session {
+    interpreter: Interpreter,
+    program: Program,
+    next: usize,
+    failed: bool,
+}
+
+impl Session {
+    /// Compiles the source with the given pipeline. Returns None, after
+    /// reporting the errors, if the program doesn't compile.
+    pub fn new(mut pipeline: Pipeline, source: &str) -> Option<Self> {
+        let resolved = pipeline.compile(source)?;
+        let mut interpreter = pipeline.interpreter();
+        interpreter.add_locals(resolved.locals);
+        let program = resolved.program;
+        Some(Self { interpreter, program, next: 0, failed: false })
+    }
+
+    pub fn step(&mut self) -> StepResult {
+        if self.is_finished() {
+            return StepResult::Finished;
+        }
+        let statement = &self.program.statements[self.next];
+        self.next += 1;
+        match self.interpreter.execute(statement) {
+            Ok(()) if self.is_finished() => {
+                self.interpreter.run_event_loop();
+                self.interpreter.run_exit_hooks();
+                StepResult::Finished
+            }
+            Ok(()) => StepResult::Stepped,
+            // Only a pipeline built with_exit_status() lets a script return
+            // from its top level, which ends it there with that status.
+            Err(Error::Return(value)) => match interpreter::script_status(&value, statement.line().unwrap_or(1)) {
+                Ok(status) => {
+                    self.next = self.program.statements.len();
+                    error::set_exit_status(status);
+                    self.interpreter.run_event_loop();
+                    self.interpreter.run_exit_hooks();
+                    StepResult::Finished
+                }
+                Err(error) => self.fail(error),
+            },
+            Err(error) => self.fail(error),
+        }
+    }
+
+    /// Reports the error that ended the program, and runs its exit hooks.
+    fn fail(&mut self, error: Error) -> StepResult {
+        self.failed = true;
+        let message = match &error {
+            Error::RuntimeError(_, message) => message.clone(),
+            Error::Throw(value, _) => self.interpreter.uncaught_message(value),
+            _ => unreachable!(),
+        };
+        self.interpreter.report(error);
+        self.interpreter.run_exit_hooks();
+        StepResult::Failed(message)
+    }
+
+    /// Steps until the program finishes or fails.
+    pub fn run(&mut self) -> StepResult {
+        loop {
+            match self.step() {
+                StepResult::Stepped => continue,
+                result => return result,
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.failed || self.next >= self.program.statements.len()
+    }
+
+    /// The interpreter running the program, for inspecting its state between steps.
+    pub fn interpreter(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+}
@@ -11,12 +11,16 @@ pub enum Stmt {
     Expression { expression: Expr },
 
     /// A print statement evaluates an expression and displays the result to the user. 
-    Print { expression: Expr },
+    /// The keyword is kept for reporting a print the interpreter's policy forbids.
+    Print { keyword: Token, expression: Expr },
     
     /// A variable declaration statement brings a new variable into the world.
     /// It stores the name token so we know what it’s declaring, along with the 
     /// initializer expression. (If there isn’t an initializer, that field is null.)
-    Var { name: Token, initializer: Option<Expr> },
+    /// A declaration can be annotated with a type, `var name: String`, which
+    /// only the type checker looks at. A `const` declaration is a Var that
+    /// isn't mutable: once defined, the variable can't be assigned.
+    Var { name: Token, type_name: Option<Token>, initializer: Option<Expr>, mutable: bool },
     
     /// Contains the list of statements that are inside the { } block. 
     Block { statements: Vec<Stmt> },
@@ -30,14 +34,46 @@ pub enum Stmt {
     /// Here we can see why it’s nice to have separate base classes for expressions 
     /// and statements. The fields below make it clear that the condition is an 
     /// expression and the body is a statement.
-    While { condition: Expr, body: Box<Stmt> },
+    /// A loop desugared from a `for` also carries the increment clause, which
+    /// runs after every iteration, including ones cut short by `continue`.
+    /// A loop can be labeled, `outer: while (...)`, so that a `break` or
+    /// `continue` in a loop nested inside it can name it. The keyword is the
+    /// `while` or `for` the loop was written with.
+    While { keyword: Token, condition: Expr, body: Box<Stmt>, increment: Option<Expr>, label: Option<Token> },
+
+    /// `for (item in collection)` runs the body once for each element of a
+    /// list, key of a map, or character of a string, with the variable bound
+    /// to it in a scope of its own. The `in` token is kept for error reporting.
+    ForIn { name: Token, keyword: Token, collection: Expr, body: Box<Stmt>, label: Option<Token> },
     
     /// A function statement is declared with a name, a list of parameters, and its body.
     Function { decl: Rc<FunctionDeclaration> },
     
+    /// Exits the nearest enclosing loop, or the enclosing loop with the given
+    /// label. The keyword is kept for error reporting.
+    Break { keyword: Token, label: Option<Token> },
+
+    /// Skips the rest of the current iteration of the nearest enclosing loop,
+    /// or of the enclosing loop with the given label.
+    Continue { keyword: Token, label: Option<Token> },
+
     /// We use the return keyword token for its location for error reporting, 
     /// and the value being returned, if any. 
     Return { keyword: Token, value: Option<Expr> },
+
+    /// Raises any value as an error, unwinding until a `try` catches it. The
+    /// keyword is kept for reporting the error if nothing does.
+    Throw { keyword: Token, value: Expr },
+
+    /// Runs the body, and if it throws, or a runtime error happens in it, runs
+    /// the handler with the error bound to the name. The catch clause can be
+    /// left out, and then the name is None and the handler empty.
+    ///
+    /// The finally block, if there is one, runs after the rest however they
+    /// ended: normally, or by a return, a break, a continue or an error. Once
+    /// it is done, that ending carries on, unless the finally block ends in
+    /// one of those ways itself, which then takes its place.
+    Try { body: Vec<Stmt>, name: Option<Token>, handler: Vec<Stmt>, finally: Option<Vec<Stmt>> },
     
     /// Stores the class’s name and the methods inside its body. Methods are represented 
     /// by the existing FunctionDeclaration struct that we use for function declaration. That 
@@ -47,7 +83,88 @@ pub enum Stmt {
     /// the superclass clause to a single identifier, but at runtime, that identifier is 
     /// evaluated as a variable access. Wrapping the name in an Expr.Variable early on in 
     /// the parser gives us an object that the resolver can hang the resolution information off of.
-    Class { name: Token, superclass: Option<Expr>, methods: Vec<Rc<FunctionDeclaration>> },
+    ///
+    /// Methods marked with `class` belong to the class itself, and are called
+    /// on it, as in `Math.square(3)`. Inside them, `this` is the class.
+    ///
+    /// The interfaces the class implements, named after a `:`, are kept as
+    /// Expr.Variables too.
+    Class {
+        name: Token,
+        superclass: Option<Expr>,
+        interfaces: Vec<Expr>,
+        methods: Vec<Rc<FunctionDeclaration>>,
+        class_methods: Vec<Rc<FunctionDeclaration>>,
+    },
+
+    /// Names the methods a class has to have to implement it, as in
+    /// `interface Shape { area(); scale(factor); }`. A class that says it
+    /// implements the interface is checked for them when it is declared.
+    Interface { name: Token, methods: Vec<MethodSignature> },
+
+    /// Adds methods to a class that has already been declared, as in
+    /// `extend Point { norm() { ... } }`. They go into the class itself, so
+    /// instances made before have them too. The class is named by an
+    /// Expr.Variable, for the same reason a superclass is.
+    Extend {
+        keyword: Token,
+        class: Expr,
+        methods: Vec<Rc<FunctionDeclaration>>,
+        class_methods: Vec<Rc<FunctionDeclaration>>,
+    },
+
+    /// Loads another file and runs its top-level code. Without an alias, it runs
+    /// in the global scope, so whatever it declares becomes available to the
+    /// importing script. With `as name`, the declarations are kept in a module
+    /// object bound to that name instead. The keyword is kept for error reporting.
+    Import { keyword: Token, path: String, alias: Option<Token> },
+}
+
+impl Stmt {
+    /// The line the statement starts on. A block, or a try, is no more than
+    /// the statements in it, so it has none of its own.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Stmt::Expression { expression } => expression.line(),
+            Stmt::If { condition, .. } => condition.line(),
+            Stmt::Block { .. } | Stmt::Try { .. } => None,
+            Stmt::Print { keyword, .. }
+            | Stmt::While { keyword, .. }
+            | Stmt::ForIn { keyword, .. }
+            | Stmt::Break { keyword, .. }
+            | Stmt::Continue { keyword, .. }
+            | Stmt::Return { keyword, .. }
+            | Stmt::Throw { keyword, .. }
+            | Stmt::Extend { keyword, .. }
+            | Stmt::Import { keyword, .. } => Some(keyword.line),
+            Stmt::Var { name, .. } | Stmt::Class { name, .. } | Stmt::Interface { name, .. } => Some(name.line),
+            Stmt::Function { decl } => Some(decl.name.line),
+        }
+    }
+
+    /// The name of the kind of statement this is, for counting them. None of
+    /// them is also the name of a kind of expression.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Stmt::Expression { .. } => "Expression",
+            Stmt::Print { .. } => "Print",
+            Stmt::Var { .. } => "Var",
+            Stmt::Block { .. } => "Block",
+            Stmt::If { .. } => "If",
+            Stmt::While { .. } => "While",
+            Stmt::ForIn { .. } => "ForIn",
+            Stmt::Function { .. } => "Function",
+            Stmt::Break { .. } => "Break",
+            Stmt::Continue { .. } => "Continue",
+            Stmt::Return { .. } => "Return",
+            Stmt::Throw { .. } => "Throw",
+            Stmt::Try { .. } => "Try",
+            Stmt::Class { .. } => "Class",
+            Stmt::Extend { .. } => "Extend",
+            Stmt::Interface { .. } => "Interface",
+            Stmt::Import { .. } => "Import",
+        }
+    }
 }
 
 impl Display for Stmt {
@@ -59,18 +176,54 @@ impl Display for Stmt {
             Stmt::Block { .. } => { write!(f, "<Block>") },
             Stmt::If { .. } => { write!(f, "<If>") },
             Stmt::While { .. } => { write!(f, "<While>") },
+            Stmt::ForIn { .. } => { write!(f, "<ForIn>") },
             Stmt::Function { .. } => { write!(f, "<Function>") },
+            Stmt::Break { .. } => { write!(f, "<Break>") },
+            Stmt::Continue { .. } => { write!(f, "<Continue>") },
             Stmt::Return { .. } => { write!(f, "<Return>") },
-            Stmt::Class { .. } => { write!(f, "<Class>") }
+            Stmt::Throw { .. } => { write!(f, "<Throw>") },
+            Stmt::Try { .. } => { write!(f, "<Try>") },
+            Stmt::Class { .. } => { write!(f, "<Class>") },
+            Stmt::Extend { .. } => { write!(f, "<Extend>") },
+            Stmt::Interface { .. } => { write!(f, "<Interface>") },
+            Stmt::Import { .. } => { write!(f, "<Import>") }
         }
     }
 }
 
 /// A function declaration has a name, a list of parameters (their names), and then the body. 
 /// We store the body as the list of statements contained inside the curly braces.
+/// The parameters and the return value can be annotated with types, as in
+/// `fun add(a: Number, b: Number) -> Number`. The interpreter ignores these;
+/// they are there for the type checker.
 #[derive(Clone, Debug)]
 pub struct FunctionDeclaration {
     pub name: Token,
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
-}
\ No newline at end of file
+
+    /// The type of each parameter, if it has one, in the same order as params.
+    pub param_types: Vec<Option<Token>>,
+
+    pub return_type: Option<Token>,
+
+    /// Whether the last parameter is a rest parameter, `...args`, which takes
+    /// whatever arguments are left over as a list.
+    pub variadic: bool,
+
+    /// Whether this is a getter: a method declared without a parameter list,
+    /// whose body runs when the property is accessed, with no call needed.
+    pub getter: bool,
+
+    /// Whether this is a setter, declared with `set` before its name, which
+    /// runs instead of writing the field when the property is assigned to.
+    pub setter: bool,
+}
+
+/// A method an interface requires: its name, and its parameters, of which a
+/// class's method has to take as many.
+#[derive(Clone, Debug)]
+pub struct MethodSignature {
+    pub name: Token,
+    pub params: Vec<Token>,
+}
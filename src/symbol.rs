@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+
+/// An interned identifier. Comparing and hashing a Symbol is just comparing
+/// and hashing a small integer, instead of walking the characters of a String.
+/// Symbols are only meaningful together with the Interner that produced them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Maps each distinct name to a Symbol. Every lexeme is copied into the
+/// interner at most once; after that, looking it up again hands back the
+/// same Symbol without allocating.
+#[derive(Default, Debug)]
+pub struct Interner {
+    symbols: HashMap<String, Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the Symbol for the given name, adding it to the table if
+    /// this is the first time we have seen it.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(self.symbols.len() as u32);
+        self.symbols.insert(name.to_string(), symbol);
+        symbol
+    }
+
+    /// Returns the Symbol for the given name only if it has already been interned.
+    pub fn lookup(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+}
@@ -0,0 +1,214 @@
+use crate::error::Error;
+use crate::fold::{self, Fold};
+use crate::interpreter::Interpreter;
+use crate::interrupt;
+use crate::policy::{Limits, Policy};
+use crate::stmt::FunctionDeclaration;
+use crate::value::channel::{Channel, Message};
+use crate::value::function::Function;
+use crate::value::native::Natives;
+use crate::value::object::Object;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How much stack a task's thread gets, the same as a main thread.
+const STACK_SIZE: usize = 8 << 20;
+
+/// How long a task waiting on a channel sleeps between checks that it still
+/// has something to wait for.
+const POLL: Duration = Duration::from_millis(10);
+
+/// The threads of one program: its own, and those of the tasks it spawned,
+/// and that they spawned in turn. They are counted so that a task waiting to
+/// receive a value can tell when nothing is left that could send one: when
+/// every thread of the program is waiting too.
+#[derive(Debug)]
+pub(crate) struct TaskGroup {
+    counts: Mutex<Counts>,
+}
+
+#[derive(Debug)]
+struct Counts {
+    running: usize,
+    waiting: usize,
+}
+
+impl TaskGroup {
+    /// A group with only the program's own thread in it.
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self { counts: Mutex::new(Counts { running: 1, waiting: 0 }) })
+    }
+
+    /// Waits until `poll` gives a value. It is called over and over, with how
+    /// long it may block for each time. Fails if every thread of the program
+    /// is waiting as well, since then none ever stops, or if the program is
+    /// interrupted.
+    pub(crate) fn wait<T>(&self, mut poll: impl FnMut(Duration) -> Option<T>) -> Result<T, String> {
+        self.waiting(|| loop {
+            if let Some(value) = poll(POLL) {
+                return Ok(value);
+            }
+            if interrupt::requested() {
+                return Err("Interrupted.".into());
+            }
+            let stuck = {
+                let counts = self.counts.lock().unwrap();
+                counts.waiting == counts.running
+            };
+            // A thread sends before it starts waiting, or finishes, so what
+            // the others sent is there to be found by now.
+            if stuck {
+                return poll(Duration::ZERO).ok_or_else(|| "Deadlock: every task is waiting for another.".into());
+            }
+        })
+    }
+
+    /// Runs `f`, counting this thread as waiting while it does.
+    pub(crate) fn waiting<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.counts.lock().unwrap().waiting += 1;
+        let result = f();
+        self.counts.lock().unwrap().waiting -= 1;
+        result
+    }
+
+    fn started(&self) {
+        self.counts.lock().unwrap().running += 1;
+    }
+
+    fn finished(&self) {
+        self.counts.lock().unwrap().running -= 1;
+    }
+}
+
+/// What a task takes over from the interpreter spawning it: the natives its
+/// globals start with, the policy and limits it runs under, and whether it
+/// joins strings and numbers.
+pub(crate) struct Settings {
+    pub(crate) natives: Natives,
+    pub(crate) policy: Policy,
+    pub(crate) limits: Limits,
+    pub(crate) mixed_concatenation: bool,
+}
+
+/// A function to run on a thread of its own, with everything it needs to
+/// start copied, so that it shares nothing with the program that spawned it
+/// but its channels.
+pub(crate) struct Task {
+    declaration: Detached,
+    argument: Option<Message>,
+    settings: Settings,
+    group: Arc<TaskGroup>,
+    result: Arc<Channel>,
+}
+
+impl Task {
+    /// A task that calls the declared function, with the argument if there
+    /// is one, and sends what it returns, or the error it fails with, on the
+    /// result channel. It runs with the settings of the program spawning it.
+    pub(crate) fn new(
+        declaration: &Rc<FunctionDeclaration>,
+        argument: Option<Message>,
+        settings: Settings,
+        group: Arc<TaskGroup>,
+        result: Arc<Channel>,
+    ) -> Self {
+        let declaration = Detached::new(declaration);
+        Self { declaration, argument, settings, group, result }
+    }
+
+    /// Starts the task's thread. The task is counted as running from now,
+    /// not from when its thread gets going, so no other can think it waits
+    /// in vain in between.
+    pub(crate) fn start(self) -> JoinHandle<()> {
+        self.group.started();
+        thread::Builder::new()
+            .name("task".into())
+            .stack_size(STACK_SIZE)
+            .spawn(move || self.run())
+            .expect("failed to start a thread for a task")
+    }
+
+    /// The task gets an interpreter of its own, with only the program's
+    /// natives for globals: nothing the program had defined, not even the
+    /// function's own closure, came along. The function is called with what
+    /// it is given, and whatever else it needs it has to receive. Errors it
+    /// reports are kept to its own thread, so they don't change how the
+    /// program exits.
+    fn run(self) {
+        let Task { declaration, argument, settings, group, result } = self;
+        let mut interpreter = Interpreter::new();
+        interpreter.set_natives(&settings.natives);
+        interpreter.set_policy(settings.policy);
+        interpreter.set_limits(settings.limits);
+        if settings.mixed_concatenation {
+            interpreter.allow_mixed_concatenation();
+        }
+        interpreter.set_task_group(group.clone());
+
+        let declaration = declaration.into_inner();
+        let name = declaration.name.clone();
+        let function = Object::Function(Function::new(declaration, interpreter.current_environment(), false));
+        let args = argument.map(|argument| argument.into_object().expect("only values are sent"));
+        let outcome = interpreter.call_checked(&function, args.into_iter().collect(), &name);
+
+        // Its own tasks, and what it printed, are done with before its result
+        // is, so whoever receives it can count on both.
+        interpreter.join_tasks();
+        interpreter.flush();
+        let message = match outcome {
+            Ok(value) => Message::from_object(&value).unwrap_or_else(Message::Failed),
+            Err(Error::RuntimeError(token, message)) => {
+                Message::Failed(format!("A task failed at line {}: {message}", token.line))
+            }
+            Err(Error::Throw(value, _)) => {
+                Message::Failed(format!("A task failed: {}", interpreter.uncaught_message(&value)))
+            }
+            Err(_) => Message::Failed("A task failed.".into()),
+        };
+        result.send(message);
+        group.finished();
+    }
+}
+
+/// A copy of a function's declaration for a task to run on another thread.
+///
+/// Tokens, syntax trees and values are built on `Rc`, so none of them is
+/// `Send`, and in general none of them can go to another thread. There are
+/// two exceptions, and these are the only two `unsafe impl Send` in the
+/// crate. Each holds for a reason of its own, which is all that makes it
+/// sound:
+///
+/// - This copy. It is made of new nodes all the way down, so no `Rc` in it
+///   is shared with the tree it was copied from, and the only other values in
+///   a syntax tree are tokens and literals, which hold plain data. So nothing
+///   in the copy is left for the thread it came from to touch, and it can be
+///   moved as a whole.
+/// - A coroutine's `Handoff`. Values go back and forth between a coroutine's
+///   thread and the thread resuming it, sharing their `Rc`s, but the two
+///   never run at the same time: each hands over control along with the
+///   value, and waits until it is handed back.
+///
+/// Neither argument covers threads that run side by side on shared data,
+/// like a pool compiling modules while the program runs.
+struct Detached(Rc<FunctionDeclaration>);
+
+unsafe impl Send for Detached {}
+
+impl Detached {
+    fn new(declaration: &Rc<FunctionDeclaration>) -> Self {
+        // Folding copies every declaration that is still shared, which they
+        // all are while the original tree is around.
+        Self(fold::fold_function(&mut Duplicate, declaration.clone()))
+    }
+
+    fn into_inner(self) -> Rc<FunctionDeclaration> {
+        self.0
+    }
+}
+
+/// A fold that changes nothing, so all it does is copy.
+struct Duplicate;
+
+impl Fold for Duplicate {}
@@ -1,25 +1,39 @@
 use std::fmt::Display;
+use std::sync::Arc;
 
 #[allow(non_camel_case_types)]
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub enum TokenType {
     // Single-character tokens.
-    LEFT_PAREN, RIGHT_PAREN, LEFT_BRACE, RIGHT_BRACE,
-    COMMA, DOT, MINUS, PLUS, SEMICOLON, SLASH, STAR,
+    LEFT_PAREN, RIGHT_PAREN, LEFT_BRACE, RIGHT_BRACE, LEFT_BRACKET, RIGHT_BRACKET,
+    COLON, COMMA, DOT, MINUS, PERCENT, PLUS, SEMICOLON, SLASH, STAR,
+    AMPERSAND, PIPE, CARET, TILDE,
 
     // One or two character tokens.
     BANG, BANG_EQUAL,
     EQUAL, EQUAL_EQUAL,
-    GREATER, GREATER_EQUAL,
-    LESS, LESS_EQUAL,
+    GREATER, GREATER_EQUAL, GREATER_GREATER,
+    LESS, LESS_EQUAL, LESS_LESS,
+    MINUS_EQUAL, PLUS_EQUAL, SLASH_EQUAL, STAR_EQUAL,
+    MINUS_MINUS, PLUS_PLUS, ARROW,
+
+    // Three character tokens.
+    ELLIPSIS,
 
     // Literals
     IDENTIFIER, STRING, NUMBER,
 
     // Keywords.
-    AND, CLASS, ELSE, FALSE, FUN, FOR, IF, NIL, OR,
-    PRINT, RETURN, SUPER, THIS, TRUE, VAR, WHILE,
+    AND, BREAK, CATCH, CLASS, CONST, CONTINUE, ELSE, FALSE, FINALLY, FUN, FOR, IF, IMPORT, MATCH, NIL, OR,
+    PRINT, RETURN, SUPER, THIS, THROW, TRUE, TRY, VAR, WHILE,
+
+    // Words that are only keywords in some places. The scanner leaves them as
+    // identifiers, and the parser gives them this type where they are keywords.
+    IS,
+
+    // Conditional compilation directives.
+    HASH_IF, HASH_ELSE, HASH_END,
 
     EOF,
 }
@@ -44,6 +58,14 @@ pub struct Token {
     /// We track which line the token appears on.
     /// This is useful for telling users where errors occurred.
     pub line: usize,
+
+    /// The 1-based column of the first character of the lexeme on its line.
+    pub column: usize,
+
+    /// The file the token was scanned from. This is only set for code that
+    /// isn't the main script, like imported modules, so errors in them can
+    /// say where they are. Every token from a file shares the one name.
+    pub file: Option<Arc<str>>,
 }
 
 impl Display for Token {
@@ -54,12 +76,14 @@ impl Display for Token {
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: Option<String>, line: usize) -> Self {
+    pub fn new(token_type: TokenType, lexeme: String, literal: Option<String>, line: usize, column: usize) -> Self {
         Token {
             token_type,
             lexeme,
             literal,
             line,
+            column,
+            file: None,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,816 @@
+use crate::error::{token_error, token_warning};
+use crate::expr::{Expr, Pattern};
+use crate::references::ReferenceIndex;
+use crate::stmt::{FunctionDeclaration, Stmt};
+use crate::token::Token;
+use crate::token::TokenType::*;
+use crate::value::native::Natives;
+use crate::value::object::Object;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::rc::Rc;
+
+/// The types the checker knows about. Any stands for a value it knows nothing
+/// about, which is compatible with everything, so unannotated code is never
+/// reported.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Type {
+    Any,
+    Nil,
+    Bool,
+    Number,
+    String,
+    List,
+    Map,
+    Module,
+
+    /// A function, with its signature when it is known.
+    Function(Option<Rc<Signature>>),
+
+    /// A class itself, the value that is called to make instances.
+    Class(std::string::String),
+
+    /// An instance of the named class.
+    Instance(std::string::String),
+
+    /// Either a value of the inner type or nil, like a variable declared
+    /// without a value and only assigned one later.
+    MaybeNil(Box<Type>),
+}
+
+/// The parameter and return types of a function.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub params: Vec<Type>,
+    pub returns: Type,
+
+    /// Whether any number of arguments may follow the params.
+    pub variadic: bool,
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Any => f.write_str("Any"),
+            Type::Nil => f.write_str("Nil"),
+            Type::Bool => f.write_str("Bool"),
+            Type::Number => f.write_str("Number"),
+            Type::String => f.write_str("String"),
+            Type::List => f.write_str("List"),
+            Type::Map => f.write_str("Map"),
+            Type::Module => f.write_str("Module"),
+            Type::Function(_) => f.write_str("Function"),
+            Type::Class(_) => f.write_str("Class"),
+            Type::Instance(class) => f.write_str(class),
+            Type::MaybeNil(inner) => write!(f, "{inner}?"),
+        }
+    }
+}
+
+impl Type {
+    /// What a value of this type is called in runtime errors, so that a
+    /// warning reads the same as the error it warns of.
+    fn value_name(&self) -> String {
+        match self {
+            Type::Bool => "boolean".into(),
+            other => other.to_string().to_lowercase(),
+        }
+    }
+}
+
+/// Checks a program against its type annotations, before it runs.
+///
+/// The checker is flow-insensitive: a variable has a single type for its
+/// whole life, rather than one that changes as values are assigned. That
+/// type is the annotation, if there is one. Otherwise it is inferred from the
+/// initializer, but only for a variable that is never assigned again (the
+/// resolver's reference index tells us which those are), and is Any for the
+/// rest. Expression types are worked out bottom-up from literals, operators
+/// and the signatures of the functions called.
+///
+/// Mismatches with annotations are reported as errors: an initializer or
+/// assigned value of the wrong type, a call with the wrong number or types of
+/// arguments, and a return of the wrong type.
+///
+/// Operations that are bound to fail at runtime, whatever the annotations
+/// say, are reported as warnings: arithmetic on a value that is known not to
+/// be a number, calling something that is known not to be callable, and so
+/// on. Values that may be nil are tracked too, so that calling one or reading
+/// a property of one is warned about. These need no annotations at all, since literals and operators give
+/// away enough types, so they can be checked before any program runs (see
+/// `warnings_only`).
+pub struct TypeChecker<'a> {
+    /// Where each name is declared and assigned, from the resolver.
+    references: &'a ReferenceIndex,
+
+    /// The types of variables in the local scopes, innermost last.
+    scopes: Vec<HashMap<std::string::String, Type>>,
+
+    globals: HashMap<std::string::String, Type>,
+
+    /// The superclass of every class in the program, by name.
+    classes: HashMap<std::string::String, Option<std::string::String>>,
+
+    /// The names of the interfaces in the program. An annotation naming one
+    /// is taken for Any, since which classes implement it is only known at
+    /// run time.
+    interfaces: HashSet<std::string::String>,
+
+    /// The declared return type of the function being checked, or None at
+    /// the top level.
+    returns: Option<Type>,
+
+    /// The class whose methods are being checked, for the type of `this`.
+    current_class: Option<std::string::String>,
+
+    /// Whether to check the annotations, or only look for operations bound to fail.
+    annotations: bool,
+
+    /// Whether `+` joins a string and a number, see `with_mixed_concatenation`.
+    mixed_concatenation: bool,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(references: &'a ReferenceIndex) -> Self {
+        let globals = Natives::standard()
+            .iter()
+            .map(|native| {
+                let signature = Signature { params: vec![Type::Any; native.arity], returns: Type::Any, variadic: false };
+                (native.name.to_string(), Type::Function(Some(Rc::new(signature))))
+            })
+            .collect();
+        Self {
+            references,
+            scopes: Vec::new(),
+            globals,
+            classes: HashMap::new(),
+            interfaces: HashSet::new(),
+            returns: None,
+            current_class: None,
+            annotations: true,
+            mixed_concatenation: false,
+        }
+    }
+
+    /// Leaves the annotations alone and only warns about operations that are
+    /// bound to fail. This is what runs before a program when warnings are on.
+    pub fn warnings_only(mut self) -> Self {
+        self.annotations = false;
+        self
+    }
+
+    /// Checks `+` as it runs when a string and a number may be joined. See
+    /// Interpreter::allow_mixed_concatenation.
+    pub fn with_mixed_concatenation(mut self) -> Self {
+        self.mixed_concatenation = true;
+        self
+    }
+
+    /// Checks a program, reporting each mismatch as a compile error.
+    pub fn check(&mut self, statements: &[Stmt]) {
+        // Functions and classes can be used before the code declaring them
+        // has run, from inside other functions, so they are known up front.
+        self.collect_classes(statements);
+        for statement in statements {
+            match statement {
+                Stmt::Function { decl } => {
+                    let function = self.function_type(decl);
+                    self.globals.insert(decl.name.lexeme.clone(), function);
+                }
+                Stmt::Class { name, .. } => {
+                    self.globals.insert(name.lexeme.clone(), Type::Class(name.lexeme.clone()));
+                }
+                _ => {}
+            }
+        }
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn collect_classes(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            match statement {
+                Stmt::Class { name, superclass, methods, class_methods, .. } => {
+                    let superclass = match superclass {
+                        Some(Expr::Variable { name }) => Some(name.lexeme.clone()),
+                        _ => None,
+                    };
+                    self.classes.insert(name.lexeme.clone(), superclass);
+                    for method in methods.iter().chain(class_methods) {
+                        self.collect_classes(&method.body);
+                    }
+                }
+                Stmt::Interface { name, .. } => {
+                    self.interfaces.insert(name.lexeme.clone());
+                }
+                Stmt::Extend { methods, class_methods, .. } => {
+                    for method in methods.iter().chain(class_methods) {
+                        self.collect_classes(&method.body);
+                    }
+                }
+                Stmt::Function { decl } => self.collect_classes(&decl.body),
+                Stmt::Block { statements } => self.collect_classes(statements),
+                Stmt::Try { body, handler, finally, .. } => {
+                    self.collect_classes(body);
+                    self.collect_classes(handler);
+                    self.collect_classes(finally.as_deref().unwrap_or_default());
+                }
+                Stmt::If { then_branch, else_branch, .. } => {
+                    self.collect_classes(std::slice::from_ref(then_branch));
+                    if let Some(else_branch) = else_branch {
+                        self.collect_classes(std::slice::from_ref(else_branch));
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::ForIn { body, .. } => self.collect_classes(std::slice::from_ref(body)),
+                _ => {}
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match statement {
+            Stmt::Expression { expression } | Stmt::Print { expression, .. } => {
+                self.expression(expression);
+            }
+            Stmt::Var { name, type_name, initializer, .. } => {
+                let value = match initializer {
+                    Some(initializer) => self.expression(initializer),
+                    None => Type::Nil,
+                };
+                let declared = match type_name {
+                    Some(type_name) => {
+                        let declared = self.annotation(type_name);
+                        if initializer.is_some() && !self.assignable(&value, &declared) {
+                            self.mismatch(name, format!("Expected {declared} but got {value}."));
+                        }
+                        declared
+                    }
+                    // A variable declared without a value is nil until it is
+                    // assigned one, which may not have happened when it is used.
+                    None if self.reassigned(name) && initializer.is_none() => Type::MaybeNil(Box::new(Type::Any)),
+                    None if self.reassigned(name) => Type::Any,
+                    None => value,
+                };
+                self.declare(name, declared);
+            }
+            Stmt::Block { statements } => {
+                self.scopes.push(HashMap::new());
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            Stmt::If { condition, then_branch, else_branch } => {
+                self.expression(condition);
+                self.narrowed(condition, |checker| checker.statement(then_branch));
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::While { condition, body, increment, .. } => {
+                self.expression(condition);
+                self.statement(body);
+                if let Some(increment) = increment {
+                    self.expression(increment);
+                }
+            }
+            Stmt::ForIn { name, keyword, collection, body, .. } => {
+                let item = match self.expression(collection) {
+                    Type::String => Type::String,
+                    Type::List | Type::Map | Type::Any | Type::MaybeNil(_) => Type::Any,
+                    _ => {
+                        token_warning(keyword.clone(), "Can only iterate over lists, maps and strings.".into());
+                        Type::Any
+                    }
+                };
+                let item = if self.reassigned(name) { Type::Any } else { item };
+                self.scopes.push(HashMap::new());
+                self.declare(name, item);
+                self.statement(body);
+                self.scopes.pop();
+            }
+            Stmt::Function { decl } => {
+                let function = self.function_type(decl);
+                let function = match self.reassigned(&decl.name) {
+                    true => Type::Function(None),
+                    false => function,
+                };
+                self.declare(&decl.name, function);
+                self.function(decl);
+            }
+            Stmt::Throw { value, .. } => {
+                self.expression(value);
+            }
+            Stmt::Try { body, name, handler, finally } => {
+                self.scopes.push(HashMap::new());
+                for statement in body {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+                if let Some(name) = name {
+                    self.scopes.push(HashMap::new());
+                    self.declare(name, Type::Any);
+                    for statement in handler {
+                        self.statement(statement);
+                    }
+                    self.scopes.pop();
+                }
+                if let Some(finally) = finally {
+                    self.scopes.push(HashMap::new());
+                    for statement in finally {
+                        self.statement(statement);
+                    }
+                    self.scopes.pop();
+                }
+            }
+            Stmt::Return { keyword, value } => {
+                let value = match value {
+                    Some(value) => self.expression(value),
+                    None => Type::Nil,
+                };
+                if let Some(returns) = self.returns.clone() {
+                    if !self.assignable(&value, &returns) {
+                        self.mismatch(keyword, format!("Expected to return {returns} but got {value}."));
+                    }
+                }
+            }
+            Stmt::Class { name, superclass, interfaces, methods, class_methods } => {
+                self.declare(name, Type::Class(name.lexeme.clone()));
+                if let Some(superclass) = superclass {
+                    self.expression(superclass);
+                }
+                for interface in interfaces {
+                    self.expression(interface);
+                }
+                let enclosing = self.current_class.replace(name.lexeme.clone());
+                for method in methods {
+                    self.function(method);
+                }
+                // In a class method, `this` is the class, not an instance of it.
+                self.current_class = None;
+                for method in class_methods {
+                    self.function(method);
+                }
+                self.current_class = enclosing;
+            }
+            Stmt::Interface { name, .. } => self.declare(name, Type::Any),
+            Stmt::Extend { class, methods, class_methods, .. } => {
+                let class = match self.expression(class) {
+                    Type::Class(name) => Some(name),
+                    _ => None,
+                };
+                let enclosing = std::mem::replace(&mut self.current_class, class);
+                for method in methods {
+                    self.function(method);
+                }
+                self.current_class = None;
+                for method in class_methods {
+                    self.function(method);
+                }
+                self.current_class = enclosing;
+            }
+            Stmt::Import { alias, .. } => {
+                if let Some(alias) = alias {
+                    self.declare(alias, Type::Module);
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } => {}
+        }
+    }
+
+    /// Checks a function's body, with its parameters in scope.
+    fn function(&mut self, decl: &FunctionDeclaration) {
+        let returns = decl.return_type.as_ref().map(|type_name| self.annotation(type_name));
+        let enclosing = self.returns.replace(returns.unwrap_or(Type::Any));
+        self.scopes.push(HashMap::new());
+        for (param, type_name) in decl.params.iter().zip(&decl.param_types) {
+            let declared = match type_name {
+                Some(type_name) => self.annotation(type_name),
+                None => Type::Any,
+            };
+            self.declare(param, declared);
+        }
+        if let Some(rest) = decl.params.last().filter(|_| decl.variadic) {
+            self.declare(rest, Type::List);
+        }
+        for statement in &decl.body {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+        self.returns = enclosing;
+    }
+
+    /// The type of a function, from the annotations in its declaration.
+    /// Unknown types in them are reported when the function is checked.
+    /// Without a return type, we can still tell from its return statements
+    /// whether it may return nil.
+    fn function_type(&self, decl: &FunctionDeclaration) -> Type {
+        let named = |type_name: &Option<Token>| type_name.as_ref().and_then(|name| self.named_type(name)).unwrap_or(Type::Any);
+        let fixed = decl.params.len() - usize::from(decl.variadic);
+        let params = decl.param_types[..fixed].iter().map(named).collect();
+        let returns = match &decl.return_type {
+            Some(_) => named(&decl.return_type),
+            None => {
+                let returns_value = any_return(&decl.body, &|value| value.is_some_and(|value| !is_nil(value)));
+                let returns_nil = any_return(&decl.body, &|value| value.map_or(true, is_nil));
+                match (returns_value, returns_nil || completes(&decl.body)) {
+                    (false, _) => Type::Nil,
+                    (true, true) => Type::MaybeNil(Box::new(Type::Any)),
+                    (true, false) => Type::Any,
+                }
+            }
+        };
+        Type::Function(Some(Rc::new(Signature { params, returns, variadic: decl.variadic })))
+    }
+
+    /// Works out the type of an expression, checking what is inside it.
+    fn expression(&mut self, expression: &Expr) -> Type {
+        match expression {
+            Expr::Literal { value } => match value {
+                Object::Nil => Type::Nil,
+                Object::Boolean(_) => Type::Bool,
+                Object::Number(_) => Type::Number,
+                Object::String(_) => Type::String,
+                _ => Type::Any,
+            },
+            Expr::Grouping { expression } => self.expression(expression),
+            Expr::Unary { operator, right } => {
+                let right = self.expression(right);
+                match operator.token_type {
+                    BANG => Type::Bool,
+                    _ => {
+                        if !maybe_number(&right) {
+                            token_warning(operator.clone(), "Operand must be a number.".into());
+                        }
+                        Type::Number
+                    }
+                }
+            }
+            // A sequence is worth whatever its last expression is.
+            Expr::Binary { left, operator, right } if operator.token_type == COMMA => {
+                self.expression(left);
+                self.expression(right)
+            }
+            Expr::Binary { left, operator, right } => {
+                // Only calls and properties are warned about for values that
+                // may be nil, so here they could be anything.
+                let left = self.expression(left).or_any_if_maybe_nil();
+                let right = self.expression(right).or_any_if_maybe_nil();
+                let fails = match operator.token_type {
+                    EQUAL_EQUAL | BANG_EQUAL | IS => false,
+                    PLUS => match (&left, &right) {
+                        (Type::String, Type::Number) | (Type::Number, Type::String) if self.mixed_concatenation => false,
+                        (Type::Any, other) | (other, Type::Any) => !matches!(other, Type::Number | Type::String),
+                        (left, right) => left != right || !matches!(left, Type::Number | Type::String),
+                    },
+                    GREATER | GREATER_EQUAL | LESS | LESS_EQUAL => match (&left, &right) {
+                        (Type::String, Type::String | Type::Any) | (Type::Any, Type::String) => false,
+                        _ => !maybe_number(&left) || !maybe_number(&right),
+                    },
+                    _ => !maybe_number(&left) || !maybe_number(&right),
+                };
+                if fails {
+                    token_warning(operator.clone(), "Operands must be numbers.".into());
+                }
+                match operator.token_type {
+                    PLUS if left == right && matches!(left, Type::Number | Type::String) => left,
+                    PLUS if self.mixed_concatenation && matches!((&left, &right), (Type::String, Type::Number) | (Type::Number, Type::String)) => {
+                        Type::String
+                    }
+                    PLUS => Type::Any,
+                    GREATER | GREATER_EQUAL | LESS | LESS_EQUAL | EQUAL_EQUAL | BANG_EQUAL | IS => Type::Bool,
+                    _ => Type::Number,
+                }
+            }
+            Expr::Logical { left: left_expr, operator, right } => {
+                let left = self.expression(left_expr);
+                let right = match operator.token_type {
+                    AND => self.narrowed(left_expr, |checker| checker.expression(right)),
+                    _ => self.expression(right),
+                };
+                match left == right {
+                    true => left,
+                    false => Type::Any,
+                }
+            }
+            Expr::Variable { name } => self.lookup(name),
+            Expr::Assign { name, value } => {
+                let value = self.expression(value);
+                let declared = self.lookup(name);
+                if !self.assignable(&value, &declared) {
+                    self.mismatch(name, format!("Expected {declared} but got {value}."));
+                }
+                value
+            }
+            Expr::Call { callee, arguments, paren } => {
+                let callee = self.expression(callee);
+                let arguments: Vec<Type> = arguments.iter().map(|argument| self.expression(argument)).collect();
+                match callee {
+                    Type::Function(Some(signature)) => {
+                        self.check_arguments(&signature, &arguments, paren);
+                        signature.returns.clone()
+                    }
+                    Type::Class(class) => Type::Instance(class),
+                    Type::Any => Type::Any,
+                    Type::MaybeNil(_) => {
+                        token_warning(paren.clone(), "This may be nil, which can't be called.".into());
+                        Type::Any
+                    }
+                    _ => {
+                        token_warning(paren.clone(), "Can only call functions and classes.".into());
+                        Type::Any
+                    }
+                }
+            }
+            Expr::Get { object, name } => {
+                let object = self.expression(object);
+                if let Type::MaybeNil(_) = object {
+                    token_warning(name.clone(), "This may be nil, which has no properties.".into());
+                } else if !matches!(object, Type::Any | Type::Instance(_) | Type::Map | Type::Module | Type::Class(_)) {
+                    let message = format!("Can't read property '{}' of a {}.", name.lexeme, object.value_name());
+                    token_warning(name.clone(), message);
+                }
+                Type::Any
+            }
+            Expr::Set { object, name, value } => {
+                let object = self.expression(object);
+                if let Type::MaybeNil(_) = object {
+                    token_warning(name.clone(), "This may be nil, which has no fields.".into());
+                } else if !matches!(object, Type::Any | Type::Instance(_) | Type::Map) {
+                    token_warning(name.clone(), "Only instances have fields.".into());
+                }
+                self.expression(value)
+            }
+            Expr::List { elements } => {
+                for element in elements {
+                    self.expression(element);
+                }
+                Type::List
+            }
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries {
+                    self.expression(key);
+                    self.expression(value);
+                }
+                Type::Map
+            }
+            Expr::Index { object, bracket, index } => {
+                let object = self.expression(object);
+                self.indexable(&object, bracket, false);
+                self.expression(index);
+                match object {
+                    Type::String => Type::String,
+                    _ => Type::Any,
+                }
+            }
+            Expr::IndexSet { object, bracket, index, value } => {
+                let object = self.expression(object);
+                self.indexable(&object, bracket, true);
+                self.expression(index);
+                self.expression(value)
+            }
+            Expr::Update { target, .. } => {
+                self.expression(target);
+                Type::Number
+            }
+            Expr::Lambda { decl } => {
+                self.function(decl);
+                self.function_type(decl)
+            }
+            Expr::Match { subject, arms, .. } => {
+                let subject = self.expression(subject);
+                let mut result = None;
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    if let Pattern::Binding { name: Some(name), type_name } = &arm.pattern {
+                        let bound = match type_name.as_deref() {
+                            Some(Expr::Variable { name }) => self.named_type(name).unwrap_or(Type::Any),
+                            _ => subject.clone(),
+                        };
+                        let bound = if self.reassigned(name) { Type::Any } else { bound };
+                        self.declare(name, bound);
+                    }
+                    let body = self.expression(&arm.body);
+                    self.scopes.pop();
+                    result = match result {
+                        None => Some(body),
+                        Some(result) if result == body => Some(result),
+                        Some(_) => Some(Type::Any),
+                    };
+                }
+                result.unwrap_or(Type::Any)
+            }
+            Expr::This { .. } => match &self.current_class {
+                Some(class) => Type::Instance(class.clone()),
+                None => Type::Any,
+            },
+            Expr::Super { .. } => Type::Any,
+        }
+    }
+
+    fn indexable(&self, object: &Type, bracket: &Token, writing: bool) {
+        match object {
+            Type::Any | Type::List | Type::Map | Type::Instance(_) | Type::MaybeNil(_) => {}
+            Type::String if writing => token_warning(bracket.clone(), "Strings can't be changed.".into()),
+            Type::String => {}
+            _ => token_warning(bracket.clone(), "Only lists, maps, strings and instances can be indexed.".into()),
+        }
+    }
+
+    fn check_arguments(&self, signature: &Signature, arguments: &[Type], paren: &Token) {
+        if signature.variadic && arguments.len() < signature.params.len() {
+            let message = format!("Expected at least {} arguments but got {}.", signature.params.len(), arguments.len());
+            self.mismatch(paren, message);
+            return;
+        }
+        if !signature.variadic && signature.params.len() != arguments.len() {
+            let message = format!("Expected {} arguments but got {}.", signature.params.len(), arguments.len());
+            self.mismatch(paren, message);
+            return;
+        }
+        for (position, (argument, param)) in arguments.iter().zip(&signature.params).enumerate() {
+            if !self.assignable(argument, param) {
+                let message = format!("Expected {param} for argument {} but got {argument}.", position + 1);
+                self.mismatch(paren, message);
+            }
+        }
+    }
+
+    /// Runs `check` knowing that a variable which may be nil isn't, when the
+    /// condition is the variable itself or compares it to nil with `!=`.
+    fn narrowed<T>(&mut self, condition: &Expr, check: impl FnOnce(&mut Self) -> T) -> T {
+        let name = match condition {
+            Expr::Variable { name } => Some(name),
+            Expr::Binary { left, operator, right } if operator.token_type == BANG_EQUAL => {
+                match (left.as_ref(), right.as_ref()) {
+                    (Expr::Variable { name }, other) | (other, Expr::Variable { name }) if is_nil(other) => Some(name),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let Some(Type::MaybeNil(inner)) = name.map(|name| self.lookup(name)) else {
+            return check(self);
+        };
+        self.scopes.push(HashMap::from([(name.unwrap().lexeme.clone(), *inner)]));
+        let result = check(self);
+        self.scopes.pop();
+        result
+    }
+
+    /// Reports a problem with the annotations, if they are being checked.
+    fn mismatch(&self, token: &Token, message: std::string::String) {
+        if self.annotations {
+            token_error(token.clone(), message);
+        }
+    }
+
+    /// Whether a value of one type can be stored where the other is expected.
+    fn assignable(&self, value: &Type, expected: &Type) -> bool {
+        match (value, expected) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (Type::MaybeNil(inner), expected) => {
+                self.assignable(&Type::Nil, expected) && self.assignable(inner, expected)
+            }
+            (value, Type::MaybeNil(inner)) => *value == Type::Nil || self.assignable(value, inner),
+            (Type::Function(_), Type::Function(_)) => true,
+            (Type::Class(_), Type::Class(_)) => true,
+            (Type::Instance(class), Type::Instance(expected)) => {
+                let mut class = Some(class);
+                while let Some(name) = class {
+                    if name == expected {
+                        return true;
+                    }
+                    class = self.classes.get(name).and_then(Option::as_ref);
+                }
+                false
+            }
+            (value, expected) => value == expected,
+        }
+    }
+
+    /// The type an annotation names, reporting names that aren't types.
+    fn annotation(&self, type_name: &Token) -> Type {
+        self.named_type(type_name).unwrap_or_else(|| {
+            self.mismatch(type_name, format!("Unknown type '{}'.", type_name.lexeme));
+            Type::Any
+        })
+    }
+
+    /// The type a name stands for. Class names stand for their instances.
+    fn named_type(&self, type_name: &Token) -> Option<Type> {
+        let named = match type_name.lexeme.as_str() {
+            "Any" => Type::Any,
+            "Nil" | "nil" => Type::Nil,
+            "Bool" => Type::Bool,
+            "Number" => Type::Number,
+            "String" => Type::String,
+            "List" => Type::List,
+            "Map" => Type::Map,
+            "Module" => Type::Module,
+            "Function" => Type::Function(None),
+            "Class" => Type::Class(std::string::String::new()),
+            class if self.classes.contains_key(class) => Type::Instance(class.to_string()),
+            interface if self.interfaces.contains(interface) => Type::Any,
+            _ => return None,
+        };
+        Some(named)
+    }
+
+    /// Whether a variable is given another value after it is declared, or is
+    /// a global declared more than once. The type of its initializer isn't
+    /// the type of the variable then.
+    fn reassigned(&self, name: &Token) -> bool {
+        let Some(binding) = self.references.binding_at(name.line, name.column) else { return true };
+        !binding.assignments.is_empty() || binding.declarations.len() > 1
+    }
+
+    fn declare(&mut self, name: &Token, declared: Type) {
+        match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.lexeme.clone(), declared),
+            None => self.globals.insert(name.lexeme.clone(), declared),
+        };
+    }
+
+    fn lookup(&self, name: &Token) -> Type {
+        self.scopes
+            .iter()
+            .rev()
+            .chain(std::iter::once(&self.globals))
+            .find_map(|scope| scope.get(&name.lexeme))
+            .cloned()
+            .unwrap_or(Type::Any)
+    }
+}
+
+/// Whether a value of this type could be a number.
+fn maybe_number(value: &Type) -> bool {
+    matches!(value, Type::Any | Type::Number)
+}
+
+impl Type {
+    /// Treats a value that may be nil as one we know nothing about.
+    fn or_any_if_maybe_nil(self) -> Type {
+        match self {
+            Type::MaybeNil(_) => Type::Any,
+            other => other,
+        }
+    }
+}
+
+fn is_nil(expression: &Expr) -> bool {
+    matches!(expression, Expr::Literal { value: Object::Nil })
+}
+
+/// Whether any return statement in a function body, outside the functions
+/// nested in it, has a value matching the predicate. A bare `return;` has None.
+fn any_return(statements: &[Stmt], matching: &dyn Fn(Option<&Expr>) -> bool) -> bool {
+    statements.iter().any(|statement| match statement {
+        Stmt::Return { value, .. } => matching(value.as_ref()),
+        Stmt::Try { body, handler, finally, .. } => {
+            any_return(body, matching)
+                || any_return(handler, matching)
+                || finally.as_ref().is_some_and(|finally| any_return(finally, matching))
+        }
+        Stmt::Block { statements } => any_return(statements, matching),
+        Stmt::If { then_branch, else_branch, .. } => {
+            any_return(std::slice::from_ref(then_branch), matching)
+                || else_branch.as_ref().is_some_and(|branch| any_return(std::slice::from_ref(branch), matching))
+        }
+        Stmt::While { body, .. } | Stmt::ForIn { body, .. } => any_return(std::slice::from_ref(body), matching),
+        _ => false,
+    })
+}
+
+/// Whether running the statements can reach their end, so that a function
+/// with them as its body could finish without a return and give back nil.
+fn completes(statements: &[Stmt]) -> bool {
+    statements.iter().all(|statement| match statement {
+        Stmt::Return { .. } | Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Throw { .. } => false,
+        // The handler runs if the body throws partway through. The finally
+        // block always runs, so if it can't complete, neither can the rest.
+        Stmt::Try { body, name, handler, finally } => {
+            (completes(body) || name.is_some() && completes(handler))
+                && finally.as_ref().map_or(true, |finally| completes(finally))
+        }
+        Stmt::Block { statements } => completes(statements),
+        Stmt::If { then_branch, else_branch: Some(else_branch), .. } => {
+            completes(std::slice::from_ref(then_branch)) || completes(std::slice::from_ref(else_branch))
+        }
+        // `while (true)` only ends with a break.
+        Stmt::While { condition: Expr::Literal { value: Object::Boolean(true) }, body, .. } => breaks(body),
+        _ => true,
+    })
+}
+
+/// Whether a loop body has a break for that loop.
+fn breaks(statement: &Stmt) -> bool {
+    match statement {
+        Stmt::Break { .. } => true,
+        Stmt::Block { statements } => statements.iter().any(breaks),
+        Stmt::Try { body, handler, finally, .. } => body.iter().chain(handler).chain(finally.iter().flatten()).any(breaks),
+        Stmt::If { then_branch, else_branch, .. } => breaks(then_branch) || else_branch.as_deref().is_some_and(breaks),
+        _ => false,
+    }
+}
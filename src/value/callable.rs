@@ -1,8 +1,18 @@
 use crate::error::Error;
 use crate::interpreter::Interpreter;
+use crate::token::Token;
 use crate::value::object::Object;
 
 pub trait Callable {
+    /// How many arguments a call must pass. A variadic callable takes at
+    /// least this many, and any more are collected into a list.
     fn arity(&self) -> usize;
-    fn call(&self, interpreter: &mut Interpreter, args: Vec<Object>) -> Result<Object, Error>;
+
+    fn variadic(&self) -> bool {
+        false
+    }
+
+    /// Calls the value with arguments already checked against arity(). The
+    /// closing parenthesis of the call is where runtime errors are reported.
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Object>, paren: &Token) -> Result<Object, Error>;
 }
\ No newline at end of file
@@ -0,0 +1,145 @@
+use crate::task::TaskGroup;
+use crate::value::map::MapKey;
+use crate::value::object::Object;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// A queue of values that tasks hand to each other. Any task holding a
+/// channel can send into it, and any can receive from it; each value sent is
+/// received once, in the order they were sent.
+///
+/// Tasks don't share anything else, so what goes through a channel is a
+/// copy: a list sent is a new list on the other side, and changing one
+/// doesn't change the other. Only values that can be copied that way can be
+/// sent: nil, booleans, numbers, strings, and lists and maps of those. A
+/// channel itself can be sent too, and then both sides hold the same one.
+#[derive(Debug, Default)]
+pub struct Channel {
+    queue: Mutex<VecDeque<Message>>,
+    arrived: Condvar,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Puts a value at the back of the queue, waking a task waiting for one.
+    pub(crate) fn send(&self, message: Message) {
+        self.queue.lock().unwrap().push_back(message);
+        self.arrived.notify_one();
+    }
+
+    /// Takes the value at the front of the queue, waiting for one to be sent
+    /// if there is none. Fails if no task is left that could send one, or if
+    /// the program is interrupted while waiting (see TaskGroup::wait).
+    pub(crate) fn receive(&self, group: &TaskGroup) -> Result<Object, String> {
+        let message = group.wait(|timeout| {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.is_empty() {
+                queue = self.arrived.wait_timeout(queue, timeout).unwrap().0;
+            }
+            queue.pop_front()
+        })?;
+        message.into_object()
+    }
+}
+
+/// A value on its way from one task to another, copied out of the objects of
+/// the task sending it, to be made into objects of the task receiving it.
+#[derive(Debug)]
+pub(crate) enum Message {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Message>),
+    Map(Vec<(Message, Message)>),
+    Channel(Arc<Channel>),
+
+    /// The error a task failed with, sent in place of its result.
+    Failed(String),
+}
+
+impl Message {
+    /// Copies a value to send. Fails for values that can't be copied, and for
+    /// lists and maps that contain themselves, which would never stop.
+    pub(crate) fn from_object(value: &Object) -> Result<Message, String> {
+        Self::copy(value, &mut Vec::new())
+    }
+
+    /// The lists and maps being copied, outermost first, are kept by address
+    /// to notice one inside itself.
+    fn copy(value: &Object, outer: &mut Vec<*const ()>) -> Result<Message, String> {
+        let address = match value {
+            Object::List(list) => Rc::as_ptr(list) as *const (),
+            Object::Map(map) => Rc::as_ptr(map) as *const (),
+            _ => std::ptr::null(),
+        };
+        if !address.is_null() {
+            if outer.contains(&address) {
+                return Err(format!("Can't send a {} that contains itself.", value.type_name()));
+            }
+            outer.push(address);
+        }
+        let message = match value {
+            Object::Nil => Message::Nil,
+            Object::Boolean(b) => Message::Boolean(*b),
+            Object::Number(n) => Message::Number(*n),
+            Object::String(s) => Message::String(s.clone()),
+            Object::Channel(channel) => Message::Channel(channel.clone()),
+            Object::List(list) => {
+                let elements = list.borrow().iter().map(|element| Self::copy(element, outer)).collect::<Result<_, _>>()?;
+                Message::List(elements)
+            }
+            Object::Map(map) => {
+                let map = map.borrow();
+                let entries = map.iter().map(|(key, value)| {
+                    let key = match key {
+                        MapKey::Number(bits) => Message::Number(f64::from_bits(*bits)),
+                        MapKey::String(s) => Message::String(s.clone()),
+                        MapKey::Instance(_) => return Err("Can't send a map with instances for keys.".to_string()),
+                    };
+                    Ok((key, Self::copy(value, outer)?))
+                });
+                Message::Map(entries.collect::<Result<_, _>>()?)
+            }
+            other => {
+                let name = other.type_name();
+                let article = if name.starts_with(['a', 'e', 'i', 'o', 'u']) { "an" } else { "a" };
+                return Err(format!("Can't send {article} {name} to another task."));
+            }
+        };
+        if !address.is_null() {
+            outer.pop();
+        }
+        Ok(message)
+    }
+
+    /// Makes the value received out of the copy. A task's error, sent in
+    /// place of its result, fails instead.
+    pub(crate) fn into_object(self) -> Result<Object, String> {
+        Ok(match self {
+            Message::Nil => Object::Nil,
+            Message::Boolean(b) => Object::Boolean(b),
+            Message::Number(n) => Object::Number(n),
+            Message::String(s) => Object::String(s),
+            Message::Channel(channel) => Object::Channel(channel),
+            Message::List(elements) => {
+                let elements = elements.into_iter().map(Message::into_object).collect::<Result<_, _>>()?;
+                Object::List(Rc::new(RefCell::new(elements)))
+            }
+            Message::Map(entries) => {
+                let mut map = HashMap::new();
+                for (key, value) in entries {
+                    let key = MapKey::from_object(&key.into_object()?).expect("keys are sent as numbers or strings");
+                    map.insert(key, value.into_object()?);
+                }
+                Object::Map(Rc::new(RefCell::new(map)))
+            }
+            Message::Failed(message) => return Err(message),
+        })
+    }
+}
@@ -1,11 +1,14 @@
 use crate::error::Error;
 use crate::interpreter::Interpreter;
+use crate::token::Token;
 use crate::value::callable::Callable;
 use crate::value::function::Function;
 use crate::value::instance::Instance;
+use crate::value::interface::Interface;
+use crate::value::native::Native;
 use crate::value::object::Object;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::rc::Rc;
 
 /// The syntactic representation of Class — the runtime representation of the 
@@ -19,20 +22,99 @@ pub struct Class {
     pub superclass: Option<Rc<Class>>,
     
     /// Even though methods are owned by the class, they are still accessed 
-    /// through instance of that class.
-    pub methods: HashMap<String, Function>,
+    /// through instance of that class. An `extend` adds to them after the
+    /// class is made, so the tables can change through the shared class.
+    pub methods: RefCell<HashMap<String, Function>>,
+
+    /// Methods declared with `class`, which are accessed through the class
+    /// itself. Like other methods, they are inherited.
+    pub class_methods: RefCell<HashMap<String, Function>>,
+
+    /// Setters, by the property they set. They are kept apart from the other
+    /// methods, since a property can have both a getter and a setter.
+    pub setters: RefCell<HashMap<String, Function>>,
+
+    /// The interfaces the class was declared to implement. Its subclasses
+    /// implement them too.
+    pub interfaces: Vec<Rc<Interface>>,
 }
 
 impl Class {
-    pub fn new(name: String, superclass: Option<Rc<Class>>, methods: HashMap<String, Function>) -> Self {
-        Self { name, superclass, methods }
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Function>,
+        class_methods: HashMap<String, Function>,
+        setters: HashMap<String, Function>,
+        interfaces: Vec<Rc<Interface>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods: RefCell::new(methods),
+            class_methods: RefCell::new(class_methods),
+            setters: RefCell::new(setters),
+            interfaces,
+        }
+    }
+
+    /// The class of the errors a `catch` gets when a runtime error happens in
+    /// its `try`. The interpreter gives each error a `message`, the `line` it
+    /// happened on, and the `stack` of calls it happened in. Scripts can make
+    /// errors of their own with `Error(message)`, and extend it to tell them
+    /// apart with `is`, calling `super.init(message)` from their own init().
+    pub fn error() -> Self {
+        let init = Native { name: "init", arity: 1, function: error_init };
+        let methods = HashMap::from([("init".to_string(), Function::NativeMethod { native: init, this: None })]);
+        Self::new("Error".to_string(), None, methods, HashMap::new(), HashMap::new(), Vec::new())
+    }
+
+    pub fn find_setter(&self, name: &str) -> Option<Function> {
+        if let Some(setter) = self.setters.borrow().get(name) {
+            return Some(setter.clone());
+        }
+        self.superclass.as_ref().and_then(|superclass| superclass.find_setter(name))
+    }
+
+    /// Whether this is the given class, or a subclass of it.
+    pub fn inherits_from(&self, other: &Class) -> bool {
+        std::ptr::eq(self, other) || self.superclass.as_ref().is_some_and(|superclass| superclass.inherits_from(other))
+    }
+
+    /// Whether the class, or a superclass of it, was declared to implement
+    /// the interface.
+    pub fn implements(&self, interface: &Interface) -> bool {
+        self.interfaces.iter().any(|implemented| std::ptr::eq(implemented.as_ref(), interface))
+            || self.superclass.as_ref().is_some_and(|superclass| superclass.implements(interface))
+    }
+
+    pub fn find_class_method(&self, name: &str) -> Option<Function> {
+        if let Some(method) = self.class_methods.borrow().get(name) {
+            return Some(method.clone());
+        }
+        self.superclass.as_ref().and_then(|superclass| superclass.find_class_method(name))
+    }
+
+    /// The methods an instance of the class has, its own and inherited, by
+    /// name, with the number of arguments each takes.
+    pub fn method_arities(&self) -> BTreeMap<String, usize> {
+        let mut arities = self.superclass.as_ref().map(|superclass| superclass.method_arities()).unwrap_or_default();
+        arities.extend(self.methods.borrow().iter().map(|(name, method)| (name.clone(), method.arity())));
+        arities
+    }
+
+    /// Like method_arities(), for the methods called on the class itself.
+    pub fn class_method_arities(&self) -> BTreeMap<String, usize> {
+        let mut arities = self.superclass.as_ref().map(|superclass| superclass.class_method_arities()).unwrap_or_default();
+        arities.extend(self.class_methods.borrow().iter().map(|(name, method)| (name.clone(), method.arity())));
+        arities
     }
 
     pub fn find_method(&self, name: &str) -> Option<Function> {
         // If a method with the same name exists in both the subclass and the superclass, 
         // the subclass one takes precedence or overrides the superclass method. S
         // ort of like how variables in inner scopes shadow outer ones.
-        if let Some(method) = self.methods.get(name) {
+        if let Some(method) = self.methods.borrow().get(name) {
             return Some(method.clone())
         }
         
@@ -40,14 +122,16 @@ impl Class {
         // from an instance of the subclass. In other words, methods are inherited 
         // from the superclass. 
         if let Some(superclass) = &self.superclass {
-            return superclass.find_method(&name)
+            return superclass.find_method(name)
         }
         
         None
     }
 }
 
-impl Callable for Class {
+/// Classes are called through the Rc they are shared by, so that the
+/// instances they create can share it too.
+impl Callable for Rc<Class> {
     /// If there is an initializer, that method’s arity determines how many arguments 
     /// you must pass when you call the class itself. If you don’t have an initializer, 
     /// the arity is zero.
@@ -59,14 +143,26 @@ impl Callable for Class {
         }
     }
 
-    fn call(&self, interpreter: &mut Interpreter, args: Vec<Object>) -> Result<Object, Error> {
+    fn variadic(&self) -> bool {
+        self.find_method("init").is_some_and(|initializer| initializer.variadic())
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Object>, paren: &Token) -> Result<Object, Error> {
         // When we “call” a class, it instantiates a new Instance 
         // for the called class and returns it.
-        let instance = Instance::new(self.clone());
+        let instance = Instance::new(Rc::clone(self));
         let instance_object = Object::Instance(Rc::new(RefCell::new(instance)));
         if let Some(initializer) = self.find_method("init") {
-            initializer.bind(&instance_object).call(interpreter, args)?;
+            initializer.bind(&instance_object).call(interpreter, args, paren)?;
         }
         Ok(instance_object)
     }
-}
\ No newline at end of file
+}
+
+/// Error's init(): keeps the message on the new error.
+fn error_init(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    if let Object::Instance(error) = &args[0] {
+        error.borrow_mut().fields.insert("message".into(), args[1].clone());
+    }
+    Ok(Object::Nil)
+}
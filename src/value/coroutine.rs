@@ -0,0 +1,227 @@
+use crate::error::{self, Error};
+use crate::value::function::Function;
+use crate::value::object::Object;
+use std::cell::{OnceCell, RefCell};
+use std::fmt::Debug;
+use std::panic;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// How much stack a coroutine's thread gets, the same as a main thread.
+const STACK_SIZE: usize = 8 << 20;
+
+thread_local! {
+    /// On a coroutine's thread, the way back to whatever resumed it.
+    static LINK: RefCell<Option<Link>> = const { RefCell::new(None) };
+}
+
+/// A function that can stop partway through, hand a value to the code that
+/// resumed it, and later carry on from where it stopped, like coroutines in
+/// Lua:
+///
+/// ```text
+/// fun count(limit) {
+///     for (var i = 1; i < limit; i = i + 1) yield(i);
+///     return "done";
+/// }
+/// var counter = coroutine(count);
+/// print resume(counter, 3); // 1, the first resume passes the argument
+/// print resume(counter, nil); // 2
+/// print resume(counter, nil); // done
+/// ```
+///
+/// A coroutine can yield from anywhere in the calls it makes, not just from
+/// its own function, so while it is suspended all of those calls have to be
+/// kept, halfway through. The interpreter keeps its calls on Rust's stack, so
+/// each coroutine runs on a thread of its own, whose stack they are kept on.
+///
+/// The threads take turns. Resuming a coroutine hands control to its thread
+/// and waits for it to yield or finish; yielding hands control back and waits
+/// to be resumed. Only one thread ever runs, so though they share the
+/// interpreter and its values, nothing is touched by two threads at once, and
+/// scripts run just as they would on a single thread.
+pub struct Coroutine {
+    function: Function,
+    state: RefCell<State>,
+
+    /// Which interpreter started it, and where that was, once it has been
+    /// started. See resume.
+    owner: OnceCell<Owner>,
+}
+
+/// The id of an interpreter, with its address.
+pub(crate) type Owner = (usize, usize);
+
+enum State {
+    /// Not resumed yet, so it has no thread.
+    Created,
+
+    /// Running, or waiting on a coroutine it resumed in turn.
+    Running,
+
+    /// Waiting in yield() to be resumed.
+    Suspended(Worker),
+
+    /// Its function has returned, or failed.
+    Done,
+}
+
+/// Why a coroutine's thread gave control back.
+pub(crate) enum Stop {
+    Yielded(Object),
+    Finished(Result<Object, Error>),
+}
+
+impl Coroutine {
+    pub fn new(function: Function) -> Self {
+        Self { function, state: RefCell::new(State::Created), owner: OnceCell::new() }
+    }
+
+    /// The function the coroutine runs.
+    pub fn function(&self) -> &Function {
+        &self.function
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(*self.state.borrow(), State::Done)
+    }
+
+    /// Runs the coroutine until it yields or finishes. The first time, its
+    /// thread is started and runs `start` with the value; after that, the
+    /// value is what the yield it is suspended in returns.
+    ///
+    /// Its thread keeps running in the interpreter that first resumed it,
+    /// so resuming it from any other fails.
+    pub(crate) fn resume<F>(&self, owner: Owner, value: Object, start: F) -> Result<Stop, String>
+    where
+        F: FnOnce(Object) -> Result<Object, Error> + 'static,
+    {
+        if *self.owner.get_or_init(|| owner) != owner {
+            return Err("Can't resume a coroutine outside the interpreter that started it.".into());
+        }
+        let state = std::mem::replace(&mut *self.state.borrow_mut(), State::Running);
+        let worker = match state {
+            State::Created => Worker::spawn(Box::new(start)),
+            State::Suspended(worker) => worker,
+            State::Running => return Err("Can't resume a coroutine that is already running.".into()),
+            State::Done => {
+                *self.state.borrow_mut() = State::Done;
+                return Err("Can't resume a coroutine that has finished.".into());
+            }
+        };
+        let stop = worker.run(value);
+        *self.state.borrow_mut() = match stop {
+            Stop::Yielded(_) => State::Suspended(worker),
+            Stop::Finished(_) => State::Done,
+        };
+        Ok(stop)
+    }
+}
+
+impl Debug for Coroutine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Coroutine").field("function", &self.function).field("done", &self.is_done()).finish()
+    }
+}
+
+/// Hands a value back to whatever resumed the coroutine running on this
+/// thread, and waits to be resumed. Returns the value it is resumed with, or
+/// None if this isn't a coroutine's thread.
+///
+/// A coroutine that is dropped while suspended will never be resumed. Its
+/// thread is then unwound from here, like a panic would, but without
+/// running any more of the script: no `finally`, no handlers.
+pub(crate) fn suspend(value: Object) -> Option<Object> {
+    LINK.with(|link| {
+        let link = link.borrow();
+        let link = link.as_ref()?;
+        link.stop.send(Handoff(Stop::Yielded(value))).expect("the resumer waits for the coroutine");
+        match link.resume.recv() {
+            Ok(value) => Some(value.into_inner()),
+            Err(_) => panic::resume_unwind(Box::new(Abandoned)),
+        }
+    })
+}
+
+/// The thread a coroutine runs on, and the channels it takes turns over.
+struct Worker {
+    resume: Option<Sender<Handoff<Object>>>,
+    stop: Receiver<Handoff<Stop>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// The two ends of a coroutine's thread that yield() uses.
+struct Link {
+    resume: Receiver<Handoff<Object>>,
+    stop: Sender<Handoff<Stop>>,
+}
+
+/// What a coroutine's thread unwinds with once it is abandoned.
+struct Abandoned;
+
+type Start = Box<dyn FnOnce(Object) -> Result<Object, Error>>;
+
+impl Worker {
+    fn spawn(start: Start) -> Self {
+        let (resume, resumed) = channel::<Handoff<Object>>();
+        let (stopped, stop) = channel::<Handoff<Stop>>();
+        let start = Handoff(start);
+        // What the coroutine reports, it reports for the program resuming it.
+        let reports = error::shared();
+        let thread = thread::Builder::new()
+            .name("coroutine".into())
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                error::share(reports);
+                let start = start.into_inner();
+                let Ok(value) = resumed.recv() else { return };
+                LINK.with(|link| *link.borrow_mut() = Some(Link { resume: resumed, stop: stopped.clone() }));
+                let result = start(value.into_inner());
+                // Nothing is left to wait for this once the coroutine is dropped.
+                let _ = stopped.send(Handoff(Stop::Finished(result)));
+            })
+            .expect("failed to start a thread for a coroutine");
+        Self { resume: Some(resume), stop, thread: Some(thread) }
+    }
+
+    /// Hands control to the thread, and waits until it gives it back.
+    fn run(&self, value: Object) -> Stop {
+        let resume = self.resume.as_ref().expect("only dropped workers hang up");
+        resume.send(Handoff(value)).expect("a suspended coroutine waits to be resumed");
+        match self.stop.recv() {
+            Ok(stop) => stop.into_inner(),
+            Err(_) => panic!("a coroutine's thread stopped without finishing"),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // Hanging up wakes a suspended thread, which then unwinds (see
+        // suspend), and a finished one is on its way out. Either way, the
+        // values it holds are dropped before this returns, while nothing
+        // else runs.
+        self.resume.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Something moved between a coroutine's thread and the thread resuming it.
+/// Objects are built on `Rc`, which isn't safe to send between threads that
+/// run at the same time. These threads never do: each hands over control
+/// along with the value, and waits until it is handed back. See
+/// task::Detached for the other place values cross threads, and why
+/// nowhere else may.
+struct Handoff<T>(T);
+
+unsafe impl<T> Send for Handoff<T> {}
+
+impl<T> Handoff<T> {
+    /// Taking the value by a method moves the whole Handoff into a closure
+    /// that uses it, and not just the field, which isn't Send.
+    fn into_inner(self) -> T {
+        self.0
+    }
+}
@@ -5,13 +5,21 @@ use crate::stmt::FunctionDeclaration;
 use crate::value::callable::Callable;
 use crate::value::object::Object;
 use crate::value::object::Object::Nil;
+use crate::token::Token;
+use crate::value::native::Native;
+use std::cell::RefCell;
 use std::rc::Rc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 /// The runtime representation of a function statement 
 #[derive(Clone, Debug)]
 pub enum Function {
-    Clock,
+    Native(Native),
+
+    /// A method of a built-in class, written in Rust. Once bound, the
+    /// instance it was called on is passed ahead of the arguments, which the
+    /// native's arity doesn't count.
+    NativeMethod { native: Native, this: Option<Box<Object>> },
+
     UserDefined {
         /// Is this function an init. We can’t simply see if the name of the function 
         /// is “init” because the user could have defined a function with that name.
@@ -38,11 +46,16 @@ impl Function {
     
     pub fn name(&self) -> String {
         match self {
-            Function::Clock => "clock".to_string(),
+            Function::Native(native) | Function::NativeMethod { native, .. } => native.name.to_string(),
             Function::UserDefined { declaration, ..} => declaration.name.lexeme.clone()
         }
     }
     
+    /// Whether this is a getter, run when its property is accessed.
+    pub fn is_getter(&self) -> bool {
+        matches!(self, Function::UserDefined { declaration, .. } if declaration.getter)
+    }
+
     pub fn bind(&self, instance_object: &Object) -> Function {
         match self {
             Function::UserDefined {declaration, closure, is_initializer } => {
@@ -54,6 +67,9 @@ impl Function {
                 scope.borrow_mut().define("this".into(), instance_object.clone()); 
                 Function::new(declaration.clone(), scope, *is_initializer)
             }
+            Function::NativeMethod { native, .. } => {
+                Function::NativeMethod { native: native.clone(), this: Some(Box::new(instance_object.clone())) }
+            }
             _ => self.clone()
         }
     }
@@ -62,19 +78,28 @@ impl Function {
 impl Callable for Function {
     fn arity(&self) -> usize {
         match self {
-            Function::Clock => 0,
-            Function::UserDefined { declaration, ..} => declaration.params.len()
+            Function::Native(native) | Function::NativeMethod { native, .. } => native.arity,
+            Function::UserDefined { declaration, ..} => declaration.params.len() - usize::from(declaration.variadic)
         }
     }
 
-    fn call(&self, interpreter: &mut Interpreter, args: Vec<Object>) -> Result<Object, Error> {
+    fn variadic(&self) -> bool {
+        matches!(self, Function::UserDefined { declaration, .. } if declaration.variadic)
+    }
+
+    fn call(&self, interpreter: &mut Interpreter, args: Vec<Object>, paren: &Token) -> Result<Object, Error> {
         match self {
-            Function::Clock => {
-                let timestamp_f64 = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs_f64();
-                Ok(Object::Number(timestamp_f64))
+            Function::Native(native) => {
+                (native.function)(interpreter, &args).map_err(|message| {
+                    interpreter.take_native_error().unwrap_or_else(|| Error::RuntimeError(paren.clone(), message))
+                })
+            }
+            Function::NativeMethod { native, this } => {
+                let this = this.as_deref().cloned().unwrap_or(Nil);
+                let args: Vec<Object> = std::iter::once(this).chain(args).collect();
+                (native.function)(interpreter, &args).map_err(|message| {
+                    interpreter.take_native_error().unwrap_or_else(|| Error::RuntimeError(paren.clone(), message))
+                })
             }
             Function::UserDefined {declaration, closure, is_initializer } => {
                 // We create a new environment at each call. We will execute the body of the function
@@ -82,8 +107,13 @@ impl Callable for Function {
                 // was the environment where the function was being called. Now, we teleport from
                 // there inside the new parameter space we’ve created for the function.
                 let scope = Environment::new(closure.clone(), &self.name());
-                for (i, param) in declaration.params.iter().enumerate() {
-                    scope.borrow_mut().define(param.lexeme.clone(), args[i].clone());
+                let mut args = args;
+                if declaration.variadic {
+                    let rest = args.split_off(self.arity());
+                    args.push(Object::List(Rc::new(RefCell::new(rest))));
+                }
+                for (param, arg) in declaration.params.iter().zip(args) {
+                    scope.borrow_mut().define(param.lexeme.clone(), arg);
                 }
 
                 match interpreter.execute_block(&declaration.body, scope) {
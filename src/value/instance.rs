@@ -7,11 +7,14 @@ use std::collections::HashMap;
 use std::fmt::Display;
 use std::rc::Rc;
 
+/// Instances are shared: every variable holding one refers to the same state.
+pub type MutableInstance = Rc<RefCell<Instance>>;
+
 /// The runtime representation of an instance of a Lox class.
 /// Where the class stores behavior, an instance stores state.
 #[derive(Clone, Debug)]
 pub struct Instance {
-    pub klass: Class,
+    pub klass: Rc<Class>,
 
     /// A bit of state stored on the instance
     pub fields: HashMap<String, Object>,
@@ -24,28 +27,29 @@ impl Display for Instance {
 }
 
 impl Instance {
-    pub fn new(klass: Class) -> Self {
+    pub fn new(klass: Rc<Class>) -> Self {
         Self { klass, fields: HashMap::new() }
     }
 
     /// Returns the property of this name. This is where the distinction between
     /// “field” and “property” becomes meaningful. When accessing a property, we
     /// might get a field, or we could hit a method defined on the instance’s class.
-    pub fn get(&self, token: &Token) -> Result<Object, Error> {
+    /// A method is bound to the instance it was accessed from, the shared one,
+    /// so that what it does to `this` is done to that instance.
+    pub fn get(instance: &MutableInstance, token: &Token) -> Result<Object, Error> {
         let name = &token.lexeme;
-        if let Some(value) = self.fields.get(name) {
+        if let Some(value) = instance.borrow().fields.get(name) {
             return Ok(value.clone());
         }
         
-        if let Some(method) = self.klass.find_method(name) {
-            // Capture the environment for 'this'  
-            let instance_object = Object::Instance(Rc::new(RefCell::new(self.clone())));
-            return Ok(Object::Function(method.bind(&instance_object)));
+        let method = instance.borrow().klass.find_method(name);
+        if let Some(method) = method {
+            return Ok(Object::Function(method.bind(&Object::Instance(instance.clone()))));
         }
 
         // We could silently return some dummy value like nil, but that behavior masks bugs
         // more often than it does anything useful. Instead, we’ll make it a runtime error.
-        Err(Error::RuntimeError(token.clone(), format!("Undefined property '{}''.", name)))
+        Err(Error::RuntimeError(token.clone(), format!("'{}' instance has no property '{}'.", instance.borrow().klass.name, name)))
     }
 
     pub fn set(&mut self, token: &Token, value: Object) {
@@ -0,0 +1,55 @@
+use crate::value::callable::Callable;
+use crate::value::class::Class;
+use std::fmt::Display;
+
+/// The runtime representation of an interface declaration: the methods a
+/// class needs to have to implement it. An interface has no behavior of its
+/// own. It is only checked against the classes that name it, when they are
+/// declared, and can be the right side of `is`.
+#[derive(Debug)]
+pub struct Interface {
+    pub name: String,
+
+    /// The name of each method, with how many arguments it takes, in the
+    /// order they were declared.
+    pub methods: Vec<(String, usize)>,
+}
+
+impl Display for Interface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<interface {}>", self.name)
+    }
+}
+
+impl Interface {
+    pub fn new(name: String, methods: Vec<(String, usize)>) -> Self {
+        Self { name, methods }
+    }
+
+    /// Checks that a class has every method of the interface, its own or
+    /// inherited, that can be called with as many arguments. Fails with the
+    /// message for the first that it doesn't.
+    pub fn check(&self, class: &Class) -> Result<(), String> {
+        for (name, arity) in &self.methods {
+            let Some(method) = class.find_method(name) else {
+                return Err(format!(
+                    "Class '{}' doesn't implement '{name}' from interface '{}'.",
+                    class.name, self.name,
+                ));
+            };
+            // A method with a rest parameter takes any more than its arity.
+            let fits = match method.variadic() {
+                true => method.arity() <= *arity,
+                false => method.arity() == *arity,
+            };
+            if !fits {
+                let arguments = if *arity == 1 { "argument" } else { "arguments" };
+                return Err(format!(
+                    "Method '{name}' of class '{}' must take {arity} {arguments} to implement interface '{}'.",
+                    class.name, self.name,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
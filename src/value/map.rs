@@ -0,0 +1,103 @@
+use crate::value::instance::MutableInstance;
+use crate::value::object::Object;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+/// Shared, mutable storage for the entries of a map.
+pub type MutableMap = Rc<RefCell<HashMap<MapKey, Object>>>;
+
+/// The values that can be used as map keys. Strings and numbers are, because
+/// they are compared by value; a list used as a key would have to be compared
+/// by identity, which is rarely what you want. An instance can be a key if
+/// its class says how to compare it by value, with a hash() method.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    /// Numbers are stored by their bits, after folding -0 into 0 so that the
+    /// two zeros, which are equal, find the same entry.
+    Number(u64),
+    String(String),
+    Instance(InstanceKey),
+}
+
+/// An instance used as a map key, along with what its hash() method returned
+/// for it. Keys are only told apart by identity here: finding the key an
+/// instance stands for, which may be another instance its equals() method
+/// says it is equal to, is the interpreter's job (see Interpreter::map_key).
+/// The hash is what the map's buckets go by, so only keys with the same hash
+/// have to be compared.
+#[derive(Clone, Debug)]
+pub struct InstanceKey {
+    pub hash: Box<MapKey>,
+    pub instance: MutableInstance,
+}
+
+impl PartialEq for InstanceKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && Rc::ptr_eq(&self.instance, &other.instance)
+    }
+}
+
+impl Eq for InstanceKey {}
+
+impl Hash for InstanceKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+impl MapKey {
+    /// Converts a value into a key, or returns None if the value can't be one.
+    /// Instances can't, here: working out their key means calling their
+    /// hash() method, which is the interpreter's job (see Interpreter::map_key).
+    pub fn from_object(value: &Object) -> Option<MapKey> {
+        match value {
+            Object::Number(n) if n.is_nan() => None,
+            Object::Number(n) => Some(MapKey::Number((n + 0.0).to_bits())),
+            Object::String(s) => Some(MapKey::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn to_object(&self) -> Object {
+        match self {
+            MapKey::Number(bits) => Object::Number(f64::from_bits(*bits)),
+            MapKey::String(s) => Object::String(s.clone()),
+            MapKey::Instance(key) => Object::Instance(key.instance.clone()),
+        }
+    }
+}
+
+impl Display for MapKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_object())
+    }
+}
+
+/// The keys of a map in a stable order: numbers first, smallest to largest,
+/// then strings alphabetically, then instances in the order of their hashes.
+/// Hash maps have no order of their own, and printing one shouldn't give
+/// different output from run to run. Only instances whose hashes are the same
+/// are left in no particular order.
+pub fn sorted_keys(map: &HashMap<MapKey, Object>) -> Vec<MapKey> {
+    let mut keys: Vec<MapKey> = map.keys().cloned().collect();
+    keys.sort_by(compare);
+    keys
+}
+
+fn compare(a: &MapKey, b: &MapKey) -> Ordering {
+    let rank = |key: &MapKey| match key {
+        MapKey::Number(_) => 0,
+        MapKey::String(_) => 1,
+        MapKey::Instance(_) => 2,
+    };
+    match (a, b) {
+        (MapKey::Number(a), MapKey::Number(b)) => f64::from_bits(*a).total_cmp(&f64::from_bits(*b)),
+        (MapKey::String(a), MapKey::String(b)) => a.cmp(b),
+        (MapKey::Instance(a), MapKey::Instance(b)) => compare(&a.hash, &b.hash),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
@@ -1,6 +1,12 @@
+pub mod channel;
 pub mod class;
+pub mod coroutine;
 pub mod instance;
+pub mod interface;
 pub mod function;
+pub mod map;
+pub mod module;
+pub mod native;
 pub mod object;
 mod callable;
 
@@ -0,0 +1,40 @@
+use crate::environment::MutableEnvironment;
+use crate::error::Error;
+use crate::token::Token;
+use crate::value::object::Object;
+use std::fmt::Display;
+
+/// The runtime representation of a module imported with `import "..." as name;`.
+/// Instead of dumping its declarations into the global scope, the module keeps
+/// them in its own environment and exposes them as properties.
+#[derive(Debug)]
+pub struct Module {
+    /// The name the module was imported as.
+    pub name: String,
+
+    /// The environment the module’s top-level code ran in.
+    pub environment: MutableEnvironment,
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<module {}>", self.name)
+    }
+}
+
+impl Module {
+    pub fn new(name: String, environment: MutableEnvironment) -> Self {
+        Self { name, environment }
+    }
+
+    /// Returns a top-level declaration of the module.
+    pub fn get(&self, token: &Token) -> Result<Object, Error> {
+        match self.environment.borrow().get_own(&token.lexeme) {
+            Some(value) => Ok(value),
+            None => Err(Error::RuntimeError(
+                token.clone(),
+                format!("Undefined property '{}' in module '{}'.", token.lexeme, self.name),
+            )),
+        }
+    }
+}
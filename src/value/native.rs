@@ -0,0 +1,477 @@
+use crate::environment::MutableEnvironment;
+use crate::interpreter::Interpreter;
+use crate::policy::Capability;
+use crate::value::callable::Callable;
+use crate::value::channel::{Channel, Message};
+use crate::value::coroutine::Coroutine;
+use crate::value::map::{self, MapKey};
+use crate::value::object::{self, Object};
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The Rust function behind a native. It gets the already-checked arguments
+/// and returns either a value or the message for a runtime error, which the
+/// interpreter reports at the call site.
+pub type NativeFn = fn(&mut Interpreter, &[Object]) -> Result<Object, String>;
+
+/// A function implemented in Rust instead of Lox, and exposed to scripts as
+/// a global. These are the primitives that user code can't write for itself.
+#[derive(Clone)]
+pub struct Native {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl Debug for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+/// The natives an interpreter defines in its globals. Different runs can
+/// be given different sets: a sandbox might only get the ones that compute
+/// something from their arguments, and a test can swap in its own `clock`
+/// that always gives the same time. The set belongs to the interpreter, so
+/// nothing global has to change for that.
+#[derive(Clone, Debug)]
+pub struct Natives {
+    natives: Vec<Native>,
+}
+
+impl Default for Natives {
+    fn default() -> Self {
+        Self::standard()
+    }
+}
+
+impl Natives {
+    /// No natives at all.
+    pub fn empty() -> Self {
+        Self { natives: Vec::new() }
+    }
+
+    /// Every native there is. This is what interpreters get unless told otherwise.
+    pub fn standard() -> Self {
+        Self { natives: standard_library() }
+    }
+
+    /// Only the natives that work out their result from their arguments,
+    /// with no access to the clock, the output, or the interpreter itself.
+    pub fn pure() -> Self {
+        const PURE: [&str; 8] = ["len", "push", "pop", "keys", "has", "slice", "chars", "codePointAt"];
+        Self { natives: standard_library().into_iter().filter(|native| PURE.contains(&native.name)).collect() }
+    }
+
+    /// Adds a native, in place of any with the same name.
+    pub fn with(mut self, native: Native) -> Self {
+        self = self.without(native.name);
+        self.natives.push(native);
+        self
+    }
+
+    /// Leaves out the native with the given name, if there is one.
+    pub fn without(mut self, name: &str) -> Self {
+        self.natives.retain(|native| native.name != name);
+        self
+    }
+
+    /// Looks up a set by the name the command line uses for it.
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "standard" => Some(Self::standard()),
+            "pure" => Some(Self::pure()),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Native> {
+        self.natives.iter()
+    }
+}
+
+/// The natives defined in a global environment, unless the interpreter is
+/// given other ones.
+fn standard_library() -> Vec<Native> {
+    vec![
+        Native { name: "clock", arity: 0, function: clock },
+        Native { name: "len", arity: 1, function: len },
+        Native { name: "push", arity: 2, function: push },
+        Native { name: "pop", arity: 1, function: pop },
+        Native { name: "keys", arity: 1, function: keys },
+        Native { name: "sort", arity: 2, function: sort },
+        Native { name: "has", arity: 2, function: has },
+        Native { name: "deepEquals", arity: 2, function: deep_equals },
+        Native { name: "clone", arity: 1, function: clone },
+        Native { name: "deepClone", arity: 1, function: deep_clone },
+        Native { name: "slice", arity: 3, function: slice },
+        Native { name: "chars", arity: 1, function: chars },
+        Native { name: "codePointAt", arity: 2, function: code_point_at },
+        Native { name: "stackTrace", arity: 0, function: stack_trace },
+        Native { name: "callerName", arity: 0, function: caller_name },
+        Native { name: "atExit", arity: 1, function: at_exit },
+        Native { name: "setTimeout", arity: 2, function: set_timeout },
+        Native { name: "clearTimeout", arity: 1, function: clear_timeout },
+        Native { name: "flush", arity: 0, function: flush },
+        Native { name: "eprintln", arity: 1, function: eprintln },
+        Native { name: "environment", arity: 0, function: environment },
+        Native { name: "eval", arity: 1, function: eval },
+        Native { name: "evalIn", arity: 2, function: eval_in },
+        Native { name: "coroutine", arity: 1, function: coroutine },
+        Native { name: "resume", arity: 2, function: resume },
+        Native { name: "yield", arity: 1, function: yield_value },
+        Native { name: "isDone", arity: 1, function: is_done },
+        Native { name: "spawn", arity: 2, function: spawn },
+        Native { name: "chanNew", arity: 0, function: chan_new },
+        Native { name: "send", arity: 2, function: send },
+        Native { name: "recv", arity: 1, function: recv },
+    ]
+}
+
+/// Seconds since the Unix epoch, for timing code.
+fn clock(_: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs_f64();
+    Ok(Object::Number(timestamp))
+}
+
+/// The number of elements in a list, entries in a map, or characters in a string.
+fn len(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::List(list) => Ok(Object::Number(list.borrow().len() as f64)),
+        Object::Map(map) => Ok(Object::Number(map.borrow().len() as f64)),
+        Object::String(s) => Ok(Object::Number(s.chars().count() as f64)),
+        _ => Err("Can only take the length of lists, maps and strings.".into()),
+    }
+}
+
+/// Appends a value to the end of a list.
+fn push(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::List(list) => {
+            list.borrow_mut().push(args[1].clone());
+            Ok(Object::Nil)
+        }
+        _ => Err("Can only push onto a list.".into()),
+    }
+}
+
+/// Removes the last value from a list and returns it.
+fn pop(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::List(list) => list.borrow_mut().pop().ok_or_else(|| "Can't pop from an empty list.".into()),
+        _ => Err("Can only pop from a list.".into()),
+    }
+}
+
+/// Sorts a list in place, calling the comparator with two of its elements to
+/// find out which goes first: it returns a negative number if the first one
+/// does, a positive one if the second does, and 0 if either can. Elements it
+/// can't tell apart keep the order they were in.
+fn sort(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::List(list) = &args[0] else {
+        return Err("Can only sort a list.".into());
+    };
+    let comparator = &args[1];
+    if !matches!(comparator, Object::Function(_) | Object::Class(_)) {
+        return Err(format!("Expected a function to compare with but got {}.", comparator.type_name()));
+    }
+    // The comparator can change the list while it is being sorted, so a copy
+    // is sorted, and put in its place at the end.
+    let elements = list.borrow().clone();
+    let sorted = merge_sort(elements, &mut |first, second| {
+        match interpreter.call_from_native(comparator, vec![first.clone(), second.clone()])? {
+            Object::Number(order) => Ok(order > 0.0),
+            other => Err(format!("The comparator must return a number but returned {}.", other.type_name())),
+        }
+    })?;
+    *list.borrow_mut() = sorted;
+    Ok(Object::Nil)
+}
+
+/// Sorts with a function telling whether the first of two elements goes
+/// after the second. The function is the script's, so it may fail, or not
+/// order the elements consistently; this stops at the first failure, and
+/// finishes whatever order it is given, which slice::sort_by doesn't promise.
+fn merge_sort<F>(mut elements: Vec<Object>, after: &mut F) -> Result<Vec<Object>, String>
+where
+    F: FnMut(&Object, &Object) -> Result<bool, String>,
+{
+    if elements.len() < 2 {
+        return Ok(elements);
+    }
+    let second_half = elements.split_off(elements.len() / 2);
+    let mut first = merge_sort(elements, after)?.into_iter().peekable();
+    let mut second = merge_sort(second_half, after)?.into_iter().peekable();
+    let mut merged = Vec::with_capacity(first.len() + second.len());
+    while let (Some(a), Some(b)) = (first.peek(), second.peek()) {
+        // Taking from the first half when the two are tied keeps the sort stable.
+        let next = if after(a, b)? { second.next() } else { first.next() };
+        merged.extend(next);
+    }
+    merged.extend(first);
+    merged.extend(second);
+    Ok(merged)
+}
+
+/// A new list holding the keys of a map, in the order they are printed in.
+fn keys(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::Map(map) => {
+            let keys = map::sorted_keys(&map.borrow()).iter().map(MapKey::to_object).collect();
+            Ok(Object::List(Rc::new(RefCell::new(keys))))
+        }
+        _ => Err("Can only take the keys of a map.".into()),
+    }
+}
+
+/// Whether a map has an entry for a key. Reading a missing key is an error,
+/// so this is how a script checks first.
+fn has(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::Map(map) => Ok(Object::Boolean(
+            interpreter.native_map_key(map, &args[1])?.is_some_and(|key| map.borrow().contains_key(&key)),
+        )),
+        _ => Err("Can only look up keys in a map.".into()),
+    }
+}
+
+/// The part of a list or string from one index up to, but not including,
+/// another, as a new list or string. Strings are sliced by character, like
+/// they are indexed.
+fn slice(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let (Object::Number(start), Object::Number(end)) = (&args[1], &args[2]) else {
+        return Err("Slice bounds must be numbers.".into());
+    };
+    let bounds = |length: usize, kind: &str| {
+        if start.fract() != 0.0 || end.fract() != 0.0 {
+            return Err("Slice bounds must be integers.".to_string());
+        }
+        if *start < 0.0 || start > end || *end > length as f64 {
+            return Err(format!("Slice {start}..{end} is out of bounds for a {kind} of length {length}."));
+        }
+        Ok(*start as usize..*end as usize)
+    };
+    match &args[0] {
+        Object::List(list) => {
+            let list = list.borrow();
+            let range = bounds(list.len(), "list")?;
+            Ok(Object::List(Rc::new(RefCell::new(list[range].to_vec()))))
+        }
+        Object::String(string) => {
+            let range = bounds(string.chars().count(), "string")?;
+            Ok(Object::String(string.chars().skip(range.start).take(range.len()).collect()))
+        }
+        _ => Err("Can only slice lists and strings.".into()),
+    }
+}
+
+/// A new list of the characters of a string, each as a string of its own.
+fn chars(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::String(string) => {
+            let chars = string.chars().map(|c| Object::String(c.to_string())).collect();
+            Ok(Object::List(Rc::new(RefCell::new(chars))))
+        }
+        _ => Err("Can only take the characters of a string.".into()),
+    }
+}
+
+/// The Unicode code point of the character at an index in a string.
+fn code_point_at(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match (&args[0], &args[1]) {
+        (Object::String(string), Object::Number(index)) => {
+            let position = object::position(*index, string.chars().count(), "string")?;
+            Ok(Object::Number(string.chars().nth(position).unwrap() as u32 as f64))
+        }
+        (Object::String(_), _) => Err("Index must be a number.".into()),
+        _ => Err("Can only take code points of a string.".into()),
+    }
+}
+
+/// The calls in progress as a list of strings, innermost first, so scripts
+/// can report where something went wrong.
+fn stack_trace(interpreter: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+    let trace = interpreter.stack_trace().into_iter().map(Object::String).collect();
+    Ok(Object::List(Rc::new(RefCell::new(trace))))
+}
+
+/// The name of the function that called the current one.
+fn caller_name(interpreter: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+    Ok(interpreter.caller_name().into())
+}
+
+/// Registers a function for the interpreter to call once the program is over,
+/// whether it finished or stopped on a runtime error.
+fn at_exit(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let arity = match &args[0] {
+        Object::Function(function) => function.arity(),
+        Object::Class(class) => class.arity(),
+        _ => return Err("Can only register functions to run at exit.".into()),
+    };
+    if arity != 0 {
+        return Err(format!("Exit functions take no arguments, but this one expects {arity}."));
+    }
+    interpreter.at_exit(args[0].clone());
+    Ok(Object::Nil)
+}
+
+/// Schedules a function to be called after a delay in milliseconds, once
+/// the program's top level is done, and gives the timer's id. See
+/// Interpreter::run_event_loop.
+fn set_timeout(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let arity = match &args[0] {
+        Object::Function(function) => function.arity(),
+        Object::Class(class) => class.arity(),
+        _ => return Err("Can only schedule functions.".into()),
+    };
+    if arity != 0 {
+        return Err(format!("Timer functions take no arguments, but this one expects {arity}."));
+    }
+    let not_a_delay = || format!("Expected a delay in milliseconds but got {}.", args[1]);
+    let delay = match args[1] {
+        Object::Number(ms) => Duration::try_from_secs_f64(ms / 1000.0).map_err(|_| not_a_delay())?,
+        _ => return Err(not_a_delay()),
+    };
+    let id = interpreter.set_timeout(args[0].clone(), delay).ok_or_else(not_a_delay)?;
+    Ok(Object::Number(id as f64))
+}
+
+/// Cancels a function scheduled with setTimeout(), given its id. Returns
+/// whether it was still to be called.
+fn clear_timeout(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match args[0] {
+        Object::Number(id) => Ok(Object::Boolean(interpreter.clear_timeout(id as usize))),
+        _ => Err(format!("Expected a timer id but got {}.", args[0].type_name())),
+    }
+}
+
+/// Whether two values are equal in structure, unlike `==`, which compares
+/// lists, maps and instances by identity. See Object::deep_equals.
+fn deep_equals(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    Ok(Object::Boolean(args[0].deep_equals(&args[1])))
+}
+
+/// A new list, map or instance holding what the one given does, so the two
+/// can change apart. See Object::shallow_clone.
+fn clone(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    Ok(args[0].shallow_clone())
+}
+
+/// Like clone(), but copies every list, map and instance inside as well. See
+/// Object::deep_clone.
+fn deep_clone(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    Ok(args[0].deep_clone())
+}
+
+/// Writes out what the program has printed so far, rather than whenever the
+/// output buffer fills up or the program ends.
+fn flush(interpreter: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+    interpreter.flush();
+    Ok(Object::Nil)
+}
+
+/// Prints a value like `print` does, but to stderr, keeping diagnostics
+/// apart from the program's output.
+fn eprintln(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    interpreter.write_error_line(&args[0].to_string());
+    Ok(Object::Nil)
+}
+
+/// The environment of the code calling, as a value, for evalIn().
+fn environment(interpreter: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+    Ok(Object::Environment(interpreter.current_environment()))
+}
+
+/// Runs a string of code where it is called from. See Interpreter::eval_in.
+fn eval(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let environment = interpreter.current_environment();
+    eval_code(interpreter, environment, &args[0])
+}
+
+/// Runs a string of code in an environment captured with environment().
+fn eval_in(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::Environment(environment) = &args[0] else {
+        return Err(format!("Expected an environment but got {}.", args[0].type_name()));
+    };
+    eval_code(interpreter, environment.clone(), &args[1])
+}
+
+fn eval_code(interpreter: &mut Interpreter, environment: MutableEnvironment, source: &Object) -> Result<Object, String> {
+    interpreter.allow(Capability::Eval)?;
+    let Object::String(source) = source else {
+        return Err(format!("Expected a string of code but got {}.", source.type_name()));
+    };
+    interpreter.eval_in(environment, source)
+}
+
+/// A coroutine that runs the given function, which takes one argument at
+/// most. See Coroutine.
+fn coroutine(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::Function(function) = &args[0] else {
+        return Err(format!("Can only make a coroutine from a function, not a {}.", args[0].type_name()));
+    };
+    if function.arity() > 1 {
+        return Err(format!("A coroutine's function takes at most 1 argument, but this one expects {}.", function.arity()));
+    }
+    Ok(Object::Coroutine(Rc::new(Coroutine::new(function.clone()))))
+}
+
+/// Runs a coroutine until it yields or returns, and gives the value it
+/// yielded or returned. The value passed in is the function's argument the
+/// first time, and what yield() returns after that.
+fn resume(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::Coroutine(coroutine) = &args[0] else {
+        return Err(format!("Expected a coroutine but got {}.", args[0].type_name()));
+    };
+    interpreter.resume(coroutine, args[1].clone())
+}
+
+/// Suspends the coroutine running, handing a value to whatever resumed it.
+fn yield_value(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    interpreter.yield_value(args[0].clone())
+}
+
+/// Whether a coroutine's function has returned, so it can't be resumed.
+fn is_done(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    match &args[0] {
+        Object::Coroutine(coroutine) => Ok(Object::Boolean(coroutine.is_done())),
+        _ => Err(format!("Expected a coroutine but got {}.", args[0].type_name())),
+    }
+}
+
+/// Runs a function, which takes one argument at most, on a thread of its
+/// own, and gives the channel its result will come on. See Task.
+fn spawn(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::Function(function) = &args[0] else {
+        return Err(format!("Can only spawn a function, not a {}.", args[0].type_name()));
+    };
+    if function.arity() > 1 {
+        return Err(format!("A task's function takes at most 1 argument, but this one expects {}.", function.arity()));
+    }
+    interpreter.spawn(function, args[1].clone())
+}
+
+/// A new, empty channel. See Channel.
+fn chan_new(_: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+    Ok(Object::Channel(Arc::new(Channel::new())))
+}
+
+/// Sends a copy of a value on a channel.
+fn send(_: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::Channel(channel) = &args[0] else {
+        return Err(format!("Expected a channel but got {}.", args[0].type_name()));
+    };
+    channel.send(Message::from_object(&args[1])?);
+    Ok(Object::Nil)
+}
+
+/// Takes the next value sent on a channel, waiting for it if need be.
+fn recv(interpreter: &mut Interpreter, args: &[Object]) -> Result<Object, String> {
+    let Object::Channel(channel) = &args[0] else {
+        return Err(format!("Expected a channel but got {}.", args[0].type_name()));
+    };
+    channel.receive(interpreter.task_group())
+}
@@ -1,12 +1,52 @@
+use crate::environment::MutableEnvironment;
 use crate::error::Error;
 use crate::token::Token;
 use crate::value::callable::Callable;
 use crate::value::class::Class;
+use crate::value::channel::Channel;
+use crate::value::coroutine::Coroutine;
 use crate::value::function::Function;
-use crate::value::instance::Instance;
+use crate::value::instance::{Instance, MutableInstance};
+use crate::value::interface::Interface;
+use crate::value::map::{self, MutableMap};
+use crate::value::module::Module;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::rc::Rc;
+use std::sync::Arc;
+
+/// Shared, mutable storage for the elements of a list.
+pub type MutableList = Rc<RefCell<Vec<Object>>>;
+
+/// Checks that a number can index a list or string of the given length, and
+/// gives the position it stands for. `kind` names what is being indexed, for
+/// the error message.
+///
+/// Strings are indexed by character, that is by Unicode scalar value, the
+/// same way `len` counts them and `for in` walks them. "héllo"[1] is "é",
+/// however many bytes it takes.
+pub fn position(index: f64, length: usize, kind: &str) -> Result<usize, String> {
+    if index.fract() != 0.0 {
+        return Err("Index must be an integer.".into());
+    }
+    if index < 0.0 || index >= length as f64 {
+        return Err(format!("Index {index} is out of bounds for a {kind} of length {length}."));
+    }
+    Ok(index as usize)
+}
+
+/// How many lists and maps deep, one inside another, a value is printed.
+/// Anything nested deeper is shown as `...`.
+const MAX_PRINT_DEPTH: usize = 16;
+
+thread_local! {
+    /// The lists and maps being printed right now, outermost first. A list
+    /// can hold itself, directly or through others, and printing its
+    /// elements would then go on forever. When one comes up again while it
+    /// is still being printed, it is shown as `[...]` or `{...}` instead.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
 
 #[derive(Clone, Debug)]
 pub enum Object {
@@ -15,8 +55,30 @@ pub enum Object {
     Number(f64),   // Lox uses double-precision numbers even for integer values.
     Nil,
     Function(Function),
-    Class(Class),
-    Instance(Rc<RefCell<Instance>>), 
+    Class(Rc<Class>),
+    Instance(MutableInstance),
+    Module(Rc<Module>),
+
+    /// A growable list of values. Lists are shared by reference, so a list
+    /// passed to a function can be changed by it.
+    List(MutableList),
+
+    /// A map from strings and numbers to values. Like lists, maps are shared
+    /// by reference.
+    Map(MutableMap),
+
+    /// The variables in scope where `environment()` was called, for running
+    /// code among them later with `evalIn()`.
+    Environment(MutableEnvironment),
+
+    /// A function that runs a bit at a time, between resume() and yield().
+    Coroutine(Rc<Coroutine>),
+
+    /// A queue that tasks running on other threads send values through.
+    Channel(Arc<Channel>),
+
+    /// The methods a class has to have to implement it.
+    Interface(Rc<Interface>),
 }
 
 impl Display for Object {
@@ -29,10 +91,51 @@ impl Display for Object {
             Object::Function(func) => f.write_fmt(format_args!("<fn {}>", func.name())),
             Object::Class(class) => f.write_fmt(format_args!("{}", class.name)),
             Object::Instance(instance) => f.write_fmt(format_args!("{}", instance.borrow())),
+            Object::Module(module) => f.write_fmt(format_args!("{module}")),
+            Object::Environment(_) => f.write_str("<environment>"),
+            Object::Coroutine(coroutine) => f.write_fmt(format_args!("<coroutine {}>", coroutine.function().name())),
+            Object::Channel(_) => f.write_str("<channel>"),
+            Object::Interface(interface) => f.write_fmt(format_args!("{interface}")),
+            Object::List(list) => nested(f, Rc::as_ptr(list).cast(), "[...]", |f| {
+                let elements = list.borrow().iter().map(Object::to_string).collect::<Vec<String>>();
+                f.write_fmt(format_args!("[{}]", elements.join(", ")))
+            }),
+            Object::Map(map) => nested(f, Rc::as_ptr(map).cast(), "{...}", |f| {
+                let map = map.borrow();
+                let entries = map::sorted_keys(&map)
+                    .iter()
+                    .map(|key| format!("{key}: {}", map[key]))
+                    .collect::<Vec<String>>();
+                f.write_fmt(format_args!("{{{}}}", entries.join(", ")))
+            }),
         }
     }
 }
 
+/// Prints a list or map with `print`, unless it is already being printed
+/// further out, or is nested too deeply, in which case it is elided.
+fn nested(
+    f: &mut std::fmt::Formatter<'_>,
+    container: *const (),
+    cycle: &str,
+    print: impl FnOnce(&mut std::fmt::Formatter<'_>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    let (seen, depth) = PRINTING.with(|printing| {
+        let printing = printing.borrow();
+        (printing.contains(&container), printing.len())
+    });
+    if seen {
+        return f.write_str(cycle);
+    }
+    if depth >= MAX_PRINT_DEPTH {
+        return f.write_str("...");
+    }
+    PRINTING.with(|printing| printing.borrow_mut().push(container));
+    let result = print(f);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
+
 impl Object {
     /// All types are partitioned into two sets, one of which are defined to be true ("truthy"),
     /// and the rest which are false (“falsey”). This partitioning is somewhat arbitrary.
@@ -52,10 +155,148 @@ impl Object {
             (Object::Number(l), Object::Number(r)) => *l == r,
             (Object::Boolean(l), Object::Boolean(r)) => *l == r,
             (Object::String(l), Object::String(r)) => *l == r,
+            // Two lists are equal only if they are the same list.
+            (Object::List(l), Object::List(r)) => Rc::ptr_eq(l, &r),
+            (Object::Map(l), Object::Map(r)) => Rc::ptr_eq(l, &r),
+            // Unless its class has an equals() method, which the interpreter
+            // calls instead, an instance is only equal to itself.
+            (Object::Instance(l), Object::Instance(r)) => Rc::ptr_eq(l, &r),
+            (Object::Environment(l), Object::Environment(r)) => Rc::ptr_eq(l, &r),
+            (Object::Coroutine(l), Object::Coroutine(r)) => Rc::ptr_eq(l, &r),
+            (Object::Channel(l), Object::Channel(r)) => Arc::ptr_eq(l, &r),
+            (Object::Interface(l), Object::Interface(r)) => Rc::ptr_eq(l, &r),
             _ => false,
         }
     }
 
+    /// Whether two values are equal in structure, for `deepEquals()`: lists
+    /// whose elements are, in the same order, maps with the same keys whose
+    /// values are, and instances of the same class whose fields are. Other
+    /// values are compared as by `==`, without calling equals() methods.
+    pub fn deep_equals(&self, other: &Object) -> bool {
+        self.deep_equals_in(other, &mut Vec::new())
+    }
+
+    /// The pairs being compared, outermost first, are kept by address. A pair
+    /// that comes up again inside itself, as with lists that contain
+    /// themselves, is taken to be equal there: if the two differ, comparing
+    /// the rest of them finds where.
+    fn deep_equals_in(&self, other: &Object, comparing: &mut Vec<(*const (), *const ())>) -> bool {
+        let pair = match (self, other) {
+            (Object::List(l), Object::List(r)) => (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ()),
+            (Object::Map(l), Object::Map(r)) => (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ()),
+            (Object::Instance(l), Object::Instance(r)) => (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ()),
+            _ => return self.is_equal(other.clone()),
+        };
+        if pair.0 == pair.1 || comparing.contains(&pair) {
+            return true;
+        }
+        comparing.push(pair);
+        let equal = match (self, other) {
+            (Object::List(l), Object::List(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| l.deep_equals_in(r, comparing))
+            }
+            (Object::Map(l), Object::Map(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                l.len() == r.len() && l.iter().all(|(key, l)| r.get(key).is_some_and(|r| l.deep_equals_in(r, comparing)))
+            }
+            (Object::Instance(l), Object::Instance(r)) => {
+                let (l, r) = (l.borrow(), r.borrow());
+                Rc::ptr_eq(&l.klass, &r.klass)
+                    && l.fields.len() == r.fields.len()
+                    && l.fields.iter().all(|(name, l)| r.fields.get(name).is_some_and(|r| l.deep_equals_in(r, comparing)))
+            }
+            _ => unreachable!("only containers get this far"),
+        };
+        comparing.pop();
+        equal
+    }
+
+    /// A copy of a list, map or instance, for `clone()`: a new one holding
+    /// the same elements, entries or fields, so adding to or removing from
+    /// the copy leaves the original alone, though what they hold is shared.
+    /// Other values are returned as they are.
+    pub fn shallow_clone(&self) -> Object {
+        match self {
+            Object::List(list) => Object::List(Rc::new(RefCell::new(list.borrow().clone()))),
+            Object::Map(map) => Object::Map(Rc::new(RefCell::new(map.borrow().clone()))),
+            Object::Instance(instance) => Object::Instance(Rc::new(RefCell::new(instance.borrow().clone()))),
+            other => other.clone(),
+        }
+    }
+
+    /// A copy of a list, map or instance and of every list, map and instance
+    /// inside it, for `deepClone()`, so nothing the copy holds is shared with
+    /// the original. Map keys are kept as they are, since an instance used
+    /// as a key stands for itself. Other values are returned as they are.
+    pub fn deep_clone(&self) -> Object {
+        self.deep_clone_in(&mut HashMap::new())
+    }
+
+    /// The copies made so far are kept by the address of their original. One
+    /// that comes up again, as in a list that contains itself, is given the
+    /// same copy, so the copy is shaped like the original, cycles and all.
+    fn deep_clone_in(&self, copies: &mut HashMap<*const (), Object>) -> Object {
+        let address = match self {
+            Object::List(list) => Rc::as_ptr(list) as *const (),
+            Object::Map(map) => Rc::as_ptr(map) as *const (),
+            Object::Instance(instance) => Rc::as_ptr(instance) as *const (),
+            other => return other.clone(),
+        };
+        if let Some(copy) = copies.get(&address) {
+            return copy.clone();
+        }
+        // The copy is made empty and recorded before it is filled, so it is
+        // there to be found by anything inside that leads back to it.
+        let copy = match self {
+            Object::List(_) => Object::List(Rc::new(RefCell::new(Vec::new()))),
+            Object::Map(_) => Object::Map(Rc::new(RefCell::new(HashMap::new()))),
+            Object::Instance(instance) => {
+                let klass = instance.borrow().klass.clone();
+                Object::Instance(Rc::new(RefCell::new(Instance::new(klass))))
+            }
+            _ => unreachable!("only containers get this far"),
+        };
+        copies.insert(address, copy.clone());
+        match (self, &copy) {
+            (Object::List(list), Object::List(into)) => {
+                let elements = list.borrow().iter().map(|element| element.deep_clone_in(copies)).collect();
+                *into.borrow_mut() = elements;
+            }
+            (Object::Map(map), Object::Map(into)) => {
+                let entries = map.borrow().iter().map(|(key, value)| (key.clone(), value.deep_clone_in(copies))).collect();
+                *into.borrow_mut() = entries;
+            }
+            (Object::Instance(instance), Object::Instance(into)) => {
+                let fields = instance.borrow().fields.iter().map(|(name, value)| (name.clone(), value.deep_clone_in(copies))).collect();
+                into.borrow_mut().fields = fields;
+            }
+            _ => unreachable!("a copy is the same kind as its original"),
+        }
+        copy
+    }
+
+    /// The name of the value's type, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Boolean(_) => "boolean",
+            Object::String(_) => "string",
+            Object::Number(_) => "number",
+            Object::Nil => "nil",
+            Object::Function(_) => "function",
+            Object::Class(_) => "class",
+            Object::Instance(_) => "instance",
+            Object::Module(_) => "module",
+            Object::List(_) => "list",
+            Object::Map(_) => "map",
+            Object::Environment(_) => "environment",
+            Object::Coroutine(_) => "coroutine",
+            Object::Channel(_) => "channel",
+            Object::Interface(_) => "interface",
+        }
+    }
+
     pub fn as_callable(&self, paren: &Token) -> Result<&dyn Callable, Error> {
         match self {
             Object::Function(f) => Ok(f),
@@ -63,4 +304,77 @@ impl Object {
             _ => Err(Error::RuntimeError(paren.clone(), "Can only call functions and classes.".to_string())),
         }
     }
-}
\ No newline at end of file
+}
+
+// Conversions between Rust values and Lox values, for hosts that embed the
+// interpreter. Going into Lox always works. Coming back out fails with the
+// name of the Lox type that was actually there.
+
+impl From<f64> for Object {
+    fn from(value: f64) -> Self {
+        Object::Number(value)
+    }
+}
+
+impl From<i32> for Object {
+    fn from(value: i32) -> Self {
+        Object::Number(value.into())
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        Object::Boolean(value)
+    }
+}
+
+impl From<&str> for Object {
+    fn from(value: &str) -> Self {
+        Object::String(value.to_string())
+    }
+}
+
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(value)
+    }
+}
+
+impl<T: Into<Object>> From<Option<T>> for Object {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Object::Nil, Into::into)
+    }
+}
+
+impl TryFrom<Object> for f64 {
+    type Error = String;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Number(n) => Ok(n),
+            other => Err(format!("Expected a number but got {}.", other.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = String;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Boolean(b) => Ok(b),
+            other => Err(format!("Expected a boolean but got {}.", other.type_name())),
+        }
+    }
+}
+
+impl TryFrom<Object> for String {
+    type Error = String;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::String(s) => Ok(s),
+            other => Err(format!("Expected a string but got {}.", other.type_name())),
+        }
+    }
+}
@@ -112,10 +112,8 @@ fn single_chars() {
 
     let input2 = "++--**..,,;;";
     let expected2 = indoc! {"
-        PLUS + null
-        PLUS + null
-        MINUS - null
-        MINUS - null
+        PLUS_PLUS ++ null
+        MINUS_MINUS -- null
         STAR * null
         STAR * null
         DOT . null
@@ -183,12 +181,12 @@ fn lexical_errors() {
 
     let input3 = "@%%#$";
     let expected3 = indoc! {"
+        PERCENT % null
+        PERCENT % null
         EOF  null
     "};
     let error3 = indoc! {"
         [line 1] Error: Unexpected character: @
-        [line 1] Error: Unexpected character: %
-        [line 1] Error: Unexpected character: %
         [line 1] Error: Unexpected character: #
         [line 1] Error: Unexpected character: $
     "};
@@ -201,13 +199,13 @@ fn lexical_errors() {
         PLUS + null
         STAR * null
         MINUS - null
+        PERCENT % null
         RIGHT_PAREN ) null
         RIGHT_BRACE } null
         EOF  null
     "};
     let error4 = indoc! {"
         [line 1] Error: Unexpected character: #
-        [line 1] Error: Unexpected character: %
         [line 1] Error: Unexpected character: @
     "};
 
@@ -248,6 +246,7 @@ fn equal() {
     let expected4 = indoc! {"
         LEFT_PAREN ( null
         LEFT_PAREN ( null
+        PERCENT % null
         EQUAL = null
         RIGHT_PAREN ) null
         RIGHT_PAREN ) null
@@ -257,7 +256,6 @@ fn equal() {
         [line 1] Error: Unexpected character: @
         [line 1] Error: Unexpected character: $
         [line 1] Error: Unexpected character: #
-        [line 1] Error: Unexpected character: %
     "};
 
     run_tokenize(input1, expected1, NO_ERROR, SUCCESS);
@@ -328,11 +326,9 @@ fn relational() {
 
     let input2 = "<<<=>>>=";
     let expected2 = indoc! {"
-        LESS < null
-        LESS < null
+        LESS_LESS << null
         LESS_EQUAL <= null
-        GREATER > null
-        GREATER > null
+        GREATER_GREATER >> null
         GREATER_EQUAL >= null
         EOF  null
     "};
@@ -490,11 +486,9 @@ fn multiline_errors() {
         RIGHT_PAREN ) null
         LEFT_BRACE { null
         RIGHT_BRACE } null
+        PLUS_PLUS ++ null
         PLUS + null
-        PLUS + null
-        PLUS + null
-        PLUS + null
-        PLUS + null
+        PLUS_PLUS ++ null
         PLUS + null
         EOF  null
     "};
@@ -524,4 +518,110 @@ fn multiline_errors() {
     run_tokenize(input2, expected2, error2, BUILD_ERROR);
     run_tokenize(input3, expected3, error3, BUILD_ERROR);
     run_tokenize(input4, expected4, error4, BUILD_ERROR);
-}
\ No newline at end of file
+}
+#[test]
+fn block_comments() {
+    let input1 = indoc! {"
+        (/* a comment
+        that spans /* nested */ lines */)
+        @
+    "};
+    let expected1 = indoc! {"
+        LEFT_PAREN ( null
+        RIGHT_PAREN ) null
+        EOF  null
+    "};
+    let error1 = indoc! {"
+        [line 3] Error: Unexpected character: @
+    "};
+
+    let input2 = "/**/*/";
+    let expected2 = indoc! {"
+        STAR * null
+        SLASH / null
+        EOF  null
+    "};
+
+    let input3 = indoc! {"
+        +
+        /* never /* closed */
+        -
+    "};
+    let expected3 = indoc! {"
+        PLUS + null
+        EOF  null
+    "};
+    let error3 = indoc! {"
+        [line 2] Error: Unterminated block comment.
+    "};
+
+    run_tokenize(input1, expected1, error1, BUILD_ERROR);
+    run_tokenize(input2, expected2, NO_ERROR, SUCCESS);
+    run_tokenize(input3, expected3, error3, BUILD_ERROR);
+}
+
+#[test]
+fn number_literals() {
+    let input1 = "0xFF 0b1010 1_000_000 3.141_5 0x 1_";
+    let expected1 = indoc! {"
+        NUMBER 0xFF 255.0
+        NUMBER 0b1010 10.0
+        NUMBER 1_000_000 1000000.0
+        NUMBER 3.141_5 3.1415
+        NUMBER 0 0.0
+        IDENTIFIER x null
+        NUMBER 1 1.0
+        IDENTIFIER _ null
+        EOF  null
+    "};
+
+    let input2 = "0x1_0000_0000_0000_0000";
+    let expected2 = indoc! {"
+        EOF  null
+    "};
+    let error2 = indoc! {"
+        [line 1] Error: Number literal is too large.
+    "};
+
+    run_tokenize(input1, expected1, NO_ERROR, SUCCESS);
+    run_tokenize(input2, expected2, error2, BUILD_ERROR);
+}
+
+#[test]
+fn scientific_notation() {
+    let input = "1e9 2.5e-3 6.02E+23 1_0e1_0 3e e5 4e+";
+    let expected = indoc! {"
+        NUMBER 1e9 1000000000.0
+        NUMBER 2.5e-3 0.0025
+        NUMBER 6.02E+23 6.02e23
+        NUMBER 1_0e1_0 100000000000.0
+        NUMBER 3 3.0
+        IDENTIFIER e null
+        IDENTIFIER e5 null
+        NUMBER 4 4.0
+        IDENTIFIER e null
+        PLUS + null
+        EOF  null
+    "};
+    run_tokenize(input, expected, NO_ERROR, SUCCESS);
+}
+
+
+#[test]
+fn overlong_tokens() {
+    let name = "a".repeat(255);
+    let input1 = format!("{name} {name}b;");
+    let expected1 = format!("IDENTIFIER {name} null\nSEMICOLON ; null\nEOF  null\n");
+    let error1 = "[line 1] Error: Identifier is too long: 256 characters, the limit is 255.\n";
+
+    let text = "x".repeat((1 << 20) + 1);
+    let input2 = format!("\n\"{text}\n\" 1");
+    let expected2 = indoc! {"
+        NUMBER 1 1.0
+        EOF  null
+    "};
+    let error2 = "[line 2] Error: String literal is too long: 1048578 characters, the limit is 1048576.\n";
+
+    run_tokenize(&input1, &expected1, error1, BUILD_ERROR);
+    run_tokenize(&input2, expected2, error2, BUILD_ERROR);
+}
@@ -0,0 +1,18 @@
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::scanner::Scanner;
+
+#[test]
+fn program_keeps_its_source() {
+    let source = "var answer = 42;\r\nprint answer;\n";
+    let tokens = Scanner::new(source.to_string()).scan_tokens();
+    let program = Parser::new(tokens).with_source(source, Some("answer.lox")).parse();
+
+    assert_eq!(program.statements.len(), 2);
+    assert_eq!(program.file.as_deref(), Some("answer.lox"));
+    assert_eq!(program.source.text(), source);
+    assert_eq!(program.source.line(1), Some("var answer = 42;"));
+    assert_eq!(program.source.line(2), Some("print answer;"));
+    assert_eq!(program.source.line(4), None);
+    assert!(program.strings.lookup("answer").is_some());
+    assert!(program.strings.lookup("print").is_none());
+}
@@ -0,0 +1,477 @@
+mod util;
+
+use indoc::indoc;
+use util::run_program;
+use util::{SUCCESS, RUNTIME_ERROR, NO_ERROR};
+
+#[test]
+fn modulo() {
+    let input = indoc! {"
+        print 10 % 3;
+        print -7 % 3;
+        print 5.5 % 2;
+        print 2 + 9 % 4 * 2;
+        for (var i = 0; i < 4; i = i + 1) {
+            if (i % 2 == 0) print \"even\"; else print \"odd\";
+        }
+    "};
+    let expected = indoc! {"
+        1
+        -1
+        1.5
+        4
+        even
+        odd
+        even
+        odd
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "print \"ten\" % 3;";
+    let expected_error = indoc! {"
+        Operands must be numbers.
+        [line 1]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn bitwise() {
+    let input = indoc! {"
+        print 12 & 10;
+        print 12 | 10;
+        print 12 ^ 10;
+        print ~5;
+        print 1 << 4;
+        print -16 >> 2;
+        print 7.9 & 3;
+        print (6 & 3) == 2;
+        print 1 + 1 << 2;
+    "};
+    let expected = indoc! {"
+        8
+        14
+        6
+        -6
+        16
+        -4
+        3
+        true
+        8
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "print 1 << 64;";
+    let expected_error = indoc! {"
+        Shift amount must be between 0 and 63.
+        [line 1]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "print ~\"bits\";";
+    let expected_error = indoc! {"
+        Operand must be a number.
+        [line 1]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn arithmetic_edge_cases() {
+    // Operators on two numbers skip the interpreter's general match, so these
+    // pin down what they gave before they did.
+    let input = indoc! {"
+        var nan = 0 / 0;
+        print nan == nan;
+        print nan != nan;
+        print nan < 1;
+        print nan >= nan;
+        print nan + 1;
+        print 1 / 0;
+        print -1 / 0;
+        print 1 / 0 == 2 / 0;
+        print 0 / -1 == 0;
+        print -7 % 3;
+        print 7 % -3;
+        print 5 % 0;
+        print 5.9 & 3;
+        print -1 | 0;
+        print nan & 1;
+        print 0.1 + 0.2 == 0.3;
+    "};
+    let expected = indoc! {"
+        false
+        true
+        false
+        false
+        NaN
+        inf
+        -inf
+        true
+        true
+        -1
+        1
+        NaN
+        1
+        -1
+        0
+        false
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn lists() {
+    let input = indoc! {"
+        var xs = [1, 2, 3,];
+        print xs;
+        print xs[0] + xs[2];
+        xs[1] = \"two\";
+        print xs;
+        push(xs, [4]);
+        print len(xs);
+        print xs[3][0];
+        xs[0] += 10;
+        xs[0]++;
+        print pop(xs);
+        print xs;
+        print [];
+        print len(\"four\");
+        fun fill(list) { push(list, nil); }
+        var ys = [];
+        fill(ys);
+        print ys;
+        print ys == ys;
+        print [1] == [1];
+        var total = 0;
+        var squares = [];
+        for (var i = 0; i < 4; i++) push(squares, i * i);
+        for (var i = 0; i < len(squares); i++) total += squares[i];
+        print total;
+    "};
+    let expected = indoc! {"
+        [1, 2, 3]
+        4
+        [1, two, 3]
+        4
+        4
+        [4]
+        [12, two, 3]
+        []
+        4
+        [nil]
+        true
+        false
+        14
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var xs = [1, 2];\nprint xs[2];";
+    let expected_error = indoc! {"
+        Index 2 is out of bounds for a list of length 2.
+        [line 2]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let expected_error = "Index 1 is out of bounds for a list of length 1.\n[line 2]\n";
+    run_program("var xs = [1, 2];\nxs[1] = pop(xs);", "", expected_error, RUNTIME_ERROR);
+    run_program("var xs = [1, 2];\nxs[1] += pop(xs);", "", expected_error, RUNTIME_ERROR);
+
+    let input = "var xs = [1, 2];\nxs[0.5] = 1;";
+    let expected_error = indoc! {"
+        Index must be an integer.
+        [line 2]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "print pop([]);";
+    let expected_error = indoc! {"
+        Can't pop from an empty list.
+        [line 1]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn maps() {
+    let input = indoc! {"
+        var ages = {\"bob\": 30, \"ann\": 25, 1: \"one\",};
+        print ages;
+        print ages[\"ann\"] + ages[\"bob\"];
+        ages[\"cid\"] = 41;
+        ages[\"bob\"] += 1;
+        ages[\"ann\"]++;
+        print ages;
+        print len(ages);
+        print keys(ages);
+        print has(ages, \"cid\");
+        print has(ages, \"dan\");
+        print has(ages, [1]);
+        print ages[-0 + 1];
+        print {};
+        print ages == ages;
+        print {} == {};
+        var nested = {\"xs\": [1, {\"y\": 2}]};
+        print nested[\"xs\"][1][\"y\"];
+    "};
+    let expected = indoc! {"
+        {1: one, ann: 25, bob: 30}
+        55
+        {1: one, ann: 26, bob: 31, cid: 41}
+        4
+        [1, ann, bob, cid]
+        true
+        false
+        false
+        one
+        {}
+        true
+        false
+        2
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var m = {\"a\": 1};\nprint m[\"b\"];";
+    let expected_error = indoc! {"
+        Undefined key 'b'.
+        [line 2]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "var m = {};\nm[nil] = 1;";
+    let expected_error = indoc! {"
+        Map keys must be strings or numbers.
+        [line 2]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn deep_equality() {
+    let input = indoc! {"
+        print [1, [2, 3]] == [1, [2, 3]];
+        print deepEquals([1, [2, 3]], [1, [2, 3]]);
+        print deepEquals([1, 2], [1, 2, 3]);
+        print deepEquals({\"a\": [1], 2: nil}, {2: nil, \"a\": [1]});
+        print deepEquals({\"a\": 1}, {\"b\": 1});
+        class Point { init(x, y) { this.x = x; this.y = y; } }
+        class Other { init(x, y) { this.x = x; this.y = y; } }
+        print deepEquals(Point(1, [2]), Point(1, [2]));
+        print deepEquals(Point(1, 2), Point(1, 3));
+        print deepEquals(Point(1, 2), Other(1, 2));
+        var a = [1];
+        push(a, a);
+        var b = [1];
+        push(b, b);
+        print deepEquals(a, b);
+        print deepEquals(1, \"1\");
+    "};
+    let expected = indoc! {"
+        false
+        true
+        false
+        true
+        false
+        true
+        false
+        false
+        true
+        false
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn cloning() {
+    let input = indoc! {"
+        var inner = [2];
+        var list = [1, inner];
+        var shallow = clone(list);
+        var deep = deepClone(list);
+        push(shallow, 3);
+        push(inner, 4);
+        print list;
+        print shallow;
+        print deep;
+        var map = {\"a\": inner};
+        var copy = deepClone(map);
+        copy.b = 1;
+        print has(map, \"b\");
+        print copy.a == inner;
+        class Point { init(x, y) { this.x = x; this.y = y; } }
+        var p = Point(1, 2);
+        var q = clone(p);
+        q.x = 10;
+        print p.x;
+        print q;
+        var a = [1];
+        push(a, a);
+        var b = deepClone(a);
+        print b[1] == b;
+        print b[1] == a;
+        print clone(\"text\");
+    "};
+    let expected = indoc! {"
+        [1, [2, 4]]
+        [1, [2, 4], 3]
+        [1, [2]]
+        false
+        false
+        1
+        Point instance
+        true
+        false
+        text
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn map_keys_as_properties() {
+    let input = indoc! {"
+        var config = {\"host\": \"localhost\", \"port\": 8080};
+        print config.host;
+        config.port = config.port + 1;
+        config.debug = true;
+        config.port++;
+        print config;
+        print config[\"debug\"];
+        var server = {\"config\": config};
+        print server.config.port;
+    "};
+    let expected = indoc! {"
+        localhost
+        {debug: true, host: localhost, port: 8082}
+        true
+        8082
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var config = {};\nprint config.missing;";
+    let expected_error = indoc! {"
+        Undefined key 'missing'.
+        [line 2]
+    "};
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn printing_cycles_and_deep_nesting() {
+    let input = indoc! {"
+        var xs = [1];
+        push(xs, xs);
+        print xs;
+        var m = {\"list\": xs};
+        m[\"self\"] = m;
+        print m;
+        var deep = [];
+        var inner = deep;
+        for (var i = 0; i < 20; i = i + 1) {
+          var next = [];
+          push(inner, next);
+          inner = next;
+        }
+        print deep;
+        var twice = [0];
+        print [twice, twice];
+    "};
+    let expected = indoc! {"
+        [1, [...]]
+        {list: [1, [...]], self: {...}}
+        [[[[[[[[[[[[[[[[...]]]]]]]]]]]]]]]]
+        [[0], [0]]
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn strings_are_sequences_of_characters() {
+    let input = indoc! {"
+        var word = \"héllo wörld\";
+        print len(word);
+        print word[1];
+        print word[7];
+        print slice(word, 6, 11);
+        print slice(word, 0, 0) == \"\";
+        print slice([1, 2, 3], 1, 3);
+        print codePointAt(word, 1);
+        print chars(\"aé\");
+        for (c in \"ñø\") print c;
+    "};
+    let expected = indoc! {"
+        11
+        é
+        ö
+        wörld
+        true
+        [2, 3]
+        233
+        [a, é]
+        ñ
+        ø
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var s = \"héllo\";\nprint s[5];";
+    let expected_error = "Index 5 is out of bounds for a string of length 5.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "var s = \"abc\";\ns[0] = \"x\";";
+    let expected_error = "Strings can't be changed.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "print slice(\"abc\", 2, 1);";
+    let expected_error = "Slice 2..1 is out of bounds for a string of length 3.\n[line 1]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn comma_operator() {
+    let input = indoc! {r#"
+        fun b() { print "b"; }
+        fun c() { print "c"; }
+        var d = "d";
+        var a = (b(), c(), d);
+        print a;
+        fun add(x, y) { return x + y; }
+        print add((1, 2), 3);
+        print [1, (2, 3)];
+        var i; var j;
+        for (i = 0, j = 10; i < j; i = i + 4, j = j - 4) {}
+        print i, j;
+    "#};
+    let expected = indoc! {"
+        b
+        c
+        d
+        5
+        [1, 3]
+        2
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn comparing_strings() {
+    let input = indoc! {r#"
+        print "apple" < "banana";
+        print "apple" < "app";
+        print "app" <= "app";
+        print "Zebra" < "apple";
+        var word = "pear";
+        print word >= "peach";
+        print word > "pears";
+    "#};
+    let expected = indoc! {"
+        true
+        false
+        true
+        true
+        true
+        false
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let expected_error = "Operands must be numbers.\n[line 1]\n";
+    run_program(r#"print "1" < 2;"#, "", expected_error, RUNTIME_ERROR);
+}
@@ -0,0 +1,706 @@
+mod util;
+
+use util::run_files;
+
+#[test]
+fn run_several_files() {
+    let (stdout, stderr, code, paths) = run_files(
+        &["run"],
+        &["print 1;", "print undefined;", "var a = 1; print a + 1;"],
+    );
+    assert_eq!(stdout, "1\n2\n");
+    assert!(stderr.contains(&format!("[{}] ok", paths[0])));
+    assert!(stderr.contains(&format!("[{}] failed with exit code 70", paths[1])));
+    assert!(stderr.contains(&format!("[{}] ok", paths[2])));
+    assert_eq!(code, 70);
+}
+
+#[test]
+fn tokenize_pretty_table() {
+    let (stdout, _, code, _) = run_files(&["tokenize", "--pretty"], &["var x\n  = 12;"]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "#  TYPE        LEXEME  LITERAL  LINE  COL");
+    assert!(lines[1].contains("\x1b[35mVAR"));
+    assert!(lines[4].contains("12.0") && lines[4].ends_with("2    5"));
+    assert_eq!(lines.len(), 7);
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn run_report_json_to_file() {
+    let report = tempfile::NamedTempFile::new().unwrap();
+    let report_path = report.path().to_str().unwrap();
+    let (stdout, stderr, code, _) = run_files(
+        &["run", "--report", "json", "--report-file", report_path],
+        &["print 1;\nprint nil + 1;"],
+    );
+    assert_eq!(stdout, "1\n");
+    assert_eq!(stderr, "Operands must be numbers.\n[line 2]\n");
+    assert_eq!(code, 70);
+
+    let json = std::fs::read_to_string(report_path).unwrap();
+    assert!(json.starts_with(r#"{"exit_code":70,"diagnostics":[{"kind":"runtime","line":2,"message":"Operands must be numbers."}],"stats":{"statements":2,"calls":0,"#));
+}
+
+#[test]
+fn run_report_for_several_files() {
+    let report = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(report.path(), "left over\n").unwrap();
+    let report_path = report.path().to_str().unwrap();
+    let (_, _, code, _) = run_files(
+        &["run", "--report", "json", "--report-file", report_path],
+        &["print 1;", "print nil + 1;"],
+    );
+    assert_eq!(code, 70);
+
+    let json = std::fs::read_to_string(report_path).unwrap();
+    let reports: Vec<&str> = json.lines().collect();
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0].starts_with(r#"{"exit_code":0,"diagnostics":[],"#));
+    assert!(reports[1].starts_with(r#"{"exit_code":70,"diagnostics":[{"kind":"runtime","#));
+}
+
+#[test]
+fn import_from_include_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("lib")).unwrap();
+    std::fs::write(dir.path().join("lib/math.lox"), "fun square(n) { return n * n; }\nprint \"loaded\";").unwrap();
+
+    let include = dir.path().to_str().unwrap();
+    let (stdout, stderr, code, paths) = run_files(
+        &["run", "--include", include],
+        &["import \"lib/math.lox\";\nimport \"lib/math.lox\";\nprint square(4);\nimport \"missing.lox\";"],
+    );
+    assert_eq!(stdout, "loaded\n16\n");
+    assert!(stderr.starts_with("Could not find module 'missing.lox'. Tried "));
+    let script_dir = std::path::Path::new(&paths[0]).parent().unwrap();
+    assert!(stderr.contains(&script_dir.join("missing.lox").display().to_string()));
+    assert!(stderr.contains(&dir.path().join("missing.lox").display().to_string()));
+    assert!(stderr.ends_with(".\n[line 4]\n"));
+    assert_eq!(code, 70);
+}
+
+#[test]
+fn import_relative_module_as_namespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, source: &str| std::fs::write(dir.path().join(path), source).unwrap();
+    std::fs::create_dir(dir.path().join("lib")).unwrap();
+    write("lib/helpers.lox", "fun times(a, b) { return a * b; }");
+    write(
+        "lib/math.lox",
+        "import \"helpers.lox\" as h;\nvar factor = 2;\nfun square(n) { return h.times(n, n); }\nfun scale(n) { return n * factor; }",
+    );
+    write(
+        "main.lox",
+        "import \"lib/math.lox\" as math;\nprint math.square(3);\nprint math.scale(5);\nprint math;\nprint factor;",
+    );
+
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .args(["run", dir.path().join("main.lox").to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9\n10\n<module math>\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Undefined variable: 'factor'\n[line 5]\n");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn module_functions_can_use_names_declared_after_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, source: &str| std::fs::write(dir.path().join(path), source).unwrap();
+    write("m.lox", "fun a() { return b() + limit; }\nfun b() { return 1; }\n{ var limit = 0; }\nprint limit;\nvar limit = 10;");
+    write("main.lox", "import \"m.lox\" as m;\nprint m.a();");
+
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .args(["run", dir.path().join("main.lox").to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Undefined variable: 'limit'\n"));
+    assert_eq!(output.status.code(), Some(70));
+
+    write("m.lox", "fun a() { return b() + limit; }\nfun b() { return 1; }\nvar limit = 10;");
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .args(["run", dir.path().join("main.lox").to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "11\n");
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn errors_in_modules_name_their_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, source: &str| std::fs::write(dir.path().join(path), source).unwrap();
+    std::fs::create_dir(dir.path().join("lib")).unwrap();
+    write("lib/math.lox", "fun half(n) {\n  return n / \"two\";\n}");
+    write("lib/broken.lox", "var x = ;");
+    write("main.lox", "import \"lib/math.lox\";\nprint half(4);");
+    write("other.lox", "import \"lib/broken.lox\";");
+
+    let run = |script: &str| {
+        assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+            .unwrap()
+            .current_dir(dir.path())
+            .args(["run", script])
+            .output()
+            .unwrap()
+    };
+    let output = run("main.lox");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Operands must be numbers.\n[lib/math.lox line 2]\n");
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run("other.lox");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[lib/broken.lox line 1] Error at ';': Expect expression.\nCould not compile module 'lib/broken.lox'.\n[line 1]\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn repl_keeps_state_and_recovers() {
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .arg("repl")
+        .write_stdin("var a = 1\nfun add(x) { return x + a; }\nadd(2)\nprint a +;\nnil + 1\n{ var b = 3; print add(b); }\n")
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n4\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error at ';': Expect expression.\nOperands must be numbers.\n[line 1]\n"
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn conditional_compilation_with_define() {
+    let program = "print 1;\n#if DEBUG\nprint 2;\n#else\nprint 3;\n#end\nprint 4;";
+    let (stdout, _, code, _) = run_files(&["run"], &[program]);
+    assert_eq!(stdout, "1\n3\n4\n");
+    assert_eq!(code, 0);
+
+    let (stdout, _, code, _) = run_files(&["run", "--define", "DEBUG"], &[program]);
+    assert_eq!(stdout, "1\n2\n4\n");
+    assert_eq!(code, 0);
+
+    let (_, stderr, code, _) = run_files(&["run"], &["#if DEBUG\nprint 1;"]);
+    assert_eq!(stderr, "[line 1] Error at '#if DEBUG': Unterminated '#if' directive.\n");
+    assert_eq!(code, 65);
+}
+
+#[test]
+fn deny_capabilities() {
+    let (stdout, stderr, code, _) = run_files(&["run", "--deny", "print"], &["var a = 1;\nprint a;"]);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "The 'print' capability is not allowed.\n[line 2]\n");
+    assert_eq!(code, 70);
+
+    let (stdout, _, code, _) = run_files(&["run", "--sandbox"], &["print 1;"]);
+    assert_eq!(stdout, "1\n");
+    assert_eq!(code, 0);
+
+    for flags in [&["run", "--sandbox"][..], &["run", "--deny", "files"]] {
+        let (_, stderr, code, _) = run_files(flags, &["import \"lib.lox\" as lib;"]);
+        assert_eq!(stderr, "The 'files' capability is not allowed.\n[line 1]\n");
+        assert_eq!(code, 70);
+    }
+
+    let (_, stderr, code, _) = run_files(&["run", "--deny", "teleport"], &["print 1;"]);
+    assert_eq!(stderr, "Unknown capability: teleport\n");
+    assert_eq!(code, 64);
+}
+
+#[test]
+fn buffered_output_is_all_written() {
+    let program = "atExit(fun() { print \"bye\"; });\nfor (var i = 0; i < 5000; i = i + 1) print i;\nflush();\nnil();";
+    let expected: String = (0..5000).map(|i| format!("{i}\n")).chain(["bye\n".to_string()]).collect();
+    for command in [&["run"][..], &["run", "--unbuffered"][..]] {
+        let (stdout, stderr, code, _) = run_files(command, &[program]);
+        assert_eq!(stdout, expected);
+        assert_eq!(stderr, "Can only call functions and classes.\n[line 4]\n");
+        assert_eq!(code, 70);
+    }
+}
+
+#[test]
+fn refactor_rename() {
+    let source = "var count = 0;\nfun bump(count) {\n  return count + 1;\n}\ncount = bump(count);\nprint count;\n";
+    let (stdout, stderr, code, _) = run_files(
+        &["refactor", "rename", "count", "total", "--line", "5", "--col", "14"],
+        &[source],
+    );
+    assert_eq!(stderr, "");
+    assert_eq!(stdout, "var total = 0;\nfun bump(count) {\n  return count + 1;\n}\ntotal = bump(total);\nprint total;\n");
+    assert_eq!(code, 0);
+
+    let (stdout, _, code, _) = run_files(
+        &["refactor", "rename", "count", "n", "--line", "3", "--col", "10"],
+        &[source],
+    );
+    assert_eq!(stdout, "var count = 0;\nfun bump(n) {\n  return n + 1;\n}\ncount = bump(count);\nprint count;\n");
+    assert_eq!(code, 0);
+
+    let (_, stderr, code, paths) = run_files(
+        &["refactor", "rename", "count", "bump", "--line", "1", "--col", "5"],
+        &[source],
+    );
+    assert_eq!(stderr, format!("{}: Can't rename to 'bump': the name is already in use.\n", paths[0]));
+    assert_eq!(code, 65);
+
+    let (_, stderr, code, paths) = run_files(
+        &["refactor", "rename", "x", "y", "--line", "1", "--col", "25"],
+        &["class A { init() { this.x = 1; } }\nprint A().x;"],
+    );
+    assert_eq!(stderr, format!("{}: Can't rename 'x': properties and methods are looked up dynamically.\n", paths[0]));
+    assert_eq!(code, 65);
+}
+
+#[test]
+fn find_references() {
+    let source = "var a = 1;\n{\n  var a = 2;\n  print a;\n}\nfun f() { return a; }\na = f();\nvar a = 3;";
+    let (stdout, stderr, code, paths) = run_files(&["refs", "--line", "6", "--col", "18"], &[source]);
+    assert_eq!(stderr, "");
+    let path = &paths[0];
+    assert_eq!(stdout, format!("{path}:1:5\n{path}:6:18\n{path}:7:1\n{path}:8:5\n"));
+    assert_eq!(code, 0);
+
+    let (stdout, _, code, paths) = run_files(&["refs", "--line", "3", "--col", "7"], &[source]);
+    let path = &paths[0];
+    assert_eq!(stdout, format!("{path}:3:7\n{path}:4:9\n"));
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn refactor_extract_function() {
+    let source = "fun report(items, label) {\n  var total = 0;\n  for (var i = 0; i < len(items); i++) total += items[i];\n  print label;\n  print total;\n}\n";
+    let (stdout, stderr, code, _) = run_files(
+        &["refactor", "extract-function", "show", "--line", "4", "--end-line", "5"],
+        &[source],
+    );
+    assert_eq!(stderr, "");
+    assert_eq!(
+        stdout,
+        "fun report(items, label) {\n  var total = 0;\n  for (var i = 0; i < len(items); i++) total += items[i];\n  fun show(label, total) {\n    print label;\n    print total;\n  }\n  show(label, total);\n}\n"
+    );
+    assert_eq!(code, 0);
+
+    let (_, stderr, code, paths) = run_files(
+        &["refactor", "extract-function", "sum", "--line", "2", "--end-line", "3"],
+        &[source],
+    );
+    assert_eq!(stderr, format!("{}: 'total' is declared in the extracted code but used after it.\n", paths[0]));
+    assert_eq!(code, 65);
+
+    let (_, stderr, code, paths) = run_files(
+        &["refactor", "extract-function", "sum", "--line", "3"],
+        &[source],
+    );
+    assert_eq!(stderr, format!("{}: Can't extract code that assigns to 'total', which is declared outside it.\n", paths[0]));
+    assert_eq!(code, 65);
+}
+
+#[test]
+fn refactor_inline_variable() {
+    let source = "fun area(r) {\n  var pi = 3.14;\n  var squared = r * r;\n  return pi * squared;\n}\nprint area(2);\n";
+    let (stdout, stderr, code, _) = run_files(
+        &["refactor", "inline-variable", "squared", "--line", "3", "--col", "7"],
+        &[source],
+    );
+    assert_eq!(stderr, "");
+    assert_eq!(stdout, "fun area(r) {\n  var pi = 3.14;\n  return pi * (r * r);\n}\nprint area(2);\n");
+    assert_eq!(code, 0);
+
+    let (_, stderr, code, paths) = run_files(
+        &["refactor", "inline-variable", "now", "--line", "1", "--col", "5"],
+        &["var now = clock();\nprint now - now;"],
+    );
+    assert_eq!(
+        stderr,
+        format!("{}: Can't inline 'now': its initializer might not give the same value each time it is evaluated.\n", paths[0])
+    );
+    assert_eq!(code, 65);
+}
+
+#[test]
+fn eprintln_writes_to_stderr() {
+    let (stdout, stderr, code, _) = run_files(&["run"], &["print 1;\neprintln(\"warning: \" + \"two\");\nprint 3;"]);
+    assert_eq!(stdout, "1\n3\n");
+    assert_eq!(stderr, "warning: two\n");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn freeze_globals() {
+    let program = "var count = 0;\nfun bump() { count = count + 1; }\ncount = 5;\nprint count;\nbump();";
+    let (stdout, stderr, code, _) = run_files(&["run", "--freeze-globals"], &[program]);
+    assert_eq!(stdout, "5\n");
+    assert_eq!(stderr, "Can't assign to global 'count' from inside a function; globals are frozen.\n[line 2]\n");
+    assert_eq!(code, 70);
+
+    let (stdout, _, code, _) = run_files(&["run"], &[program]);
+    assert_eq!(stdout, "5\n");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn mixed_concat_joins_strings_and_numbers() {
+    let program = "var count = 3;\nprint \"count: \" + count;\nprint 1.5 + \"x\";\nprint \"a\" + nil;";
+    let (stdout, stderr, code, _) = run_files(&["run", "--mixed-concat", "--warnings"], &[program]);
+    assert_eq!(stdout, "count: 3\n1.5x\n");
+    assert_eq!(stderr, "[line 4] Warning at '+': Operands must be numbers.\nOperands must be numbers.\n[line 4]\n");
+    assert_eq!(code, 70);
+
+    let (stdout, stderr, code, _) = run_files(&["run"], &[program]);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "Operands must be numbers.\n[line 2]\n");
+    assert_eq!(code, 70);
+}
+
+#[test]
+fn eval_runs_code_where_it_is_called() {
+    let program = r#"var greeting = "hi";
+fun counter() {
+  var count = 0;
+  eval("count = count + 1; var doubled = count * 2;");
+  print eval("doubled + count;");
+  return environment();
+}
+var scope = counter();
+print evalIn(scope, "count = count + 10; count;");
+var there = " there";
+print eval("greeting + there;");
+class Box {
+  init(value) { this.value = value; }
+  peek() { return eval("this.value;"); }
+}
+print Box(7).peek();
+var message = "from eval";
+try {
+  eval("throw message;");
+} catch (error) {
+  print error;
+}
+try {
+  eval("1 +");
+} catch (error) {
+  print error.message;
+}
+print eval("1 + 2;");
+eval("var;");"#;
+    let (stdout, stderr, code, _) = run_files(&["run", "--allow", "eval"], &[program]);
+    assert_eq!(stdout, "3\n11\nhi there\n7\nfrom eval\nCould not compile the code given to eval: [line 1] Error at end: Expect expression.\n3\n");
+    assert_eq!(stderr, "Could not compile the code given to eval: [line 1] Error at ';': Expect variable name\n[line 29]\n");
+    assert_eq!(code, 70);
+
+    let (stdout, stderr, code, _) = run_files(&["run"], &["print eval(\"1\");"]);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "The 'eval' capability is not allowed.\n[line 1]\n");
+    assert_eq!(code, 70);
+}
+
+#[test]
+fn errors_in_a_task_stay_in_the_task() {
+    let program = r#"
+try {
+  recv(spawn(fun() -> eval("1 +"), nil));
+} catch (error) {
+  print error.message;
+}
+print "still fine";"#;
+    let (stdout, stderr, code, _) = run_files(&["run", "--allow", "eval"], &[program]);
+    assert_eq!(stdout, "A task failed at line 3: Could not compile the code given to eval: [line 1] Error at end: Expect expression.\nstill fine\n");
+    assert_eq!(stderr, "");
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn natives_flag_picks_the_set_of_natives() {
+    let (stdout, stderr, code, _) = run_files(&["run", "--natives", "pure"], &["print len(\"abc\");\nprint clock();"]);
+    assert_eq!(stdout, "3\n");
+    assert_eq!(stderr, "Undefined variable: 'clock'\n[line 2]\n");
+    assert_eq!(code, 70);
+
+    let (_, stderr, code, _) = run_files(&["run", "--natives", "lots"], &["print 1;"]);
+    assert_eq!(stderr, "Unknown set of natives: lots\n");
+    assert_eq!(code, 64);
+}
+
+#[test]
+fn explain_counts_the_nodes_that_ran() {
+    let source = "fun f(x) { return x + 1; }\nprint f(1) + f(2);";
+    let (stdout, stderr, code, _) = run_files(&["run", "--explain"], &[source]);
+    assert_eq!(stdout, "5\n");
+    assert_eq!(
+        stderr,
+        "Literal   4\nVariable  4\nBinary    3\nCall      2\nReturn    2\nFunction  1\nPrint     1\n"
+    );
+    assert_eq!(code, 0);
+}
+
+#[test]
+fn imports_search_next_to_the_importer_then_lox_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, source: &str| std::fs::write(dir.path().join(path), source).unwrap();
+    std::fs::create_dir(dir.path().join("app")).unwrap();
+    std::fs::create_dir(dir.path().join("shared")).unwrap();
+    write("shared/greet.lox", "print \"shared greet\";");
+    write("shared/util.lox", "print \"shared util\";");
+    write("app/util.lox", "print \"app util\";");
+    write("app/main.lox", "import \"util.lox\";\nimport \"greet.lox\";\nimport \"nowhere.lox\";");
+
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .current_dir(dir.path())
+        .env("LOX_PATH", dir.path().join("shared"))
+        .args(["run", "app/main.lox"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "app util\nshared greet\n");
+    let tried = ["app/nowhere.lox", "./nowhere.lox", &dir.path().join("shared/nowhere.lox").display().to_string()];
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("Could not find module 'nowhere.lox'. Tried {}.\n[line 3]\n", tried.join(", "))
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn shadowing_natives_warns_unless_strict_or_allowed() {
+    let source = "var clock = 1;\nfun len(x) { return 0; }\n{ var push = 2; }\nprint clock + len(3);";
+    let (stdout, stderr, code, _) = run_files(&["run"], &[source]);
+    assert_eq!(stdout, "1\n");
+    assert_eq!(
+        stderr,
+        "[line 1] Warning at 'clock': 'clock' shadows the native function of the same name.\n\
+         [line 2] Warning at 'len': 'len' shadows the native function of the same name.\n"
+    );
+    assert_eq!(code, 0);
+
+    let (stdout, stderr, code, _) = run_files(&["run", "--strict"], &[source]);
+    assert_eq!(stdout, "");
+    assert_eq!(
+        stderr,
+        "[line 1] Error at 'clock': Can't shadow the native function 'clock'.\n\
+         [line 2] Error at 'len': Can't shadow the native function 'len'.\n"
+    );
+    assert_eq!(code, 65);
+
+    let (stdout, stderr, code, _) = run_files(&["run", "--shadowing", "allow"], &[source]);
+    assert_eq!((stdout.as_str(), stderr.as_str(), code), ("1\n", "", 0));
+}
+
+#[test]
+fn test_command_checks_expectations_in_comments() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, source: &str| std::fs::write(dir.path().join(path), source).unwrap();
+    std::fs::create_dir(dir.path().join("more")).unwrap();
+    write(
+        "a.lox",
+        "print 1 + 2; // expect: 3\nprint \"a\"; // expect: a\nprint nil + 1; // expect runtime error: Operands must be numbers.",
+    );
+    write("more/b.lox", "var = 1; // Error at '=': Expect variable name\n// [line 3] Error at end: Expect ';' after value.\nprint 2");
+    write("more/c.lox", "print 4; // expect: 5\nprint 6;");
+    write("more/d.lox", "fun f( { // expect parse error");
+    write("more/notes.txt", "print 1;");
+
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(["test", "a.lox", "more"])
+        .output()
+        .unwrap();
+    let expected = "PASS a.lox\n\
+                    PASS more/b.lox\n\
+                    FAIL more/c.lox\n  \
+                    Expected output '5' on line 1 and got '4'.\n  \
+                    Got output '6' when none was expected.\n\
+                    PASS more/d.lox\n\
+                    3 passed, 1 failed.\n";
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn official_test_suite_pass_rates() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, source: &str| std::fs::write(dir.path().join(path), source).unwrap();
+    for category in ["test", "test/assignment", "test/benchmark", "test/while"] {
+        std::fs::create_dir(dir.path().join(category)).unwrap();
+    }
+    write("test/empty_file.lox", "");
+    write("test/assignment/global.lox", "var a = 1;\na = 2;\nprint a; // expect: 2");
+    write("test/assignment/grouping.lox", "var a = 1;\n(a) = 2; // Error at '=': Invalid assignment target.");
+    write("test/assignment/undefined.lox", "unknown = 1; // expect runtime error: Undefined variable 'unknown'.");
+    write("test/benchmark/fib.lox", "print 1; // expect: 2");
+    write("test/while/syntax.lox", "var i = 0;\nwhile (i < 2) { print i; i = i + 1; }\n// expect: 0\n// expect: 1");
+
+    let output = assert_cmd::Command::cargo_bin("codecrafters-interpreter")
+        .unwrap()
+        .args(["test", "--official", dir.path().to_str().unwrap()])
+        .output()
+        .unwrap();
+    let expected = "(top level)        1/1  100.0%\n\
+                    assignment         2/3   66.7%\n\
+                    while              1/1  100.0%\n\
+                    total              4/5   80.0%\n";
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn call_runs_main_with_arguments_and_exits_with_its_result() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("main.lox");
+    std::fs::write(&script, "print \"top\";\nfun main(count, name, loud) {\n  print [count, name, loud];\n  return count;\n}\n").unwrap();
+    let run = |args: &[&str]| {
+        assert_cmd::Command::cargo_bin("codecrafters-interpreter").unwrap().args(args).output().unwrap()
+    };
+    let script = script.to_str().unwrap();
+
+    let output = run(&["run", "--call", "main", script, "3", "bob", "true"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "top\n[3, bob, true]\n");
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = run(&["run", "--call", "main", script, "3"]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Expected 3 arguments but got 1.\n[line 2]\n");
+    assert_eq!(output.status.code(), Some(70));
+
+    let output = run(&["run", "--call", "main", script, "bob", "bob", "nil"]);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "main() must return an integer or nil, not bob.\n[line 2]\n");
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn exit_status_comes_from_a_top_level_return_or_the_last_expression() {
+    let source = "var x = 3;\nif (x > 2) return x + 1;\nprint \"unreachable\";";
+    let (stdout, stderr, code, _) = run_files(&["run", "--exit-status"], &[source]);
+    assert_eq!((stdout.as_str(), stderr.as_str(), code), ("", "", 4));
+
+    let (stdout, _, code, _) = run_files(&["run", "--exit-status"], &["print \"hi\";\n2 + 5;"]);
+    assert_eq!((stdout.as_str(), code), ("hi\n", 7));
+
+    let (_, stderr, code, _) = run_files(&["run"], &[source]);
+    assert_eq!(stderr, "[line 2] Error at 'return': Can't return from top-level code.\n");
+    assert_eq!(code, 65);
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_stops_the_program_with_a_stack_trace() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::{Command, Stdio};
+
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("spin.lox");
+    std::fs::write(&script, "atExit(fun() { print \"bye\"; });\nfun spin() {\n  print \"started\";\n  while (true) {}\n}\nspin();\n").unwrap();
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin("codecrafters-interpreter"))
+        .args(["run", "--unbuffered"])
+        .arg(&script)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "started\n");
+
+    Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    let status = child.wait().unwrap();
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    let mut stderr = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut stderr).unwrap();
+    assert_eq!(stderr, "Interrupted.\n[line 4] in spin()\n[line 6] in script\n");
+    assert_eq!(rest, "bye\n");
+    assert_eq!(status.code(), Some(130));
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_at_the_repl_prompt_is_ignored() {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin("codecrafters-interpreter"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    writeln!(stdin, "print 1;").unwrap();
+    stdout.read_line(&mut line).unwrap();
+    assert_eq!(line, "1\n");
+
+    // Long enough for the line to finish and the prompt to be waiting.
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    for _ in 0..2 {
+        Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    }
+    writeln!(stdin, "print 2;").unwrap();
+    drop(stdin);
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "2\n");
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+}
+
+#[cfg(unix)]
+#[test]
+fn serve_answers_requests_on_a_socket() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::process::{Command, Stdio};
+
+    let dir = tempfile::tempdir().unwrap();
+    let library = dir.path().join("lib.lox");
+    std::fs::write(&library, "var greeting = \"hi\";").unwrap();
+    let socket = dir.path().join("lox.sock");
+    let mut server = Command::new(assert_cmd::cargo::cargo_bin("codecrafters-interpreter"))
+        .arg("serve")
+        .arg("--socket")
+        .arg(&socket)
+        .arg(&library)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stream = (0..100)
+        .find_map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            UnixStream::connect(&socket).ok()
+        })
+        .expect("the server should start listening");
+
+    let requests = [
+        r#"{"command": "evaluate", "source": "print greeting; var x = 1; 1 + 2;"}"#,
+        r#"{"command": "run", "source": "print x;"}"#,
+        r#"{"command": "compile", "source": ""}"#,
+    ];
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    let mut responses = Vec::new();
+    for request in requests {
+        writeln!(writer, "{request}").unwrap();
+        let mut response = String::new();
+        reader.read_line(&mut response).unwrap();
+        responses.push(response);
+    }
+    server.kill().unwrap();
+    let output = server.wait_with_output().unwrap();
+    // What went wrong is in the answer, not the server's own output.
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    assert_eq!(responses, [
+        "{\"exit_code\":0,\"value\":\"3\",\"stdout\":\"hi\\n\",\"diagnostics\":[]}\n",
+        "{\"exit_code\":70,\"stdout\":\"\",\"diagnostics\":[{\"kind\":\"runtime\",\"line\":1,\"message\":\"Undefined variable: 'x'\"}]}\n",
+        "{\"error\":\"Unknown command: compile.\"}\n",
+    ]);
+}
@@ -0,0 +1,185 @@
+mod util;
+
+use indoc::indoc;
+use util::run_program;
+use util::{SUCCESS, BUILD_ERROR, RUNTIME_ERROR, NO_ERROR};
+
+#[test]
+fn compound_assignment() {
+    let input = indoc! {"
+        var counter = 10;
+        counter += 5;
+        counter -= 3;
+        counter *= 2;
+        counter /= 4;
+        print counter;
+        var s = \"a\";
+        s += \"b\";
+        print s;
+        class Box {}
+        var box = Box();
+        box.value = 1;
+        box.value += 41;
+        print box.value;
+        fun outer() {
+            var n = 1;
+            fun inner() { n += 1; return n; }
+            inner();
+            return inner();
+        }
+        print outer();
+        var a = 1;
+        var b = 2;
+        a += b += 3;
+        print a;
+    "};
+    let expected = indoc! {"
+        6
+        ab
+        42
+        3
+        6
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var a = 1;\n(a) += 2;";
+    let expected_error = "[line 2] Error at '+=': Invalid assignment target.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn increment_and_decrement() {
+    let input = indoc! {"
+        var i = 5;
+        print i++;
+        print i;
+        print ++i;
+        print i--;
+        print --i;
+        print -i;
+        class Counter {}
+        var c = Counter();
+        c.count = 0;
+        c.count++;
+        ++c.count;
+        print c.count--;
+        print c.count;
+        fun make() {
+            var n = 0;
+            fun next() { return n++; }
+            return next;
+        }
+        var next = make();
+        next();
+        print next();
+        for (var j = 0; j < 3; j++) print j;
+    "};
+    let expected = indoc! {"
+        5
+        6
+        7
+        7
+        5
+        -5
+        2
+        1
+        1
+        0
+        1
+        2
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "1++;";
+    let expected_error = "[line 1] Error at '++': Invalid increment target.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+
+    let input = "var s = \"a\";\ns++;";
+    let expected_error = "Operand must be a number.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn const_declarations() {
+    let input = indoc! {"
+        const limit = 3;
+        {
+          const greeting = \"hi\";
+          print greeting;
+          var limit = 4;
+          limit = limit + 1;
+          print limit;
+        }
+        print limit;
+    "};
+    let expected = indoc! {"
+        hi
+        5
+        3
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        fun f() {
+          const x = 1;
+          x = 2;
+          x++;
+        }
+    "};
+    let expected_error = indoc! {"
+        [line 3] Error at 'x': Can't assign to constant 'x'.
+        [line 4] Error at 'x': Can't assign to constant 'x'.
+    "};
+    run_program(input, "", expected_error, BUILD_ERROR);
+
+    let input = "const x;";
+    let expected_error = "[line 1] Error at ';': Expect '=' after constant name.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+
+    let input = "const x = 1;\nfun f() { x = 2; }\nf();";
+    let expected_error = "Can't assign to constant 'x'.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "const x = 1;\n{ var x = 3; print x; }\nvar x = 2;\nprint x;";
+    let expected_error = "Can't redeclare constant 'x'.\n[line 3]\n";
+    run_program(input, "3\n", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn destructuring_declarations() {
+    let input = indoc! {"
+        var calls = 0;
+        fun pair() {
+          calls = calls + 1;
+          return [1, 2];
+        }
+        var (a, b) = pair();
+        print a + b;
+        print calls;
+        fun swap(list) {
+          var [first, second, third] = list;
+          return [third, second, first];
+        }
+        print swap([1, 2, 3, 4]);
+        {
+          var (x) = [\"only\"];
+          var (y) = [\"again\"];
+          print x + y;
+        }
+    "};
+    let expected = indoc! {"
+        3
+        1
+        [3, 2, 1]
+        onlyagain
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var [a, b] = [1];";
+    let expected_error = "Index 1 is out of bounds for a list of length 1.\n[line 1]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "var (a, b];";
+    let expected_error = "[line 1] Error at ']': Expect ')' after variable names.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
@@ -0,0 +1,252 @@
+mod util;
+
+use indoc::indoc;
+use util::run_program;
+use util::{SUCCESS, BUILD_ERROR, NO_ERROR, RUNTIME_ERROR};
+
+#[test]
+fn break_statement() {
+    let input = indoc! {"
+        var i = 0;
+        while (true) {
+            if (i == 3) break;
+            print i;
+            i = i + 1;
+        }
+        for (var j = 0; j < 10; j = j + 1) {
+            for (var k = 0; k < 10; k = k + 1) {
+                if (k == 1) break;
+                print j + k;
+            }
+            if (j == 1) break;
+        }
+    "};
+    let expected = indoc! {"
+        0
+        1
+        2
+        0
+        1
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        break;
+        while (true) {
+            fun f() { break; }
+        }
+    "};
+    let expected_error = indoc! {"
+        [line 1] Error at 'break': Can't use 'break' outside of a loop.
+        [line 3] Error at 'break': Can't use 'break' outside of a loop.
+    "};
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn continue_statement() {
+    let input = indoc! {"
+        for (var i = 0; i < 5; i = i + 1) {
+            if (i == 1 or i == 3) continue;
+            print i;
+        }
+        var j = 0;
+        while (j < 4) {
+            j = j + 1;
+            if (j == 2) continue;
+            print j;
+        }
+    "};
+    let expected = indoc! {"
+        0
+        2
+        4
+        1
+        3
+        4
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        continue;
+        for (;;) {
+            fun f() { continue; }
+        }
+    "};
+    let expected_error = indoc! {"
+        [line 1] Error at 'continue': Can't use 'continue' outside of a loop.
+        [line 3] Error at 'continue': Can't use 'continue' outside of a loop.
+    "};
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn for_in_statement() {
+    let input = indoc! {"
+        var list = [1, 2, 3];
+        for (item in list) {
+            if (item == 2) continue;
+            push(list, item * 10);
+            print item;
+        }
+        print list;
+        for (var key in {\"b\": 1, 2: 0, \"a\": 3}) print key;
+        var closures = [];
+        for (c in \"héllo\") {
+            if (c == \"l\") break;
+            fun show() { print c; }
+            push(closures, show);
+        }
+        for (show in closures) show();
+        var in = 1;
+        print in;
+    "};
+    let expected = indoc! {"
+        1
+        3
+        [1, 2, 3, 10, 30]
+        2
+        a
+        b
+        h
+        é
+        1
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "for (x in 12) print x;";
+    run_program(input, "", "Can only iterate over lists, maps and strings.\n[line 1]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn labeled_break_and_continue() {
+    let input = indoc! {"
+        outer: for (var i = 0; i < 3; i = i + 1) {
+            inner: for (j in [0, 1, 2]) {
+                if (j == 1) continue outer;
+                if (i == 2) break outer;
+                print i * 10 + j;
+            }
+        }
+        var n = 0;
+        rows: while (n < 3) {
+            n = n + 1;
+            while (true) {
+                if (n == 2) continue rows;
+                break;
+            }
+            print n;
+        }
+    "};
+    let expected = indoc! {"
+        0
+        10
+        1
+        3
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        outer: while (true) {
+            fun f() { break outer; }
+            continue inner;
+        }
+        break outer;
+    "};
+    let expected_error = indoc! {"
+        [line 2] Error at 'break': Can't use 'break' outside of a loop.
+        [line 3] Error at 'inner': No enclosing loop is labeled 'inner'.
+        [line 5] Error at 'break': Can't use 'break' outside of a loop.
+    "};
+    run_program(input, "", expected_error, BUILD_ERROR);
+
+    let expected_error = "[line 1] Error at 'print': Expect loop after label.\n";
+    run_program("here: print 1;", "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn pragmas_limit_steps_and_time() {
+    let input = indoc! {"
+        // Counts for a while.
+        //!max_steps: 100
+        var i = 0;
+        try {
+            while (true) i = i + 1;
+        } catch (error) {
+            print \"caught\";
+        }
+    "};
+    run_program(input, "", "Exceeded the limit of 100 steps.\n[line 5]\n", RUNTIME_ERROR);
+
+    let input = "//!timeout: 50ms\nfun tick() {}\nwhile (true) tick();";
+    run_program(input, "", "Exceeded the time limit of 50ms.\n[line 3]\n", RUNTIME_ERROR);
+
+    let input = "//!max_steps: 100\nfor (var i = 0; i < 10; i = i + 1) {}\nprint \"done\";";
+    run_program(input, "done\n", NO_ERROR, SUCCESS);
+
+    let input = "//!timeout: soon\n//!max_runs: 1\nprint 1;\n//!max_steps: x";
+    let expected_error = indoc! {"
+        [line 1] Error: Invalid timeout 'soon', expected seconds or milliseconds, like 2s or 500ms.
+        [line 2] Error: Unknown pragma 'max_runs'.
+    "};
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn match_expressions() {
+    let input = indoc! {"
+        class Shape {}
+        class Circle < Shape {}
+        fun describe(value) {
+          return match (value) {
+            0 -> \"zero\",
+            -1 -> \"minus one\",
+            \"hi\" -> \"a greeting\",
+            nil -> \"nothing\",
+            n: Number -> n * 10,
+            c: Circle -> \"a circle\",
+            _: Shape -> \"some shape\",
+            list: List -> len(list),
+            _ -> \"something else\",
+          };
+        }
+        print describe(0);
+        print describe(-1);
+        print describe(\"hi\");
+        print describe(nil);
+        print describe(2);
+        print describe(Circle());
+        print describe(Shape());
+        print describe([1, 2]);
+        print describe(true);
+        var n = \"outer\";
+        print match (1) { n -> n + 1 };
+        print n;
+    "};
+    let expected = indoc! {"
+        zero
+        minus one
+        a greeting
+        nothing
+        20
+        a circle
+        some shape
+        2
+        something else
+        2
+        outer
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "print match (3) {\n  1 -> \"one\",\n};";
+    let expected_error = "No pattern matches the value 3.\n[line 1]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "var notAClass = 1;\nprint match (3) { x: notAClass -> x };";
+    let expected_error = "'notAClass' is not a type or a class.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "print match (3) { + -> 1 };";
+    let expected_error = "[line 1] Error at '+': Expect pattern.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
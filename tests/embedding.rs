@@ -0,0 +1,355 @@
+use codecrafters_interpreter::cst::ConcreteSyntax;
+use codecrafters_interpreter::error;
+use codecrafters_interpreter::expr::Expr;
+use codecrafters_interpreter::fold::{self, Fold};
+use codecrafters_interpreter::lox::{evaluate_expression, ClassInfo, Lox};
+use codecrafters_interpreter::macros;
+use codecrafters_interpreter::pass::{Pass, Reporter};
+use codecrafters_interpreter::pipeline::{Pipeline, Resolved};
+use codecrafters_interpreter::policy::Limits;
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::session::{Session, StepResult};
+use codecrafters_interpreter::stmt::Stmt;
+use codecrafters_interpreter::token::{Token, TokenType};
+use codecrafters_interpreter::value::native::{Native, Natives};
+use codecrafters_interpreter::value::object::Object;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn session_steps_one_statement_at_a_time() {
+    let source = "var frames = 0;\nframes = frames + 1;\nframes = frames + 1;";
+    let mut session = Session::new(Pipeline::new(), source).unwrap();
+
+    assert_eq!(session.step(), StepResult::Stepped);
+    assert_eq!(session.step(), StepResult::Stepped);
+    assert!(!session.is_finished());
+    assert_eq!(session.step(), StepResult::Finished);
+    assert_eq!(session.step(), StepResult::Finished);
+
+    let mut session = Session::new(Pipeline::new(), "var a = 1;\nvar b = a + nil;\nvar c = 3;").unwrap();
+    assert_eq!(session.run(), StepResult::Failed("Operands must be numbers.".to_string()));
+    assert!(session.is_finished());
+
+    error::reset();
+    let source = "var a = 1;\nif (a > 0) return 3;\nprint a;";
+    let mut session = Session::new(Pipeline::new().with_exit_status(), source).unwrap();
+    assert_eq!(session.step(), StepResult::Stepped);
+    assert_eq!(session.step(), StepResult::Finished);
+    assert!(session.is_finished());
+    assert_eq!(error::exit_code(), 3);
+
+    let mut session = Session::new(Pipeline::new().with_exit_status(), "return \"done\";").unwrap();
+    let expected = "A script's result must be an integer or nil, not done.";
+    assert_eq!(session.run(), StepResult::Failed(expected.to_string()));
+}
+
+#[test]
+fn host_defined_globals() {
+    let mut lox = Lox::new();
+    lox.set_global("name", "lox");
+    lox.set_global("retries", 3);
+    lox.set_global("verbose", true);
+    lox.set_global("missing", None::<f64>);
+
+    let code = lox.run("var greeting = \"hi \" + name;\nfun twice(n) { return n * 2; }\nvar total = twice(retries);\nvar off = !verbose;\nvar empty = missing == nil;");
+    assert_eq!(code, 0);
+    assert_eq!(lox.get_global::<String>("greeting"), Ok("hi lox".to_string()));
+    assert_eq!(lox.get_global::<f64>("total"), Ok(6.0));
+    assert_eq!(lox.get_global::<bool>("off"), Ok(false));
+    assert_eq!(lox.get_global::<bool>("empty"), Ok(true));
+
+    assert_eq!(lox.run("total = twice(total);"), 0);
+    assert_eq!(lox.get_global::<f64>("total"), Ok(12.0));
+
+    assert_eq!(lox.get_global::<f64>("name"), Err("Expected a number but got string.".to_string()));
+    assert_eq!(lox.get_global::<f64>("nope"), Err("Undefined variable 'nope'.".to_string()));
+}
+
+#[test]
+fn forks_share_a_prelude_but_not_their_changes() {
+    let mut prelude = Lox::new();
+    prelude.set_global("limit", 10);
+    let code = prelude.run("var hits = 0;\nfun hit() { hits = hits + 1; return hits; }\nclass Greeter { greet(name) { return \"hi \" + name; } }");
+    assert_eq!(code, 0);
+
+    let mut first = prelude.fork();
+    assert_eq!(first.run("hit(); hit(); var mine = Greeter().greet(\"first\");"), 0);
+    assert_eq!(first.get_global::<f64>("hits"), Ok(2.0));
+    assert_eq!(first.get_global::<String>("mine"), Ok("hi first".to_string()));
+
+    let mut second = prelude.fork();
+    let outcome = second.evaluate("hit() + limit;");
+    assert_eq!(outcome.value.map(|value| value.to_string()), Some("11".to_string()));
+    assert_eq!(second.get_global::<String>("mine"), Err("Undefined variable 'mine'.".to_string()));
+    assert_eq!(prelude.get_global::<f64>("hits"), Ok(0.0));
+}
+
+#[test]
+fn coroutines_only_resume_in_the_interpreter_that_started_them() {
+    let mut parent = Box::new(Lox::new());
+    let code = parent.run("var seen = \"parent\";\nvar c = coroutine(fun() { print seen; yield(1); print seen; yield(2); });\nresume(c, nil);");
+    assert_eq!(code, 0);
+
+    let mut fork = parent.fork();
+    drop(parent);
+    let outcome = fork.evaluate("seen = \"fork\";\nresume(c, nil);");
+    assert_eq!(outcome.stdout, "");
+    assert_eq!(outcome.diagnostics[0].message, "Can't resume a coroutine outside the interpreter that started it.");
+    assert_eq!(fork.evaluate("1 + 1;").value.map(|value| value.to_string()), Some("2".to_string()));
+}
+
+#[test]
+fn classes_describe_their_methods() {
+    let mut lox = Lox::new();
+    let source = "class Base { init(name) { this.name = name; } stop() {} class create() { return Base(\"x\"); } }\n\
+                  class Plugin < Base { run(input, output) {} }\n\
+                  var notAClass = 1;";
+    assert_eq!(lox.run(source), 0);
+
+    let strings = |methods: &[(&str, usize)]| methods.iter().map(|(name, arity)| (name.to_string(), *arity)).collect::<Vec<_>>();
+    assert_eq!(lox.classes(), vec![
+        ClassInfo {
+            name: "Base".to_string(),
+            superclass: None,
+            methods: strings(&[("init", 1), ("stop", 0)]),
+            class_methods: strings(&[("create", 0)]),
+        },
+        ClassInfo { name: "Error".to_string(), superclass: None, methods: strings(&[("init", 1)]), class_methods: vec![] },
+        ClassInfo {
+            name: "Plugin".to_string(),
+            superclass: Some("Base".to_string()),
+            methods: strings(&[("init", 1), ("run", 2), ("stop", 0)]),
+            class_methods: strings(&[("create", 0)]),
+        },
+    ]);
+}
+
+#[test]
+fn each_interpreter_gets_its_own_natives() {
+    fn fixed_clock(_: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+        Ok(Object::Number(1000.0))
+    }
+    let natives = Natives::pure().with(Native { name: "clock", arity: 0, function: fixed_clock });
+    let mut testing = Lox::with_pipeline(Pipeline::new().with_natives(natives));
+    let mut standard = Lox::new();
+
+    assert_eq!(testing.evaluate("clock() + len([1, 2]);").value.map(|value| value.to_string()), Some("1002".to_string()));
+    assert!(standard.evaluate("clock();").value.is_some_and(|now| now.to_string() != "1000"));
+
+    let outcome = testing.evaluate("eprintln(\"hi\");");
+    assert_eq!(outcome.diagnostics[0].message, "Undefined variable: 'eprintln'");
+    assert_eq!(testing.fork().evaluate("clock();").value.map(|value| value.to_string()), Some("1000".to_string()));
+}
+
+#[test]
+fn tasks_start_with_the_natives_of_their_program() {
+    fn fixed_clock(_: &mut Interpreter, _: &[Object]) -> Result<Object, String> {
+        Ok(Object::Number(1000.0))
+    }
+    let natives = Natives::standard().with(Native { name: "clock", arity: 0, function: fixed_clock });
+    let mut testing = Lox::with_pipeline(Pipeline::new().with_natives(natives));
+
+    let outcome = testing.evaluate("recv(spawn(fun() -> clock(), nil));");
+    assert_eq!(outcome.value.map(|value| value.to_string()), Some("1000".to_string()));
+}
+
+#[test]
+fn line_events_for_every_statement_and_loop_check() {
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let mut lox = Lox::new();
+    let recorder = lines.clone();
+    lox.on_line(move |line| recorder.borrow_mut().push(line));
+    let source = "var total = 0;\nfor (var i = 0;\n     i < 2;\n     i = i + 1) {\n  total = total + i;\n}\nfor (c in \"ab\")\n  total = total + 1;\nprint total;";
+    assert_eq!(lox.evaluate(source).stdout, "3\n");
+    assert_eq!(*lines.borrow(), [1, 2, 3, 5, 4, 3, 5, 4, 3, 7, 8, 7, 8, 7, 9]);
+}
+
+#[test]
+fn evaluate_returns_a_structured_outcome() {
+    let outcome = evaluate_expression("(1 + 2) * 4");
+    assert_eq!(outcome.value.map(|value| value.to_string()), Some("12".to_string()));
+    assert!(outcome.diagnostics.is_empty());
+
+    let outcome = evaluate_expression("-\"muffin\"");
+    assert!(outcome.value.is_none());
+    assert_eq!(outcome.diagnostics[0].message, "Operand must be a number.");
+
+    let mut lox = Lox::new();
+    let outcome = lox.evaluate("var x = 20;\nprint \"computing\";\nx + 22;");
+    assert_eq!(outcome.stdout, "computing\n");
+    assert_eq!(f64::try_from(outcome.value.unwrap()), Ok(42.0));
+
+    let outcome = lox.evaluate("print x;\nx = nil + 1;");
+    assert_eq!(outcome.stdout, "20\n");
+    assert!(outcome.value.is_none());
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].kind, "runtime");
+}
+
+#[test]
+fn folding_the_syntax_tree() {
+    let pipeline = Pipeline::new();
+    let source = "var day = 60 * 60 * 24;\nfun f() { return -(2 - 3) + !nil; }\nprint \"a\" + \"b\" == \"ab\";";
+    let program = pipeline.optimize(pipeline.parse(pipeline.scan(source), source).unwrap());
+    let printed: Vec<String> = program.statements.iter().map(|stmt| format!("{stmt:?}")).collect();
+    assert!(printed[0].contains("Number(86400.0)"));
+    assert!(printed[1].contains("Number(1.0)") && printed[1].contains("Boolean(true)"));
+    assert!(printed[2].contains("Literal { value: Boolean(true) }"));
+
+    // A pass only overrides the nodes it cares about.
+    struct CountVariables(usize);
+    impl Fold for CountVariables {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            if let Expr::Variable { .. } = expr {
+                self.0 += 1;
+            }
+            fold::fold_expr(self, expr)
+        }
+    }
+    let source = "var a = 1; fun f(b) { return a + b; } print f(a)[0];";
+    let program = pipeline.parse(pipeline.scan(source), source).unwrap();
+    let mut counter = CountVariables(0);
+    counter.fold_program(program.statements);
+    assert_eq!(counter.0, 4);
+}
+
+#[test]
+fn concrete_syntax_keeps_every_byte() {
+    let source = "// größe\nvar  x =\t\"é\" ; /* a\n /* nested */ b */\n#if DEBUG\nprint x;\n#end\n  // done\n";
+    let syntax = ConcreteSyntax::new(source);
+    assert_eq!(syntax.to_source(), source);
+    assert_eq!(syntax.tokens[0].leading, "// größe\n");
+    assert_eq!(&source[syntax.tokens[3].span.clone()], "\"é\"");
+    assert_eq!(syntax.tokens.last().unwrap().leading, "\n  // done\n");
+
+    let x = syntax.token_at(5, 7).unwrap();
+    let renamed = syntax.replace(|index, _| (index == x).then(|| "y".to_string()));
+    assert_eq!(renamed, source.replace("print x", "print y"));
+}
+
+/// Rewrites `times(n) body` into `for (var _i = 0; _i < (n); _i = _i + 1) body`.
+fn times(tokens: Vec<Token>) -> Result<Vec<Token>, (Token, String)> {
+    let mut output = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        let is_call = tokens.peek().is_some_and(|next| next.token_type == TokenType::LEFT_PAREN);
+        if token.lexeme != "times" || !is_call {
+            output.push(token);
+            continue;
+        }
+        output.extend(macros::tokens("for (var _i = 0; _i <", &token));
+        let mut depth = 0;
+        for inner in tokens.by_ref() {
+            match inner.token_type {
+                TokenType::LEFT_PAREN => depth += 1,
+                TokenType::RIGHT_PAREN => depth -= 1,
+                TokenType::EOF => return Err((inner, "Expect ')' after times count.".into())),
+                _ => {}
+            }
+            output.push(inner);
+            if depth == 0 {
+                break;
+            }
+        }
+        output.extend(macros::tokens("; _i = _i + 1)", &token));
+    }
+    Ok(output)
+}
+
+#[test]
+fn macros_rewrite_the_token_stream() {
+    let mut lox = Lox::with_pipeline(Pipeline::new().with_macro(times));
+    let outcome = lox.evaluate("var n = 0;
+times(1 + 2) { n = n + 1; }
+times(2) print n;");
+    assert_eq!(outcome.stdout, "3\n3\n");
+
+    // Without the macro, `times` is just a name.
+    let outcome = Lox::new().evaluate("fun times(n) { return n * 2; }\nprint times(2);");
+    assert_eq!(outcome.stdout, "4\n");
+}
+
+#[test]
+fn passes_run_between_resolving_and_running() {
+    struct NoPrint;
+    impl Pass for NoPrint {
+        fn name(&self) -> &str {
+            "no-print"
+        }
+        fn run(&self, resolved: &mut Resolved, reporter: &mut Reporter) -> bool {
+            for statement in &resolved.program.statements {
+                if let Stmt::Print { keyword, .. } = statement {
+                    reporter.warning(keyword, "Print statement left in.");
+                }
+            }
+            false
+        }
+    }
+
+    struct TimesTen;
+    impl Fold for TimesTen {
+        fn fold_expr(&mut self, expr: Expr) -> Expr {
+            match fold::fold_expr(self, expr) {
+                Expr::Literal { value: Object::Number(n) } => Expr::Literal { value: Object::Number(n * 10.0) },
+                other => other,
+            }
+        }
+    }
+    impl Pass for TimesTen {
+        fn name(&self) -> &str {
+            "times-ten"
+        }
+        fn run(&self, resolved: &mut Resolved, _: &mut Reporter) -> bool {
+            let statements = std::mem::take(&mut resolved.program.statements);
+            resolved.program.statements = TimesTen.fold_program(statements);
+            true
+        }
+    }
+
+    // Looks up the `y` in f's `return x + y;`, which TimesTen rebuilt.
+    struct LocalReads;
+    impl Pass for LocalReads {
+        fn name(&self) -> &str {
+            "local-reads"
+        }
+        fn run(&self, resolved: &mut Resolved, reporter: &mut Reporter) -> bool {
+            for statement in &resolved.program.statements {
+                let Stmt::Function { decl } = statement else { continue };
+                let Some(Stmt::Return { keyword, value: Some(Expr::Binary { right, .. }) }) = decl.body.last() else { continue };
+                if resolved.locals.contains_key(&(right.as_ref() as *const Expr)) {
+                    reporter.warning(keyword, "Returns a local.");
+                }
+            }
+            false
+        }
+    }
+
+    let mut lox = Lox::with_pipeline(Pipeline::new().with_pass(TimesTen).with_pass(LocalReads));
+    let outcome = lox.evaluate("var x = 1;\nfun f() { var y = 2; return x + y; }\nprint f();");
+    assert_eq!(outcome.stdout, "30\n");
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].message, "Warning at 'return': Returns a local. [local-reads]");
+
+    let mut lox = Lox::with_pipeline(Pipeline::new().with_pass(NoPrint).with_pass(TimesTen));
+    let outcome = lox.evaluate("var x = 1;\nfun f() { var y = 2; return x + y; }\nprint f();");
+    assert_eq!(outcome.stdout, "30\n");
+    assert_eq!(outcome.diagnostics.len(), 1);
+    assert_eq!(outcome.diagnostics[0].kind, "warning");
+    assert_eq!(outcome.diagnostics[0].line, 3);
+    assert_eq!(outcome.diagnostics[0].message, "Warning at 'print': Print statement left in. [no-print]");
+}
+
+#[test]
+fn limits_set_by_the_host_hold_whatever_the_pragmas_say() {
+    let mut lox = Lox::new();
+    lox.set_limits(Limits { max_steps: Some(100), timeout: None });
+    let mut fork = lox.fork();
+
+    let outcome = lox.evaluate("//!max_steps: 1000000\nwhile (true) {}");
+    assert_eq!(outcome.diagnostics[0].message, "Exceeded the limit of 100 steps.");
+    let outcome = fork.evaluate("while (true) {}");
+    assert_eq!(outcome.diagnostics[0].message, "Exceeded the limit of 100 steps.");
+    let outcome = fork.evaluate("//!max_steps: 10\nwhile (true) {}");
+    assert_eq!(outcome.diagnostics[0].message, "Exceeded the limit of 10 steps.");
+}
@@ -0,0 +1,323 @@
+mod util;
+
+use indoc::indoc;
+use util::run_program;
+use util::{SUCCESS, BUILD_ERROR, RUNTIME_ERROR, NO_ERROR};
+
+#[test]
+fn anonymous_functions() {
+    let input = indoc! {"
+        fun apply(f, x) { return f(x); }
+        print apply(fun (x) -> x * 2, 21);
+        print apply(fun (s) { var t = s + s; return t; }, \"ab\");
+        var add = fun (a, b) -> a + b;
+        print add(1, 2);
+        print add;
+        fun adder(n) { return fun (x) -> x + n; }
+        print adder(10)(5);
+        fun (x) -> x;
+        print (fun () -> \"now\")();
+    "};
+    let expected = indoc! {"
+        42
+        abab
+        3
+        <fn lambda>
+        15
+        now
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var f = fun (x) x;";
+    let expected_error = "[line 1] Error at 'x': Expect '{' or '->' before function body.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn stack_inspection() {
+    let input = indoc! {"
+        fun inner() {
+          print callerName();
+          var trace = stackTrace();
+          for (var i = 0; i < len(trace); i++) print trace[i];
+        }
+        fun outer() {
+          inner();
+        }
+        class Greeter {
+          greet() { outer(); }
+        }
+        Greeter().greet();
+        print callerName();
+        fun top() { print callerName(); }
+        top();
+        print stackTrace();
+    "};
+    let expected = indoc! {"
+        outer
+        [line 3] in inner()
+        [line 7] in outer()
+        [line 10] in greet()
+        [line 12] in script
+        nil
+        script
+        [[line 16] in script]
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn exit_hooks() {
+    let input = indoc! {"
+        var log = [];
+        atExit(fun () { print \"second\"; });
+        atExit(fun () {
+          print \"first\";
+          print log;
+        });
+        push(log, \"ran\");
+        print \"main\";
+    "};
+    let expected = indoc! {"
+        main
+        first
+        [ran]
+        second
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        atExit(fun () { print \"cleanup\"; });
+        print \"before\";
+        nil();
+        print \"after\";
+    "};
+    let expected_error = indoc! {"
+        Can only call functions and classes.
+        [line 3]
+    "};
+    run_program(input, "before\ncleanup\n", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn timers() {
+    let input = indoc! {"
+        setTimeout(fun () { print \"later\"; }, 20);
+        var cancelled = setTimeout(fun () { print \"never\"; }, 10);
+        setTimeout(fun () {
+          print \"soon\";
+          setTimeout(fun () { print \"nested\"; }, 0);
+        }, 0);
+        print clearTimeout(cancelled);
+        print clearTimeout(cancelled);
+        atExit(fun () { print \"bye\"; });
+        print \"now\";
+    "};
+    let expected = indoc! {"
+        true
+        false
+        now
+        soon
+        nested
+        later
+        bye
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        setTimeout(fun () { nil(); }, 0);
+        setTimeout(fun () { print \"dropped\"; }, 10);
+        print \"scheduled\";
+    "};
+    let expected_error = indoc! {"
+        Can only call functions and classes.
+        [line 1]
+    "};
+    run_program(input, "scheduled\n", expected_error, RUNTIME_ERROR);
+
+    let expected_error = "Expected a delay in milliseconds but got -1.\n[line 1]\n";
+    run_program("setTimeout(clock, -1);", "", expected_error, RUNTIME_ERROR);
+    let expected_error = "Expected a delay in milliseconds but got 18000000000000000000000.\n[line 1]\n";
+    run_program("setTimeout(clock, 1.8e22);", "", expected_error, RUNTIME_ERROR);
+    let expected_error = "Expected a delay in milliseconds but got NaN.\n[line 1]\n";
+    run_program("setTimeout(clock, 0 / 0);", "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn rest_parameters() {
+    let input = indoc! {"
+        fun log(level, ...args) {
+            print [level, len(args)];
+            return args;
+        }
+        print log(\"info\");
+        print log(\"warn\", 1, \"two\", nil);
+        var all = fun(...items) -> items;
+        print all(1, 2);
+        class Tuple { init(...items) { this.items = items; } }
+        print Tuple(3, 4).items;
+        log();
+    "};
+    let expected = indoc! {"
+        [info, 0]
+        []
+        [warn, 3]
+        [1, two, nil]
+        [1, 2]
+        [3, 4]
+    "};
+    run_program(input, expected, "Expected at least 1 arguments but got 0.\n[line 11]\n", RUNTIME_ERROR);
+
+    let input = "fun f(...rest, last) {}";
+    let expected_error = "[line 1] Error at ',': The rest parameter must be the last one.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn coroutines() {
+    let input = indoc! {"
+        fun count(limit) {
+            for (var i = 1; i < limit; i = i + 1) yield(i);
+            return \"done\";
+        }
+        var counter = coroutine(count);
+        print counter;
+        print resume(counter, 3);
+        print resume(counter, nil);
+        print resume(counter, nil);
+        print isDone(counter);
+
+        fun half(x) { var got = yield(x / 2); return got + 1; }
+        var conversation = coroutine(fun(x) {
+            var answer = half(x);
+            try { yield(answer); throw \"boom\"; } catch (e) { print \"caught \" + e; }
+            return \"bye\";
+        });
+        print resume(conversation, 10);
+        print resume(conversation, 41);
+        print resume(conversation, nil);
+
+        var forever = coroutine(fun() { while (true) yield(1); });
+        print resume(forever, nil);
+        forever = nil;
+
+        var self;
+        self = coroutine(fun() { resume(self, nil); });
+        resume(self, nil);
+    "};
+    let expected = indoc! {"
+        <coroutine count>
+        1
+        2
+        done
+        true
+        5
+        42
+        caught boom
+        bye
+        1
+    "};
+    let expected_error = indoc! {"
+        Can't resume a coroutine that is already running.
+        [line 27]
+    "};
+    run_program(input, expected, expected_error, RUNTIME_ERROR);
+
+    run_program("yield(1);", "", "Can't yield outside a coroutine.\n[line 1]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn tasks_and_channels() {
+    let input = indoc! {"
+        fun square(n) { return n * n; }
+        var results = [];
+        for (var i = 1; i <= 3; i = i + 1) push(results, spawn(square, i));
+        for (var i = 0; i < 3; i = i + 1) results[i] = recv(results[i]);
+        print results;
+
+        fun sum(channels) {
+          var total = 0;
+          for (var n = recv(channels[0]); n != nil; n = recv(channels[0])) total = total + n;
+          send(channels[1], total);
+        }
+        var inbox = chanNew();
+        var outbox = chanNew();
+        spawn(sum, [inbox, outbox]);
+        for (var i = 1; i <= 4; i = i + 1) send(inbox, i);
+        send(inbox, nil);
+        print recv(outbox);
+
+        fun change(list) {
+          push(list, 3);
+          return list;
+        }
+        var list = [1, 2];
+        print recv(spawn(change, list));
+        print list;
+
+        var secret = 1;
+        print recv(spawn(fun() { return secret; }, nil));
+    "};
+    let expected = indoc! {"
+        [1, 4, 9]
+        10
+        [1, 2, 3]
+        [1, 2]
+    "};
+    let expected_error = indoc! {"
+        A task failed at line 28: Undefined variable: 'secret'
+        [line 28]
+    "};
+    run_program(input, expected, expected_error, RUNTIME_ERROR);
+
+    let expected_error = "Deadlock: every task is waiting for another.\n[line 1]\n";
+    run_program("recv(chanNew());", "", expected_error, RUNTIME_ERROR);
+    let expected_error = "Can't send a function to another task.\n[line 1]\n";
+    run_program("send(chanNew(), clock);", "", expected_error, RUNTIME_ERROR);
+    let expected_error = "Can't send an instance to another task.\n[line 1]\n";
+    run_program("class A {} send(chanNew(), A());", "", expected_error, RUNTIME_ERROR);
+}
+
+#[test]
+fn sorting_with_a_comparator() {
+    let input = indoc! {r#"
+        var numbers = [3, 1, 2, 10];
+        sort(numbers, fun(a, b) -> a - b);
+        print numbers;
+        sort(numbers, fun(a, b) -> b - a);
+        print numbers;
+
+        class Person { init(name, age) { this.name = name; this.age = age; } }
+        var people = [Person("ann", 30), Person("bob", 25), Person("cy", 30), Person("di", 25)];
+        sort(people, fun(a, b) -> a.age - b.age);
+        for (var p in people) print p.name;
+
+        var calls = 0;
+        sort([2, 1], fun(a, b) { calls = calls + 1; sort([b, a], fun(x, y) -> x - y); return a - b; });
+        print calls;
+
+        try {
+            sort([1, 2], fun(a, b) { throw "stop"; });
+        } catch (e) {
+            print "caught " + e;
+        }
+        sort([1, 2], fun(a, b) -> "yes");
+    "#};
+    let expected = indoc! {"
+        [1, 2, 3, 10]
+        [10, 3, 2, 1]
+        bob
+        di
+        ann
+        cy
+        1
+        caught stop
+    "};
+    let expected_error = indoc! {"
+        The comparator must return a number but returned string.
+        [line 21]
+    "};
+    run_program(input, expected, expected_error, RUNTIME_ERROR);
+
+    run_program("sort([1], 2);", "", "Expected a function to compare with but got number.\n[line 1]\n", RUNTIME_ERROR);
+}
@@ -0,0 +1,34 @@
+use codecrafters_interpreter::interpreter::Interpreter;
+use codecrafters_interpreter::parser::Parser;
+use codecrafters_interpreter::resolver::Resolver;
+use codecrafters_interpreter::scanner::Scanner;
+use codecrafters_interpreter::stmt::Stmt;
+use std::collections::HashMap;
+
+fn parse(source: &str) -> Vec<Stmt> {
+    let tokens = Scanner::new(source.to_string()).scan_tokens();
+    Parser::new(tokens).parse().statements
+}
+
+#[test]
+fn incremental_resolution_keeps_globals() {
+    let mut resolver = Resolver::new();
+    let mut interpreter = Interpreter::new_with_resolver(HashMap::new());
+
+    let first = parse("var greeting = \"hi\"; fun greet() { return greeting; }");
+    interpreter.add_locals(resolver.resolve(&first));
+    interpreter.interpret(&first);
+
+    let second = parse("fun twice() { var word = greet(); return word + word; }");
+    interpreter.add_locals(resolver.resolve(&second));
+    interpreter.interpret(&second);
+
+    assert!(resolver.knows_global("greeting"));
+    assert!(resolver.knows_global("twice"));
+    assert!(!resolver.knows_global("word"));
+
+    let tokens = Scanner::new("twice()".to_string()).scan_tokens();
+    let expr = Parser::new(tokens).expression().ok().unwrap();
+    let value = interpreter.evaluate(&expr).ok().unwrap();
+    assert_eq!(value.to_string(), "hihi");
+}
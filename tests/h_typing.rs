@@ -0,0 +1,127 @@
+mod util;
+
+use indoc::indoc;
+use util::{run_files, run_program};
+use util::{SUCCESS, BUILD_ERROR, NO_ERROR};
+
+#[test]
+fn annotations_are_ignored_at_runtime() {
+    let input = indoc! {"
+        fun add(a: Number, b: Number) -> Number { return a + b; }
+        var s: String = \"x\";
+        var twice = fun (n: Number) -> n * 2;
+        class Point { init(x: Number) { this.x = x; } }
+        var p: Point = Point(1);
+        print add(1, 2);
+        print s;
+        print twice(p.x);
+    "};
+    run_program(input, "3\nx\n2\n", NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn typecheck() {
+    let input = indoc! {"
+        fun add(a: Number, b: Number) -> Number { return a + b; }
+        class Shape {}
+        class Circle < Shape {}
+        fun area(shape: Shape) -> Number { return 0; }
+        var greeting = \"hi\";
+        var n: Number = add(1, 2) * 3;
+        var ok: Bool = n > 2 and true;
+        print area(Circle());
+        print add(greeting, 1);
+        var s: String = add(1, 2);
+        fun name() -> String { return 1; }
+        var c: Circle = Shape();
+        var count = 0;
+        count = \"many\";
+        var fixed: Number = 1;
+        fixed = \"one\";
+        var q: Quantity = 1;
+        add(1);
+        fun scale(by: Factor) -> Number { return by; }
+    "};
+    let (stdout, stderr, code, _) = run_files(&["typecheck"], &[input]);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, indoc! {"
+        [line 9] Error at ')': Expected Number for argument 1 but got String.
+        [line 10] Error at 's': Expected String but got Number.
+        [line 11] Error at 'return': Expected to return String but got Number.
+        [line 12] Error at 'c': Expected Circle but got Shape.
+        [line 16] Error at 'fixed': Expected Number but got String.
+        [line 17] Error at 'Quantity': Unknown type 'Quantity'.
+        [line 18] Error at ')': Expected 2 arguments but got 1.
+        [line 19] Error at 'Factor': Unknown type 'Factor'.
+    "});
+    assert_eq!(code, BUILD_ERROR);
+
+    let (_, stderr, code, _) = run_files(&["typecheck"], &["var x: Number = 1;\nprint x + 1;"]);
+    assert_eq!(stderr, "");
+    assert_eq!(code, SUCCESS);
+}
+
+#[test]
+fn warnings_for_operations_bound_to_fail() {
+    let input = indoc! {"
+        fun never() {
+          var label = \"a\";
+          print label - 1;
+          var n = 3;
+          n();
+          print -\"x\";
+          print n.field;
+          print nil[0];
+          print \"a\" + \"b\" + 1;
+        }
+        var changing = \"a\";
+        changing = 1;
+        print changing - 1;
+        print 1 + 2;
+    "};
+    let (stdout, stderr, code, _) = run_files(&["run", "--warnings"], &[input]);
+    assert_eq!(stdout, "0\n3\n");
+    assert_eq!(stderr, indoc! {"
+        [line 3] Warning at '-': Operands must be numbers.
+        [line 5] Warning at ')': Can only call functions and classes.
+        [line 6] Warning at '-': Operand must be a number.
+        [line 7] Warning at 'field': Can't read property 'field' of a number.
+        [line 8] Warning at '[': Only lists, maps, strings and instances can be indexed.
+        [line 9] Warning at '+': Operands must be numbers.
+    "});
+    assert_eq!(code, SUCCESS);
+
+    let (_, stderr, _, _) = run_files(&["run"], &["fun f() { return \"a\" - 1; }"]);
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn warnings_for_values_that_may_be_nil() {
+    let input = indoc! {"
+        class Point { init() { this.x = 1; } }
+        fun find(wanted) {
+          if (wanted) return Point();
+        }
+        fun never() {
+          find(true).x;
+          var point;
+          if (clock() > 0) point = Point();
+          point.x = 2;
+          point();
+          if (point != nil) print point.x;
+          print point and point.x;
+          var found = find(false);
+          if (found) print found.x;
+        }
+        print find(true).x;
+    "};
+    let (stdout, stderr, code, _) = run_files(&["run", "--warnings"], &[input]);
+    assert_eq!(stdout, "1\n");
+    assert_eq!(stderr, indoc! {"
+        [line 6] Warning at 'x': This may be nil, which has no properties.
+        [line 9] Warning at 'x': This may be nil, which has no fields.
+        [line 10] Warning at ')': This may be nil, which can't be called.
+        [line 16] Warning at 'x': This may be nil, which has no properties.
+    "});
+    assert_eq!(code, SUCCESS);
+}
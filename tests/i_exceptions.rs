@@ -0,0 +1,251 @@
+mod util;
+
+use indoc::indoc;
+use util::run_program;
+use util::{SUCCESS, BUILD_ERROR, RUNTIME_ERROR, NO_ERROR};
+
+#[test]
+fn throw_and_catch() {
+    let input = indoc! {"
+        class Oops { init(reason) { this.reason = reason; } }
+        fun check(n) {
+            if (n > 1) throw Oops(\"too big\");
+            return n;
+        }
+        fun run(n) { return check(n); }
+        try {
+            print run(1);
+            print run(2);
+            print \"not reached\";
+        } catch (e) {
+            print e.reason;
+            for (line in e.stack) print line;
+        }
+        try {
+            print nil + 1;
+        } catch (e) {
+            print e.message;
+        }
+        try { throw \"plain\"; } catch (e) { print e; }
+        print \"done\";
+    "};
+    let expected = indoc! {"
+        1
+        too big
+        [line 3] in check()
+        [line 6] in run()
+        [line 9] in script
+        Operands must be numbers.
+        plain
+        done
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn uncaught_throw_reports_where_it_came_from() {
+    let input = indoc! {"
+        fun inner() {
+            throw \"boom\";
+        }
+        fun outer() {
+            try { print 1; } catch (e) {}
+            inner();
+        }
+        outer();
+    "};
+    let expected_error = indoc! {"
+        Uncaught boom
+        [line 2] in inner()
+        [line 6] in outer()
+        [line 8] in script
+    "};
+    run_program(input, "1\n", expected_error, RUNTIME_ERROR);
+
+    run_program("throw 42;", "", "Uncaught 42\n[line 1] in script\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn runtime_errors_are_caught_as_error_instances() {
+    let input = indoc! {"
+        fun divide(list) {
+            return list[3];
+        }
+        try {
+            divide([1, 2]);
+        } catch (e) {
+            print e is Error;
+            print e.message;
+            print e.line;
+            for (line in e.stack) print line;
+        }
+        class NotFound < Error {
+            init(name) { this.message = name + \" not found\"; }
+        }
+        try {
+            throw NotFound(\"file\");
+        } catch (e) {
+            print e is Error;
+            print e.message;
+            print len(e.stack);
+        }
+        class Error {}
+        try { print -\"x\"; } catch (e) { print e is Error; }
+    "};
+    let expected = indoc! {"
+        true
+        Index 3 is out of bounds for a list of length 2.
+        2
+        [line 2] in divide()
+        [line 5] in script
+        true
+        file not found
+        1
+        false
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn errors_made_and_rethrown_by_scripts() {
+    let input = indoc! {"
+        try { throw Error(\"boom\"); } catch (e) { print e.message; }
+        class Missing < Error {
+            init(name) { super.init(name + \" is missing\"); }
+        }
+        try { throw Missing(\"key\"); } catch (e) { print e.message; }
+        fun rethrow() {
+            try { print nil + 1; } catch (e) { throw e; }
+        }
+        rethrow();
+    "};
+    let expected_error = indoc! {"
+        Uncaught Error: Operands must be numbers.
+        [line 7] in rethrow()
+        [line 9] in script
+    "};
+    run_program(input, "boom\nkey is missing\n", expected_error, RUNTIME_ERROR);
+
+    let input = "class Missing < Error {} throw Missing(\"key\");";
+    run_program(input, "", "Uncaught Missing: key\n[line 1] in script\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn finally_runs_however_the_try_ends() {
+    let input = indoc! {"
+        fun returns() {
+            try { return \"from try\"; } finally { print \"finally after return\"; }
+        }
+        print returns();
+
+        fun overrides() {
+            try { return \"from try\"; } finally { return \"from finally\"; }
+        }
+        print overrides();
+
+        fun swallows() {
+            try { throw \"lost\"; } finally { return \"swallowed\"; }
+        }
+        print swallows();
+
+        for (var i = 0; i < 3; i = i + 1) {
+            try {
+                if (i == 1) continue;
+                if (i == 2) break;
+                print \"body \" + \"ran\";
+            } finally {
+                print i;
+            }
+        }
+
+        fun search() {
+            outer: for (var i = 0; i < 3; i = i + 1) {
+                for (var j = 0; j < 3; j = j + 1) {
+                    try {
+                        if (j == 1) continue outer;
+                        if (i == 2) break outer;
+                    } finally {
+                        print \"cell\";
+                    }
+                }
+            }
+            return \"searched\";
+        }
+        print search();
+
+        while (true) {
+            try { throw \"dropped\"; } finally { break; }
+        }
+        print \"after loop\";
+
+        try {
+            try {
+                print nil + 1;
+            } catch (e) {
+                print \"inner catch\";
+                throw \"rethrown\";
+            } finally {
+                print \"inner finally\";
+            }
+        } catch (e) {
+            print e;
+        } finally {
+            print \"outer finally\";
+        }
+
+        fun failing() {
+            try { return 1; } finally { print -\"x\"; }
+        }
+        try { failing(); } catch (e) { print e.message; }
+    "};
+    let expected = indoc! {"
+        finally after return
+        from try
+        from finally
+        swallowed
+        body ran
+        0
+        1
+        2
+        cell
+        cell
+        cell
+        cell
+        cell
+        searched
+        after loop
+        inner catch
+        inner finally
+        rethrown
+        outer finally
+        Operand must be a number.
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "try { throw \"boom\"; } finally { print \"cleanup\"; }";
+    run_program(input, "cleanup\n", "Uncaught boom\n[line 1] in script\n", RUNTIME_ERROR);
+
+    let input = indoc! {"
+        fun g() { throw \"inner\"; }
+        fun f() {
+            try { g(); } finally { return 1; }
+        }
+        print f();
+        throw \"top\";
+    "};
+    run_program(input, "1\n", "Uncaught top\n[line 6] in script\n", RUNTIME_ERROR);
+
+    let input = indoc! {"
+        fun g() { throw \"kept\"; }
+        fun f() {
+            try { g(); } finally { print \"cleanup\"; }
+        }
+        f();
+    "};
+    let expected_error = "Uncaught kept\n[line 1] in g()\n[line 3] in f()\n[line 5] in script\n";
+    run_program(input, "cleanup\n", expected_error, RUNTIME_ERROR);
+
+    let input = "try { print 1; }";
+    let expected_error = "[line 1] Error at end: Expect 'catch' or 'finally' after try block.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
@@ -0,0 +1,427 @@
+mod util;
+
+use indoc::indoc;
+use util::run_program;
+use util::{SUCCESS, BUILD_ERROR, RUNTIME_ERROR, NO_ERROR};
+
+#[test]
+fn class_methods() {
+    let input = indoc! {"
+        class Math {
+            class square(n) { return n * n; }
+            class cube(n) { return n * this.square(n); }
+        }
+        print Math.square(3);
+        print Math.cube(2);
+        class Geometry < Math {
+            class area(side) { return super.square(side); }
+        }
+        print Geometry.square(4);
+        print Geometry.area(5);
+    "};
+    let expected = indoc! {"
+        9
+        8
+        16
+        25
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        class Math { square(n) { return n * n; } }
+        Math.square(3);
+    "};
+    run_program(input, "", "'square' is an instance method of 'Math'; call it on an instance.\n[line 2]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn methods_change_the_instance_itself() {
+    let input = indoc! {"
+        class Counter {
+            init() { this.count = 0; }
+            add() { this.count = this.count + 1; return this; }
+        }
+        var counter = Counter();
+        counter.add();
+        counter.add().add();
+        print counter.count;
+    "};
+    run_program(input, "3\n", NO_ERROR, SUCCESS);
+}
+
+#[test]
+fn getters() {
+    let input = indoc! {"
+        class Circle {
+            init(radius) { this.radius = radius; }
+            area { return 3 * this.radius * this.radius; }
+            diameter -> Number { return this.radius * 2; }
+            class unit { return Circle(1); }
+        }
+        var circle = Circle(2);
+        print circle.area;
+        circle.radius = 3;
+        print circle.area;
+        print circle.diameter;
+        print Circle.unit.area;
+        class Ring < Circle {}
+        print Ring(4).diameter;
+        class Double < Circle { area { return super.area * 2; } }
+        print Double(1).area;
+    "};
+    let expected = indoc! {"
+        12
+        27
+        6
+        3
+        8
+        6
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = indoc! {"
+        class Broken { value { return nil(); } }
+        print Broken().value;
+    "};
+    run_program(input, "", "Can only call functions and classes.\n[line 1]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn setters() {
+    let input = indoc! {"
+        class Person {
+            init(age) { this.age = age; }
+            set age(value) {
+                if (value < 0) throw \"Age can't be negative.\";
+                this.age = value;
+            }
+            set name(value) { this.first = value; }
+            name { return \"Ms. \" + this.first; }
+        }
+        var person = Person(30);
+        print person.age;
+        person.age = 31;
+        person.age++;
+        print person.age;
+        print person.name = \"Ada\";
+        print person.name;
+        class Student < Person {}
+        try {
+            Student(20).age = -1;
+        } catch (error) {
+            print error;
+        }
+    "};
+    let expected = indoc! {"
+        30
+        32
+        Ada
+        Ms. Ada
+        Age can't be negative.
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "class Broken { set value(a, b) {} }";
+    let expected_error = "[line 1] Error at 'value': A setter takes exactly one parameter.\n";
+    run_program(input, "", expected_error, BUILD_ERROR);
+}
+
+#[test]
+fn inheritance() {
+    let input = indoc! {"
+        class Doughnut {
+            init(filling) { this.filling = filling; }
+            cook() { print \"Fry until golden brown.\"; }
+            describe() { return \"A doughnut with \" + this.filling; }
+        }
+        class BostonCream < Doughnut {
+            init() { super.init(\"custard\"); }
+            cook() {
+                super.cook();
+                print \"Pipe full of custard and coat with chocolate.\";
+            }
+        }
+        class Glazed < BostonCream {}
+        var doughnut = Glazed();
+        doughnut.cook();
+        print doughnut.describe();
+        print doughnut;
+    "};
+    let expected = indoc! {"
+        Fry until golden brown.
+        Pipe full of custard and coat with chocolate.
+        A doughnut with custard
+        Glazed instance
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    run_program("var NotAClass = 1;\nclass Oops\n  < NotAClass {}", "", "Superclass must be a class.\n[line 3]\n", RUNTIME_ERROR);
+
+    let cases = [
+        ("class Oops < Oops {}", "[line 1] Error at 'Oops': A class can't inherit from itself.\n"),
+        ("class Base { f() { super.f(); } }", "[line 1] Error at 'super': Can't use 'super' in a class with no superclass.\n"),
+        ("super.f();", "[line 1] Error at 'super': Can't use 'super' outside of a class.\n"),
+    ];
+    for (input, expected_error) in cases {
+        run_program(input, "", expected_error, BUILD_ERROR);
+    }
+}
+
+#[test]
+fn is_operator() {
+    let input = indoc! {"
+        class Shape {}
+        class Circle < Shape {}
+        class Square < Shape {}
+        var circle = Circle();
+        print circle is Circle;
+        print circle is Shape;
+        print circle is Square;
+        print Shape() is Circle;
+        print 1 is Shape;
+        print Circle is Shape;
+        print !(circle is Square) == true;
+        var is = \"still a name\";
+        print is;
+    "};
+    let expected = indoc! {"
+        true
+        true
+        false
+        false
+        false
+        false
+        true
+        still a name
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    run_program("class A {}\nprint A() is 1;", "", "Right operand of 'is' must be a class or an interface.\n[line 2]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn equals_and_hash_methods() {
+    let input = indoc! {"
+        class Point {
+          init(x, y) { this.x = x; this.y = y; }
+          equals(other) { return other is Point and this.x == other.x and this.y == other.y; }
+          hash() { return this.x * 1000 + this.y; }
+        }
+        class Plain {}
+        var a = Point(1, 2);
+        print a == Point(1, 2);
+        print a != Point(2, 1);
+        print a == nil;
+        var p = Plain();
+        print p == p;
+        print p == Plain();
+        var m = {a: \"first\"};
+        m[Point(1, 2)] = \"again\";
+        m[Point(3, 4)] = \"other\";
+        print len(keys(m));
+        print m[Point(1, 2)];
+        print has(m, Point(3, 4));
+        print has(m, Point(5, 6));
+        m[p] = 1;
+    "};
+    let expected = indoc! {"
+        true
+        true
+        false
+        true
+        false
+        2
+        again
+        true
+        false
+    "};
+    let expected_error = indoc! {"
+        Only instances whose class has a hash() method can be map keys.
+        [line 21]
+    "};
+    run_program(input, expected, expected_error, RUNTIME_ERROR);
+
+    let input = indoc! {"
+        class P {
+          init(x) { this.x = x; }
+          equals(other) { return other is P and this.x == other.x; }
+          hash() { return 1; }
+        }
+        var m = {};
+        m[P(1)] = \"one\";
+        m[P(2)] = \"two\";
+        m[P(1)] = \"uno\";
+        print len(keys(m));
+        print m[P(1)];
+        print m[P(2)];
+        print has(m, P(3));
+        print {P(1): 1, P(1): 2, P(2): 3}[P(1)];
+        print P(1) == 1;
+        print 1 == P(1);
+        print P(1) == P(1);
+        class Agreeable { equals(other) { return true; } }
+        print Agreeable() == 3;
+        print 3 == Agreeable();
+    "};
+    let expected = indoc! {"
+        2
+        uno
+        two
+        false
+        2
+        false
+        false
+        true
+        false
+        false
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "class Odd { hash() { return nil; } }\nvar m = {Odd(): 1};";
+    run_program(input, "", "hash() must return a number or a string.\n[line 2]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn properties_by_computed_name() {
+    let input = indoc! {r#"
+        class Temperature {
+          init(celsius) { this.celsius = celsius; }
+          fahrenheit { return this.celsius * 9 / 5 + 32; }
+          greet() { return "hi"; }
+        }
+        var t = Temperature(100);
+        var field = "cel" + "sius";
+        print t[field];
+        print t["fahrenheit"];
+        print t["greet"]();
+        t["label"] = "boiling";
+        t[field] += 1;
+        t["celsius"]++;
+        print t.label;
+        print t.celsius;
+        print t["missing"];
+    "#};
+    let expected = indoc! {"
+        100
+        212
+        hi
+        boiling
+        102
+    "};
+    let expected_error = indoc! {"
+        'Temperature' instance has no property 'missing'.
+        [line 16]
+    "};
+    run_program(input, expected, expected_error, RUNTIME_ERROR);
+
+    let input = "class A {}\nA()[1] = 2;";
+    run_program(input, "", "Property names must be strings.\n[line 2]\n", RUNTIME_ERROR);
+}
+
+#[test]
+fn extending_a_class() {
+    let input = indoc! {r#"
+        class Point {
+          init(x, y) { this.x = x; this.y = y; }
+        }
+        var p = Point(3, 4);
+        var unit = "units";
+        extend Point {
+          norm() { return this.x * this.x + this.y * this.y; }
+          label { return this.norm() + " " + unit; }
+          class origin() { return this(0, 0); }
+        }
+        print p.norm();
+        print Point.origin().norm();
+        class Point3 < Point {}
+        print Point3(1, 2).norm();
+        extend Point {
+          norm() { return "replaced"; }
+        }
+        print p.norm();
+        print p.label;
+    "#};
+    let expected = indoc! {"
+        25
+        0
+        5
+        replaced
+        replaced units
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "var extend = 1;\nprint extend + 1;\nvar NotAClass = 1;\nextend NotAClass {}";
+    run_program(input, "2\n", "Can only extend a class.\n[line 4]\n", RUNTIME_ERROR);
+
+    let input = "class A {}\nextend A {\n  f() { return super.f(); }\n}";
+    run_program(input, "", "[line 3] Error at 'super': Can't use 'super' in an extension.\n", BUILD_ERROR);
+}
+
+#[test]
+fn interfaces() {
+    let input = indoc! {r#"
+        interface Shape {
+          area();
+          scale(factor);
+        }
+        interface Named { name(); }
+        class Base { name() { return "shape"; } }
+        class Square < Base : Shape, Named {
+          init(side) { this.side = side; }
+          area() { return this.side * this.side; }
+          scale(factor) { return Square(this.side * factor); }
+        }
+        class Big < Square {}
+        fun describe(s: Shape) { print s.name(); print s.area(); }
+        print Shape;
+        describe(Square(2).scale(3));
+        print Big(1) is Shape;
+        print Base() is Shape;
+        print 1 is Shape;
+    "#};
+    let expected = indoc! {"
+        <interface Shape>
+        shape
+        36
+        true
+        false
+        false
+    "};
+    run_program(input, expected, NO_ERROR, SUCCESS);
+
+    let input = "interface Shape { area(); }\nclass Circle : Shape {\n  perimeter() {}\n}";
+    run_program(input, "", "Class 'Circle' doesn't implement 'area' from interface 'Shape'.\n[line 2]\n", RUNTIME_ERROR);
+
+    let input = "interface Shape { scale(factor); }\nclass Circle : Shape { scale() {} }";
+    let expected_error = "Method 'scale' of class 'Circle' must take 1 argument to implement interface 'Shape'.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+    let input = "interface Shape { move(x, y); }\nclass Circle : Shape { move(x) {} }";
+    let expected_error = "Method 'move' of class 'Circle' must take 2 arguments to implement interface 'Shape'.\n[line 2]\n";
+    run_program(input, "", expected_error, RUNTIME_ERROR);
+
+    let input = "class Base {}\nclass Circle : Base {}";
+    run_program(input, "", "Can only implement an interface.\n[line 2]\n", RUNTIME_ERROR);
+
+    let input = "interface Shape { area() }";
+    run_program(input, "", "[line 1] Error at '}': Expect ';' after method signature.\n", BUILD_ERROR);
+}
+
+#[test]
+fn property_errors_name_the_receiver() {
+    let class = "class Point {\n  init() { this.x = 1; }\n  norm() { return 0; }\n  class origin() { return Point(); }\n}\n";
+
+    let input = format!("{class}print Point.norm;");
+    run_program(&input, "", "'norm' is an instance method of 'Point'; call it on an instance.\n[line 6]\n", RUNTIME_ERROR);
+
+    let input = format!("{class}print Point().origin;");
+    run_program(&input, "", "'origin' is a class method of 'Point'; call it on the class.\n[line 6]\n", RUNTIME_ERROR);
+
+    let input = format!("{class}print Point.x;");
+    run_program(&input, "", "Class 'Point' has no class method 'x'.\n[line 6]\n", RUNTIME_ERROR);
+
+    let input = format!("{class}print Point().y;");
+    run_program(&input, "", "'Point' instance has no property 'y'.\n[line 6]\n", RUNTIME_ERROR);
+
+    run_program("var n = 3;\nprint n.x;", "", "Can't read property 'x' of a number.\n[line 2]\n", RUNTIME_ERROR);
+    run_program("print nil.x;", "", "Can't read property 'x' of a nil.\n[line 1]\n", RUNTIME_ERROR);
+}
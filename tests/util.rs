@@ -12,7 +12,7 @@ pub fn assert_tokenize(input: &str) -> Assert {
     write!(temp_file, "{input}").expect("Failed to write to temp file");
 
     let mut cmd = Command::cargo_bin("codecrafters-interpreter").expect("Binary not found");
-    cmd.args(&["tokenize", temp_file.path().to_str().unwrap()]);
+    cmd.args(["tokenize", temp_file.path().to_str().unwrap()]);
 
     cmd.assert()
 }
@@ -27,7 +27,7 @@ pub fn run_tokenize(
     write!(temp_file, "{input}").expect("Failed to write to temp file");
 
     let mut cmd = Command::cargo_bin("codecrafters-interpreter").expect("Binary not found");
-    cmd.args(&["tokenize", temp_file.path().to_str().unwrap()]);
+    cmd.args(["tokenize", temp_file.path().to_str().unwrap()]);
 
     let output = cmd.output().expect("Failed to run binary");
 
@@ -39,3 +39,43 @@ pub fn run_tokenize(
     assert_eq!(stderr, expected_error);
     assert_eq!(exit_code, expected_code);
 }
+
+/// Writes each input to its own temp file and runs the given command over all of
+/// them. Returns stdout, stderr, exit code and the temp file paths (in order).
+pub fn run_files(command: &[&str], inputs: &[&str]) -> (String, String, i32, Vec<String>) {
+    let files: Vec<NamedTempFile> = inputs
+        .iter()
+        .map(|input| {
+            let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
+            write!(temp_file, "{input}").expect("Failed to write to temp file");
+            temp_file
+        })
+        .collect();
+    let paths: Vec<String> = files.iter().map(|f| f.path().to_str().unwrap().to_string()).collect();
+
+    let mut cmd = Command::cargo_bin("codecrafters-interpreter").expect("Binary not found");
+    cmd.args(command).args(&paths);
+    let output = cmd.output().expect("Failed to run binary");
+
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+        output.status.code().unwrap_or(-1),
+        paths,
+    )
+}
+
+pub const RUNTIME_ERROR: i32 = 70;
+
+pub fn run_program(
+    input: &str,
+    expected: &str,
+    expected_error: &str,
+    expected_code: i32)
+{
+    let (stdout, stderr, exit_code, _) = run_files(&["run"], &[input]);
+
+    assert_eq!(stdout, expected);
+    assert_eq!(stderr, expected_error);
+    assert_eq!(exit_code, expected_code);
+}